@@ -175,6 +175,10 @@ impl completion::CompletionModel for CompletionModel {
     type Response = AwsConverseOutput;
     type StreamingResponse = ();
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     async fn completion(
         &self,
         completion_request: completion::CompletionRequest,
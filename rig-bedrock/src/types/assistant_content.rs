@@ -108,6 +108,9 @@ impl TryFrom<RigAssistantContent> for aws_bedrock::ContentBlock {
     fn try_from(value: RigAssistantContent) -> Result<Self, Self::Error> {
         match value.0 {
             AssistantContent::Text(text) => Ok(aws_bedrock::ContentBlock::Text(text.text)),
+            AssistantContent::Refusal(refusal) => {
+                Ok(aws_bedrock::ContentBlock::Text(refusal.refusal))
+            }
             AssistantContent::ToolCall(tool_call) => {
                 let doc: AwsDocument = tool_call.function.arguments.into();
                 Ok(aws_bedrock::ContentBlock::ToolUse(
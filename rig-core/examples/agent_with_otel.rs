@@ -0,0 +1,90 @@
+//! Export the traces of an agent to an OpenTelemetry collector (e.g.: Jaeger), over OTLP/HTTP.
+//!
+//! Start a collector, e.g.:
+//! `docker run --rm -p 16686:16686 -p 4318:4318 jaegertracing/all-in-one:latest`
+//! then run this example with `cargo run --example agent_with_otel --features otel`, and open
+//! the Jaeger UI at http://localhost:16686.
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{Resource, trace::SdkTracerProvider};
+use rig::prelude::*;
+use rig::{
+    completion::{Prompt, ToolDefinition},
+    providers,
+    telemetry::{self, opentelemetry::trace::TracerProvider},
+    tool::Tool,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tracing_subscriber::prelude::*;
+
+#[derive(Deserialize)]
+struct OperationArgs {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Math error")]
+struct MathError;
+
+struct Adder;
+
+impl Tool for Adder {
+    const NAME: &'static str = "add";
+    type Error = MathError;
+    type Args = OperationArgs;
+    type Output = i32;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: "add".to_string(),
+            description: "Add x and y together".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "x": { "type": "number", "description": "The first number to add" },
+                    "y": { "type": "number", "description": "The second number to add" }
+                },
+                "required": ["x", "y"],
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        Ok(args.x + args.y)
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    // Export the spans to the OTLP/HTTP endpoint of the collector
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint("http://localhost:4318/v1/traces")
+        .build()?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name("rig-agent").build())
+        .build();
+
+    // Bridge the spans of Rig (prompt runs, turns, completions, tool calls) to the exporter
+    tracing_subscriber::registry()
+        .with(telemetry::otel_layer(provider.tracer("rig")))
+        .with(tracing_subscriber::fmt::layer())
+        .init();
+
+    let agent = providers::openai::Client::from_env()
+        .agent(providers::openai::GPT_4O)
+        .preamble("You are a calculator. Use the tools provided to answer the user's question.")
+        .max_tokens(1024)
+        .tool(Adder)
+        .build();
+
+    let answer = agent.prompt("Calculate 2 + 5").multi_turn(2).await?;
+    println!("Calculator Agent: {answer}");
+
+    // Flush the remaining spans before exiting
+    provider.shutdown()?;
+
+    Ok(())
+}
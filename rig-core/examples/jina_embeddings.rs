@@ -0,0 +1,34 @@
+use rig::Embed;
+use rig::embeddings::EmbeddingsBuilder;
+use rig::prelude::*;
+use rig::providers::jina;
+
+#[derive(Embed, Debug)]
+struct Greetings {
+    #[embed]
+    message: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    // Initialize the Jina AI client
+    let client = jina::Client::from_env();
+    let model = client
+        .embedding_model(jina::JINA_EMBEDDINGS_V3)
+        .task(jina::Task::RetrievalPassage);
+
+    let embeddings = EmbeddingsBuilder::new(model)
+        .document(Greetings {
+            message: "Hello, world!".to_string(),
+        })?
+        .document(Greetings {
+            message: "Goodbye, world!".to_string(),
+        })?
+        .build()
+        .await
+        .expect("Failed to embed documents");
+
+    println!("{embeddings:?}");
+
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use syn::{Attribute, DataStruct, Meta, parse_quote};
+use syn::{Attribute, DataStruct, Meta, parse_quote_spanned, spanned::Spanned};
 
 use crate::EMBED;
 
@@ -16,10 +16,13 @@ pub(crate) fn basic_embed_fields(data_struct: &DataStruct) -> impl Iterator<Item
 }
 
 /// Adds bounds to where clause that force all fields tagged with `#[embed]` to implement the `Embed` trait.
+/// The bounds are spanned to the field types so that unsatisfied bounds are reported on the offending field.
 pub(crate) fn add_struct_bounds(generics: &mut syn::Generics, field_type: &syn::Type) {
     let where_clause = generics.make_where_clause();
 
-    where_clause.predicates.push(parse_quote! {
-        #field_type: Embed
-    });
+    where_clause
+        .predicates
+        .push(parse_quote_spanned! {field_type.span()=>
+            #field_type: Embed
+        });
 }
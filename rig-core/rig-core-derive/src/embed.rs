@@ -1,6 +1,6 @@
 use proc_macro2::TokenStream;
-use quote::quote;
-use syn::DataStruct;
+use quote::{quote, quote_spanned};
+use syn::{DataStruct, spanned::Spanned};
 
 use crate::{
     basic::{add_struct_bounds, basic_embed_fields},
@@ -14,6 +14,14 @@ pub(crate) fn expand_derive_embedding(input: &mut syn::DeriveInput) -> syn::Resu
 
     let target_stream = match data {
         syn::Data::Struct(data_struct) => {
+            if let Some(field) = basic_embed_fields(data_struct).find(|field| field.ident.is_none())
+            {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "#[embed] is only supported on named fields",
+                ));
+            }
+
             let (basic_targets, basic_target_size) = data_struct.basic(generics);
             let (custom_targets, custom_target_size) = data_struct.custom()?;
 
@@ -73,15 +81,16 @@ impl StructParser for DataStruct {
 
                 let field_name = &field.ident;
 
-                quote! {
-                    self.#field_name
+                // Span the call to the field type so that errors point at the offending field.
+                quote_spanned! {field.ty.span()=>
+                    Embed::embed(&self.#field_name, embedder)?;
                 }
             })
             .collect::<Vec<_>>();
 
         (
             quote! {
-                #(#embed_targets.embed(embedder)?;)*
+                #(#embed_targets)*
             },
             embed_targets.len(),
         )
@@ -1,15 +1,32 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use crate::{
-    completion::{CompletionModel, Document},
-    tool::{Tool, ToolSet},
+    completion::{CompletionModel, Document, RetryPolicy},
+    context_policy::ContextPolicy,
+    embeddings::EmbeddingModel,
+    media_limits::MediaLimits,
+    message::ToolCall,
+    moderation::{Moderator, ModeratorDyn},
+    redact::Redactor,
+    schema::SchemaValidator,
+    tool::{BatchToolHandler, StreamingTool, Tool, ToolSet},
     vector_store::VectorStoreIndexDyn,
 };
 
 #[cfg(feature = "rmcp")]
 use crate::tool::rmcp::McpTool as RmcpTool;
 
-use super::Agent;
+use super::{
+    Agent, DuplicateToolCallIds, FinalAnswerTool, RetrievalFilter, ToolCallStyle, ToolFilter,
+    ToolResultFormatter, ToolRetrieval,
+};
+
+/// Default maximum number of tool calls with invalid arguments sent back to the model per
+/// prompt (see [AgentBuilder::validate_tool_args]).
+pub const DEFAULT_MAX_ARG_RETRIES: usize = 3;
 
 /// A builder for creating an agent
 ///
@@ -35,6 +52,8 @@ use super::Agent;
 pub struct AgentBuilder<M: CompletionModel> {
     /// Name of the agent used for logging and debugging
     name: Option<String>,
+    /// Whether the name of the agent is prepended to the preamble
+    name_in_preamble: bool,
     /// Completion model (e.g.: OpenAI's gpt-3.5-turbo-1106, Cohere's command-r)
     model: M,
     /// System prompt
@@ -47,6 +66,8 @@ pub struct AgentBuilder<M: CompletionModel> {
     additional_params: Option<serde_json::Value>,
     /// Maximum number of tokens for the completion
     max_tokens: Option<u64>,
+    /// Whether the model may call multiple tools in a single turn
+    parallel_tool_calls: Option<bool>,
     /// List of vector store, with the sample number
     dynamic_context: Vec<(usize, Box<dyn VectorStoreIndexDyn>)>,
     /// Dynamic tools
@@ -55,31 +76,101 @@ pub struct AgentBuilder<M: CompletionModel> {
     temperature: Option<f64>,
     /// Actual tool implementations
     tools: ToolSet,
+    /// Name of the tool whose invocation ends a multi-turn prompt
+    final_answer_tool: Option<String>,
+    /// Redactor applied to the prompt and chat history before each completion request
+    redactor: Option<Arc<dyn Redactor>>,
+    /// Whether the redactor is also applied to tool results
+    redact_tool_results: bool,
+    /// What to do when the model returns multiple tool calls with the same id in a single turn
+    duplicate_tool_call_ids: DuplicateToolCallIds,
+    /// Names of the tools whose result is provided by the caller
+    pause_on_tools: HashSet<String>,
+    /// Function formatting the text of tool results before they are sent back to the model
+    tool_result_formatter: Option<ToolResultFormatter>,
+    /// Whether tool calls with invalid arguments are sent back to the model to be retried
+    validate_tool_args: bool,
+    /// Maximum number of tool calls with invalid arguments sent back to the model per prompt
+    max_arg_retries: usize,
+    /// Size limits applied to the images and documents of the prompt and chat history
+    media_limits: Option<MediaLimits>,
+    /// Whether an id is generated for the assistant messages added to the chat history
+    generate_message_ids: bool,
+    include_reasoning: bool,
+    completion_retry: Option<RetryPolicy>,
+    schema_validator: Option<Arc<dyn SchemaValidator>>,
+    tool_filter: Option<ToolFilter>,
+    should_retrieve: Option<RetrievalFilter>,
+    log_prompt_content: Option<usize>,
+    keep_raw: bool,
+    moderator: Option<Arc<dyn ModeratorDyn>>,
+    moderate_output: bool,
+    locale: Option<String>,
+    context_policy: Option<ContextPolicy>,
+    tool_call_style: ToolCallStyle,
+    sequential_tools: bool,
+    tool_retrieval: Option<Arc<ToolRetrieval>>,
+    citations: bool,
 }
 
 impl<M: CompletionModel> AgentBuilder<M> {
     pub fn new(model: M) -> Self {
         Self {
             name: None,
+            name_in_preamble: false,
             model,
             preamble: None,
             static_context: vec![],
             static_tools: vec![],
             temperature: None,
             max_tokens: None,
+            parallel_tool_calls: None,
             additional_params: None,
             dynamic_context: vec![],
             dynamic_tools: vec![],
             tools: ToolSet::default(),
+            final_answer_tool: None,
+            redactor: None,
+            redact_tool_results: false,
+            duplicate_tool_call_ids: DuplicateToolCallIds::default(),
+            pause_on_tools: HashSet::new(),
+            tool_result_formatter: None,
+            validate_tool_args: false,
+            max_arg_retries: DEFAULT_MAX_ARG_RETRIES,
+            media_limits: None,
+            generate_message_ids: false,
+            include_reasoning: false,
+            completion_retry: None,
+            schema_validator: None,
+            tool_filter: None,
+            should_retrieve: None,
+            log_prompt_content: None,
+            keep_raw: false,
+            moderator: None,
+            moderate_output: false,
+            locale: None,
+            context_policy: None,
+            tool_call_style: ToolCallStyle::default(),
+            sequential_tools: false,
+            tool_retrieval: None,
+            citations: false,
         }
     }
 
-    /// Set the name of the agent
+    /// Set the name of the agent. The name is included in the tracing spans of the agent. To use
+    /// the agent as a tool with a name of its own, see [agent_as_tool](super::agent_as_tool).
     pub fn name(mut self, name: &str) -> Self {
         self.name = Some(name.into());
         self
     }
 
+    /// Set whether the name of the agent is prepended to its preamble (e.g.: "You are Gandalf.")
+    /// so that the model knows how it is referred to (defaults to `false`).
+    pub fn name_in_preamble(mut self, name_in_preamble: bool) -> Self {
+        self.name_in_preamble = name_in_preamble;
+        self
+    }
+
     /// Set the system prompt
     pub fn preamble(mut self, preamble: &str) -> Self {
         self.preamble = Some(preamble.into());
@@ -96,6 +187,69 @@ impl<M: CompletionModel> AgentBuilder<M> {
         self
     }
 
+    /// Set the policy trimming the chat history to a token budget before each completion
+    /// request, e.g.: to keep long conversations within the context window of the model (see
+    /// [crate::context_policy]). By default, the chat history is sent in full.
+    pub fn context_policy(mut self, context_policy: ContextPolicy) -> Self {
+        self.context_policy = Some(context_policy);
+        self
+    }
+
+    /// Set how the model calls the tools of the agent. With [ToolCallStyle::ReAct], the tools
+    /// are described in the preamble and called by the model in text, for models without
+    /// native tool calling (see [ToolCallStyle]). Defaults to native tool calls.
+    pub fn tool_call_style(mut self, tool_call_style: ToolCallStyle) -> Self {
+        self.tool_call_style = tool_call_style;
+        self
+    }
+
+    /// Set whether the tool calls of a turn are executed one at a time, in the order the model
+    /// emitted them, e.g.: for tools whose side effects must happen in order. By default, they
+    /// are executed concurrently. Either way, their results are sent back to the model in the
+    /// order of the calls. Streaming prompt requests always execute them one at a time.
+    pub fn sequential_tools(mut self, sequential_tools: bool) -> Self {
+        self.sequential_tools = sequential_tools;
+        self
+    }
+
+    /// Only advertise the `top_k` static tools whose description is the most similar to each
+    /// prompt, by embedding the descriptions (once) and the prompt with `model` (see
+    /// [ToolRetrieval]). Useful for agents with many tools, whose definitions are expensive to
+    /// send and confuse the model.
+    ///
+    /// The tools that are not advertised can still be called if the model names them.
+    pub fn tool_retrieval(mut self, model: impl EmbeddingModel + 'static, top_k: usize) -> Self {
+        self.tool_retrieval = Some(Arc::new(ToolRetrieval::new(model, top_k)));
+        self
+    }
+
+    /// Ask the model to cite the context documents (static or retrieved) its answers are based
+    /// on. The citations are removed from the output and returned as structured data in
+    /// [PromptResponse::citations](crate::agent::PromptResponse::citations) (see
+    /// [PromptRequest::extended_details](crate::agent::PromptRequest::extended_details)).
+    ///
+    /// The citations are requested in the preamble and parsed from the answer, so they work with
+    /// any provider, but the model may omit or paraphrase them: the span of a quote in its
+    /// document is only known if it is quoted verbatim. The native citations of providers
+    /// (e.g.: Anthropic's or Gemini's grounding metadata) are not mapped.
+    ///
+    /// When streaming (see [StreamingPrompt](crate::streaming::StreamingPrompt)), the text of
+    /// each turn is only yielded once it is complete and its citations are removed. The
+    /// citations are then returned in
+    /// [FinalResponse::citations](crate::agent::FinalResponse::citations).
+    pub fn citations(mut self, citations: bool) -> Self {
+        self.citations = citations;
+        self
+    }
+
+    /// Set the default locale of the responses (e.g.: `fr-FR`): the model is instructed, at the
+    /// end of the preamble, to respond in the language of the locale. The locale can be
+    /// overridden for a prompt with [AgentPromptConfig::locale](crate::agent::AgentPromptConfig::locale).
+    pub fn locale(mut self, locale: &str) -> Self {
+        self.locale = Some(locale.into());
+        self
+    }
+
     /// Add a static context document to the agent
     pub fn context(mut self, doc: &str) -> Self {
         self.static_context.push(Document {
@@ -114,6 +268,29 @@ impl<M: CompletionModel> AgentBuilder<M> {
         self
     }
 
+    /// Add a static streaming tool to the agent. When the agent is streamed, the tool is called
+    /// with the fragments of its arguments as the model generates them (see [StreamingTool]).
+    pub fn streaming_tool(mut self, tool: impl StreamingTool + 'static) -> Self {
+        let toolname = tool.name();
+        self.tools.add_streaming_tool(tool);
+        self.static_tools.push(toolname);
+        self
+    }
+
+    /// Add a final answer tool to the agent. When the model calls this tool, the multi-turn
+    /// prompt stops and the arguments of the call are returned as the agent's answer
+    /// (see [PromptRequest::final_answer](super::PromptRequest::final_answer)).
+    pub fn final_answer_tool<T>(mut self, tool: FinalAnswerTool<T>) -> Self
+    where
+        FinalAnswerTool<T>: Tool + 'static,
+    {
+        let toolname = tool.name();
+        self.tools.add_tool(tool);
+        self.static_tools.push(toolname.clone());
+        self.final_answer_tool = Some(toolname);
+        self
+    }
+
     // Add an MCP tool (from `rmcp`) to the agent
     #[cfg_attr(docsrs, doc(cfg(feature = "rmcp")))]
     #[cfg(feature = "rmcp")]
@@ -124,6 +301,15 @@ impl<M: CompletionModel> AgentBuilder<M> {
         self
     }
 
+    /// Add static tools to the agent under a namespace: each tool is advertised to the model as
+    /// `{namespace}_{name}` and dispatched back to the original tool (see
+    /// [ToolSet::add_tools_namespaced]).
+    pub fn namespaced_tools(mut self, namespace: &str, toolset: ToolSet) -> Self {
+        let toolnames = self.tools.add_tools_namespaced(namespace, toolset);
+        self.static_tools.extend(toolnames);
+        self
+    }
+
     /// Add some dynamic context to the agent. On each prompt, `sample` documents from the
     /// dynamic context will be inserted in the request.
     pub fn dynamic_context(
@@ -136,6 +322,37 @@ impl<M: CompletionModel> AgentBuilder<M> {
         self
     }
 
+    /// Set a predicate deciding, given the RAG text of a prompt, whether documents are
+    /// retrieved from the dynamic context, e.g.: to skip the vector search for greetings
+    /// ("hi", "thanks") which would only add latency and irrelevant context. When retrieval
+    /// is skipped, the prompt is sent without dynamic context documents.
+    ///
+    /// By default, documents are retrieved for every prompt. [is_substantive_query](super::is_substantive_query)
+    /// implements a simple heuristic skipping short greetings and acknowledgements:
+    /// ```rust,ignore
+    /// let agent = openai.agent(openai::GPT_4O)
+    ///     .dynamic_context(2, index)
+    ///     .should_retrieve(rig::agent::is_substantive_query)
+    ///     .build();
+    /// ```
+    pub fn should_retrieve(
+        mut self,
+        should_retrieve: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.should_retrieve = Some(Arc::new(should_retrieve));
+        self
+    }
+
+    /// Same as [AgentBuilder::dynamic_context], for an already boxed vector index.
+    pub(super) fn boxed_dynamic_context(
+        mut self,
+        sample: usize,
+        dynamic_context: Box<dyn VectorStoreIndexDyn>,
+    ) -> Self {
+        self.dynamic_context.push((sample, dynamic_context));
+        self
+    }
+
     /// Add some dynamic tools to the agent. On each prompt, `sample` tools from the
     /// dynamic toolset will be inserted in the request.
     pub fn dynamic_tools(
@@ -161,6 +378,205 @@ impl<M: CompletionModel> AgentBuilder<M> {
         self
     }
 
+    /// Set whether the model may call multiple tools in a single turn (only supported by some
+    /// providers, e.g.: OpenAI, Anthropic). When disabled, the model calls at most one tool per
+    /// turn, which is useful for tools that must be called in a specific order.
+    pub fn parallel_tool_calls(mut self, parallel_tool_calls: bool) -> Self {
+        self.parallel_tool_calls = Some(parallel_tool_calls);
+        self
+    }
+
+    /// Set a redactor that scrubs sensitive content (e.g.: PII, secrets) from the text of the
+    /// prompt, chat history, preamble and context documents right before each completion
+    /// request (see [crate::redact]). Tool definitions are sent as-is.
+    pub fn redactor(mut self, redactor: impl Redactor + 'static) -> Self {
+        self.redactor = Some(Arc::new(redactor));
+        self
+    }
+
+    /// Set whether the redactor is also applied to the text of tool results (defaults to `false`).
+    pub fn redact_tool_results(mut self, redact_tool_results: bool) -> Self {
+        self.redact_tool_results = redact_tool_results;
+        self
+    }
+
+    /// Set a moderator checking the text of each prompt before any completion request (see
+    /// [crate::moderation]). Flagged prompts fail with
+    /// [PromptError::Moderated](crate::completion::PromptError::Moderated) without reaching
+    /// the model.
+    pub fn moderator(mut self, moderator: impl Moderator + 'static) -> Self {
+        self.moderator = Some(Arc::new(moderator));
+        self
+    }
+
+    /// Set whether the moderator also checks the final response of prompts (defaults to `false`).
+    ///
+    /// When streaming (see [crate::streaming::StreamingPrompt]), the text of each turn is then
+    /// held back until the turn is complete and allowed by the moderator.
+    pub fn moderate_output(mut self, moderate_output: bool) -> Self {
+        self.moderate_output = moderate_output;
+        self
+    }
+
+    /// Set size limits for the images and documents of the prompt and chat history, checked
+    /// (and, for the image dimensions, enforced by downscaling) right before each completion
+    /// request (see [crate::media_limits]). Requests with content over the limits fail with a
+    /// [CompletionError::RequestError](crate::completion::CompletionError::RequestError).
+    pub fn media_limits(mut self, media_limits: MediaLimits) -> Self {
+        self.media_limits = Some(media_limits);
+        self
+    }
+
+    /// Set what to do when the model returns multiple tool calls with the same id in a single
+    /// turn (see [DuplicateToolCallIds]). Defaults to renaming the duplicate tool calls.
+    pub fn duplicate_tool_call_ids(
+        mut self,
+        duplicate_tool_call_ids: DuplicateToolCallIds,
+    ) -> Self {
+        self.duplicate_tool_call_ids = duplicate_tool_call_ids;
+        self
+    }
+
+    /// Pause prompt requests when the model calls the tool named `name` instead of calling it:
+    /// the request fails with [PromptError::PendingToolCalls](crate::completion::PromptError::PendingToolCalls),
+    /// which lets the caller provide the result of the tool call (e.g.: after a human approved
+    /// it) and continue the prompt (see [super::PendingToolCalls]).
+    ///
+    /// The tool must still be added to the agent (e.g.: with [AgentBuilder::tool]) for the model
+    /// to know its definition. Streaming prompt requests stop the same way: the stream ends with
+    /// the [PromptError::PendingToolCalls](crate::completion::PromptError::PendingToolCalls)
+    /// error once the turn is complete.
+    pub fn pause_on_tool(mut self, name: &str) -> Self {
+        self.pause_on_tools.insert(name.to_string());
+        self
+    }
+
+    /// Set a handler receiving all the tool calls of each turn at once, e.g.: to batch the
+    /// external requests of several tool calls into a single one (see [BatchToolHandler]). The
+    /// tool calls it does not return a result for are dispatched to their tool as usual.
+    pub fn batch_tool_handler(mut self, handler: impl BatchToolHandler + 'static) -> Self {
+        self.tools.set_batch_handler(handler);
+        self
+    }
+
+    /// Set a function formatting the text of tool results before they are sent back to the
+    /// model, e.g.: to frame them as `Observation: ...` or wrap them in XML tags, which some
+    /// models respond better to. The function is given the tool call and the text output of the
+    /// tool (the JSON serialization of its output by default), and returns the text to send.
+    ///
+    /// The formatter is not applied to non-text tool results (e.g.: images) nor to the results
+    /// passed to [PromptHook::on_tool_result](super::PromptHook::on_tool_result).
+    pub fn tool_result_formatter(
+        mut self,
+        formatter: impl Fn(&ToolCall, &str) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.tool_result_formatter = Some(Arc::new(formatter));
+        self
+    }
+
+    /// Set whether tool calls whose arguments do not match the arguments of the tool are sent
+    /// back to the model to be retried (defaults to `false`, in which case the prompt fails).
+    ///
+    /// When enabled, the result of such a tool call describes the error along with the JSON
+    /// schema of the tool's arguments, and the model can call the tool again in the next turn
+    /// (up to [AgentBuilder::max_arg_retries] times per prompt, the prompt fails afterwards).
+    /// Note that the retries count towards the prompt's depth (see [PromptRequest::multi_turn](super::PromptRequest::multi_turn)).
+    pub fn validate_tool_args(mut self, validate_tool_args: bool) -> Self {
+        self.validate_tool_args = validate_tool_args;
+        self
+    }
+
+    /// Set the maximum number of tool calls with invalid arguments sent back to the model per
+    /// prompt (see [AgentBuilder::validate_tool_args]). Defaults to [DEFAULT_MAX_ARG_RETRIES].
+    pub fn max_arg_retries(mut self, max_arg_retries: usize) -> Self {
+        self.max_arg_retries = max_arg_retries;
+        self
+    }
+
+    /// Set whether a unique id (a UUID v4) is generated for each assistant message the agent
+    /// adds to the chat history (defaults to `false`, in which case their id is `None`).
+    ///
+    /// The ids give every assistant message a stable handle (e.g.: for logging, editing or
+    /// regenerating from a given message) and are kept when the chat history is serialized.
+    /// User messages have no id: use [MessageMetadata](crate::completion::history::MessageMetadata)
+    /// to attach data to them.
+    pub fn generate_message_ids(mut self, generate_message_ids: bool) -> Self {
+        self.generate_message_ids = generate_message_ids;
+        self
+    }
+
+    /// Set whether the reasoning of reasoning models (e.g.: OpenAI's o-series, Claude with
+    /// extended thinking) is included in the text returned by prompts (defaults to `false`).
+    ///
+    /// When enabled, the reasoning of the final response is prepended to its text, between
+    /// `<reasoning>` and `</reasoning>` tags. Either way, the reasoning is kept in the chat
+    /// history as [AssistantContent::Reasoning](crate::message::AssistantContent::Reasoning)
+    /// (and redacted by the agent's redactor, if any), and it is never mistaken for the final
+    /// answer of the model.
+    pub fn include_reasoning(mut self, include_reasoning: bool) -> Self {
+        self.include_reasoning = include_reasoning;
+        self
+    }
+
+    /// Validate the arguments of tool calls against the parameters schema of their tool with
+    /// `validator` (e.g.: [BasicSchemaValidator](crate::schema::BasicSchemaValidator)) before
+    /// calling the tool, in addition to their deserialization into the tool's arguments.
+    ///
+    /// Invalid arguments fail the tool call with a [ToolError::SchemaValidationError](crate::tool::ToolError::SchemaValidationError),
+    /// which is sent back to the model if [AgentBuilder::validate_tool_args] is enabled.
+    /// The arguments of streaming tools are not validated, as they are streamed to the tool.
+    pub fn schema_validator(mut self, validator: impl SchemaValidator + 'static) -> Self {
+        self.schema_validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// Retry the completion request of each turn of a prompt that fails with a transient error
+    /// (e.g.: network error, rate limiting, server error, see [RetryPolicy]), instead of failing
+    /// the whole prompt. Can be overridden per prompt with
+    /// [PromptRequest::completion_retry](super::PromptRequest::completion_retry) (or
+    /// [StreamingPromptRequest::completion_retry](super::StreamingPromptRequest::completion_retry)
+    /// for streamed prompts).
+    pub fn completion_retry(mut self, policy: RetryPolicy) -> Self {
+        self.completion_retry = Some(policy);
+        self
+    }
+
+    /// Log the content of the messages of the completion requests and responses of prompts
+    /// (at the `DEBUG` level of the `rig` target), each line truncated to `max_chars`
+    /// characters. Useful to debug prompt construction.
+    ///
+    /// Without this option, only a summary of the requests and responses (number of messages,
+    /// roles, tool calls) is logged, as their content may contain sensitive data. The content
+    /// is logged after redaction (see [AgentBuilder::redactor]).
+    pub fn log_prompt_content(mut self, max_chars: usize) -> Self {
+        self.log_prompt_content = Some(max_chars);
+        self
+    }
+
+    /// Keep the response of the provider to the last completion request of prompts as JSON
+    /// in [PromptResponse::raw](crate::agent::PromptResponse::raw) (defaults to `false`), e.g.:
+    /// to access provider-specific fields (system fingerprint, logprobs, citations) that rig
+    /// doesn't model in its completion response.
+    ///
+    /// The JSON is the serialization of the provider's response type
+    /// ([CompletionModel::Response](crate::completion::CompletionModel::Response)).
+    pub fn keep_raw(mut self, keep_raw: bool) -> Self {
+        self.keep_raw = keep_raw;
+        self
+    }
+
+    /// Set a predicate deciding, given the name of a tool, whether the tool is currently
+    /// available to the agent, e.g.: to gate tools behind a feature flag, the tier of the user
+    /// or the state of the application without rebuilding the agent.
+    ///
+    /// The predicate is evaluated for each completion request: unavailable tools (static or
+    /// dynamic) are not sent to the model, and calls to them fail with
+    /// [ToolSetError::ToolUnavailable](crate::tool::ToolSetError::ToolUnavailable).
+    pub fn tool_filter(mut self, filter: impl Fn(&str) -> bool + Send + Sync + 'static) -> Self {
+        self.tool_filter = Some(Arc::new(filter));
+        self
+    }
+
     /// Set additional parameters to be passed to the model
     pub fn additional_params(mut self, params: serde_json::Value) -> Self {
         self.additional_params = Some(params);
@@ -169,18 +585,52 @@ impl<M: CompletionModel> AgentBuilder<M> {
 
     /// Build the agent
     pub fn build(self) -> Agent<M> {
+        let preamble = match (&self.name, self.preamble) {
+            (Some(name), Some(preamble)) if self.name_in_preamble => {
+                format!("You are {name}.\n{preamble}")
+            }
+            (Some(name), None) if self.name_in_preamble => format!("You are {name}."),
+            (_, preamble) => preamble.unwrap_or_default(),
+        };
+
         Agent {
             name: self.name,
             model: Arc::new(self.model),
-            preamble: self.preamble.unwrap_or_default(),
-            static_context: self.static_context,
+            preamble,
+            static_context: Arc::new(self.static_context),
             static_tools: self.static_tools,
             temperature: self.temperature,
             max_tokens: self.max_tokens,
+            parallel_tool_calls: self.parallel_tool_calls,
             additional_params: self.additional_params,
             dynamic_context: Arc::new(self.dynamic_context),
             dynamic_tools: Arc::new(self.dynamic_tools),
             tools: Arc::new(self.tools),
+            final_answer_tool: self.final_answer_tool,
+            redactor: self.redactor,
+            redact_tool_results: self.redact_tool_results,
+            duplicate_tool_call_ids: self.duplicate_tool_call_ids,
+            pause_on_tools: self.pause_on_tools,
+            tool_result_formatter: self.tool_result_formatter,
+            validate_tool_args: self.validate_tool_args,
+            max_arg_retries: self.max_arg_retries,
+            media_limits: self.media_limits,
+            generate_message_ids: self.generate_message_ids,
+            include_reasoning: self.include_reasoning,
+            completion_retry: self.completion_retry,
+            schema_validator: self.schema_validator,
+            tool_filter: self.tool_filter,
+            should_retrieve: self.should_retrieve,
+            log_prompt_content: self.log_prompt_content,
+            keep_raw: self.keep_raw,
+            moderator: self.moderator,
+            moderate_output: self.moderate_output,
+            locale: self.locale,
+            context_policy: self.context_policy,
+            tool_call_style: self.tool_call_style,
+            sequential_tools: self.sequential_tools,
+            tool_retrieval: self.tool_retrieval,
+            citations: self.citations,
         }
     }
 }
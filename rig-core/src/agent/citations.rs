@@ -0,0 +1,223 @@
+//! This module implements the citations of RAG agents (see
+//! [AgentBuilder::citations](super::AgentBuilder::citations)): the model is instructed to end
+//! its answer with the sources it used, as JSON, which are parsed into [Citation]s and removed
+//! from the answer.
+//!
+//! # Example
+//! ```rust,ignore
+//! use rig::completion::Prompt;
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .dynamic_context(3, index)
+//!     .citations(true)
+//!     .build();
+//!
+//! let response = agent.prompt("When was the company founded?").extended_details().await?;
+//! for citation in response.citations {
+//!     println!("{}: \"{}\"", citation.doc_id, citation.quote);
+//! }
+//! ```
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use crate::completion::{CompletionRequest, Document};
+
+/// Marker preceding the citations at the end of the answers of the model.
+const CITATIONS: &str = "Citations:";
+
+/// A passage of a context document supporting the answer of an agent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Citation {
+    /// The id of the cited document
+    pub doc_id: String,
+    /// The cited passage, as quoted by the model
+    pub quote: String,
+    /// The byte range of the quote in the text of the document, if it was quoted verbatim
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Range<usize>>,
+}
+
+/// Instruct the model to cite the documents of `request`, at the end of the preamble. Requests
+/// without documents are left as is.
+pub(crate) fn prepare_request(request: &mut CompletionRequest) {
+    if request.documents.is_empty() {
+        return;
+    }
+
+    let instructions = format!(
+        "When your answer uses the content of the attached files, end it with a line \
+        containing `{CITATIONS}` followed by a JSON array of the passages you used, quoted \
+        verbatim, in the following format:\n\n\
+        {CITATIONS} [{{\"doc_id\": \"<the id of the file>\", \"quote\": \"<the passage>\"}}]"
+    );
+    request.preamble = Some(match request.preamble.take() {
+        Some(preamble) if !preamble.is_empty() => format!("{preamble}\n\n{instructions}"),
+        _ => instructions,
+    });
+}
+
+/// Split the citations off the end of `answer`. Citations of documents which are not part of
+/// `documents` are dropped, and the span of each quote in its document is located.
+///
+/// The answer is returned as is if it does not end with valid citations.
+pub(crate) fn parse(answer: &str, documents: &[Document]) -> (String, Vec<Citation>) {
+    #[derive(Deserialize)]
+    struct RawCitation {
+        doc_id: String,
+        quote: String,
+    }
+
+    let Some(index) = answer.rfind(CITATIONS) else {
+        return (answer.to_string(), vec![]);
+    };
+    let json = answer[index + CITATIONS.len()..]
+        .trim()
+        .trim_start_matches("```json")
+        .trim_matches('`')
+        .trim();
+    let citations = match serde_json::from_str::<Vec<RawCitation>>(json) {
+        Ok(citations) => citations,
+        Err(e) => {
+            tracing::warn!(target: "rig", "Failed to parse the citations of the answer: {e}");
+            return (answer.to_string(), vec![]);
+        }
+    };
+
+    let citations = citations
+        .into_iter()
+        .filter_map(|citation| {
+            let Some(document) = documents.iter().find(|doc| doc.id == citation.doc_id) else {
+                tracing::warn!(
+                    target: "rig",
+                    "Dropping the citation of unknown document {}",
+                    citation.doc_id
+                );
+                return None;
+            };
+            let span = document
+                .text
+                .find(&citation.quote)
+                .map(|start| start..start + citation.quote.len());
+
+            Some(Citation {
+                doc_id: citation.doc_id,
+                quote: citation.quote,
+                span,
+            })
+        })
+        .collect();
+
+    (answer[..index].trim_end().to_string(), citations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Citation, parse};
+    use crate::{
+        agent::AgentBuilder,
+        completion::Prompt,
+        test_utils::{MockCompletionModel, document},
+    };
+
+    #[test]
+    fn test_parse() {
+        let documents = vec![document("doc1", "Rig was founded in 2024 by Playgrounds.")];
+        let (answer, citations) = parse(
+            "Rig was founded in 2024.\n\nCitations: [\
+            {\"doc_id\": \"doc1\", \"quote\": \"founded in 2024\"}, \
+            {\"doc_id\": \"doc1\", \"quote\": \"founded last year\"}, \
+            {\"doc_id\": \"doc2\", \"quote\": \"founded in 2024\"}]",
+            &documents,
+        );
+
+        assert_eq!(answer, "Rig was founded in 2024.");
+        // The paraphrased quote has no span, and the unknown document is dropped
+        assert_eq!(
+            citations,
+            vec![
+                Citation {
+                    doc_id: "doc1".to_string(),
+                    quote: "founded in 2024".to_string(),
+                    span: Some(8..23),
+                },
+                Citation {
+                    doc_id: "doc1".to_string(),
+                    quote: "founded last year".to_string(),
+                    span: None,
+                },
+            ]
+        );
+
+        // Answers without valid citations are kept as is
+        let (answer, citations) = parse("I don't know.", &documents);
+        assert_eq!((answer.as_str(), citations.len()), ("I don't know.", 0));
+        let (answer, citations) = parse("Citations: none", &documents);
+        assert_eq!((answer.as_str(), citations.len()), ("Citations: none", 0));
+    }
+
+    #[tokio::test]
+    async fn test_agent_citations() {
+        let model = MockCompletionModel::new().with_text(
+            "It was founded in 2024.\nCitations: [{\"doc_id\": \"static_doc_0\", \"quote\": \"in 2024\"}]",
+        );
+        let agent = AgentBuilder::new(model.clone())
+            .preamble("You are a helpful assistant.")
+            .context("Rig was founded in 2024.")
+            .citations(true)
+            .build();
+
+        let response = agent
+            .prompt("When was Rig founded?")
+            .extended_details()
+            .await
+            .unwrap();
+        assert_eq!(response.output, "It was founded in 2024.");
+        assert_eq!(response.citations.len(), 1);
+        assert_eq!(response.citations[0].span, Some(16..23));
+
+        let preamble = model.requests()[0].preamble.clone().unwrap();
+        assert!(preamble.starts_with("You are a helpful assistant.\n\n"));
+        assert!(preamble.contains("Citations:"));
+    }
+
+    #[tokio::test]
+    async fn test_stream_citations() {
+        use futures::StreamExt;
+
+        use crate::{agent::MultiTurnStreamItem, streaming::StreamingPrompt};
+
+        let model = MockCompletionModel::new().with_text(
+            "It was founded in 2024.\nCitations: [{\"doc_id\": \"static_doc_0\", \"quote\": \"in 2024\"}]",
+        );
+        let agent = AgentBuilder::new(model.clone())
+            .context("Rig was founded in 2024.")
+            .citations(true)
+            .build();
+
+        let mut stream = agent.stream_prompt("When was Rig founded?").await;
+        let mut text = String::new();
+        let mut final_response = None;
+        while let Some(item) = stream.next().await {
+            match item.unwrap() {
+                MultiTurnStreamItem::Text(chunk) => text.push_str(&chunk.text),
+                MultiTurnStreamItem::FinalResponse(response) => final_response = Some(response),
+                _ => {}
+            }
+        }
+
+        // The citations are neither streamed nor part of the response
+        assert_eq!(text, "It was founded in 2024.\n");
+        let final_response = final_response.unwrap();
+        assert_eq!(final_response.response(), "It was founded in 2024.");
+        assert_eq!(final_response.citations().len(), 1);
+        assert_eq!(final_response.citations()[0].span, Some(16..23));
+        assert!(
+            model.requests()[0]
+                .preamble
+                .as_ref()
+                .unwrap()
+                .contains("Citations:")
+        );
+    }
+}
@@ -1,19 +1,42 @@
-use super::prompt_request::{self, PromptRequest};
+use super::{
+    prompt_request::{
+        self, DuplicateToolCallIds, PromptRequest, RetrievalTrace, ToolResultFormatter,
+    },
+    react::ToolCallStyle,
+    tool_retrieval::ToolRetrieval,
+};
 use crate::{
     agent::prompt_request::streaming::StreamingPromptRequest,
     completion::{
-        Chat, Completion, CompletionError, CompletionModel, CompletionRequestBuilder, Document,
-        GetTokenUsage, Message, Prompt, PromptError,
+        Chat, Completion, CompletionError, CompletionModel, CompletionRequestBuilder,
+        CompletionResponse, Document, GetTokenUsage, Message, Prompt, PromptError, RetryPolicy,
+        ToolDefinition,
     },
+    context_policy::{ContextPolicy, estimate_text_tokens, estimate_tokens},
+    media_limits::MediaLimits,
+    moderation::ModeratorDyn,
+    redact::{Redactor, redact_message},
+    schema::SchemaValidator,
     streaming::{StreamingChat, StreamingCompletion, StreamingPrompt},
-    tool::ToolSet,
-    vector_store::{VectorStoreError, request::VectorSearchRequest},
+    tool::{ToolSet, ToolSetError},
+    vector_store::{ScoredDocument, VectorStoreError, request::VectorSearchRequest},
 };
 use futures::{StreamExt, TryStreamExt, stream};
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 const UNKNOWN_AGENT_NAME: &str = "Unnamed Agent";
 
+/// Predicate deciding, given the name of a tool, whether the tool is currently available to
+/// the agent (see [crate::agent::AgentBuilder::tool_filter]).
+pub type ToolFilter = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// Predicate deciding, given the RAG text of a prompt, whether documents are retrieved from
+/// the dynamic context of the agent (see [crate::agent::AgentBuilder::should_retrieve]).
+pub type RetrievalFilter = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
 /// Struct representing an LLM agent. An agent is an LLM model combined with a preamble
 /// (i.e.: system prompt) and a static set of context documents and tools.
 /// All context documents and tools are always provided to the agent when prompted.
@@ -34,6 +57,16 @@ const UNKNOWN_AGENT_NAME: &str = "Unnamed Agent";
 ///     .await
 ///     .expect("Failed to prompt the agent");
 /// ```
+///
+/// Cloning an agent is cheap: its model, tools, context documents and vector indexes are
+/// shared (behind [Arc]s) between the clones, and only its small configuration values (e.g.:
+/// preamble, temperature) are copied, regardless of how many tools and documents it has. To
+/// prompt an agent from many tasks concurrently (e.g.: in a web server), clone it into each
+/// task instead of sharing a reference to it:
+/// ```rust,ignore
+/// let agent = agent.clone();
+/// tokio::spawn(async move { agent.prompt("Hello!").await });
+/// ```
 #[derive(Clone)]
 #[non_exhaustive]
 pub struct Agent<M: CompletionModel> {
@@ -44,13 +77,15 @@ pub struct Agent<M: CompletionModel> {
     /// System prompt
     pub preamble: String,
     /// Context documents always available to the agent
-    pub static_context: Vec<Document>,
+    pub static_context: Arc<Vec<Document>>,
     /// Tools that are always available to the agent (identified by their name)
     pub static_tools: Vec<String>,
     /// Temperature of the model
     pub temperature: Option<f64>,
     /// Maximum number of tokens for the completion
     pub max_tokens: Option<u64>,
+    /// Whether the model may call multiple tools in a single turn
+    pub parallel_tool_calls: Option<bool>,
     /// Additional parameters to be passed to the model
     pub additional_params: Option<serde_json::Value>,
     /// List of vector store, with the sample number
@@ -59,6 +94,57 @@ pub struct Agent<M: CompletionModel> {
     pub dynamic_tools: Arc<Vec<(usize, Box<dyn crate::vector_store::VectorStoreIndexDyn>)>>,
     /// Actual tool implementations
     pub tools: Arc<ToolSet>,
+    /// Name of the tool whose invocation ends a multi-turn prompt (see [super::FinalAnswerTool])
+    pub final_answer_tool: Option<String>,
+    /// Redactor applied to the prompt and chat history before each completion request
+    pub redactor: Option<Arc<dyn Redactor>>,
+    /// Whether the redactor is also applied to tool results
+    pub redact_tool_results: bool,
+    /// What to do when the model returns multiple tool calls with the same id in a single turn
+    pub duplicate_tool_call_ids: DuplicateToolCallIds,
+    /// Names of the tools whose result is provided by the caller (see [super::PendingToolCalls])
+    pub pause_on_tools: HashSet<String>,
+    /// Function formatting the text of tool results before they are sent back to the model
+    pub tool_result_formatter: Option<ToolResultFormatter>,
+    /// Whether tool calls with invalid arguments are sent back to the model to be retried
+    pub validate_tool_args: bool,
+    /// Maximum number of tool calls with invalid arguments sent back to the model per prompt
+    pub max_arg_retries: usize,
+    /// Size limits applied to the images and documents of the prompt and chat history
+    pub media_limits: Option<MediaLimits>,
+    /// Whether an id is generated for the assistant messages added to the chat history
+    pub generate_message_ids: bool,
+    /// Whether the reasoning of the model is included in the text returned by prompts
+    pub include_reasoning: bool,
+    /// Retry policy of the completion requests of prompts
+    pub completion_retry: Option<RetryPolicy>,
+    /// Validator of the arguments of tool calls against the parameters schema of the tools
+    pub schema_validator: Option<Arc<dyn SchemaValidator>>,
+    /// Predicate deciding which tools are currently available to the agent
+    pub tool_filter: Option<ToolFilter>,
+    /// Predicate deciding which prompts warrant retrieving documents from the dynamic context
+    pub should_retrieve: Option<RetrievalFilter>,
+    /// Maximum number of characters of each message logged at debug level, if the content of
+    /// the requests and responses of prompts is logged
+    pub log_prompt_content: Option<usize>,
+    /// Whether the raw provider response is kept in the responses of prompts
+    pub keep_raw: bool,
+    /// Moderator checking the text of prompts before any completion request
+    pub moderator: Option<Arc<dyn ModeratorDyn>>,
+    /// Whether the moderator also checks the final response of prompts
+    pub moderate_output: bool,
+    /// Default locale of the responses of the agent (e.g.: `fr-FR`)
+    pub locale: Option<String>,
+    /// Policy trimming the chat history to a token budget before each completion request
+    pub context_policy: Option<ContextPolicy>,
+    /// How the model calls the tools of the agent
+    pub tool_call_style: ToolCallStyle,
+    /// Whether the tool calls of a turn are executed one at a time
+    pub sequential_tools: bool,
+    /// Selection of the static tools advertised for each prompt
+    pub tool_retrieval: Option<Arc<ToolRetrieval>>,
+    /// Whether the model cites the context documents of its answers
+    pub citations: bool,
 }
 
 impl<M: CompletionModel> Completion<M> for Agent<M> {
@@ -68,7 +154,47 @@ impl<M: CompletionModel> Completion<M> for Agent<M> {
         prompt: impl Into<Message> + Send,
         chat_history: Vec<Message>,
     ) -> Result<CompletionRequestBuilder<M>, CompletionError> {
-        let prompt = prompt.into();
+        self.completion_with_retrieval(prompt.into(), chat_history)
+            .await
+            .map(|(builder, _)| builder)
+    }
+}
+
+impl<M: CompletionModel> Agent<M> {
+    /// Same as [Completion::completion], also returning the documents retrieved from the
+    /// dynamic context of the agent for the request.
+    pub(crate) async fn completion_with_retrieval(
+        &self,
+        prompt: Message,
+        chat_history: Vec<Message>,
+    ) -> Result<(CompletionRequestBuilder<M>, Vec<ScoredDocument>), CompletionError> {
+        let mut prompt = prompt;
+        let mut chat_history = chat_history;
+
+        // Scrub sensitive content before anything leaves the process (including RAG queries).
+        // The preamble and the context documents are redacted as the request is built below.
+        if let Some(redactor) = &self.redactor {
+            prompt = redact_message(redactor.as_ref(), prompt, self.redact_tool_results);
+            chat_history = chat_history
+                .into_iter()
+                .map(|message| redact_message(redactor.as_ref(), message, self.redact_tool_results))
+                .collect();
+        }
+
+        if let Some(media_limits) = &self.media_limits {
+            prompt = media_limits
+                .apply(prompt)
+                .map_err(|e| CompletionError::RequestError(Box::new(e)))?;
+            chat_history = chat_history
+                .into_iter()
+                .map(|message| media_limits.apply(message))
+                .collect::<Result<_, _>>()
+                .map_err(|e| CompletionError::RequestError(Box::new(e)))?;
+        }
+
+        if let Some(context_policy) = &self.context_policy {
+            chat_history = context_policy.apply(chat_history, &prompt);
+        }
 
         // Find the latest message in the chat history that contains RAG text
         let rag_text = prompt.rag_text();
@@ -82,44 +208,40 @@ impl<M: CompletionModel> Completion<M> for Agent<M> {
         let completion_request = self
             .model
             .completion_request(prompt)
-            .preamble(self.preamble.clone())
+            .preamble(self.preamble_with_locale(self.locale.as_deref()))
             .messages(chat_history)
             .temperature_opt(self.temperature)
             .max_tokens_opt(self.max_tokens)
+            .parallel_tool_calls_opt(self.parallel_tool_calls)
             .additional_params_opt(self.additional_params.clone())
-            .documents(self.static_context.clone());
+            .documents(
+                self.static_context
+                    .iter()
+                    .map(|document| self.redact_document(document))
+                    .collect::<Vec<_>>(),
+            );
 
         // If the agent has RAG text, we need to fetch the dynamic context and tools
-        let agent = match &rag_text {
+        let (agent, retrieved) = match &rag_text {
             Some(text) => {
-                let dynamic_context = stream::iter(self.dynamic_context.iter())
-                    .then(|(num_sample, index)| async {
-                        let req = VectorSearchRequest::builder().query(text).samples(*num_sample as u64).build().expect("Creating VectorSearchRequest here shouldn't fail since the query and samples to return are always present");
-                        Ok::<_, VectorStoreError>(
-                            index
-                                .top_n(req)
-                                .await?
-                                .into_iter()
-                                .map(|(_, id, doc)| {
-                                    // Pretty print the document if possible for better readability
-                                    let text = serde_json::to_string_pretty(&doc)
-                                        .unwrap_or_else(|_| doc.to_string());
-
-                                    Document {
-                                        id,
-                                        text,
-                                        additional_props: HashMap::new(),
-                                    }
-                                })
-                                .collect::<Vec<_>>(),
-                        )
-                    })
-                    .try_fold(vec![], |mut acc, docs| async {
-                        acc.extend(docs);
-                        Ok(acc)
-                    })
+                let retrieved = self
+                    .retrieve(text)
                     .await
                     .map_err(|e| CompletionError::RequestError(Box::new(e)))?;
+                let dynamic_context = retrieved
+                    .iter()
+                    .map(|retrieved| {
+                        // Pretty print the document if possible for better readability
+                        let text = serde_json::to_string_pretty(&retrieved.document)
+                            .unwrap_or_else(|_| retrieved.document.to_string());
+
+                        self.redact_document(&Document {
+                            id: retrieved.id.clone(),
+                            text,
+                            additional_props: HashMap::new(),
+                        })
+                    })
+                    .collect::<Vec<_>>();
 
                 let dynamic_tools = stream::iter(self.dynamic_tools.iter())
                     .then(|(num_sample, index)| async {
@@ -134,7 +256,7 @@ impl<M: CompletionModel> Completion<M> for Agent<M> {
                         )
                     })
                     .try_fold(vec![], |mut acc, docs| async {
-                        for doc in docs {
+                        for doc in docs.into_iter().filter(|doc| self.is_tool_available(doc)) {
                             if let Some(tool) = self.tools.get(&doc) {
                                 acc.push(tool.definition(text.into()).await)
                             } else {
@@ -146,47 +268,135 @@ impl<M: CompletionModel> Completion<M> for Agent<M> {
                     .await
                     .map_err(|e| CompletionError::RequestError(Box::new(e)))?;
 
-                let static_tools = stream::iter(self.static_tools.iter())
-                    .filter_map(|toolname| async move {
-                        if let Some(tool) = self.tools.get(toolname) {
-                            Some(tool.definition(text.into()).await)
-                        } else {
-                            tracing::warn!(
-                                "Tool implementation not found in toolset: {}",
-                                toolname
-                            );
-                            None
-                        }
-                    })
-                    .collect::<Vec<_>>()
-                    .await;
+                let static_tools = stream::iter(
+                    self.static_tools
+                        .iter()
+                        .filter(|toolname| self.is_tool_available(toolname)),
+                )
+                .filter_map(|toolname| async move {
+                    if let Some(tool) = self.tools.get(toolname) {
+                        Some(tool.definition(text.into()).await)
+                    } else {
+                        tracing::warn!("Tool implementation not found in toolset: {}", toolname);
+                        None
+                    }
+                })
+                .collect::<Vec<_>>()
+                .await;
+                let static_tools = match &self.tool_retrieval {
+                    Some(tool_retrieval) => tool_retrieval
+                        .select(text, static_tools)
+                        .await
+                        .map_err(|e| CompletionError::RequestError(Box::new(e)))?,
+                    None => static_tools,
+                };
 
-                completion_request
+                let completion_request = completion_request
                     .documents(dynamic_context)
-                    .tools([static_tools.clone(), dynamic_tools].concat())
+                    .tools([static_tools.clone(), dynamic_tools].concat());
+                (completion_request, retrieved)
             }
             None => {
-                let static_tools = stream::iter(self.static_tools.iter())
-                    .filter_map(|toolname| async move {
-                        if let Some(tool) = self.tools.get(toolname) {
-                            // TODO: tool definitions should likely take an `Option<String>`
-                            Some(tool.definition("".into()).await)
-                        } else {
-                            tracing::warn!(
-                                "Tool implementation not found in toolset: {}",
-                                toolname
-                            );
-                            None
-                        }
-                    })
-                    .collect::<Vec<_>>()
-                    .await;
+                let static_tools = stream::iter(
+                    self.static_tools
+                        .iter()
+                        .filter(|toolname| self.is_tool_available(toolname)),
+                )
+                .filter_map(|toolname| async move {
+                    if let Some(tool) = self.tools.get(toolname) {
+                        // TODO: tool definitions should likely take an `Option<String>`
+                        Some(tool.definition("".into()).await)
+                    } else {
+                        tracing::warn!("Tool implementation not found in toolset: {}", toolname);
+                        None
+                    }
+                })
+                .collect::<Vec<_>>()
+                .await;
 
-                completion_request.tools(static_tools)
+                (completion_request.tools(static_tools), vec![])
             }
         };
 
-        Ok(agent)
+        Ok((agent, retrieved))
+    }
+
+    /// The preamble of the agent (redacted by the redactor of the agent, if any), followed by
+    /// the instruction to respond in `locale`, if any.
+    pub(crate) fn preamble_with_locale(&self, locale: Option<&str>) -> String {
+        let preamble = self.redact(&self.preamble);
+        let Some(locale) = locale else {
+            return preamble;
+        };
+
+        let instruction = format!(
+            "Respond in the language of the `{locale}` locale and follow its conventions \
+            (e.g.: for dates, numbers and currencies)."
+        );
+        if preamble.is_empty() {
+            instruction
+        } else {
+            format!("{preamble}\n\n{instruction}")
+        }
+    }
+
+    /// `text` redacted by the redactor of the agent, if any.
+    fn redact(&self, text: &str) -> String {
+        match &self.redactor {
+            Some(redactor) => redactor.redact(text),
+            None => text.to_string(),
+        }
+    }
+
+    /// `document` with its text redacted by the redactor of the agent, if any.
+    fn redact_document(&self, document: &Document) -> Document {
+        Document {
+            text: self.redact(&document.text),
+            ..document.clone()
+        }
+    }
+
+    /// Retrieve the documents of the dynamic context of the agent (see
+    /// [crate::agent::AgentBuilder::dynamic_context]) for the RAG text `text`, with their
+    /// similarity score, unless retrieval is skipped for `text` (see
+    /// [crate::agent::AgentBuilder::should_retrieve]).
+    pub async fn retrieve(&self, text: &str) -> Result<Vec<ScoredDocument>, VectorStoreError> {
+        // Trivial prompts (e.g.: greetings) are answered without retrieving documents
+        if !self.should_retrieve(text) {
+            tracing::debug!(target: "rig", "Skipping the retrieval of dynamic context");
+            return Ok(vec![]);
+        }
+
+        stream::iter(self.dynamic_context.iter())
+            .then(|(num_sample, index)| async {
+                let req = VectorSearchRequest::builder().query(text).samples(*num_sample as u64).build().expect("Creating VectorSearchRequest here shouldn't fail since the query and samples to return are always present");
+                Ok::<_, VectorStoreError>(
+                    index
+                        .top_n(req)
+                        .await?
+                        .into_iter()
+                        .map(ScoredDocument::from)
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .try_fold(vec![], |mut acc, docs| async {
+                acc.extend(docs);
+                Ok(acc)
+            })
+            .await
+    }
+
+    /// Prompt the agent, returning its answer along with the documents retrieved from its
+    /// dynamic context and the context sent to the model (see [RetrievalTrace]), e.g.: to debug
+    /// the wrong answers of a RAG agent. With tools, the trace is the one of the last turn.
+    ///
+    /// The response of `agent.prompt(prompt).extended_details()` also contains the trace.
+    pub async fn prompt_with_retrieval_trace(
+        &self,
+        prompt: impl Into<Message> + Send,
+    ) -> Result<(String, RetrievalTrace), PromptError> {
+        let response = self.prompt(prompt).extended_details().await?;
+        Ok((response.output, response.retrieval))
     }
 }
 
@@ -285,4 +495,385 @@ impl<M: CompletionModel> Agent<M> {
     pub(crate) fn name_owned(&self) -> String {
         self.name.clone().unwrap_or(UNKNOWN_AGENT_NAME.to_string())
     }
+
+    /// Have the agent respond to an existing conversation without a new user prompt (e.g.: to
+    /// resume after a tool result was appended to the history outside of the agent).
+    /// The last message of `history` is used as the prompt, and the agent's response is appended
+    /// to `history`.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// history.push(Message::User {
+    ///     content: OneOrMany::one(UserContent::tool_result("call_1", OneOrMany::one(result.into()))),
+    /// });
+    ///
+    /// let response = agent.continue_conversation(&mut history).await?;
+    /// ```
+    pub fn continue_conversation<'a>(
+        &'a self,
+        history: &'a mut Vec<Message>,
+    ) -> PromptRequest<'a, prompt_request::Standard, M, ()> {
+        PromptRequest::from_history(self, history)
+    }
+
+    /// Blocking version of [Prompt::prompt] (see [blocking](crate::blocking) for the
+    /// runtime nesting caveats).
+    #[cfg_attr(docsrs, doc(cfg(feature = "blocking")))]
+    #[cfg(feature = "blocking")]
+    #[allow(clippy::result_large_err)]
+    pub fn prompt_blocking(
+        &self,
+        prompt: impl Into<Message> + Send,
+    ) -> Result<String, PromptError> {
+        crate::blocking::block_on(async { Prompt::prompt(self, prompt).await })
+    }
+
+    /// Run a single turn of the agent without side effects: the completion request is built and
+    /// sent like for a prompt, but nothing is added to `chat_history` and the tools the model
+    /// calls are not executed. Useful to preview the response of the model (e.g.: to estimate
+    /// the cost of a prompt).
+    pub async fn dry_run(
+        &self,
+        prompt: impl Into<Message> + Send,
+        chat_history: &[Message],
+    ) -> Result<CompletionResponse<M::Response>, CompletionError> {
+        self.completion(prompt, chat_history.to_vec())
+            .await?
+            .send()
+            .await
+    }
+
+    /// Describe how the agent is configured: its name, model, preamble and the tools it can
+    /// call (e.g.: to generate help text or to check that an agent is configured as expected).
+    pub async fn describe(&self) -> Result<AgentDescription, ToolSetError> {
+        let mut tools = self.tools.get_tool_definitions().await?;
+        tools.sort_by(|tool_1, tool_2| tool_1.name.cmp(&tool_2.name));
+
+        Ok(AgentDescription {
+            name: self.name.clone(),
+            model: self.model.model_name().map(str::to_string),
+            preamble: self.preamble.clone(),
+            static_context: self.static_context.to_vec(),
+            tools,
+        })
+    }
+
+    /// Whether documents are retrieved from the dynamic context for the RAG text `text`,
+    /// according to the agent's retrieval predicate (see
+    /// [crate::agent::AgentBuilder::should_retrieve]).
+    pub fn should_retrieve(&self, text: &str) -> bool {
+        self.should_retrieve
+            .as_ref()
+            .is_none_or(|should_retrieve| should_retrieve(text))
+    }
+
+    /// Whether the tool named `name` is currently available to the agent, according to its
+    /// tool filter (see [crate::agent::AgentBuilder::tool_filter]).
+    pub fn is_tool_available(&self, name: &str) -> bool {
+        self.tool_filter.as_ref().is_none_or(|filter| filter(name))
+    }
+
+    /// Check the tools the agent can send in a request against the limits of the provider of
+    /// `model` (see [CompletionModel::tool_limits]), e.g.: to catch an agent with too many tools
+    /// before its first request fails.
+    ///
+    /// Dynamic tools are accounted for in the worst case: the largest of them are assumed to be
+    /// retrieved, as many as the number of samples of the dynamic tool indexes.
+    pub async fn validate_tools_for(
+        &self,
+        model: &impl CompletionModel,
+    ) -> Result<(), ToolSetError> {
+        let mut tools = vec![];
+        let mut dynamic_tools = vec![];
+        for (name, tool) in &self.tools.tools {
+            let definition = tool.definition(String::new()).await;
+            if self.static_tools.contains(name) {
+                tools.push(definition);
+            } else {
+                let size = serde_json::to_string(&definition)?.len();
+                dynamic_tools.push((size, definition));
+            }
+        }
+
+        let samples = self.dynamic_tools.iter().map(|(samples, _)| samples).sum();
+        dynamic_tools.sort_by(|(size_1, _), (size_2, _)| size_2.cmp(size_1));
+        tools.extend(
+            dynamic_tools
+                .into_iter()
+                .take(samples)
+                .map(|(_, definition)| definition),
+        );
+
+        model.tool_limits().validate(&tools)
+    }
+
+    /// Estimate the number of tokens of a request of the agent with `messages` (the chat
+    /// history followed by the prompt), to compare with the context window of its model (see
+    /// [CompletionModel::context_window]), e.g.: to trim or split a conversation before the
+    /// provider rejects it.
+    ///
+    /// The preamble, the static context and the definitions of the static tools are counted,
+    /// as well as the maximum number of tokens of the response (see
+    /// [crate::agent::AgentBuilder::max_tokens]). The documents and tools retrieved from the
+    /// dynamic context are not. Tokens are estimated with
+    /// [estimate_tokens](crate::context_policy::estimate_tokens).
+    pub async fn estimate_context(&self, messages: &[Message]) -> ContextEstimate {
+        let mut tokens = estimate_text_tokens(&self.preamble_with_locale(self.locale.as_deref()));
+        tokens += self
+            .static_context
+            .iter()
+            .map(|document| estimate_text_tokens(&document.to_string()))
+            .sum::<usize>();
+        for name in &self.static_tools {
+            if let Some(tool) = self.tools.get(name)
+                && self.is_tool_available(name)
+            {
+                let definition = tool.definition(String::new()).await;
+                tokens += serde_json::to_string(&definition)
+                    .map(|json| estimate_text_tokens(&json))
+                    .unwrap_or_default();
+            }
+        }
+        tokens += messages.iter().map(estimate_tokens).sum::<usize>();
+        tokens += self.max_tokens.unwrap_or_default() as usize;
+
+        ContextEstimate {
+            tokens,
+            context_window: self.model.context_window(),
+        }
+    }
+
+    /// Whether a request of the agent with `messages` (the chat history followed by the prompt)
+    /// is estimated to fit the context window of its model (see [Agent::estimate_context]).
+    /// Always `true` if the context window of the model is unknown.
+    pub async fn will_fit(&self, messages: &[Message]) -> bool {
+        self.estimate_context(messages).await.fits()
+    }
+}
+
+/// Estimated size of a request of an [Agent] (see [Agent::estimate_context]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextEstimate {
+    /// Estimated number of tokens of the request, including the maximum number of tokens of
+    /// the response
+    pub tokens: usize,
+    /// Context window of the model in tokens, if known (see [CompletionModel::context_window])
+    pub context_window: Option<usize>,
+}
+
+impl ContextEstimate {
+    /// Whether the request fits the context window (`true` if the context window is unknown).
+    pub fn fits(&self) -> bool {
+        self.context_window
+            .is_none_or(|context_window| self.tokens <= context_window)
+    }
+
+    /// The number of tokens left in the context window, if known (negative if the request
+    /// does not fit).
+    pub fn remaining(&self) -> Option<i64> {
+        self.context_window
+            .map(|context_window| context_window as i64 - self.tokens as i64)
+    }
+}
+
+/// Description of the configuration of an [Agent] (see [Agent::describe]).
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AgentDescription {
+    /// Name of the agent
+    pub name: Option<String>,
+    /// Name of the completion model, if known (see [CompletionModel::model_name])
+    pub model: Option<String>,
+    /// System prompt
+    pub preamble: String,
+    /// Context documents always available to the agent
+    pub static_context: Vec<Document>,
+    /// Definitions of all the tools of the agent (static and dynamic), sorted by name
+    pub tools: Vec<ToolDefinition>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        agent::AgentBuilder,
+        completion::{Message, Prompt, ToolDefinition},
+        context_policy::{estimate_text_tokens, estimate_tokens},
+        message::AssistantContent,
+        test_utils::MockCompletionModel,
+        tool::Tool,
+    };
+
+    struct Search;
+
+    impl Tool for Search {
+        const NAME: &'static str = "search";
+        type Error = std::convert::Infallible;
+        type Args = serde_json::Value;
+        type Output = String;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: Self::NAME.to_string(),
+                description: "Search the web".to_string(),
+                parameters: serde_json::json!({"type": "object"}),
+            }
+        }
+
+        async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
+            Ok(String::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_describe() {
+        let agent = AgentBuilder::new(MockCompletionModel::new())
+            .name("assistant")
+            .preamble("You are a helpful assistant.")
+            .context("Some context")
+            .tool(Search)
+            .build();
+
+        let description = agent.describe().await.unwrap();
+
+        assert_eq!(description.name.as_deref(), Some("assistant"));
+        assert_eq!(description.model, None);
+        assert_eq!(description.preamble, "You are a helpful assistant.");
+        assert_eq!(description.static_context[0].text, "Some context");
+        assert_eq!(description.tools.len(), 1);
+        assert_eq!(description.tools[0].name, "search");
+        assert_eq!(description.tools[0].description, "Search the web");
+    }
+
+    #[tokio::test]
+    async fn test_will_fit() {
+        let messages = vec![Message::user("Hello"), Message::assistant("Hi!")];
+        let agent = AgentBuilder::new(MockCompletionModel::new())
+            .preamble("You are a helpful assistant.")
+            .tool(Search)
+            .max_tokens(100)
+            .build();
+
+        // The context window of the model is unknown
+        let estimate = agent.estimate_context(&messages).await;
+        assert_eq!(estimate.context_window, None);
+        assert!(estimate.fits());
+        assert!(agent.will_fit(&messages).await);
+
+        // The preamble, the tool definition, the messages and the response are counted
+        let tokens = estimate_text_tokens("You are a helpful assistant.")
+            + estimate_text_tokens(
+                &serde_json::to_string(&Search.definition(String::new()).await).unwrap(),
+            )
+            + messages.iter().map(estimate_tokens).sum::<usize>()
+            + 100;
+        assert_eq!(estimate.tokens, tokens);
+
+        let agent = AgentBuilder::new(MockCompletionModel::new().with_context_window(tokens))
+            .preamble("You are a helpful assistant.")
+            .tool(Search)
+            .max_tokens(100)
+            .build();
+        let estimate = agent.estimate_context(&messages).await;
+        assert_eq!(estimate.remaining(), Some(0));
+        assert!(agent.will_fit(&messages).await);
+
+        let mut messages = messages;
+        messages.push(Message::user("Tell me more"));
+        let estimate = agent.estimate_context(&messages).await;
+        assert!(!estimate.fits());
+        assert!(estimate.remaining().is_some_and(|remaining| remaining < 0));
+        assert!(!agent.will_fit(&messages).await);
+    }
+
+    #[tokio::test]
+    async fn test_clone_shares_agent() {
+        use crate::completion::Prompt;
+        use std::sync::Arc;
+
+        let model = (0..4).fold(MockCompletionModel::new(), |model, _| {
+            model.with_text("Hello!")
+        });
+        let agent = AgentBuilder::new(model)
+            .context("Some context")
+            .tool(Search)
+            .build();
+        let clone = agent.clone();
+
+        assert!(Arc::ptr_eq(&agent.model, &clone.model));
+        assert!(Arc::ptr_eq(&agent.tools, &clone.tools));
+        assert!(Arc::ptr_eq(&agent.static_context, &clone.static_context));
+
+        // Clones can be moved into concurrent tasks
+        let handles = (0..4)
+            .map(|_| {
+                let agent = agent.clone();
+                tokio::spawn(async move { agent.prompt("Hi!").await })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            assert_eq!(handle.await.unwrap().unwrap(), "Hello!");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_name() {
+        let agent = AgentBuilder::new(MockCompletionModel::new())
+            .name("Research assistant")
+            .name_in_preamble(true)
+            .preamble("Answer the questions of the user.")
+            .build();
+
+        assert_eq!(
+            agent.preamble,
+            "You are Research assistant.\nAnswer the questions of the user."
+        );
+
+        // The tool name of the agent does not change with its name
+        let orchestrator = AgentBuilder::new(MockCompletionModel::new())
+            .tool(agent)
+            .build();
+        let description = orchestrator.describe().await.unwrap();
+        assert_eq!(description.tools[0].name, "agent_tool");
+    }
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let model =
+            MockCompletionModel::new().with_tool_call("call_1", "search", serde_json::json!({}));
+        let agent = AgentBuilder::new(model.clone()).tool(Search).build();
+
+        let history = vec![Message::user("Hello"), Message::assistant("Hi!")];
+        let response = agent.dry_run("Search the web", &history).await.unwrap();
+
+        // The tool call is returned but not executed
+        assert!(matches!(
+            response.choice.first(),
+            AssistantContent::ToolCall(tool_call) if tool_call.function.name == "search"
+        ));
+        assert_eq!(model.requests().len(), 1);
+        assert_eq!(model.requests()[0].chat_history.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_redact_preamble_and_context() {
+        let model = MockCompletionModel::new().with_text("Hi!");
+        let agent = AgentBuilder::new(model.clone())
+            .preamble("The admin password is secret.")
+            .context("The backup password is secret too.")
+            .redactor(|text: &str| text.replace("secret", "[REDACTED]"))
+            .locale("fr-FR")
+            .build();
+
+        agent.prompt("Hello").await.unwrap();
+
+        let requests = model.requests();
+        let preamble = requests[0].preamble.as_deref().unwrap();
+        assert!(preamble.starts_with("The admin password is [REDACTED].\n\n"));
+        assert!(preamble.contains("fr-FR"));
+        assert_eq!(
+            requests[0].documents[0].text,
+            "The backup password is [REDACTED] too."
+        );
+        // The agent itself is left untouched
+        assert_eq!(agent.preamble, "The admin password is secret.");
+    }
 }
@@ -0,0 +1,126 @@
+//! This module defines the [FinalAnswerTool], a tool whose invocation by the model ends a
+//! multi-turn prompt and carries the structured result of the agent.
+//!
+//! # Example
+//! ```rust,ignore
+//! use rig::{agent::FinalAnswerTool, completion::Prompt, providers::openai};
+//!
+//! #[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+//! struct Report {
+//!     summary: String,
+//!     sources: Vec<String>,
+//! }
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("Research the topic using your tools, then call `final_answer` with your report.")
+//!     .tool(WebSearch)
+//!     .final_answer_tool(FinalAnswerTool::<Report>::new())
+//!     .build();
+//!
+//! let report: Report = agent
+//!     .prompt("Research the history of the flurbo.")
+//!     .multi_turn(10)
+//!     .final_answer::<Report>()
+//!     .await?;
+//! ```
+use std::marker::PhantomData;
+
+use schemars::{JsonSchema, schema_for};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{completion::ToolDefinition, tool::Tool};
+
+/// Default name of the [FinalAnswerTool].
+pub const FINAL_ANSWER_TOOL_NAME: &str = "final_answer";
+
+/// A tool used by the model to submit its final, structured, answer of type `T`.
+///
+/// When registered on an agent with [AgentBuilder::final_answer_tool](super::AgentBuilder::final_answer_tool),
+/// a call to this tool stops the multi-turn prompt loop and the arguments of the call are
+/// returned as the answer (see [PromptRequest::final_answer](super::PromptRequest::final_answer)).
+pub struct FinalAnswerTool<T> {
+    description: String,
+    _t: PhantomData<T>,
+}
+
+impl<T> FinalAnswerTool<T> {
+    pub fn new() -> Self {
+        Self {
+            description: "Submit your final answer. Calling this tool ends the conversation."
+                .to_string(),
+            _t: PhantomData,
+        }
+    }
+
+    /// Set the description of the tool given to the model (e.g.: to explain when to call it).
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+}
+
+impl<T> Default for FinalAnswerTool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("FinalAnswerError")]
+pub struct FinalAnswerError;
+
+impl<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync> Tool
+    for FinalAnswerTool<T>
+{
+    const NAME: &'static str = FINAL_ANSWER_TOOL_NAME;
+    type Error = FinalAnswerError;
+    type Args = T;
+    type Output = T;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: self.description.clone(),
+            parameters: json!(schema_for!(T)),
+        }
+    }
+
+    async fn call(&self, answer: Self::Args) -> Result<Self::Output, Self::Error> {
+        Ok(answer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::tool::Tool;
+
+    use super::{FINAL_ANSWER_TOOL_NAME, FinalAnswerTool};
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize, schemars::JsonSchema)]
+    struct Answer {
+        city: String,
+        population: u64,
+    }
+
+    #[tokio::test]
+    async fn test_final_answer_tool() {
+        let tool = FinalAnswerTool::<Answer>::new().description("Submit the city");
+
+        let definition = tool.definition(String::new()).await;
+        assert_eq!(definition.name, FINAL_ANSWER_TOOL_NAME);
+        assert_eq!(definition.description, "Submit the city");
+        assert_eq!(definition.parameters["required"][0], "city");
+
+        let answer = tool
+            .call(Answer {
+                city: "Paris".to_string(),
+                population: 2_102_650,
+            })
+            .await
+            .unwrap();
+        assert_eq!(answer.city, "Paris");
+    }
+}
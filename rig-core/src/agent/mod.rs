@@ -61,7 +61,7 @@
 //!     .expect("Failed to send completion request");
 //! ```
 //!
-//! RAG Agent example
+//! RAG Agent example (see [RagAgentBuilder] for a builder packaging this whole flow)
 //! ```rust
 //! use rig::{
 //!     completion::Prompt,
@@ -107,15 +107,32 @@
 //!     .expect("Failed to prompt the agent");
 //! ```
 mod builder;
+mod citations;
 mod completion;
+mod final_answer;
 pub(crate) mod prompt_request;
+mod rag;
+mod react;
+pub(crate) mod summarize;
 mod tool;
+mod tool_retrieval;
 
 pub use crate::message::Text;
-pub use builder::AgentBuilder;
-pub use completion::Agent;
+pub use builder::{AgentBuilder, DEFAULT_MAX_ARG_RETRIES};
+pub use citations::Citation;
+pub use completion::{Agent, AgentDescription, ContextEstimate, RetrievalFilter, ToolFilter};
+pub use final_answer::{FINAL_ANSWER_TOOL_NAME, FinalAnswerError, FinalAnswerTool};
 pub use prompt_request::PromptHook;
 pub use prompt_request::streaming::{
-    FinalResponse, MultiTurnStreamItem, StreamingPromptRequest, stream_to_stdout,
+    FinalResponse, MultiTurnStreamItem, StreamingError, StreamingPromptRequest, stream_to_stdout,
+    stream_to_writer,
 };
-pub use prompt_request::{PromptRequest, PromptResponse};
+pub use prompt_request::{
+    AgentPromptConfig, DuplicateToolCallIds, PendingToolCalls, PromptRequest, PromptResponse,
+    RetrievalTrace, ToolResultFormatter,
+};
+pub use rag::{DEFAULT_RAG_SAMPLES, RagAgentBuilder, is_substantive_query};
+pub use react::{REACT_STOP_SEQUENCE, ToolCallStyle};
+pub use summarize::SUMMARIZE_PREAMBLE;
+pub use tool::{AgentTool, agent_as_tool};
+pub use tool_retrieval::ToolRetrieval;
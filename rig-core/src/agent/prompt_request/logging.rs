@@ -0,0 +1,160 @@
+//! Debug logging of the completion requests sent and the responses received by prompts.
+//!
+//! Requests and responses are always summarized (number of messages, roles, tool calls) at the
+//! `DEBUG` level of the `rig` target. The content of their messages is only logged when enabled
+//! with [crate::agent::AgentBuilder::log_prompt_content], since it may contain sensitive data.
+use crate::{
+    OneOrMany,
+    agent::{Agent, summarize::transcript},
+    completion::{CompletionModel, CompletionRequest, Message},
+    message::AssistantContent,
+};
+
+/// Log the completion request of a turn of a prompt.
+pub(crate) fn log_request<M: CompletionModel>(agent: &Agent<M>, request: &CompletionRequest) {
+    if !tracing::enabled!(target: "rig", tracing::Level::DEBUG) {
+        return;
+    }
+
+    let roles = request
+        .chat_history
+        .iter()
+        .map(|message| match message {
+            Message::User { .. } => "user",
+            Message::Assistant { .. } => "assistant",
+        })
+        .collect::<Vec<_>>();
+    tracing::debug!(
+        target: "rig",
+        messages = request.chat_history.len(),
+        roles = ?roles,
+        documents = request.documents.len(),
+        tools = request.tools.len(),
+        "Sending completion request"
+    );
+
+    if let Some(max_chars) = agent.log_prompt_content {
+        if let Some(preamble) = &request.preamble {
+            tracing::debug!(target: "rig", "Preamble: {}", truncate(preamble, max_chars));
+        }
+        for message in request.chat_history.iter() {
+            log_message(message, max_chars);
+        }
+    }
+}
+
+/// Log the content of the response to a turn of a prompt.
+pub(crate) fn log_response<M: CompletionModel>(agent: &Agent<M>, choice: &[AssistantContent]) {
+    if !tracing::enabled!(target: "rig", tracing::Level::DEBUG) {
+        return;
+    }
+
+    let texts = choice
+        .iter()
+        .filter(|content| matches!(content, AssistantContent::Text(_)))
+        .count();
+    let tool_calls = choice
+        .iter()
+        .filter_map(|content| match content {
+            AssistantContent::ToolCall(tool_call) => Some(tool_call.function.name.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    tracing::debug!(
+        target: "rig",
+        texts,
+        tool_calls = ?tool_calls,
+        "Received completion response"
+    );
+
+    if let Some(max_chars) = agent.log_prompt_content
+        && let Ok(content) = OneOrMany::many(choice.to_vec())
+    {
+        log_message(&Message::Assistant { id: None, content }, max_chars);
+    }
+}
+
+fn log_message(message: &Message, max_chars: usize) {
+    for line in transcript(message).lines() {
+        tracing::debug!(target: "rig", "{}", truncate(line, max_chars));
+    }
+}
+
+/// Truncate `text` to `max_chars` characters.
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!(
+            "{}... ({} more characters)",
+            &text[..end],
+            text[end..].chars().count()
+        ),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::truncate;
+    use crate::{agent::AgentBuilder, completion::Prompt, test_utils::MockCompletionModel};
+
+    /// A log writer appending to a shared buffer.
+    #[derive(Clone, Default)]
+    struct Logs(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    async fn prompt_logs(log_prompt_content: Option<usize>) -> String {
+        let logs = Logs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_ansi(false)
+            .with_writer({
+                let logs = logs.clone();
+                move || logs.clone()
+            })
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let mut agent = AgentBuilder::new(MockCompletionModel::new().with_text("Paris"));
+        if let Some(max_chars) = log_prompt_content {
+            agent = agent.log_prompt_content(max_chars);
+        }
+        agent
+            .build()
+            .prompt("What is the capital of France?")
+            .await
+            .unwrap();
+
+        String::from_utf8(logs.0.lock().unwrap().clone()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_log_prompts() {
+        let logs = prompt_logs(None).await;
+        assert!(logs.contains("Sending completion request messages=1 roles=[\"user\"]"));
+        assert!(logs.contains("Received completion response texts=1 tool_calls=[]"));
+        // The content of the messages is not logged by default
+        assert!(!logs.contains("capital"));
+
+        let logs = prompt_logs(Some(20)).await;
+        assert!(logs.contains("User: What is the ca... (16 more characters)"));
+        assert!(logs.contains("Assistant: Paris"));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("Hello", 5), "Hello");
+        assert_eq!(truncate("Héllo, world!", 5), "Héllo... (8 more characters)");
+    }
+}
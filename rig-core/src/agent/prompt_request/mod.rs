@@ -1,24 +1,47 @@
+mod logging;
 pub(crate) mod streaming;
 
-use std::{future::IntoFuture, marker::PhantomData};
+use std::{
+    collections::{HashMap, HashSet},
+    future::IntoFuture,
+    marker::PhantomData,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
 use futures::{FutureExt, StreamExt, future::BoxFuture, stream};
+use serde::Deserialize;
+use tracing::Instrument;
 
 use crate::{
     OneOrMany,
-    completion::{Completion, CompletionError, CompletionModel, Message, PromptError, Usage},
-    message::{AssistantContent, UserContent},
-    tool::ToolSetError,
+    completion::{
+        CompletionError, CompletionModel, CompletionRequest, CompletionResponse, Message, Pricing,
+        PromptError, RetryPolicy, ToolChoice, Usage,
+    },
+    message::{AssistantContent, ToolCall, ToolResultContent, UserContent},
+    telemetry,
+    tool::{ToolError, ToolSetError},
+    vector_store::ScoredDocument,
 };
 
-use super::Agent;
+use super::{
+    Agent,
+    citations::{self, Citation},
+    react::{self, ToolCallStyle},
+};
 
 pub trait PromptType {}
 pub struct Standard;
 pub struct Extended;
+pub struct FinalAnswer<T>(PhantomData<T>);
 
 impl PromptType for Standard {}
 impl PromptType for Extended {}
+impl<T> PromptType for FinalAnswer<T> {}
 
 /// A builder for creating prompt requests with customizable options.
 /// Uses generics to track which options have been set during the build process.
@@ -34,8 +57,9 @@ where
     M: CompletionModel,
     P: PromptHook<M>,
 {
-    /// The prompt message to send to the model
-    prompt: Message,
+    /// The prompt message to send to the model. If `None`, the model responds to the
+    /// existing chat history (see [PromptRequest::from_history])
+    prompt: Option<Message>,
     /// Optional chat history to include with the prompt
     /// Note: chat history needs to outlive the agent as it might be used with other agents
     chat_history: Option<&'a mut Vec<Message>>,
@@ -47,18 +71,81 @@ where
     state: PhantomData<S>,
     /// Optional per-request hook for events
     hook: Option<P>,
+    /// Optional closure to inspect or modify the completion request before each turn is sent
+    inspect_request: Option<RequestInspector<'a>>,
+    /// Optional reusable settings overriding the agent's settings
+    config: Option<AgentPromptConfig>,
+    /// Optional instant by which the whole prompt request must complete
+    deadline: Option<Instant>,
+    /// Optional retry policy of the completion requests, overriding the agent's
+    completion_retry: Option<RetryPolicy>,
+    /// Optional callback invoked when the maximum depth is exceeded
+    on_max_depth: Option<MaxDepthHandler<'a>>,
+    /// Whether a last completion request without tool calls is sent when the maximum depth is
+    /// exceeded, to get a text answer from the results of the last tool calls
+    final_answer_after_max_depth: bool,
+    /// Optional callback invoked with each message appended to the chat history
+    on_message: Option<MessageHandler<'a>>,
 }
 
+/// Closure called with the completion request of each turn, right before it is sent
+/// (see [PromptRequest::inspect_request]).
+type RequestInspector<'a> = Box<dyn FnMut(&mut CompletionRequest) + Send + 'a>;
+
+/// Callback invoked with the chat history when the maximum depth is exceeded, returning an
+/// optional response to use instead of failing (see [PromptRequest::on_max_depth]).
+type MaxDepthHandler<'a> = Box<dyn FnOnce(&[Message]) -> Option<String> + Send + 'a>;
+
+/// Callback invoked with each message appended to the chat history, whose failure aborts the
+/// prompt request (see [PromptRequest::on_message]).
+type MessageHandler<'a> = Box<
+    dyn Fn(&Message) -> BoxFuture<'a, Result<(), Box<dyn std::error::Error + Send + Sync>>>
+        + Send
+        + Sync
+        + 'a,
+>;
+
 impl<'a, M: CompletionModel> PromptRequest<'a, Standard, M, ()> {
     /// Create a new PromptRequest with the given prompt and model
     pub fn new(agent: &'a Agent<M>, prompt: impl Into<Message>) -> Self {
         Self {
-            prompt: prompt.into(),
+            prompt: Some(prompt.into()),
             chat_history: None,
             max_depth: 0,
             agent,
             state: PhantomData,
             hook: None,
+            inspect_request: None,
+            config: None,
+            deadline: None,
+            completion_retry: None,
+            on_max_depth: None,
+            final_answer_after_max_depth: false,
+            on_message: None,
+        }
+    }
+
+    /// Create a new PromptRequest that continues the given chat history without a new prompt,
+    /// i.e.: the model responds to the existing conversation (e.g.: after a tool result was
+    /// appended to the history outside of the agent).
+    ///
+    /// If the chat history is empty, sending the request returns a
+    /// [`crate::completion::request::PromptError::EmptyChatHistory`] error.
+    pub fn from_history(agent: &'a Agent<M>, history: &'a mut Vec<Message>) -> Self {
+        Self {
+            prompt: None,
+            chat_history: Some(history),
+            max_depth: 0,
+            agent,
+            state: PhantomData,
+            hook: None,
+            inspect_request: None,
+            config: None,
+            deadline: None,
+            completion_retry: None,
+            on_max_depth: None,
+            final_answer_after_max_depth: false,
+            on_message: None,
         }
     }
 }
@@ -82,8 +169,43 @@ where
             agent: self.agent,
             state: PhantomData,
             hook: self.hook,
+            inspect_request: self.inspect_request,
+            config: self.config,
+            deadline: self.deadline,
+            completion_retry: self.completion_retry,
+            on_max_depth: self.on_max_depth,
+            final_answer_after_max_depth: self.final_answer_after_max_depth,
+            on_message: self.on_message,
+        }
+    }
+
+    /// Return the arguments of the agent's final answer tool call, deserialized as `T`
+    /// (see [crate::agent::AgentBuilder::final_answer_tool]).
+    ///
+    /// Note: This changes the type of the response from `.send` to return a `T` instead of
+    /// a simple `String`. If the model answers with text instead of calling the final answer
+    /// tool, a [`crate::completion::request::PromptError::FinalAnswerError`] is returned.
+    pub fn final_answer<T>(self) -> PromptRequest<'a, FinalAnswer<T>, M, P>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        PromptRequest {
+            prompt: self.prompt,
+            chat_history: self.chat_history,
+            max_depth: self.max_depth,
+            agent: self.agent,
+            state: PhantomData,
+            hook: self.hook,
+            inspect_request: self.inspect_request,
+            config: self.config,
+            deadline: self.deadline,
+            completion_retry: self.completion_retry,
+            on_max_depth: self.on_max_depth,
+            final_answer_after_max_depth: self.final_answer_after_max_depth,
+            on_message: self.on_message,
         }
     }
+
     /// Set the maximum depth for multi-turn conversations (ie, the maximum number of turns an LLM can have calling tools before writing a text response).
     /// If the maximum turn number is exceeded, it will return a [`crate::completion::request::PromptError::MaxDepthError`].
     pub fn multi_turn(self, depth: usize) -> PromptRequest<'a, S, M, P> {
@@ -94,6 +216,13 @@ where
             agent: self.agent,
             state: PhantomData,
             hook: self.hook,
+            inspect_request: self.inspect_request,
+            config: self.config,
+            deadline: self.deadline,
+            completion_retry: self.completion_retry,
+            on_max_depth: self.on_max_depth,
+            final_answer_after_max_depth: self.final_answer_after_max_depth,
+            on_message: self.on_message,
         }
     }
 
@@ -106,9 +235,160 @@ where
             agent: self.agent,
             state: PhantomData,
             hook: self.hook,
+            inspect_request: self.inspect_request,
+            config: self.config,
+            deadline: self.deadline,
+            completion_retry: self.completion_retry,
+            on_max_depth: self.on_max_depth,
+            final_answer_after_max_depth: self.final_answer_after_max_depth,
+            on_message: self.on_message,
+        }
+    }
+
+    /// Inspect or modify the completion request right before it is sent to the model,
+    /// e.g.: to log the exact outgoing request or tweak its parameters.
+    ///
+    /// Note: the closure runs on every turn of a multi-turn prompt, not just once. State can be
+    /// kept in the closure to act differently per turn (e.g.: lower the temperature after the first turn).
+    pub fn inspect_request(
+        self,
+        inspect: impl FnMut(&mut CompletionRequest) + Send + 'a,
+    ) -> PromptRequest<'a, S, M, P> {
+        PromptRequest {
+            prompt: self.prompt,
+            chat_history: self.chat_history,
+            max_depth: self.max_depth,
+            agent: self.agent,
+            state: PhantomData,
+            hook: self.hook,
+            inspect_request: Some(Box::new(inspect)),
+            config: self.config,
+            deadline: self.deadline,
+            completion_retry: self.completion_retry,
+            on_max_depth: self.on_max_depth,
+            final_answer_after_max_depth: self.final_answer_after_max_depth,
+            on_message: self.on_message,
+        }
+    }
+
+    /// Send the prompt request, returning the text of the response along with the full
+    /// completion response of the last turn (e.g.: to access its token usage or the raw response
+    /// of the provider).
+    pub async fn send_full(self) -> Result<(String, CompletionResponse<M::Response>), PromptError> {
+        self.extended_details()
+            .send_with_response()
+            .await
+            .map(|(response, completion_response)| (response.output, completion_response))
+    }
+
+    /// Apply reusable settings to the prompt request (see [AgentPromptConfig]). The settings of
+    /// `config` that are set override the maximum depth of the request (see
+    /// [PromptRequest::multi_turn]) and the agent's settings for every turn.
+    pub fn with_config(self, config: &AgentPromptConfig) -> PromptRequest<'a, S, M, P> {
+        PromptRequest {
+            prompt: self.prompt,
+            chat_history: self.chat_history,
+            max_depth: config.max_depth.unwrap_or(self.max_depth),
+            agent: self.agent,
+            state: PhantomData,
+            hook: self.hook,
+            inspect_request: self.inspect_request,
+            config: Some(config.clone()),
+            deadline: self.deadline,
+            completion_retry: self.completion_retry,
+            on_max_depth: self.on_max_depth,
+            final_answer_after_max_depth: self.final_answer_after_max_depth,
+            on_message: self.on_message,
         }
     }
 
+    /// Set a hard deadline for the whole prompt request, regardless of how many turns it takes.
+    /// If the deadline is reached, the in-flight completion request or tool calls are cancelled
+    /// and a [`crate::completion::request::PromptError::DeadlineExceeded`] error is returned.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Set the maximum wall-clock time the whole prompt request can take, starting now
+    /// (see [PromptRequest::deadline]).
+    pub fn max_duration(self, max_duration: Duration) -> Self {
+        self.deadline(Instant::now() + max_duration)
+    }
+
+    /// Retry the completion request of each turn that fails with a transient error (see
+    /// [RetryPolicy]), overriding the agent's retry policy (see
+    /// [AgentBuilder::completion_retry](crate::agent::AgentBuilder::completion_retry)).
+    ///
+    /// Only the failed turn is retried, so the previous turns (and their tool calls) are kept.
+    /// The retries count towards the [PromptRequest::deadline], if any.
+    pub fn completion_retry(mut self, policy: RetryPolicy) -> Self {
+        self.completion_retry = Some(policy);
+        self
+    }
+
+    /// Set a callback invoked with the chat history right before the request gives up with a
+    /// [`crate::completion::request::PromptError::MaxDepthError`], e.g.: to log or alert.
+    ///
+    /// If the callback returns `Some(response)`, the request succeeds with `response` instead
+    /// (e.g.: a best-effort answer salvaged from the chat history).
+    pub fn on_max_depth(
+        mut self,
+        on_max_depth: impl FnOnce(&[Message]) -> Option<String> + Send + 'a,
+    ) -> Self {
+        self.on_max_depth = Some(Box::new(on_max_depth));
+        self
+    }
+
+    /// When the last turn allowed by the maximum depth ends with tool calls, send one more
+    /// completion request with [ToolChoice::None], so that the model answers with text from the
+    /// results of these tool calls instead of the request failing with a
+    /// [`crate::completion::request::PromptError::MaxDepthError`]. The tools are still sent, as
+    /// the chat history refers to them, but only providers supporting the tool choice (e.g.:
+    /// OpenAI, Anthropic) prevent the model from calling them.
+    ///
+    /// If the model still does not answer with text, the request fails as usual (see
+    /// [PromptRequest::on_max_depth]).
+    pub fn final_answer_after_max_depth(mut self, final_answer_after_max_depth: bool) -> Self {
+        self.final_answer_after_max_depth = final_answer_after_max_depth;
+        self
+    }
+
+    /// Set a callback invoked with each message appended to the chat history (the prompt, the
+    /// responses of the model and the results of tool calls), e.g.: to persist the conversation
+    /// as it happens so that an interrupted request can be resumed from the persisted history
+    /// (see [PromptRequest::from_history]).
+    ///
+    /// The callback is awaited before the request proceeds, and if it fails, the request fails
+    /// with a [`crate::completion::request::PromptError::MessageHandlerError`]. A response with
+    /// tool calls is only passed to the callback once the tools are called, so that if the
+    /// deadline of the request (see [PromptRequest::deadline]) is exceeded meanwhile, the
+    /// persisted conversation matches the chat history of the error (without these tool calls).
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let response = agent
+    ///     .prompt("Plan my trip")
+    ///     .multi_turn(10)
+    ///     .on_message(|message| {
+    ///         let message = message.clone();
+    ///         async move { db.append(&run_id, &message).await }
+    ///     })
+    ///     .await?;
+    /// ```
+    pub fn on_message<F, Fut, E>(mut self, on_message: F) -> Self
+    where
+        F: Fn(&Message) -> Fut + Send + Sync + 'a,
+        Fut: Future<Output = Result<(), E>> + Send + 'a,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.on_message = Some(Box::new(move |message| {
+            let future = on_message(message);
+            Box::pin(async move { future.await.map_err(Into::into) })
+        }));
+        self
+    }
+
     /// Attach a per-request hook for tool call events
     pub fn with_hook<P2>(self, hook: P2) -> PromptRequest<'a, S, M, P2>
     where
@@ -121,6 +401,103 @@ where
             agent: self.agent,
             state: PhantomData,
             hook: Some(hook),
+            inspect_request: self.inspect_request,
+            config: self.config,
+            deadline: self.deadline,
+            completion_retry: self.completion_retry,
+            on_max_depth: self.on_max_depth,
+            final_answer_after_max_depth: self.final_answer_after_max_depth,
+            on_message: self.on_message,
+        }
+    }
+}
+
+/// Reusable settings for prompt requests, to configure many prompts the same way without
+/// repeating the same chain of setters (see [PromptRequest::with_config]).
+///
+/// # Example
+/// ```rust,ignore
+/// let config = AgentPromptConfig::new().max_depth(5).temperature(0.2);
+///
+/// for question in questions {
+///     let answer = agent.prompt(question).with_config(&config).await?;
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AgentPromptConfig {
+    /// Maximum depth for multi-turn conversations (see [PromptRequest::multi_turn])
+    pub max_depth: Option<usize>,
+    /// Temperature of the model
+    pub temperature: Option<f64>,
+    /// Maximum number of tokens for the completion
+    pub max_tokens: Option<u64>,
+    /// Whether the model may call multiple tools in a single turn
+    pub parallel_tool_calls: Option<bool>,
+    /// Additional parameters to be passed to the model
+    pub additional_params: Option<serde_json::Value>,
+    /// Locale of the response, overriding the locale of the agent (see [AgentBuilder::locale](super::AgentBuilder::locale))
+    pub locale: Option<String>,
+}
+
+impl AgentPromptConfig {
+    /// Create a new config that keeps all the settings of the agent.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum depth for multi-turn conversations
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Set the temperature of the model
+    pub fn temperature(mut self, temperature: f64) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Set the maximum number of tokens for the completion
+    pub fn max_tokens(mut self, max_tokens: u64) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Set whether the model may call multiple tools in a single turn
+    pub fn parallel_tool_calls(mut self, parallel_tool_calls: bool) -> Self {
+        self.parallel_tool_calls = Some(parallel_tool_calls);
+        self
+    }
+
+    /// Set additional parameters to be passed to the model
+    pub fn additional_params(mut self, params: serde_json::Value) -> Self {
+        self.additional_params = Some(params);
+        self
+    }
+
+    /// Set the locale of the response, overriding the locale of the agent
+    pub fn locale(mut self, locale: &str) -> Self {
+        self.locale = Some(locale.into());
+        self
+    }
+
+    /// Override the settings of `request` (built by `agent`) with the settings of the config
+    /// that are set.
+    fn apply<M: CompletionModel>(&self, agent: &Agent<M>, request: &mut CompletionRequest) {
+        if let Some(temperature) = self.temperature {
+            request.temperature = Some(temperature);
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            request.max_tokens = Some(max_tokens);
+        }
+        if let Some(parallel_tool_calls) = self.parallel_tool_calls {
+            request.parallel_tool_calls = Some(parallel_tool_calls);
+        }
+        if let Some(additional_params) = &self.additional_params {
+            request.additional_params = Some(additional_params.clone());
+        }
+        if let Some(locale) = &self.locale {
+            request.preamble = Some(agent.preamble_with_locale(Some(locale)));
         }
     }
 }
@@ -159,6 +536,15 @@ pub trait PromptHook<M: CompletionModel>: Clone + Send + Sync {
         async {}
     }
 
+    #[allow(unused_variables)]
+    /// Called when the model returns text along with tool calls (e.g.: "Let me check the
+    /// weather..."), before the tools are invoked. `turn` is the number of the turn in the
+    /// multi-turn prompt, starting at 1. The text of the final answer is not passed to this hook.
+    /// This function is for non-streamed responses, streamed responses yield the text as it is generated.
+    fn on_assistant_text(&self, turn: usize, text: &str) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+
     #[allow(unused_variables)]
     /// Called before a tool is invoked.
     fn on_tool_call(&self, tool_name: &str, args: &str) -> impl Future<Output = ()> + Send {
@@ -208,6 +594,20 @@ where
     }
 }
 
+impl<'a, M, P, T> IntoFuture for PromptRequest<'a, FinalAnswer<T>, M, P>
+where
+    M: CompletionModel,
+    P: PromptHook<M> + 'static,
+    T: for<'de> Deserialize<'de> + Send + 'a,
+{
+    type Output = Result<T, PromptError>;
+    type IntoFuture = BoxFuture<'a, Self::Output>; // This future should not outlive the agent
+
+    fn into_future(self) -> Self::IntoFuture {
+        self.send().boxed()
+    }
+}
+
 impl<M, P> PromptRequest<'_, Standard, M, P>
 where
     M: CompletionModel,
@@ -218,10 +618,93 @@ where
     }
 }
 
+/// What an agent does when the model returns multiple tool calls with the same id in a single
+/// turn (see [crate::agent::AgentBuilder::duplicate_tool_call_ids]).
+///
+/// Tool results are matched to their tool call by id, so providers usually reject the next
+/// request of the conversation if the ids of the tool calls are not unique.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateToolCallIds {
+    /// Suffix the id of the duplicate tool calls to make them unique
+    /// (e.g.: `call_1`, `call_1_1`, `call_1_2`) (default).
+    #[default]
+    Rename,
+    /// Fail the prompt with [PromptError::DuplicateToolCallId].
+    Error,
+}
+
+impl DuplicateToolCallIds {
+    /// Make the id of `tool_call` unique among the ids of the tool calls already `seen` in the
+    /// turn (or return the duplicate id as an error), then add it to `seen`.
+    pub(crate) fn dedup(
+        self,
+        tool_call: &mut ToolCall,
+        seen: &mut HashSet<String>,
+    ) -> Result<(), String> {
+        if seen.contains(&tool_call.id) {
+            if self == DuplicateToolCallIds::Error {
+                return Err(tool_call.id.clone());
+            }
+
+            // `seen` has the id itself, so at most `seen.len() - 1` of these suffixes are taken
+            let Some(suffix) =
+                (1..=seen.len()).find(|n| !seen.contains(&format!("{}_{n}", tool_call.id)))
+            else {
+                return Err(tool_call.id.clone());
+            };
+            tracing::warn!(
+                "The model returned multiple tool calls with id `{}`, renaming it to `{}_{suffix}`",
+                tool_call.id,
+                tool_call.id
+            );
+            tool_call.id = format!("{}_{suffix}", tool_call.id);
+            tool_call.call_id = tool_call
+                .call_id
+                .take()
+                .map(|call_id| format!("{call_id}_{suffix}"));
+        }
+
+        seen.insert(tool_call.id.clone());
+        Ok(())
+    }
+}
+
+/// The response of a prompt with extended details (see [PromptRequest::extended_details]).
+///
+/// New fields may be added to the response: use [PromptResponse::new] and its `with_*` methods
+/// to create one.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct PromptResponse {
     pub output: String,
     pub total_usage: Usage,
+    /// Arguments of the agent's final answer tool call, if the model called it
+    /// (see [crate::agent::AgentBuilder::final_answer_tool]). In that case, `output` contains
+    /// the same arguments serialized as a JSON string.
+    pub final_answer: Option<serde_json::Value>,
+    /// Estimated cost (in dollars) of all the completion requests made for this prompt.
+    /// `None` if the price of the model is unknown (see [CompletionModel::price_per_1k_tokens]).
+    pub estimated_cost: Option<f64>,
+    /// The documents retrieved for the completion request of the last turn, and the context
+    /// it was sent with.
+    pub retrieval: RetrievalTrace,
+    /// The provider's response to the completion request of the last turn as JSON, if the
+    /// agent keeps it (see [crate::agent::AgentBuilder::keep_raw]).
+    pub raw: Option<serde_json::Value>,
+    /// The context documents cited by the answer, if the agent asks for citations (see
+    /// [crate::agent::AgentBuilder::citations]). In that case, the citations are removed from
+    /// `output`.
+    pub citations: Vec<Citation>,
+}
+
+/// The documents retrieved from the dynamic context of an agent for a completion request (see
+/// [crate::agent::AgentBuilder::dynamic_context]), to debug what a RAG agent based its answer on.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RetrievalTrace {
+    /// The retrieved documents, with their similarity score to the RAG text of the prompt
+    pub documents: Vec<ScoredDocument>,
+    /// The context documents of the request (static and retrieved), as sent to the model
+    pub context: String,
 }
 
 impl PromptResponse {
@@ -229,8 +712,67 @@ impl PromptResponse {
         Self {
             output: output.into(),
             total_usage,
+            final_answer: None,
+            estimated_cost: None,
+            retrieval: RetrievalTrace::default(),
+            raw: None,
+            citations: vec![],
+        }
+    }
+
+    /// Creates a response from the arguments of the agent's final answer tool call.
+    pub fn from_final_answer(final_answer: serde_json::Value, total_usage: Usage) -> Self {
+        Self {
+            final_answer: Some(final_answer.clone()),
+            ..Self::new(final_answer.to_string(), total_usage)
         }
     }
+
+    /// Sets the estimated cost of the response from the price of the model.
+    pub fn with_pricing(mut self, pricing: Option<Pricing>) -> Self {
+        self.estimated_cost = pricing.map(|pricing| self.total_usage.estimated_cost(&pricing));
+        self
+    }
+
+    /// Sets the documents retrieved for the response.
+    pub fn with_retrieval(mut self, retrieval: RetrievalTrace) -> Self {
+        self.retrieval = retrieval;
+        self
+    }
+
+    /// Sets the raw provider response of the last turn.
+    pub fn with_raw(mut self, raw: Option<serde_json::Value>) -> Self {
+        self.raw = raw;
+        self
+    }
+
+    /// Sets the citations of the response.
+    pub fn with_citations(mut self, citations: Vec<Citation>) -> Self {
+        self.citations = citations;
+        self
+    }
+}
+
+impl<M, P, T> PromptRequest<'_, FinalAnswer<T>, M, P>
+where
+    M: CompletionModel,
+    P: PromptHook<M>,
+    T: for<'de> Deserialize<'de>,
+{
+    async fn send(self) -> Result<T, PromptError> {
+        let response = self.extended_details().send().await?;
+
+        let Some(final_answer) = response.final_answer else {
+            return Err(PromptError::FinalAnswerError(format!(
+                "The final answer tool was not called. Response: {}",
+                response.output
+            )));
+        };
+
+        serde_json::from_value(final_answer).map_err(|e| {
+            PromptError::FinalAnswerError(format!("Failed to deserialize the final answer: {e}"))
+        })
+    }
 }
 
 impl<M, P> PromptRequest<'_, Extended, M, P>
@@ -238,31 +780,76 @@ where
     M: CompletionModel,
     P: PromptHook<M>,
 {
-    #[tracing::instrument(skip(self), fields(agent_name = self.agent.name()))]
     async fn send(self) -> Result<PromptResponse, PromptError> {
+        self.send_with_response()
+            .await
+            .map(|(response, _)| response)
+    }
+
+    /// Run the prompt request, returning the response along with the full completion response
+    /// of the last turn.
+    async fn send_with_response(
+        self,
+    ) -> Result<(PromptResponse, CompletionResponse<M::Response>), PromptError> {
+        telemetry::traced(telemetry::agent_span(self.agent.name()), self.run()).await
+    }
+
+    async fn run(self) -> Result<(PromptResponse, CompletionResponse<M::Response>), PromptError> {
         let agent = self.agent;
-        let chat_history = if let Some(history) = self.chat_history {
-            history.push(self.prompt);
-            history
-        } else {
-            &mut vec![self.prompt]
+        let on_message = self.on_message;
+        let has_prompt = self.prompt.is_some();
+        let chat_history = match (self.chat_history, self.prompt) {
+            (Some(history), Some(prompt)) => {
+                history.push(prompt);
+                history
+            }
+            (Some(history), None) => history,
+            (None, prompt) => &mut prompt.into_iter().collect::<Vec<_>>(),
         };
 
+        if chat_history.is_empty() {
+            return Err(PromptError::EmptyChatHistory);
+        }
+
+        if let Some(text) = chat_history.last().and_then(user_text) {
+            moderate(agent, &text).await?;
+        }
+
+        // Flagged prompts are not persisted
+        if has_prompt {
+            persist_last(&on_message, chat_history).await?;
+        }
+
         let mut current_max_depth = 0;
+        let arg_retries = AtomicUsize::new(0);
         let mut usage = Usage::new();
+        let mut inspect_request = self.inspect_request;
+        let config = self.config;
+        let deadline = self.deadline.map(tokio::time::Instant::from_std);
+        let completion_retry = self.completion_retry.or(agent.completion_retry);
+        let mut last_response = None;
+        let mut retrieval = RetrievalTrace::default();
+        let mut documents = vec![];
 
         // We need to do at least 2 loops for 1 roundtrip (user expects normal message)
         let last_prompt = loop {
-            let prompt = chat_history
-                .last()
-                .cloned()
-                .expect("there should always be at least one message in the chat history");
+            let Some(prompt) = chat_history.last().cloned() else {
+                return Err(PromptError::EmptyChatHistory);
+            };
 
-            if current_max_depth > self.max_depth + 1 {
+            let extra_turns = usize::from(self.final_answer_after_max_depth);
+            if current_max_depth > self.max_depth + 1 + extra_turns {
                 break prompt;
             }
 
             current_max_depth += 1;
+            let turn_span = telemetry::turn_span(current_max_depth);
+
+            if deadline.is_some_and(|deadline| tokio::time::Instant::now() >= deadline) {
+                return Err(PromptError::DeadlineExceeded {
+                    chat_history: chat_history.clone(),
+                });
+            }
 
             if self.max_depth > 1 {
                 tracing::info!(
@@ -277,16 +864,96 @@ where
                     .await;
             }
 
-            let resp = agent
-                .completion(
+            let Some(builder) = until(
+                deadline,
+                agent.completion_with_retrieval(
                     prompt.clone(),
                     chat_history[..chat_history.len() - 1].to_vec(),
-                )
-                .await?
-                .send()
-                .await?;
+                ),
+            )
+            .await
+            else {
+                return Err(PromptError::DeadlineExceeded {
+                    chat_history: chat_history.clone(),
+                });
+            };
+            let (builder, retrieved) = builder?;
+            let mut request = builder.build();
+
+            if let Some(config) = &config {
+                config.apply(agent, &mut request);
+            }
+
+            // The extra turn after the maximum depth only answers from the tool results. The tools
+            // are kept, since the chat history refers to them, but they cannot be called anymore
+            if self.final_answer_after_max_depth && current_max_depth > self.max_depth + 2 {
+                tracing::info!(
+                    "Max depth {} exceeded, requesting a final answer without tool calls",
+                    self.max_depth
+                );
+                request.tool_choice = Some(ToolChoice::None);
+            }
+
+            if agent.tool_call_style == ToolCallStyle::ReAct {
+                react::prepare_request(&mut request);
+            }
+
+            if agent.citations {
+                citations::prepare_request(&mut request);
+                documents = request.documents.clone();
+            }
+
+            if let Some(inspect) = inspect_request.as_mut() {
+                inspect(&mut request);
+            }
+
+            retrieval = RetrievalTrace {
+                documents: retrieved,
+                context: request.documents.iter().map(ToString::to_string).collect(),
+            };
+
+            logging::log_request(agent, &request);
+
+            let chat_span =
+                turn_span.in_scope(|| telemetry::chat_span(agent.model.model_name(), &request));
+            let completion = async {
+                match completion_retry {
+                    Some(policy) => {
+                        policy
+                            .retry(|| agent.model.completion(request.clone()))
+                            .await
+                    }
+                    None => agent.model.completion(request).await,
+                }
+            }
+            .instrument(chat_span.clone());
+            let Some(resp) = until(deadline, completion).await else {
+                return Err(PromptError::DeadlineExceeded {
+                    chat_history: chat_history.clone(),
+                });
+            };
+            telemetry::record_result(&chat_span, &resp);
+            let mut resp = resp?;
+            telemetry::record_usage(&chat_span, &resp.usage);
+            logging::log_response(agent, &resp.choice.iter().cloned().collect::<Vec<_>>());
+
+            if agent.tool_call_style == ToolCallStyle::ReAct {
+                resp.choice =
+                    react::parse_response(resp.choice, format!("react_{}", chat_history.len()))?;
+            }
 
             usage += resp.usage;
+            telemetry::record_usage(&tracing::Span::current(), &usage);
+
+            let mut tool_call_ids = HashSet::new();
+            for content in resp.choice.iter_mut() {
+                if let AssistantContent::ToolCall(tool_call) = content {
+                    agent
+                        .duplicate_tool_call_ids
+                        .dedup(tool_call, &mut tool_call_ids)
+                        .map_err(PromptError::DuplicateToolCallId)?;
+                }
+            }
 
             if let Some(ref hook) = self.hook {
                 hook.on_completion_response(&prompt, &resp).await;
@@ -298,79 +965,227 @@ where
                 .partition(|choice| matches!(choice, AssistantContent::ToolCall(_)));
 
             chat_history.push(Message::Assistant {
-                id: None,
+                id: message_id(agent),
                 content: resp.choice.clone(),
             });
 
-            if tool_calls.is_empty() {
-                let merged_texts = texts
-                    .into_iter()
-                    .filter_map(|content| {
-                        if let AssistantContent::Text(text) = content {
-                            Some(text.text.clone())
-                        } else {
-                            None
+            let merged_texts = texts
+                .iter()
+                .filter_map(|content| {
+                    if let AssistantContent::Text(text) = content {
+                        Some(text.text.clone())
+                    } else {
+                        None
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            // The final answer tool is called like any other tool so that its call is resolved
+            // in the chat history, but the conversation stops after this turn.
+            let final_answer = agent
+                .final_answer_tool
+                .as_ref()
+                .and_then(|final_answer_tool| {
+                    tool_calls.iter().find_map(|choice| match choice {
+                        AssistantContent::ToolCall(tool_call)
+                            if &tool_call.function.name == final_answer_tool =>
+                        {
+                            Some(tool_call.function.arguments.clone())
                         }
+                        _ => None,
                     })
-                    .collect::<Vec<_>>()
-                    .join("\n");
+                });
+
+            // Flagged responses are not persisted
+            if agent.moderate_output {
+                if tool_calls.is_empty() {
+                    moderate(agent, &merged_texts).await?;
+                } else if let Some(final_answer) = &final_answer {
+                    moderate(agent, &final_answer.to_string()).await?;
+                }
+            }
+            // The tool calls are persisted once they are called within the deadline
+            if tool_calls.is_empty() {
+                persist_last(&on_message, chat_history).await?;
+            }
+
+            // A refusal is not an answer: it is surfaced as an error rather than (empty) text
+            if tool_calls.is_empty()
+                && let Some(reason) = refusal(&texts)
+            {
+                return Err(PromptError::Refused { reason });
+            }
 
+            if tool_calls.is_empty() {
                 if self.max_depth > 1 {
                     tracing::info!("Depth reached: {}/{}", current_max_depth, self.max_depth);
                 }
 
+                let (merged_texts, citations) = if agent.citations {
+                    citations::parse(&merged_texts, &documents)
+                } else {
+                    (merged_texts, vec![])
+                };
+
+                let merged_texts = if agent.include_reasoning {
+                    with_reasoning(&texts, merged_texts)
+                } else {
+                    merged_texts
+                };
+
                 // If there are no tool calls, depth is not relevant, we can just return the merged text response.
-                return Ok(PromptResponse::new(merged_texts, usage));
+                return Ok((
+                    PromptResponse::new(merged_texts, usage)
+                        .with_pricing(agent.model.price_per_1k_tokens())
+                        .with_retrieval(retrieval)
+                        .with_raw(raw_response(agent, &resp))
+                        .with_citations(citations),
+                    resp,
+                ));
+            }
+
+            if let Some(ref hook) = self.hook
+                && !merged_texts.is_empty()
+            {
+                hook.on_assistant_text(current_max_depth, &merged_texts)
+                    .await;
             }
 
             let hook = self.hook.clone();
-            let tool_content = stream::iter(tool_calls)
-                .then(|choice| {
-                    let hook1 = hook.clone();
-                    let hook2 = hook.clone();
-                    async move {
-                        if let AssistantContent::ToolCall(tool_call) = choice {
+            let arg_retries = &arg_retries;
+            // The tool calls are executed concurrently (unless the agent executes them
+            // sequentially), and their results are kept in the order of the calls
+            let concurrency = if agent.sequential_tools {
+                1
+            } else {
+                tool_calls.len().max(1)
+            };
+            let tool_content = async move {
+                let mut batched = match call_tool_batch(agent, &tool_calls).await {
+                    Ok(batched) => batched,
+                    Err(e) => return vec![Err(e)],
+                };
+                let calls = tool_calls
+                    .into_iter()
+                    .filter_map(|choice| match choice {
+                        AssistantContent::ToolCall(tool_call) => Some(tool_call),
+                        _ => None,
+                    })
+                    .map(|tool_call| {
+                        let hook = hook.clone();
+                        let batched_output = batched.remove(&tool_call.id);
+                        async move {
                             let tool_name = &tool_call.function.name;
+                            // The result of these tools is provided by the caller
+                            if agent.pause_on_tools.contains(tool_name) {
+                                return Ok((tool_call.clone(), None));
+                            }
+
                             let args = tool_call.function.arguments.to_string();
-                            if let Some(hook) = hook1 {
+                            if let Some(hook) = &hook {
                                 hook.on_tool_call(tool_name, &args).await;
                             }
-                            let output = agent.tools.call(tool_name, args.clone()).await?;
-                            if let Some(hook) = hook2 {
-                                hook.on_tool_result(tool_name, &args, &output.to_string())
+                            let output = match batched_output {
+                                Some(output) => output,
+                                None => {
+                                    invalid_arguments_feedback(
+                                        agent,
+                                        tool_name,
+                                        call_tool(agent, tool_name, args.clone()).await,
+                                        arg_retries,
+                                    )
+                                    .await?
+                                }
+                            };
+                            if let Some(hook) = &hook {
+                                hook.on_tool_result(tool_name, &args, &tool_result_text(&output))
                                     .await;
                             }
-                            if let Some(call_id) = tool_call.call_id.clone() {
-                                Ok(UserContent::tool_result_with_call_id(
-                                    tool_call.id.clone(),
-                                    call_id,
-                                    OneOrMany::one(output.into()),
-                                ))
-                            } else {
-                                Ok(UserContent::tool_result(
-                                    tool_call.id.clone(),
-                                    OneOrMany::one(output.into()),
-                                ))
-                            }
-                        } else {
-                            unreachable!(
-                                "This should never happen as we already filtered for `ToolCall`"
-                            )
+                            let output = format_tool_result(agent, tool_call, output);
+                            Ok((tool_call.clone(), Some(output)))
                         }
-                    }
-                })
-                .collect::<Vec<Result<UserContent, ToolSetError>>>()
-                .await
+                    })
+                    .collect::<Vec<_>>();
+
+                stream::iter(calls)
+                    .buffered(concurrency)
+                    .collect::<Vec<Result<(ToolCall, Option<ToolOutput>), ToolSetError>>>()
+                    .await
+            }
+            .instrument(turn_span)
+            .boxed();
+
+            let Some(tool_content) = until(deadline, tool_content).await else {
+                // Drop the tool calls that could not complete so that every tool call of the
+                // chat history has a result
+                chat_history.pop();
+                return Err(PromptError::DeadlineExceeded {
+                    chat_history: chat_history.clone(),
+                });
+            };
+            persist_last(&on_message, chat_history).await?;
+
+            let tool_content = tool_content
                 .into_iter()
                 .collect::<Result<Vec<_>, _>>()
                 .map_err(|e| CompletionError::RequestError(Box::new(e)))?;
 
+            if tool_content.iter().any(|(_, output)| output.is_none()) {
+                return Err(PromptError::PendingToolCalls(Box::new(PendingToolCalls {
+                    chat_history: chat_history.clone(),
+                    results: tool_content,
+                })));
+            }
+
+            let tool_results =
+                OneOrMany::many(tool_content.into_iter().filter_map(|(tool_call, output)| {
+                    output.map(|output| tool_result(&tool_call, output))
+                }))
+                .map_err(|_| {
+                    CompletionError::ResponseError(
+                        "The tool calls of the response have no result".into(),
+                    )
+                })?;
             chat_history.push(Message::User {
-                content: OneOrMany::many(tool_content).expect("There is atleast one tool call"),
+                content: tool_results,
             });
+            persist_last(&on_message, chat_history).await?;
+
+            if let Some(final_answer) = final_answer {
+                return Ok((
+                    PromptResponse::from_final_answer(final_answer, usage)
+                        .with_pricing(agent.model.price_per_1k_tokens())
+                        .with_retrieval(retrieval)
+                        .with_raw(raw_response(agent, &resp)),
+                    resp,
+                ));
+            }
+
+            last_response = Some(resp);
         };
 
-        // If we reach here, we never resolved the final tool call. We need to do ... something.
+        // At least one turn ran before exceeding the max depth, so there is a last response
+        if let Some(resp) = last_response
+            && let Some(output) = self
+                .on_max_depth
+                .and_then(|on_max_depth| on_max_depth(chat_history))
+        {
+            tracing::info!(
+                "Max depth {} exceeded, using fallback response",
+                self.max_depth
+            );
+            return Ok((
+                PromptResponse::new(output, usage)
+                    .with_pricing(agent.model.price_per_1k_tokens())
+                    .with_retrieval(retrieval)
+                    .with_raw(raw_response(agent, &resp)),
+                resp,
+            ));
+        }
+
+        // If we reach here, the model kept calling tools until the maximum depth was exceeded
+        // (see `final_answer_after_max_depth` to get a final answer instead).
         Err(PromptError::MaxDepthError {
             max_depth: self.max_depth,
             chat_history: chat_history.clone(),
@@ -378,3 +1193,2096 @@ where
         })
     }
 }
+
+/// Pass the last message of `chat_history` to the `on_message` callback, if any.
+async fn persist_last(
+    on_message: &Option<MessageHandler<'_>>,
+    chat_history: &[Message],
+) -> Result<(), PromptError> {
+    if let Some(on_message) = on_message
+        && let Some(message) = chat_history.last()
+    {
+        on_message(message)
+            .await
+            .map_err(PromptError::MessageHandlerError)?;
+    }
+    Ok(())
+}
+
+/// Run `future` to completion, unless `deadline` is reached first (in which case `None` is
+/// returned and the future is dropped).
+async fn until<F: std::future::Future>(
+    deadline: Option<tokio::time::Instant>,
+    future: F,
+) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+/// The output of a tool call.
+type ToolOutput = OneOrMany<ToolResultContent>;
+
+/// The result of `tool_call`, to be sent back to the model.
+fn tool_result(tool_call: &ToolCall, output: ToolOutput) -> UserContent {
+    match tool_call.call_id.clone() {
+        Some(call_id) => {
+            UserContent::tool_result_with_call_id(tool_call.id.clone(), call_id, output)
+        }
+        None => UserContent::tool_result(tool_call.id.clone(), output),
+    }
+}
+
+/// Tool calls awaiting a result from the caller, returned in a [PromptError::PendingToolCalls]
+/// error when the model calls a tool registered with
+/// [crate::agent::AgentBuilder::pause_on_tool] (e.g.: to let a human approve the tool call).
+///
+/// Provide the result of each pending tool call with [PendingToolCalls::resume], then continue
+/// the prompt from [PendingToolCalls::into_chat_history] (e.g.: with
+/// [Agent::continue_conversation]). The results of the other tool calls of the turn are kept.
+///
+/// # Example
+/// ```rust,ignore
+/// let mut chat_history = vec![];
+/// let response = match agent.prompt("Delete my account").with_history(&mut chat_history).await {
+///     Err(PromptError::PendingToolCalls(mut pending)) => {
+///         for tool_call in pending.tool_calls().into_iter().cloned().collect::<Vec<_>>() {
+///             let result = if ask_user(&tool_call) { run(&tool_call) } else { "Denied by the user".into() };
+///             pending.resume(&tool_call.id, result);
+///         }
+///         let mut chat_history = pending.into_chat_history().expect("All tool calls are resolved");
+///         agent.continue_conversation(&mut chat_history).await?
+///     }
+///     response => response?,
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct PendingToolCalls {
+    /// The chat history, ending with the assistant message containing the tool calls
+    chat_history: Vec<Message>,
+    /// The tool calls of the turn, in order, with their result (`None` while pending)
+    results: Vec<(ToolCall, Option<ToolOutput>)>,
+}
+
+impl PendingToolCalls {
+    /// The tool calls still awaiting a result.
+    pub fn tool_calls(&self) -> Vec<&ToolCall> {
+        self.results
+            .iter()
+            .filter(|(_, output)| output.is_none())
+            .map(|(tool_call, _)| tool_call)
+            .collect()
+    }
+
+    /// The chat history, ending with the assistant message containing the tool calls.
+    pub fn chat_history(&self) -> &[Message] {
+        &self.chat_history
+    }
+
+    /// Provide the result of the pending tool call with the given `id`.
+    /// Returns `false` if there is no pending tool call with this id.
+    pub fn resume(&mut self, id: &str, result: impl Into<String>) -> bool {
+        match self
+            .results
+            .iter_mut()
+            .find(|(tool_call, output)| tool_call.id == id && output.is_none())
+        {
+            Some((_, output)) => {
+                *output = Some(OneOrMany::one(ToolResultContent::text(result)));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The chat history with the results of the tool calls appended, to continue the prompt.
+    /// Returns `self` as error if some tool calls are still pending.
+    pub fn into_chat_history(self) -> Result<Vec<Message>, Self> {
+        if !self.tool_calls().is_empty() {
+            return Err(self);
+        }
+
+        let mut chat_history = self.chat_history;
+        if let Ok(content) =
+            OneOrMany::many(self.results.into_iter().filter_map(|(tool_call, output)| {
+                output.map(|output| tool_result(&tool_call, output))
+            }))
+        {
+            chat_history.push(Message::User { content });
+        }
+        Ok(chat_history)
+    }
+}
+
+/// Call the tool `tool_name` of `agent` with `args`, after validating them against the
+/// parameters schema of the tool if the agent has a schema validator (see
+/// [crate::agent::AgentBuilder::schema_validator]).
+pub(crate) async fn call_tool<M: CompletionModel>(
+    agent: &Agent<M>,
+    tool_name: &str,
+    args: String,
+) -> Result<ToolOutput, ToolSetError> {
+    telemetry::traced(telemetry::tool_span(tool_name), async {
+        if !agent.is_tool_available(tool_name) {
+            return Err(ToolSetError::ToolUnavailable(tool_name.to_string()));
+        }
+
+        validate_tool_args(agent, tool_name, &args).await?;
+
+        agent.tools.call_with_content(tool_name, args).await
+    })
+    .await
+}
+
+/// Validate `args` against the parameters schema of the tool `tool_name` of `agent`, if the
+/// agent has a schema validator (see [crate::agent::AgentBuilder::schema_validator]).
+async fn validate_tool_args<M: CompletionModel>(
+    agent: &Agent<M>,
+    tool_name: &str,
+    args: &str,
+) -> Result<(), ToolSetError> {
+    if let Some(validator) = &agent.schema_validator
+        && let Some(tool) = agent.tools.get(tool_name)
+    {
+        let schema = tool.definition(String::new()).await.parameters;
+        let value = serde_json::from_str(args)
+            .map_err(|e| ToolSetError::ToolCallError(ToolError::InvalidArguments(e)))?;
+        validator
+            .validate(&schema, &value)
+            .map_err(|e| ToolSetError::ToolCallError(e.into()))?;
+    }
+    Ok(())
+}
+
+/// Call the batch handler of the toolset of `agent` (see
+/// [crate::agent::AgentBuilder::batch_tool_handler]) with the `tool_calls` of a turn, except the
+/// calls of unknown or unavailable tools, of the final answer tool, of the tools whose result is
+/// provided by the caller, and the calls with invalid arguments. These calls are dispatched to
+/// [call_tool], which reports their errors.
+pub(crate) async fn call_tool_batch<M: CompletionModel>(
+    agent: &Agent<M>,
+    tool_calls: &[&AssistantContent],
+) -> Result<HashMap<String, ToolOutput>, ToolSetError> {
+    if !agent.tools.has_batch_handler() {
+        return Ok(HashMap::new());
+    }
+
+    let mut batch = vec![];
+    for choice in tool_calls {
+        let AssistantContent::ToolCall(tool_call) = choice else {
+            continue;
+        };
+        let tool_name = &tool_call.function.name;
+        if agent.tools.contains(tool_name)
+            && agent.is_tool_available(tool_name)
+            && !agent.pause_on_tools.contains(tool_name)
+            && agent.final_answer_tool.as_ref() != Some(tool_name)
+            && validate_tool_args(agent, tool_name, &tool_call.function.arguments.to_string())
+                .await
+                .is_ok()
+        {
+            batch.push(tool_call.clone());
+        }
+    }
+
+    agent.tools.call_batch(batch).await
+}
+
+/// If `result` failed because of invalid arguments and the agent validates tool arguments (see
+/// [crate::agent::AgentBuilder::validate_tool_args]), return a tool result describing the error
+/// and the expected arguments schema instead, so that the model can call the tool again, unless
+/// `retries` (the number of invalid calls so far in the prompt) exceeds the agent's limit.
+pub(crate) async fn invalid_arguments_feedback<M: CompletionModel>(
+    agent: &Agent<M>,
+    tool_name: &str,
+    result: Result<ToolOutput, ToolSetError>,
+    retries: &AtomicUsize,
+) -> Result<ToolOutput, ToolSetError> {
+    let (error, message) = match result {
+        Err(ToolSetError::ToolCallError(ToolError::InvalidArguments(error)))
+            if agent.validate_tool_args =>
+        {
+            let message = error.to_string();
+            (ToolError::InvalidArguments(error), message)
+        }
+        Err(ToolSetError::ToolCallError(ToolError::SchemaValidationError(error)))
+            if agent.validate_tool_args =>
+        {
+            let message = error.to_string();
+            (ToolError::SchemaValidationError(error), message)
+        }
+        result => return result,
+    };
+
+    if retries.fetch_add(1, Ordering::SeqCst) >= agent.max_arg_retries {
+        return Err(ToolSetError::ToolCallError(error));
+    }
+
+    let schema = match agent.tools.get(tool_name) {
+        Some(tool) => tool.definition(String::new()).await.parameters,
+        None => serde_json::Value::Null,
+    };
+    tracing::warn!(
+        "Invalid arguments for tool `{tool_name}`, asking the model to retry: {message}"
+    );
+
+    Ok(OneOrMany::one(ToolResultContent::text(format!(
+        "Error: invalid arguments for tool `{tool_name}`: {message}. \
+        The arguments must match this JSON schema: {schema}. \
+        Call the tool again with valid arguments."
+    ))))
+}
+
+/// Function formatting the text of a tool result before it is sent back to the model (see
+/// [crate::agent::AgentBuilder::tool_result_formatter]). It is given the tool call and the
+/// text output of the tool, and returns the text to send.
+pub type ToolResultFormatter = Arc<dyn Fn(&ToolCall, &str) -> String + Send + Sync>;
+
+/// Apply the tool result formatter of `agent`, if any, to the text parts of `output`.
+pub(crate) fn format_tool_result<M: CompletionModel>(
+    agent: &Agent<M>,
+    tool_call: &ToolCall,
+    mut output: ToolOutput,
+) -> ToolOutput {
+    if let Some(formatter) = &agent.tool_result_formatter {
+        for content in output.iter_mut() {
+            if let ToolResultContent::Text(text) = content {
+                text.text = formatter(tool_call, &text.text);
+            }
+        }
+    }
+    output
+}
+
+/// The id of a new assistant message of the chat history: a UUID v4 if the agent generates
+/// message ids, `None` otherwise.
+pub(crate) fn message_id<M: CompletionModel>(agent: &Agent<M>) -> Option<String> {
+    agent
+        .generate_message_ids
+        .then(|| uuid::Uuid::new_v4().to_string())
+}
+
+/// Prepend the reasoning of a response (if any) to its text, between `<reasoning>` tags
+/// (see [AgentBuilder::include_reasoning](crate::agent::AgentBuilder::include_reasoning)).
+fn with_reasoning(content: &[&AssistantContent], text: String) -> String {
+    let reasoning = content
+        .iter()
+        .filter_map(|content| match content {
+            AssistantContent::Reasoning(reasoning) => Some(reasoning.reasoning.join("\n")),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    if reasoning.is_empty() {
+        return text;
+    }
+
+    format!(
+        "<reasoning>\n{}\n</reasoning>\n{text}",
+        reasoning.join("\n")
+    )
+}
+
+/// The text of `message`, if it is a user message (e.g.: to moderate a prompt).
+fn user_text(message: &Message) -> Option<String> {
+    let Message::User { content } = message else {
+        return None;
+    };
+
+    Some(
+        content
+            .iter()
+            .filter_map(|content| match content {
+                UserContent::Text(text) => Some(text.text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
+
+/// Check `text` with the agent's moderator (if any), failing with [PromptError::Moderated] if it
+/// is flagged.
+async fn moderate<M: CompletionModel>(agent: &Agent<M>, text: &str) -> Result<(), PromptError> {
+    let Some(moderator) = &agent.moderator else {
+        return Ok(());
+    };
+    if text.trim().is_empty() {
+        return Ok(());
+    }
+
+    let result = moderator.check(text).await?;
+    if result.flagged {
+        tracing::info!(target: "rig", categories = ?result.categories, "Content flagged by the moderator");
+        return Err(PromptError::Moderated {
+            categories: result.categories,
+        });
+    }
+    Ok(())
+}
+
+/// The JSON of the provider's response, if the agent keeps it.
+fn raw_response<M: CompletionModel>(
+    agent: &Agent<M>,
+    response: &CompletionResponse<M::Response>,
+) -> Option<serde_json::Value> {
+    if !agent.keep_raw {
+        return None;
+    }
+
+    serde_json::to_value(&response.raw_response)
+        .inspect_err(|err| tracing::warn!("Failed to serialize the raw response: {err}"))
+        .ok()
+}
+
+/// The reason of the refusals of a response, if the model refused to answer.
+fn refusal(content: &[&AssistantContent]) -> Option<String> {
+    let refusals = content
+        .iter()
+        .filter_map(|content| match content {
+            AssistantContent::Refusal(refusal) => Some(refusal.refusal.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    (!refusals.is_empty()).then(|| refusals.join("\n"))
+}
+
+/// The text representation of a tool result passed to [PromptHook::on_tool_result].
+/// Non-text content (e.g.: images) is replaced by a placeholder.
+pub(crate) fn tool_result_text(content: &OneOrMany<ToolResultContent>) -> String {
+    content
+        .iter()
+        .map(|content| match content {
+            ToolResultContent::Text(text) => text.text.as_str(),
+            ToolResultContent::Image(_) => "[Image]",
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{StreamExt, TryStreamExt};
+
+    use super::streaming::{FinalResponse, MultiTurnStreamItem, StreamingError};
+    use crate::{
+        OneOrMany,
+        agent::{AgentBuilder, AgentPromptConfig, DuplicateToolCallIds, PromptHook},
+        completion::{
+            CompletionError, CompletionResponse, Message, Pricing, Prompt, PromptError, ToolChoice,
+            ToolDefinition, Usage,
+        },
+        message::{AssistantContent, ToolCall, ToolResultContent, UserContent},
+        moderation::ModerationResult,
+        streaming::StreamingPrompt,
+        test_utils::MockCompletionModel,
+        tool::{Tool, ToolSetError},
+    };
+
+    #[tokio::test]
+    async fn test_generate_message_ids() {
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "echo", serde_json::json!({"text": "hi"}))
+            .with_text("hi");
+        let agent = AgentBuilder::new(model)
+            .tool(Echo)
+            .generate_message_ids(true)
+            .build();
+        let mut chat_history = vec![];
+
+        agent
+            .prompt("Echo hi")
+            .multi_turn(2)
+            .with_history(&mut chat_history)
+            .await
+            .unwrap();
+
+        let ids = chat_history
+            .iter()
+            .filter_map(|message| match message {
+                Message::Assistant { id, .. } => Some(id.clone().unwrap()),
+                Message::User { .. } => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ids.len(), 2);
+        assert_ne!(ids[0], ids[1]);
+
+        // The ids survive a serialization round-trip
+        let serialized = serde_json::to_string(&chat_history).unwrap();
+        let deserialized: Vec<Message> = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, chat_history);
+    }
+
+    #[tokio::test]
+    async fn test_include_reasoning() {
+        let response = || {
+            MockCompletionModel::new().with_response(CompletionResponse {
+                choice: crate::OneOrMany::many(vec![
+                    AssistantContent::Reasoning(crate::message::Reasoning::new(
+                        "The user wants a greeting.",
+                    )),
+                    AssistantContent::text("Hello!"),
+                ])
+                .unwrap(),
+                usage: Usage::new(),
+                raw_response: (),
+            })
+        };
+
+        // The reasoning is stripped from the returned text by default, but kept in the history
+        let agent = AgentBuilder::new(response()).build();
+        let mut chat_history = vec![];
+        let text = agent
+            .prompt("Greet me")
+            .with_history(&mut chat_history)
+            .await
+            .unwrap();
+        assert_eq!(text, "Hello!");
+        let Some(Message::Assistant { content, .. }) = chat_history.last() else {
+            panic!("Expected an assistant message");
+        };
+        assert!(matches!(content.first(), AssistantContent::Reasoning(_)));
+
+        let agent = AgentBuilder::new(response())
+            .include_reasoning(true)
+            .build();
+        let text = agent.prompt("Greet me").await.unwrap();
+        assert_eq!(
+            text,
+            "<reasoning>\nThe user wants a greeting.\n</reasoning>\nHello!"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_completion_retry() {
+        let policy = crate::completion::RetryPolicy::new(2)
+            .initial_backoff(std::time::Duration::from_millis(1));
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "echo", serde_json::json!({"text": "hi"}))
+            .with_status_error(503, "Service Unavailable")
+            .with_text("hi");
+        let agent = AgentBuilder::new(model.clone())
+            .tool(Echo)
+            .completion_retry(policy)
+            .build();
+
+        // The second turn is retried without losing the first one
+        let response = agent.prompt("Echo hi").multi_turn(2).await.unwrap();
+        assert_eq!(response, "hi");
+        let requests = model.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[1].chat_history, requests[2].chat_history);
+
+        // Errors that are not transient are not retried, and the request overrides the agent
+        let model = MockCompletionModel::new()
+            .with_status_error(401, "Unauthorized")
+            .with_text("hi");
+        let agent = AgentBuilder::new(model.clone()).build();
+        assert!(agent.prompt("Hi").completion_retry(policy).await.is_err());
+        assert_eq!(model.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stream_completion_retry() {
+        let policy = crate::completion::RetryPolicy::new(2)
+            .initial_backoff(std::time::Duration::from_millis(1));
+        let model = MockCompletionModel::new()
+            .with_status_error(429, "Too Many Requests")
+            .with_text("hi");
+        let agent = AgentBuilder::new(model.clone())
+            .completion_retry(policy)
+            .build();
+
+        let response = agent
+            .stream_prompt("Hi")
+            .stream_to_writer(&mut vec![])
+            .await
+            .unwrap();
+        assert_eq!(response, "hi");
+        assert_eq!(model.requests().len(), 2);
+
+        // The request overrides the agent
+        let model = MockCompletionModel::new()
+            .with_status_error(429, "Too Many Requests")
+            .with_text("hi");
+        let agent = AgentBuilder::new(model.clone()).build();
+        let error = agent
+            .stream_prompt("Hi")
+            .completion_retry(crate::completion::RetryPolicy::new(0))
+            .stream_to_writer(&mut vec![])
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            StreamingError::Completion(CompletionError::ProviderStatusError { .. })
+        ));
+        assert_eq!(model.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_continue_conversation() {
+        let model = MockCompletionModel::new().with_text("Paris, as I said.");
+        let agent = AgentBuilder::new(model.clone()).build();
+
+        let mut history = vec![
+            Message::user("What is the capital of France?"),
+            Message::assistant("Paris."),
+            Message::user("Are you sure?"),
+        ];
+
+        let response = agent.continue_conversation(&mut history).await.unwrap();
+        assert_eq!(response, "Paris, as I said.");
+
+        // No new message was added to the history before the request
+        let requests = model.requests();
+        assert_eq!(requests[0].chat_history.len(), 3);
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[3], Message::assistant("Paris, as I said."));
+    }
+
+    #[tokio::test]
+    async fn test_continue_empty_conversation() {
+        let model = MockCompletionModel::new().with_text("Hello!");
+        let agent = AgentBuilder::new(model.clone()).build();
+
+        let response = agent.continue_conversation(&mut vec![]).await;
+        assert!(matches!(response, Err(PromptError::EmptyChatHistory)));
+        assert!(model.requests().is_empty());
+    }
+
+    struct Echo;
+
+    impl Tool for Echo {
+        const NAME: &'static str = "echo";
+        type Error = std::convert::Infallible;
+        type Args = serde_json::Value;
+        type Output = serde_json::Value;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: Self::NAME.to_string(),
+                description: "Echo the arguments".to_string(),
+                parameters: serde_json::json!({"type": "object"}),
+            }
+        }
+
+        async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+            Ok(args)
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct AssistantTextHook {
+        texts: std::sync::Arc<std::sync::Mutex<Vec<(usize, String)>>>,
+    }
+
+    impl PromptHook<MockCompletionModel> for AssistantTextHook {
+        async fn on_assistant_text(&self, turn: usize, text: &str) {
+            self.texts.lock().unwrap().push((turn, text.to_string()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_on_assistant_text() {
+        let model = MockCompletionModel::new()
+            .with_response(CompletionResponse {
+                choice: OneOrMany::many(vec![
+                    AssistantContent::text("Let me check the weather..."),
+                    AssistantContent::tool_call("call_1", "echo", serde_json::json!({})),
+                ])
+                .unwrap(),
+                usage: Usage::new(),
+                raw_response: (),
+            })
+            .with_tool_call("call_2", "echo", serde_json::json!({}))
+            .with_text("It is sunny.");
+        let agent = AgentBuilder::new(model).tool(Echo).build();
+        let hook = AssistantTextHook::default();
+
+        let response = agent
+            .prompt("What is the weather?")
+            .multi_turn(3)
+            .with_hook(hook.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(response, "It is sunny.");
+        // Only the text accompanying tool calls is passed to the hook
+        assert_eq!(
+            *hook.texts.lock().unwrap(),
+            vec![(1, "Let me check the weather...".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tool_result_formatter() {
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "echo", serde_json::json!({"city": "Paris"}))
+            .with_text("It is sunny.");
+        let agent = AgentBuilder::new(model.clone())
+            .tool(Echo)
+            .tool_result_formatter(|tool_call, output| {
+                format!("Observation ({}): {output}", tool_call.function.name)
+            })
+            .build();
+
+        agent
+            .prompt("What is the weather?")
+            .multi_turn(2)
+            .await
+            .unwrap();
+
+        let requests = model.requests();
+        let Some(Message::User { content }) = requests[1].chat_history.iter().last() else {
+            panic!("Expected the tool result in a user message");
+        };
+        let UserContent::ToolResult(tool_result) = content.first() else {
+            panic!("Expected a tool result");
+        };
+        assert_eq!(
+            tool_result.content.first(),
+            crate::message::ToolResultContent::text(r#"Observation (echo): {"city":"Paris"}"#)
+        );
+    }
+
+    #[derive(serde::Deserialize)]
+    struct AddArgs {
+        x: i32,
+        y: i32,
+    }
+
+    struct Add;
+
+    impl Tool for Add {
+        const NAME: &'static str = "add";
+        type Error = std::convert::Infallible;
+        type Args = AddArgs;
+        type Output = i32;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: Self::NAME.to_string(),
+                description: "Add x and y together".to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "x": { "type": "number" },
+                        "y": { "type": "number" }
+                    }
+                }),
+            }
+        }
+
+        async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+            Ok(args.x + args.y)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate_tool_args() {
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "add", serde_json::json!({"x": "1", "y": 2}))
+            .with_tool_call("call_2", "add", serde_json::json!({"x": 1, "y": 2}))
+            .with_text("1 + 2 = 3");
+        let agent = AgentBuilder::new(model.clone())
+            .tool(Add)
+            .validate_tool_args(true)
+            .build();
+
+        let response = agent.prompt("What is 1 + 2?").multi_turn(3).await.unwrap();
+        assert_eq!(response, "1 + 2 = 3");
+
+        // The error and the schema of the arguments are sent back to the model
+        let requests = model.requests();
+        let Some(Message::User { content }) = requests[1].chat_history.iter().last() else {
+            panic!("Expected the tool result in a user message");
+        };
+        let UserContent::ToolResult(tool_result) = content.first() else {
+            panic!("Expected a tool result");
+        };
+        let crate::message::ToolResultContent::Text(text) = tool_result.content.first() else {
+            panic!("Expected a text tool result");
+        };
+        assert!(text.text.contains("invalid arguments for tool `add`"));
+        assert!(text.text.contains(r#""properties":{"x":{"type":"number"}"#));
+    }
+
+    #[tokio::test]
+    async fn test_schema_validator() {
+        use crate::schema::SchemaValidationError;
+
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "add", serde_json::json!({"x": 1000, "y": 2}))
+            .with_tool_call("call_2", "add", serde_json::json!({"x": 1, "y": 2}))
+            .with_text("1 + 2 = 3");
+        let agent = AgentBuilder::new(model.clone())
+            .tool(Add)
+            .validate_tool_args(true)
+            .schema_validator(|_: &serde_json::Value, args: &serde_json::Value| {
+                match args["x"].as_f64() {
+                    Some(x) if x >= 100.0 => Err(SchemaValidationError::new(["$.x: too large"])),
+                    _ => Ok(()),
+                }
+            })
+            .build();
+
+        let response = agent.prompt("What is 1 + 2?").multi_turn(3).await.unwrap();
+        assert_eq!(response, "1 + 2 = 3");
+
+        // The validation error is sent back to the model
+        let requests = model.requests();
+        let Some(Message::User { content }) = requests[1].chat_history.iter().last() else {
+            panic!("Expected the tool result in a user message");
+        };
+        let UserContent::ToolResult(tool_result) = content.first() else {
+            panic!("Expected a tool result");
+        };
+        let crate::message::ToolResultContent::Text(text) = tool_result.content.first() else {
+            panic!("Expected a text tool result");
+        };
+        assert!(
+            text.text
+                .contains("invalid arguments for tool `add`: $.x: too large")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_tool_args_retries_exhausted() {
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "add", serde_json::json!({"x": "1", "y": 2}))
+            .with_tool_call("call_2", "add", serde_json::json!({"x": "1", "y": 2}))
+            .with_text("1 + 2 = 3");
+        let agent = AgentBuilder::new(model.clone())
+            .tool(Add)
+            .validate_tool_args(true)
+            .max_arg_retries(1)
+            .build();
+
+        let response = agent.prompt("What is 1 + 2?").multi_turn(3).await;
+        assert!(matches!(response, Err(PromptError::CompletionError(_))));
+        assert_eq!(model.requests().len(), 2);
+
+        // Without validation, the first invalid call fails the prompt
+        let model = MockCompletionModel::new().with_tool_call(
+            "call_1",
+            "add",
+            serde_json::json!({"x": "1", "y": 2}),
+        );
+        let agent = AgentBuilder::new(model.clone()).tool(Add).build();
+
+        let response = agent.prompt("What is 1 + 2?").multi_turn(3).await;
+        assert!(matches!(response, Err(PromptError::CompletionError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_inspect_request() {
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "echo", serde_json::json!({}))
+            .with_text("Done");
+        let agent = AgentBuilder::new(model.clone())
+            .temperature(1.0)
+            .tool(Echo)
+            .build();
+
+        let mut turn = 0;
+        let response = agent
+            .prompt("Hello")
+            .multi_turn(2)
+            .inspect_request(|request| {
+                // Lower the temperature after the first turn
+                if turn > 0 {
+                    request.temperature = Some(0.0);
+                }
+                turn += 1;
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(response, "Done");
+        assert_eq!(turn, 2);
+
+        let requests = model.requests();
+        assert_eq!(requests[0].temperature, Some(1.0));
+        assert_eq!(requests[1].temperature, Some(0.0));
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_tool_call_ids() {
+        let duplicate_tool_calls = || {
+            MockCompletionModel::new()
+                .with_response(CompletionResponse {
+                    choice: OneOrMany::many(vec![
+                        AssistantContent::tool_call("call_1", "echo", serde_json::json!({"n": 1})),
+                        AssistantContent::tool_call("call_1", "echo", serde_json::json!({"n": 2})),
+                    ])
+                    .unwrap(),
+                    usage: Usage::new(),
+                    raw_response: (),
+                })
+                .with_text("Done")
+        };
+
+        // By default, the duplicate tool call is renamed
+        let model = duplicate_tool_calls();
+        let agent = AgentBuilder::new(model.clone()).tool(Echo).build();
+        let response = agent.prompt("Hello").multi_turn(2).await.unwrap();
+        assert_eq!(response, "Done");
+
+        let requests = model.requests();
+        let chat_history = requests[1].chat_history.iter().collect::<Vec<_>>();
+        let Message::Assistant { content, .. } = chat_history[1] else {
+            panic!("Expected an assistant message");
+        };
+        let ids = content
+            .iter()
+            .filter_map(|content| match content {
+                AssistantContent::ToolCall(tool_call) => Some(tool_call.id.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["call_1", "call_1_1"]);
+
+        let Message::User { content } = chat_history[2] else {
+            panic!("Expected a user message");
+        };
+        let ids = content
+            .iter()
+            .filter_map(|content| match content {
+                UserContent::ToolResult(tool_result) => Some(tool_result.id.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["call_1", "call_1_1"]);
+
+        // Or the prompt fails
+        let agent = AgentBuilder::new(duplicate_tool_calls())
+            .tool(Echo)
+            .duplicate_tool_call_ids(DuplicateToolCallIds::Error)
+            .build();
+        let response = agent.prompt("Hello").multi_turn(2).await;
+        assert!(matches!(
+            response,
+            Err(PromptError::DuplicateToolCallId(id)) if id == "call_1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_batch_tool_handler() {
+        let model = MockCompletionModel::new()
+            .with_response(CompletionResponse {
+                choice: OneOrMany::many((1..=3).map(|n| {
+                    AssistantContent::tool_call(
+                        format!("call_{n}"),
+                        "echo",
+                        serde_json::json!({"n": n}),
+                    )
+                }))
+                .unwrap(),
+                usage: Usage::new(),
+                raw_response: (),
+            })
+            .with_text("Done");
+
+        // The handler answers the first two calls in one go, the last one is dispatched to
+        // the tool
+        let batches = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let agent = AgentBuilder::new(model.clone())
+            .tool(Echo)
+            .batch_tool_handler({
+                let batches = batches.clone();
+                move |tool_calls: Vec<ToolCall>| {
+                    let ids = tool_calls
+                        .iter()
+                        .map(|tool_call| tool_call.id.clone())
+                        .collect::<Vec<_>>();
+                    batches.lock().unwrap().push(ids);
+                    async move {
+                        Ok(tool_calls
+                            .into_iter()
+                            .take(2)
+                            .map(|tool_call| {
+                                let output = OneOrMany::one(ToolResultContent::text(format!(
+                                    "Batched {}",
+                                    tool_call.function.arguments["n"]
+                                )));
+                                (tool_call.id, output)
+                            })
+                            .collect())
+                    }
+                }
+            })
+            .build();
+
+        let response = agent.prompt("Hello").multi_turn(2).await.unwrap();
+        assert_eq!(response, "Done");
+        assert_eq!(
+            *batches.lock().unwrap(),
+            vec![vec!["call_1", "call_2", "call_3"]]
+        );
+
+        let requests = model.requests();
+        let chat_history = requests[1].chat_history.iter().collect::<Vec<_>>();
+        let Message::User { content } = chat_history[2] else {
+            panic!("Expected a user message");
+        };
+        let results = content
+            .iter()
+            .filter_map(|content| match content {
+                UserContent::ToolResult(tool_result) => match tool_result.content.first() {
+                    ToolResultContent::Text(text) => Some((tool_result.id.clone(), text.text)),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            vec![
+                ("call_1".to_string(), "Batched 1".to_string()),
+                ("call_2".to_string(), "Batched 2".to_string()),
+                ("call_3".to_string(), r#"{"n":3}"#.to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_tool_handler_skips_invalid_calls() {
+        use crate::{agent::FinalAnswerTool, schema::SchemaValidationError};
+
+        #[derive(serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+        struct Sum {
+            sum: i32,
+        }
+
+        let model = MockCompletionModel::new()
+            .with_response(CompletionResponse {
+                choice: OneOrMany::many([
+                    AssistantContent::tool_call(
+                        "call_1",
+                        "add",
+                        serde_json::json!({"x": 1000, "y": 2}),
+                    ),
+                    AssistantContent::tool_call(
+                        "call_2",
+                        "add",
+                        serde_json::json!({"x": 1, "y": 2}),
+                    ),
+                    AssistantContent::tool_call(
+                        "call_3",
+                        "final_answer",
+                        serde_json::json!({"sum": 3}),
+                    ),
+                ])
+                .unwrap(),
+                usage: Usage::new(),
+                raw_response: (),
+            })
+            .with_tool_call("call_4", "missing", serde_json::json!({}));
+        let batches = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let agent = AgentBuilder::new(model)
+            .tool(Add)
+            .final_answer_tool(FinalAnswerTool::<Sum>::new())
+            .validate_tool_args(true)
+            .schema_validator(|_: &serde_json::Value, args: &serde_json::Value| {
+                match args["x"].as_f64() {
+                    Some(x) if x >= 100.0 => Err(SchemaValidationError::new(["$.x: too large"])),
+                    _ => Ok(()),
+                }
+            })
+            .batch_tool_handler({
+                let batches = batches.clone();
+                move |tool_calls: Vec<ToolCall>| {
+                    batches.lock().unwrap().push(
+                        tool_calls
+                            .iter()
+                            .map(|tool_call| tool_call.id.clone())
+                            .collect::<Vec<_>>(),
+                    );
+                    async move {
+                        Ok(tool_calls
+                            .into_iter()
+                            .map(|tool_call| {
+                                (
+                                    tool_call.id,
+                                    OneOrMany::one(ToolResultContent::text("Batched")),
+                                )
+                            })
+                            .collect())
+                    }
+                }
+            })
+            .build();
+
+        // Only the valid call of a regular tool is batched: the invalid call gets the usual
+        // feedback, and the final answer tool is called as usual
+        let mut chat_history = vec![];
+        let response = agent
+            .prompt("What is 1 + 2?")
+            .with_history(&mut chat_history)
+            .extended_details()
+            .await
+            .unwrap();
+        assert_eq!(response.final_answer, Some(serde_json::json!({"sum": 3})));
+        assert_eq!(*batches.lock().unwrap(), vec![vec!["call_2"]]);
+        let Message::User { content } = &chat_history[2] else {
+            panic!("Expected the tool results");
+        };
+        let results = content
+            .iter()
+            .filter_map(|content| match content {
+                UserContent::ToolResult(tool_result) => match tool_result.content.first() {
+                    ToolResultContent::Text(text) => Some(text.text),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert!(results[0].contains("invalid arguments for tool `add`"));
+        assert_eq!(results[1], "Batched");
+        assert_eq!(results[2], r#"{"sum":3}"#);
+
+        // Calls of unknown tools are not batched either
+        let result = agent.prompt("What is 1 + 2?").await;
+        assert!(matches!(result, Err(PromptError::CompletionError(_))));
+        assert_eq!(batches.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_locale() {
+        let model = MockCompletionModel::new()
+            .with_text("Bonjour")
+            .with_text("Hallo");
+        let agent = AgentBuilder::new(model.clone())
+            .preamble("You are a helpful assistant.")
+            .locale("fr-FR")
+            .build();
+
+        agent.prompt("Hello").await.unwrap();
+        agent
+            .prompt("Hello")
+            .with_config(&AgentPromptConfig::new().locale("de-DE"))
+            .await
+            .unwrap();
+
+        let requests = model.requests();
+        let preamble = requests[0].preamble.as_deref().unwrap();
+        assert!(preamble.starts_with("You are a helpful assistant.\n\n"));
+        assert!(preamble.contains("`fr-FR` locale"));
+        let preamble = requests[1].preamble.as_deref().unwrap();
+        assert!(preamble.contains("`de-DE` locale"));
+        assert!(!preamble.contains("fr-FR"));
+    }
+
+    #[tokio::test]
+    async fn test_on_message() {
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "echo", serde_json::json!({}))
+            .with_text("Done")
+            .with_text("Not persisted");
+        let agent = AgentBuilder::new(model).tool(Echo).build();
+        let persisted = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+
+        let mut chat_history = vec![];
+        let response = agent
+            .prompt("Hello")
+            .with_history(&mut chat_history)
+            .multi_turn(2)
+            .on_message(|message| {
+                let persisted = persisted.clone();
+                let message = message.clone();
+                async move {
+                    persisted.lock().unwrap().push(message);
+                    Ok::<_, std::io::Error>(())
+                }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(response, "Done");
+        // The prompt, the tool call, the tool result and the answer
+        assert_eq!(chat_history.len(), 4);
+        assert_eq!(*persisted.lock().unwrap(), chat_history);
+
+        // A failure of the callback aborts the request
+        let response = agent
+            .prompt("Hello")
+            .on_message(|_| async { Err(std::io::Error::other("Database is down")) })
+            .await;
+        assert!(matches!(
+            response,
+            Err(PromptError::MessageHandlerError(e)) if e.to_string() == "Database is down"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_on_message_moderated() {
+        let moderator = |text: &str| {
+            if text.contains("forbidden") {
+                ModerationResult::flagged(["forbidden"])
+            } else {
+                ModerationResult::allowed()
+            }
+        };
+        let model = MockCompletionModel::new().with_text("Here is the forbidden answer");
+        let agent = AgentBuilder::new(model)
+            .moderator(moderator)
+            .moderate_output(true)
+            .build();
+        let persisted = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let on_message = |message: &Message| {
+            let persisted = persisted.clone();
+            let message = message.clone();
+            async move {
+                persisted.lock().unwrap().push(message);
+                Ok::<_, std::io::Error>(())
+            }
+        };
+
+        // Neither flagged prompts...
+        let result = agent
+            .prompt("Tell me the forbidden answer")
+            .on_message(on_message)
+            .await;
+        assert!(matches!(result, Err(PromptError::Moderated { .. })));
+        assert!(persisted.lock().unwrap().is_empty());
+
+        // ...nor flagged responses are persisted
+        let result = agent
+            .prompt("What is the answer?")
+            .on_message(on_message)
+            .await;
+        assert!(matches!(result, Err(PromptError::Moderated { .. })));
+        assert_eq!(
+            *persisted.lock().unwrap(),
+            vec![Message::user("What is the answer?")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_config() {
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "echo", serde_json::json!({}))
+            .with_text("Done")
+            .with_text("Done again");
+        let agent = AgentBuilder::new(model.clone())
+            .temperature(1.0)
+            .max_tokens(100)
+            .tool(Echo)
+            .build();
+
+        let config = AgentPromptConfig::new().max_depth(2).temperature(0.0);
+
+        // The config allows the tool call round-trip and overrides the temperature on every turn
+        let response = agent.prompt("Hello").with_config(&config).await.unwrap();
+        assert_eq!(response, "Done");
+        let response = agent.prompt("Hello").with_config(&config).await.unwrap();
+        assert_eq!(response, "Done again");
+
+        let requests = model.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(
+            requests
+                .iter()
+                .all(|request| request.temperature == Some(0.0))
+        );
+        assert!(
+            requests
+                .iter()
+                .all(|request| request.max_tokens == Some(100))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_full() {
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "echo", serde_json::json!({}))
+            .with_response(CompletionResponse {
+                choice: OneOrMany::one(AssistantContent::text("Done")),
+                usage: Usage {
+                    input_tokens: 10,
+                    output_tokens: 2,
+                    total_tokens: 12,
+                },
+                raw_response: (),
+            });
+        let agent = AgentBuilder::new(model).tool(Echo).build();
+
+        let (text, response) = agent
+            .prompt("Hello")
+            .multi_turn(2)
+            .send_full()
+            .await
+            .unwrap();
+
+        // The completion response is the one of the last turn
+        assert_eq!(text, "Done");
+        assert_eq!(response.choice.first(), AssistantContent::text("Done"));
+        assert_eq!(response.usage.total_tokens, 12);
+    }
+
+    #[tokio::test]
+    async fn test_choice_is_one_message() {
+        let model = MockCompletionModel::new()
+            .with_response(CompletionResponse {
+                choice: OneOrMany::many(vec![
+                    AssistantContent::text("Let me check."),
+                    AssistantContent::tool_call("call_1", "echo", serde_json::json!({})),
+                ])
+                .unwrap(),
+                usage: Usage::new(),
+                raw_response: (),
+            })
+            .with_response(CompletionResponse {
+                choice: OneOrMany::many(vec![
+                    AssistantContent::text("Part 1"),
+                    AssistantContent::text("Part 2"),
+                ])
+                .unwrap(),
+                usage: Usage::new(),
+                raw_response: (),
+            });
+        let agent = AgentBuilder::new(model).tool(Echo).build();
+
+        let mut chat_history = vec![];
+        let response = agent
+            .prompt("Hello")
+            .multi_turn(2)
+            .with_history(&mut chat_history)
+            .await
+            .unwrap();
+
+        // The texts of a choice are parts of the same message, so they are concatenated...
+        assert_eq!(response, "Part 1\nPart 2");
+        // ...and each choice is pushed to the chat history as a single assistant message
+        assert_eq!(chat_history.len(), 4);
+        assert!(matches!(
+            &chat_history[1],
+            Message::Assistant { content, .. } if content.len() == 2
+        ));
+        assert!(matches!(
+            &chat_history[3],
+            Message::Assistant { content, .. } if content.len() == 2
+        ));
+    }
+
+    #[derive(Clone, Default)]
+    struct Counter {
+        count: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl Tool for Counter {
+        const NAME: &'static str = "increment";
+        type Error = std::convert::Infallible;
+        type Args = serde_json::Value;
+        type Output = usize;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: Self::NAME.to_string(),
+                description: "Increment the counter".to_string(),
+                parameters: serde_json::json!({"type": "object"}),
+            }
+        }
+
+        async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
+            Ok(self.count.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stateful_tool() {
+        let model = MockCompletionModel::new()
+            .with_response(CompletionResponse {
+                choice: OneOrMany::many(vec![
+                    AssistantContent::tool_call("call_1", "increment", serde_json::json!({})),
+                    AssistantContent::tool_call("call_2", "increment", serde_json::json!({})),
+                ])
+                .unwrap(),
+                usage: Usage::new(),
+                raw_response: (),
+            })
+            .with_tool_call("call_3", "increment", serde_json::json!({}))
+            .with_text("Counted to 3");
+        let counter = Counter::default();
+        let agent = AgentBuilder::new(model.clone())
+            .tool(counter.clone())
+            .build();
+
+        agent.prompt("Count to 3").multi_turn(3).await.unwrap();
+
+        // The state is shared across turns and with the caller
+        assert_eq!(counter.count.load(std::sync::atomic::Ordering::SeqCst), 3);
+        let chat_history = &model.requests()[2].chat_history;
+        let results = chat_history
+            .iter()
+            .flat_map(|message| match message {
+                Message::User { content } => content.iter().cloned().collect(),
+                _ => vec![],
+            })
+            .filter_map(|content| match content {
+                UserContent::ToolResult(result) => Some(result.content.first()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            vec![
+                crate::message::ToolResultContent::text("1"),
+                crate::message::ToolResultContent::text("2"),
+                crate::message::ToolResultContent::text("3"),
+            ]
+        );
+    }
+
+    /// A tool waiting for `ms` milliseconds, recording when each call starts and ends.
+    #[derive(Clone, Default)]
+    struct Wait {
+        events: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    impl Tool for Wait {
+        const NAME: &'static str = "wait";
+        type Error = std::convert::Infallible;
+        type Args = serde_json::Value;
+        type Output = String;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: Self::NAME.to_string(),
+                description: "Wait for some time".to_string(),
+                parameters: serde_json::json!({"type": "object"}),
+            }
+        }
+
+        async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+            let name = args["name"].as_str().unwrap_or_default().to_string();
+            self.events.lock().unwrap().push(format!("start {name}"));
+            tokio::time::sleep(std::time::Duration::from_millis(
+                args["ms"].as_u64().unwrap_or_default(),
+            ))
+            .await;
+            self.events.lock().unwrap().push(format!("end {name}"));
+            Ok(name)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_order() {
+        let wait_calls = || {
+            MockCompletionModel::new()
+                .with_response(CompletionResponse {
+                    choice: OneOrMany::many(vec![
+                        AssistantContent::tool_call(
+                            "call_1",
+                            "wait",
+                            serde_json::json!({"name": "slow", "ms": 50}),
+                        ),
+                        AssistantContent::tool_call(
+                            "call_2",
+                            "wait",
+                            serde_json::json!({"name": "fast", "ms": 0}),
+                        ),
+                    ])
+                    .unwrap(),
+                    usage: Usage::new(),
+                    raw_response: (),
+                })
+                .with_text("Done")
+        };
+        let result_ids = |model: &MockCompletionModel| {
+            let requests = model.requests();
+            let Message::User { content } = requests[1].chat_history.iter().last().unwrap() else {
+                panic!("Expected a user message");
+            };
+            content
+                .iter()
+                .filter_map(|content| match content {
+                    UserContent::ToolResult(result) => Some(result.id.clone()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // By default, the calls are executed concurrently, but their results are in order
+        let (model, tool) = (wait_calls(), Wait::default());
+        let agent = AgentBuilder::new(model.clone()).tool(tool.clone()).build();
+        agent.prompt("Wait").multi_turn(2).await.unwrap();
+
+        assert_eq!(
+            *tool.events.lock().unwrap(),
+            vec!["start slow", "start fast", "end fast", "end slow"]
+        );
+        assert_eq!(result_ids(&model), vec!["call_1", "call_2"]);
+
+        // Sequential tools are executed one at a time, in order
+        let (model, tool) = (wait_calls(), Wait::default());
+        let agent = AgentBuilder::new(model.clone())
+            .tool(tool.clone())
+            .sequential_tools(true)
+            .build();
+        agent.prompt("Wait").multi_turn(2).await.unwrap();
+
+        assert_eq!(
+            *tool.events.lock().unwrap(),
+            vec!["start slow", "end slow", "start fast", "end fast"]
+        );
+        assert_eq!(result_ids(&model), vec!["call_1", "call_2"]);
+    }
+
+    #[tokio::test]
+    async fn test_pause_on_tool() {
+        let model = MockCompletionModel::new()
+            .with_response(CompletionResponse {
+                choice: OneOrMany::many(vec![
+                    AssistantContent::tool_call(
+                        "call_1",
+                        "echo",
+                        serde_json::json!({"text": "rm -rf"}),
+                    ),
+                    AssistantContent::tool_call("call_2", "increment", serde_json::json!({})),
+                ])
+                .unwrap(),
+                usage: Usage::new(),
+                raw_response: (),
+            })
+            .with_text("Done");
+        let counter = Counter::default();
+        let agent = AgentBuilder::new(model.clone())
+            .tool(Echo)
+            .tool(counter.clone())
+            .pause_on_tool("echo")
+            .build();
+
+        let mut chat_history = vec![];
+        let Err(PromptError::PendingToolCalls(mut pending)) = agent
+            .prompt("Clean up")
+            .multi_turn(3)
+            .with_history(&mut chat_history)
+            .await
+        else {
+            panic!("Expected pending tool calls");
+        };
+
+        // Only the paused tool awaits a result, the other tool calls of the turn ran
+        assert_eq!(
+            pending
+                .tool_calls()
+                .iter()
+                .map(|tool_call| tool_call.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["call_1"]
+        );
+        assert_eq!(counter.count.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(pending.chat_history().len(), 2);
+
+        assert!(!pending.resume("call_2", "Already done"));
+        // The chat history cannot be continued until every tool call has a result
+        let mut pending = pending.into_chat_history().unwrap_err();
+        assert!(pending.resume("call_1", "Denied by the user"));
+        let mut chat_history = pending.into_chat_history().unwrap();
+
+        let response = agent
+            .continue_conversation(&mut chat_history)
+            .await
+            .unwrap();
+        assert_eq!(response, "Done");
+
+        let Message::User { content } = &chat_history[2] else {
+            panic!("Expected the tool results");
+        };
+        let results = content
+            .iter()
+            .filter_map(|content| match content {
+                UserContent::ToolResult(result) => {
+                    Some((result.id.as_str(), result.content.first()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            vec![
+                (
+                    "call_1",
+                    crate::message::ToolResultContent::text("Denied by the user")
+                ),
+                ("call_2", crate::message::ToolResultContent::text("1")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_pause_on_tool() {
+        let model = MockCompletionModel::new().with_response(CompletionResponse {
+            choice: OneOrMany::many(vec![
+                AssistantContent::tool_call(
+                    "call_1",
+                    "echo",
+                    serde_json::json!({"text": "rm -rf"}),
+                ),
+                AssistantContent::tool_call("call_2", "increment", serde_json::json!({})),
+            ])
+            .unwrap(),
+            usage: Usage::new(),
+            raw_response: (),
+        });
+        let counter = Counter::default();
+        let agent = AgentBuilder::new(model.clone())
+            .tool(Echo)
+            .tool(counter.clone())
+            .pause_on_tool("echo")
+            .build();
+
+        let mut stream = agent.stream_prompt("Clean up").multi_turn(3).await;
+        let mut error = None;
+        while let Some(item) = stream.next().await {
+            if let Err(e) = item {
+                error = Some(e);
+            }
+        }
+
+        // The paused tool is not called, and the stream stops before the next turn
+        let Some(StreamingError::Prompt(PromptError::PendingToolCalls(pending))) = error else {
+            panic!("Expected pending tool calls, got {error:?}");
+        };
+        assert_eq!(
+            pending
+                .tool_calls()
+                .iter()
+                .map(|tool_call| tool_call.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["call_1"]
+        );
+        assert_eq!(counter.count.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(pending.chat_history().len(), 2);
+        assert_eq!(model.requests().len(), 1);
+    }
+
+    struct Sleep;
+
+    impl Tool for Sleep {
+        const NAME: &'static str = "sleep";
+        type Error = std::convert::Infallible;
+        type Args = serde_json::Value;
+        type Output = String;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: Self::NAME.to_string(),
+                description: "Sleep for a while".to_string(),
+                parameters: serde_json::json!({"type": "object"}),
+            }
+        }
+
+        async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
+            tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+            Ok("Done".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_max_duration() {
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "sleep", serde_json::json!({}))
+            .with_text("Done");
+        let agent = AgentBuilder::new(model.clone()).tool(Sleep).build();
+
+        let start = std::time::Instant::now();
+        let result = agent
+            .prompt("Sleep")
+            .multi_turn(2)
+            .max_duration(std::time::Duration::from_millis(50))
+            .await;
+
+        // The tool call is cancelled and dropped from the chat history
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        let Err(PromptError::DeadlineExceeded { chat_history }) = result else {
+            panic!("Expected a DeadlineExceeded error, got {result:?}");
+        };
+        assert_eq!(chat_history, vec![Message::user("Sleep")]);
+        assert_eq!(model.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_max_duration_on_message() {
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "sleep", serde_json::json!({}))
+            .with_text("Done");
+        let agent = AgentBuilder::new(model).tool(Sleep).build();
+        let persisted = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+
+        let result = agent
+            .prompt("Sleep")
+            .multi_turn(2)
+            .max_duration(std::time::Duration::from_millis(50))
+            .on_message(|message| {
+                let persisted = persisted.clone();
+                let message = message.clone();
+                async move {
+                    persisted.lock().unwrap().push(message);
+                    Ok::<_, std::io::Error>(())
+                }
+            })
+            .await;
+
+        // The cancelled tool call is not persisted either
+        let Err(PromptError::DeadlineExceeded { chat_history }) = result else {
+            panic!("Expected a DeadlineExceeded error, got {result:?}");
+        };
+        assert_eq!(*persisted.lock().unwrap(), chat_history);
+    }
+
+    #[tokio::test]
+    async fn test_stream_max_duration() {
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "sleep", serde_json::json!({}))
+            .with_text("Done");
+        let agent = AgentBuilder::new(model.clone()).tool(Sleep).build();
+
+        let start = std::time::Instant::now();
+        let mut stream = agent
+            .stream_prompt("Sleep")
+            .multi_turn(2)
+            .max_duration(std::time::Duration::from_millis(50))
+            .await;
+        let mut error = None;
+        while let Some(item) = stream.next().await {
+            if let Err(e) = item {
+                error = Some(e);
+            }
+        }
+
+        // The tool call is cancelled and dropped from the chat history
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        let Some(StreamingError::Prompt(PromptError::DeadlineExceeded { chat_history })) = error
+        else {
+            panic!("Expected a DeadlineExceeded error, got {error:?}");
+        };
+        assert_eq!(chat_history, vec![Message::user("Sleep")]);
+        assert_eq!(model.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_deadline_before_turn() {
+        let model = MockCompletionModel::new().with_text("Hello!");
+        let agent = AgentBuilder::new(model.clone()).build();
+
+        let result = agent
+            .prompt("Hello")
+            .deadline(std::time::Instant::now())
+            .await;
+
+        assert!(matches!(result, Err(PromptError::DeadlineExceeded { .. })));
+        assert!(model.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_on_max_depth() {
+        let model = || {
+            MockCompletionModel::new()
+                .with_tool_call("call_1", "echo", serde_json::json!({}))
+                .with_tool_call("call_2", "echo", serde_json::json!({}))
+        };
+
+        // The callback sees the chat history and can salvage a response
+        let agent = AgentBuilder::new(model()).tool(Echo).build();
+        let response = agent
+            .prompt("Hello")
+            .on_max_depth(|chat_history| {
+                Some(format!("Gave up after {} messages", chat_history.len()))
+            })
+            .await
+            .unwrap();
+        assert_eq!(response, "Gave up after 5 messages");
+
+        // ...or just observe the failure
+        let agent = AgentBuilder::new(model()).tool(Echo).build();
+        let called = std::sync::atomic::AtomicBool::new(false);
+        let result = agent
+            .prompt("Hello")
+            .on_max_depth(|_| {
+                called.store(true, std::sync::atomic::Ordering::SeqCst);
+                None
+            })
+            .await;
+        assert!(matches!(result, Err(PromptError::MaxDepthError { .. })));
+        assert!(called.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_max_depth() {
+        let model = || {
+            MockCompletionModel::new()
+                .with_tool_call("call_1", "echo", serde_json::json!({}))
+                .with_tool_call("call_2", "echo", serde_json::json!({}))
+                .with_text("Not reached")
+        };
+
+        let mock = model();
+        let agent = AgentBuilder::new(mock.clone()).tool(Echo).build();
+        let result = agent.prompt("Hello").await;
+        let Err(PromptError::MaxDepthError {
+            max_depth,
+            chat_history,
+            prompt,
+        }) = result
+        else {
+            panic!("Expected a MaxDepthError, got {result:?}");
+        };
+        assert_eq!(max_depth, 0);
+        // The prompt, then a tool call and its result for each turn
+        assert_eq!(chat_history.len(), 5);
+        assert_eq!(chat_history.last(), Some(&prompt));
+        assert_eq!(mock.requests().len(), 2);
+
+        // Streamed prompts fail the same way
+        let mock = model();
+        let agent = AgentBuilder::new(mock.clone()).tool(Echo).build();
+        let error = agent
+            .stream_prompt("Hello")
+            .stream_to_writer(&mut vec![])
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            StreamingError::Prompt(PromptError::MaxDepthError { max_depth: 0, .. })
+        ));
+        assert_eq!(mock.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_final_answer_after_max_depth() {
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "echo", serde_json::json!({}))
+            .with_tool_call("call_2", "echo", serde_json::json!({}))
+            .with_text("The echo tool was called twice");
+        let agent = AgentBuilder::new(model.clone()).tool(Echo).build();
+
+        let response = agent
+            .prompt("Hello")
+            .final_answer_after_max_depth(true)
+            .await
+            .unwrap();
+
+        assert_eq!(response, "The echo tool was called twice");
+        let requests = model.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(!requests[1].tools.is_empty());
+        // The final request still carries the tools, but cannot call them, and has the results of
+        // the last tool calls
+        assert!(!requests[2].tools.is_empty());
+        assert_eq!(requests[2].tool_choice, Some(ToolChoice::None));
+        assert_eq!(requests[2].chat_history.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_keep_raw() {
+        let model = MockCompletionModel::new().with_text("Hi!").with_text("Hi!");
+
+        let agent = AgentBuilder::new(model.clone()).build();
+        let response = agent.prompt("Hello").extended_details().await.unwrap();
+        assert_eq!(response.raw, None);
+
+        let agent = AgentBuilder::new(model).keep_raw(true).build();
+        let response = agent.prompt("Hello").extended_details().await.unwrap();
+        // The mock model's raw response is `()`
+        assert_eq!(response.raw, Some(serde_json::Value::Null));
+    }
+
+    #[tokio::test]
+    async fn test_moderator() {
+        let moderator = |text: &str| {
+            if text.contains("forbidden") {
+                ModerationResult::flagged(["forbidden"])
+            } else {
+                ModerationResult::allowed()
+            }
+        };
+        let model = MockCompletionModel::new()
+            .with_text("Hello!")
+            .with_text("Here is the forbidden answer");
+        let agent = AgentBuilder::new(model.clone())
+            .moderator(moderator)
+            .moderate_output(true)
+            .build();
+
+        // Flagged prompts are not sent to the model
+        let result = agent.prompt("Tell me the forbidden answer").await;
+        assert!(matches!(
+            result,
+            Err(PromptError::Moderated { categories }) if categories == vec!["forbidden"]
+        ));
+        assert!(model.requests().is_empty());
+
+        assert_eq!(agent.prompt("Hi").await.unwrap(), "Hello!");
+
+        // ...and neither are flagged responses returned
+        let result = agent.prompt("What is the answer?").await;
+        assert!(matches!(result, Err(PromptError::Moderated { .. })));
+        assert_eq!(model.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stream_moderator() {
+        let moderator = |text: &str| {
+            if text.contains("forbidden") {
+                ModerationResult::flagged(["forbidden"])
+            } else {
+                ModerationResult::allowed()
+            }
+        };
+        let model = MockCompletionModel::new()
+            .with_text("Hello!")
+            .with_text("Here is the forbidden answer");
+        let agent = AgentBuilder::new(model.clone())
+            .moderator(moderator)
+            .moderate_output(true)
+            .build();
+        async fn collect(
+            stream: impl futures::Stream<Item = Result<MultiTurnStreamItem, StreamingError>>,
+        ) -> Vec<Result<Option<String>, StreamingError>> {
+            stream
+                .map_ok(|item| match item {
+                    MultiTurnStreamItem::Text(text) => Some(text.text),
+                    _ => None,
+                })
+                .collect()
+                .await
+        }
+
+        // Flagged prompts are not sent to the model
+        let items = collect(agent.stream_prompt("Tell me the forbidden answer").await).await;
+        assert!(matches!(
+            items.as_slice(),
+            [Err(StreamingError::Prompt(PromptError::Moderated { categories }))]
+                if categories == &vec!["forbidden"]
+        ));
+        assert!(model.requests().is_empty());
+
+        let items = collect(agent.stream_prompt("Hi").await).await;
+        let texts = items
+            .into_iter()
+            .filter_map(|item| item.unwrap())
+            .collect::<String>();
+        assert_eq!(texts, "Hello!\n");
+
+        // ...and neither is the text of flagged responses streamed
+        let items = collect(agent.stream_prompt("What is the answer?").await).await;
+        assert!(
+            items
+                .iter()
+                .all(|item| !matches!(item, Ok(Some(text)) if text.contains("forbidden")))
+        );
+        assert!(matches!(
+            items.last(),
+            Some(Err(StreamingError::Prompt(PromptError::Moderated { .. })))
+        ));
+        assert_eq!(model.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_refusal() {
+        let model = MockCompletionModel::new().with_response(MockCompletionModel::response(
+            AssistantContent::refusal("I can't help with that."),
+        ));
+        let agent = AgentBuilder::new(model).build();
+
+        let result = agent.prompt("How do I pick a lock?").await;
+
+        assert!(matches!(
+            result,
+            Err(PromptError::Refused { reason }) if reason == "I can't help with that."
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stream_usage() {
+        let response = |content, input_tokens, output_tokens| CompletionResponse {
+            usage: Usage {
+                input_tokens,
+                output_tokens,
+                total_tokens: input_tokens + output_tokens,
+            },
+            ..MockCompletionModel::response(content)
+        };
+        let model = MockCompletionModel::new()
+            .with_response(response(
+                AssistantContent::tool_call("call_1", "echo", serde_json::json!({})),
+                10,
+                2,
+            ))
+            .with_response(response(AssistantContent::text("Done"), 20, 5));
+        let agent = AgentBuilder::new(model).tool(Echo).build();
+
+        let mut stream = agent.stream_prompt("Hello").multi_turn(2).await;
+        let mut usages = vec![];
+        let mut final_response = None;
+        while let Some(item) = stream.next().await {
+            match item {
+                Ok(MultiTurnStreamItem::Usage(usage)) => usages.push(usage),
+                Ok(MultiTurnStreamItem::FinalResponse(res)) => final_response = Some(res),
+                _ => {}
+            }
+        }
+
+        // The usage of each turn is yielded, then aggregated in the final response
+        assert_eq!(
+            usages
+                .iter()
+                .map(|usage| usage.total_tokens)
+                .collect::<Vec<_>>(),
+            vec![12, 25]
+        );
+        let final_response = final_response.expect("Expected a final response");
+        assert_eq!(final_response.usage().input_tokens, 30);
+        assert_eq!(final_response.usage().output_tokens, 7);
+        // The price of the mock model is unknown
+        assert_eq!(final_response.estimated_cost(), None);
+
+        let final_response = final_response.with_pricing(Some(Pricing::new(1.0, 2.0)));
+        assert_eq!(final_response.estimated_cost(), Some(0.044));
+    }
+
+    #[tokio::test]
+    async fn test_stream_to_writer() {
+        let model = MockCompletionModel::new()
+            .with_response(CompletionResponse {
+                choice: OneOrMany::many([
+                    AssistantContent::text("Let me check."),
+                    AssistantContent::tool_call("call_1", "echo", serde_json::json!({})),
+                ])
+                .unwrap(),
+                ..MockCompletionModel::response(AssistantContent::text(""))
+            })
+            .with_text("Done");
+        let agent = AgentBuilder::new(model).tool(Echo).build();
+
+        let mut output = vec![];
+        let response = agent
+            .stream_prompt("Hello")
+            .multi_turn(2)
+            .stream_to_writer(&mut output)
+            .await
+            .unwrap();
+
+        assert_eq!(response, "Done");
+        // The texts of the two turns are written on separate lines
+        assert_eq!(String::from_utf8(output).unwrap(), "Let me check.\nDone\n");
+    }
+
+    #[tokio::test]
+    async fn test_stream_to_writer_without_usage() {
+        // The turns are separated even if the provider does not report their usage
+        let mut stream: std::pin::Pin<
+            Box<dyn futures::Stream<Item = Result<MultiTurnStreamItem, StreamingError>> + Send>,
+        > = Box::pin(futures::stream::iter([
+            Ok(MultiTurnStreamItem::text("Let me check.")),
+            Ok(MultiTurnStreamItem::TurnEnd),
+            Ok(MultiTurnStreamItem::text("Done")),
+            Ok(MultiTurnStreamItem::TurnEnd),
+            Ok(MultiTurnStreamItem::final_response("Done", Usage::new())),
+        ]));
+
+        let mut output = vec![];
+        let response = super::streaming::stream_to_writer(&mut stream, &mut output)
+            .await
+            .unwrap();
+
+        assert_eq!(response.response(), "Done");
+        assert_eq!(String::from_utf8(output).unwrap(), "Let me check.\nDone\n");
+    }
+
+    #[tokio::test]
+    async fn test_stream_reconnect() {
+        let model = MockCompletionModel::new()
+            .with_connection_reset()
+            .with_text("Done");
+        let agent = AgentBuilder::new(model.clone()).build();
+
+        // The turn is restarted since nothing was streamed before the connection was reset
+        let mut output = vec![];
+        let response = agent
+            .stream_prompt("Hello")
+            .reconnect(1)
+            .stream_to_writer(&mut output)
+            .await
+            .unwrap();
+        assert_eq!(response, "Done");
+        assert_eq!(model.requests().len(), 2);
+
+        // Without reconnecting, the connection reset is surfaced to the caller
+        let model = MockCompletionModel::new()
+            .with_connection_reset()
+            .with_text("Done");
+        let agent = AgentBuilder::new(model).build();
+        let error = agent
+            .stream_prompt("Hello")
+            .stream_to_writer(&mut vec![])
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            StreamingError::Completion(CompletionError::ConnectionReset(_))
+        ));
+    }
+
+    #[test]
+    fn test_final_response_without_cost() {
+        let json = serde_json::json!({
+            "response": "Done",
+            "aggregatedUsage": {"input_tokens": 1, "output_tokens": 1, "total_tokens": 2}
+        });
+        let final_response: FinalResponse = serde_json::from_value(json).unwrap();
+        assert_eq!(final_response.estimated_cost(), None);
+    }
+
+    #[tokio::test]
+    async fn test_tool_filter() {
+        use std::sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        };
+
+        let premium = Arc::new(AtomicBool::new(false));
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "add", serde_json::json!({"x": 1, "y": 2}))
+            .with_tool_call("call_2", "add", serde_json::json!({"x": 1, "y": 2}))
+            .with_text("3");
+        let agent = AgentBuilder::new(model.clone())
+            .tool(Echo)
+            .tool(Add)
+            .tool_filter({
+                let premium = premium.clone();
+                move |name| name != "add" || premium.load(Ordering::SeqCst)
+            })
+            .build();
+
+        // Unavailable tools are not sent to the model, and calls to them are rejected
+        let result = agent.prompt("1 + 2?").multi_turn(2).await;
+        let Err(PromptError::CompletionError(CompletionError::RequestError(error))) = result else {
+            panic!("Expected the call to `add` to fail");
+        };
+        assert!(matches!(
+            error.downcast_ref::<ToolSetError>(),
+            Some(ToolSetError::ToolUnavailable(name)) if name == "add"
+        ));
+        let tools = |request: &crate::completion::CompletionRequest| {
+            request
+                .tools
+                .iter()
+                .map(|tool| tool.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(tools(&model.requests()[0]), vec!["echo"]);
+
+        // The filter is evaluated for each request
+        premium.store(true, Ordering::SeqCst);
+        let response = agent.prompt("1 + 2?").multi_turn(2).await.unwrap();
+        assert_eq!(response, "3");
+        let mut names = tools(&model.requests()[1]);
+        names.sort();
+        assert_eq!(names, vec!["add", "echo"]);
+    }
+}
@@ -1,18 +1,35 @@
 use crate::{
     OneOrMany,
-    agent::prompt_request::PromptHook,
+    agent::citations::{self, Citation},
+    agent::prompt_request::{
+        PendingToolCalls, PromptHook, call_tool, format_tool_result, invalid_arguments_feedback,
+        logging::{log_request, log_response},
+        message_id, moderate, tool_result, tool_result_text, until, user_text,
+    },
     completion::GetTokenUsage,
-    message::{AssistantContent, Reasoning, ToolResultContent, UserContent},
+    message::{AssistantContent, Reasoning},
     streaming::{StreamedAssistantContent, StreamingCompletion},
+    telemetry,
+};
+use futures::{
+    Stream, StreamExt,
+    channel::mpsc,
+    future::{self, Either},
+    stream::FuturesUnordered,
 };
-use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
-use std::{pin::Pin, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    pin::Pin,
+    sync::{Arc, atomic::AtomicUsize},
+    time::{Duration, Instant},
+};
 use tokio::sync::RwLock;
+use tracing::Instrument;
 
 use crate::{
     agent::Agent,
-    completion::{CompletionError, CompletionModel, PromptError},
+    completion::{CompletionError, CompletionModel, Pricing, PromptError, RetryPolicy},
     message::{Message, Text},
     tool::ToolSetError,
 };
@@ -26,8 +43,15 @@ type StreamingResult = Pin<Box<dyn Stream<Item = Result<MultiTurnStreamItem, Str
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(tag = "type", rename_all = "camelCase")]
+#[non_exhaustive]
 pub enum MultiTurnStreamItem {
     Text(Text),
+    /// Token usage of a turn, yielded at the end of each turn for which the provider reported
+    /// it. The usage of all the turns is aggregated in the [FinalResponse].
+    Usage(crate::completion::Usage),
+    /// The end of a turn, once its whole response was streamed (e.g.: before the results of its
+    /// tool calls are sent back to the model).
+    TurnEnd,
     FinalResponse(FinalResponse),
 }
 
@@ -36,6 +60,10 @@ pub enum MultiTurnStreamItem {
 pub struct FinalResponse {
     response: String,
     aggregated_usage: crate::completion::Usage,
+    #[serde(default)]
+    estimated_cost: Option<f64>,
+    #[serde(default)]
+    citations: Vec<Citation>,
 }
 
 impl FinalResponse {
@@ -43,6 +71,8 @@ impl FinalResponse {
         Self {
             response: String::new(),
             aggregated_usage: crate::completion::Usage::new(),
+            estimated_cost: None,
+            citations: vec![],
         }
     }
 
@@ -53,6 +83,24 @@ impl FinalResponse {
     pub fn usage(&self) -> crate::completion::Usage {
         self.aggregated_usage
     }
+
+    /// Estimated cost (in dollars) of all the completion requests made for this prompt,
+    /// like [PromptResponse::estimated_cost](super::PromptResponse::estimated_cost).
+    /// `None` if the price of the model is unknown.
+    pub fn estimated_cost(&self) -> Option<f64> {
+        self.estimated_cost
+    }
+
+    /// The context documents cited by the response, like
+    /// [PromptResponse::citations](super::PromptResponse::citations).
+    pub fn citations(&self) -> &[Citation] {
+        &self.citations
+    }
+
+    pub(crate) fn with_pricing(mut self, pricing: Option<Pricing>) -> Self {
+        self.estimated_cost = pricing.map(|pricing| self.aggregated_usage.estimated_cost(&pricing));
+        self
+    }
 }
 
 impl MultiTurnStreamItem {
@@ -66,11 +114,14 @@ impl MultiTurnStreamItem {
         Self::FinalResponse(FinalResponse {
             response: response.to_string(),
             aggregated_usage,
+            estimated_cost: None,
+            citations: vec![],
         })
     }
 }
 
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum StreamingError {
     #[error("CompletionError: {0}")]
     Completion(#[from] CompletionError),
@@ -78,6 +129,8 @@ pub enum StreamingError {
     Prompt(#[from] PromptError),
     #[error("ToolSetError: {0}")]
     Tool(#[from] ToolSetError),
+    #[error("IoError: {0}")]
+    Io(#[from] std::io::Error),
 }
 
 /// A builder for creating prompt requests with customizable options.
@@ -104,6 +157,12 @@ where
     agent: Arc<Agent<M>>,
     /// Optional per-request hook for events
     hook: Option<P>,
+    /// Maximum number of times a turn is restarted when its connection is reset
+    max_reconnects: usize,
+    /// Optional deadline of the whole request
+    deadline: Option<Instant>,
+    /// Retry policy of the completion request of each turn, overriding the agent's
+    completion_retry: Option<RetryPolicy>,
 }
 
 impl<M, P> StreamingPromptRequest<M, P>
@@ -120,6 +179,9 @@ where
             max_depth: 0,
             agent,
             hook: None,
+            max_reconnects: 0,
+            deadline: None,
+            completion_retry: None,
         }
     }
 
@@ -147,9 +209,62 @@ where
             max_depth: self.max_depth,
             agent: self.agent,
             hook: Some(hook),
+            max_reconnects: self.max_reconnects,
+            deadline: self.deadline,
+            completion_retry: self.completion_retry,
         }
     }
 
+    /// Restart the completion request of a turn, up to `max_reconnects` times, when its
+    /// connection is reset before the model streamed anything (see
+    /// [CompletionError::ConnectionReset]), e.g.: on flaky networks. Defaults to `0`.
+    ///
+    /// A connection reset after the model started streaming is returned as an error, since the
+    /// content already streamed cannot be resumed: the caller can restart the prompt instead.
+    pub fn reconnect(mut self, max_reconnects: usize) -> Self {
+        self.max_reconnects = max_reconnects;
+        self
+    }
+
+    /// Retry the completion request of each turn that fails with a transient error before
+    /// anything was streamed (see [RetryPolicy]), overriding the agent's retry policy (see
+    /// [AgentBuilder::completion_retry](crate::agent::AgentBuilder::completion_retry)).
+    ///
+    /// Errors once the model started streaming are returned as is (see
+    /// [StreamingPromptRequest::reconnect] for connections reset before that).
+    pub fn completion_retry(mut self, policy: RetryPolicy) -> Self {
+        self.completion_retry = Some(policy);
+        self
+    }
+
+    /// Set a hard deadline for the whole prompt request, regardless of how many turns it takes.
+    /// If the deadline is reached, the in-flight completion request or tool calls are cancelled
+    /// and the stream ends with a [`crate::completion::request::PromptError::DeadlineExceeded`]
+    /// error, whose chat history has the turns completed so far.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Set the maximum wall-clock time the whole prompt request can take, starting now
+    /// (see [StreamingPromptRequest::deadline]).
+    pub fn max_duration(self, max_duration: Duration) -> Self {
+        self.deadline(Instant::now() + max_duration)
+    }
+
+    /// Send the prompt request and write the text of the response to `writer` as it is
+    /// generated (see [stream_to_writer]), e.g.: `std::io::stdout()` for CLI apps.
+    /// Returns the full text of the response.
+    pub async fn stream_to_writer(
+        self,
+        writer: &mut impl std::io::Write,
+    ) -> Result<String, StreamingError> {
+        let mut stream = self.send().await;
+        stream_to_writer(&mut stream, writer)
+            .await
+            .map(|response| response.response)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn send(self) -> StreamingResult {
         let agent_name = self.agent.name_owned();
@@ -164,26 +279,38 @@ where
             let prompt = req.prompt;
             let agent = req.agent;
 
-            let chat_history = if let Some(mut history) = req.chat_history {
-                history.push(prompt.clone());
-                Arc::new(RwLock::new(history))
-            } else {
-                Arc::new(RwLock::new(vec![prompt.clone()]))
-            };
+            // The prompt of each turn is appended to the chat history once it is sent
+            let chat_history = Arc::new(RwLock::new(req.chat_history.unwrap_or_default()));
 
             let mut current_max_depth = 0;
             let mut last_prompt_error = String::new();
+            let arg_retries = AtomicUsize::new(0);
 
             let mut last_text_response = String::new();
             let mut is_text_response = false;
             let mut max_depth_reached = false;
 
             let mut aggregated_usage = crate::completion::Usage::new();
+            let deadline = req.deadline.map(tokio::time::Instant::from_std);
+            let completion_retry = req.completion_retry.or(agent.completion_retry);
+            // The text of each turn is only yielded once the moderator allowed it
+            let moderate_output = agent.moderate_output && agent.moderator.is_some();
+            // ...or once its citations are removed
+            let buffer_output = moderate_output || agent.citations;
+            let mut response_citations = vec![];
 
             Box::pin(async_stream::stream! {
                 let mut current_prompt = prompt.clone();
                 let mut did_call_tool = false;
 
+                // Flagged prompts are not sent to the model
+                if let Some(text) = user_text(&prompt)
+                    && let Err(e) = moderate(&agent, &text).await
+                {
+                    yield Err(e.into());
+                    return;
+                }
+
                 'outer: loop {
                     if current_max_depth > req.max_depth + 1 {
                         last_prompt_error = current_prompt.rag_text().unwrap_or_default();
@@ -202,27 +329,111 @@ where
                     }
 
                     if let Some(ref hook) = req.hook {
-                        let reader = chat_history.read().await;
-                        let prompt = reader.last().cloned().expect("there should always be at least one message in the chat history");
-                        let chat_history_except_last = reader[..reader.len() - 1].to_vec();
-
-                        hook.on_completion_call(&prompt, &chat_history_except_last)
+                        hook.on_completion_call(&current_prompt, &chat_history.read().await)
                             .await;
                     }
 
+                    let history = (*chat_history.read().await).clone();
+                    chat_history.write().await.push(current_prompt.clone());
 
-                    let mut stream = agent
-                        .stream_completion(current_prompt.clone(), (*chat_history.read().await).clone())
-                        .await?
-                        .stream()
-                        .await?;
+                    if deadline.is_some_and(|deadline| tokio::time::Instant::now() >= deadline) {
+                        yield Err(PromptError::DeadlineExceeded {
+                            chat_history: chat_history.read().await.clone(),
+                        }.into());
+                        break 'outer;
+                    }
 
-                    chat_history.write().await.push(current_prompt.clone());
+                    let Some(request) = until(deadline, agent.stream_completion(current_prompt.clone(), history)).await else {
+                        yield Err(PromptError::DeadlineExceeded {
+                            chat_history: chat_history.read().await.clone(),
+                        }.into());
+                        break 'outer;
+                    };
+                    let mut request = request?.build();
+                    if agent.citations {
+                        citations::prepare_request(&mut request);
+                    }
+                    log_request(&agent, &request);
+                    let chat_span = telemetry::chat_span(agent.model.model_name(), &request);
+                    let stream = async {
+                        match completion_retry {
+                            Some(policy) => policy.retry(|| agent.model.stream(request.clone())).await,
+                            None => agent.model.stream(request.clone()).await,
+                        }
+                    };
+                    let Some(stream) = until(deadline, stream.instrument(chat_span.clone())).await else {
+                        yield Err(PromptError::DeadlineExceeded {
+                            chat_history: chat_history.read().await.clone(),
+                        }.into());
+                        break 'outer;
+                    };
+                    let mut stream = stream?;
+
+                    // A connection reset before anything was streamed is restarted transparently
+                    let mut reconnects = 0;
+                    let mut first_content = Some(loop {
+                        let Some(content) = until(deadline, stream.next()).await else {
+                            yield Err(PromptError::DeadlineExceeded {
+                                chat_history: chat_history.read().await.clone(),
+                            }.into());
+                            break 'outer;
+                        };
+                        match content {
+                            Some(Err(CompletionError::ConnectionReset(e))) if reconnects < req.max_reconnects => {
+                                reconnects += 1;
+                                tracing::warn!(target: "rig", "Connection reset ({e}), reconnecting ({reconnects}/{})", req.max_reconnects);
+                                stream = agent.model.stream(request.clone()).await?;
+                            }
+                            content => break content,
+                        }
+                    });
 
                     let mut tool_calls = vec![];
                     let mut tool_results = vec![];
+                    let mut turn_text = String::new();
+                    let mut turn_text_ended = false;
+                    let mut tool_call_ids = HashSet::new();
+
+                    // Streaming tools are started on the first fragment of their arguments and
+                    // driven while the model generates the rest of the response.
+                    let mut streaming_arguments = HashMap::new();
+                    let mut streaming_calls = FuturesUnordered::new();
+                    let mut streaming_results: HashMap<String, Result<_, ToolSetError>> = HashMap::new();
+
+                    loop {
+                        let content = if let Some(content) = first_content.take() {
+                            content
+                        } else {
+                            let next = async {
+                                if streaming_calls.is_empty() {
+                                    Either::Left(stream.next().await)
+                                } else {
+                                    match future::select(stream.next(), streaming_calls.next()).await {
+                                        Either::Left((content, _)) => Either::Left(content),
+                                        Either::Right((result, _)) => Either::Right(result),
+                                    }
+                                }
+                            };
+                            match until(deadline, next).await {
+                                Some(Either::Left(content)) => content,
+                                Some(Either::Right(result)) => {
+                                    if let Some((id, result)) = result {
+                                        streaming_results.insert(id, result);
+                                    }
+                                    continue;
+                                }
+                                None => {
+                                    yield Err(PromptError::DeadlineExceeded {
+                                        chat_history: chat_history.read().await.clone(),
+                                    }.into());
+                                    break 'outer;
+                                }
+                            }
+                        };
+                        let Some(content) = content else {
+                            break;
+                        };
 
-                    while let Some(content) = stream.next().await {
                         match content {
                             Ok(StreamedAssistantContent::Text(text)) => {
                                 if !is_text_response {
@@ -230,31 +441,88 @@ where
                                     is_text_response = true;
                                 }
                                 last_text_response.push_str(&text.text);
-                                yield Ok(MultiTurnStreamItem::text(&text.text));
+                                turn_text.push_str(&text.text);
+                                if !buffer_output {
+                                    yield Ok(MultiTurnStreamItem::text(&text.text));
+                                }
                                 did_call_tool = false;
                             },
-                            Ok(StreamedAssistantContent::ToolCall(tool_call)) => {
+                            Ok(StreamedAssistantContent::ToolCall(mut tool_call)) => {
+                                // Streaming tools are looked up with the id the model streamed the arguments with.
+                                let id = tool_call.id.clone();
+                                agent.duplicate_tool_call_ids.dedup(&mut tool_call, &mut tool_call_ids).map_err(PromptError::DuplicateToolCallId)?;
+
+                                // The result of these tools is provided by the caller
+                                if agent.pause_on_tools.contains(&tool_call.function.name) {
+                                    tool_calls.push(AssistantContent::ToolCall(tool_call.clone()));
+                                    tool_results.push((tool_call, None));
+                                    did_call_tool = true;
+                                    continue;
+                                }
+
                                 if let Some(ref hook) = req.hook {
                                     hook.on_tool_call(&tool_call.function.name, &tool_call.function.arguments.to_string()).await;
                                 }
-                                let tool_result =
-                                    agent.tools.call(&tool_call.function.name, tool_call.function.arguments.to_string()).await?;
+                                let tool_result = async {
+                                    match streaming_arguments.remove(&id) {
+                                        Some(arguments) => {
+                                            // The arguments are complete: close the stream of arguments and wait for the tool.
+                                            drop(arguments);
+                                            loop {
+                                                if let Some(result) = streaming_results.remove(&id) {
+                                                    break result;
+                                                }
+                                                if let Some((id, result)) = streaming_calls.next().await {
+                                                    streaming_results.insert(id, result);
+                                                }
+                                            }
+                                        }
+                                        None => {
+                                            let result = call_tool(&agent, &tool_call.function.name, tool_call.function.arguments.to_string()).await;
+                                            invalid_arguments_feedback(&agent, &tool_call.function.name, result, &arg_retries).await
+                                        }
+                                    }
+                                };
+                                // The tool calls of the turn are dropped, so that every tool call of the chat history has a result
+                                let Some(tool_result) = until(deadline, tool_result).await else {
+                                    yield Err(PromptError::DeadlineExceeded {
+                                        chat_history: chat_history.read().await.clone(),
+                                    }.into());
+                                    break 'outer;
+                                };
+                                let tool_result = tool_result?;
 
                                 if let Some(ref hook) = req.hook {
-                                    hook.on_tool_result(&tool_call.function.name, &tool_call.function.arguments.to_string(), &tool_result.to_string())
+                                    hook.on_tool_result(&tool_call.function.name, &tool_call.function.arguments.to_string(), &tool_result_text(&tool_result))
                                         .await;
                                 }
+                                let tool_result = format_tool_result(&agent, &tool_call, tool_result);
                                 let tool_call_msg = AssistantContent::ToolCall(tool_call.clone());
 
                                 tool_calls.push(tool_call_msg);
-                                tool_results.push((tool_call.id, tool_call.call_id, tool_result));
+                                tool_results.push((tool_call, Some(tool_result)));
 
                                 did_call_tool = true;
                                 // break;
                             },
+                            Ok(StreamedAssistantContent::ToolCallDelta { id, name, delta }) => {
+                                if !streaming_arguments.contains_key(&id) && agent.tools.is_streaming_tool(&name) && agent.is_tool_available(&name) && !agent.pause_on_tools.contains(&name) {
+                                    let (sender, receiver) = mpsc::unbounded();
+                                    streaming_arguments.insert(id.clone(), sender);
+
+                                    let (agent, id) = (agent.clone(), id.clone());
+                                    streaming_calls.push(async move {
+                                        let result = agent.tools.call_streaming(&name, Box::pin(receiver)).await;
+                                        (id, result)
+                                    });
+                                }
+                                if let Some(arguments) = streaming_arguments.get(&id) {
+                                    let _ = arguments.unbounded_send(delta);
+                                }
+                            },
                             Ok(StreamedAssistantContent::Reasoning(rig::message::Reasoning { reasoning, id })) => {
                                 chat_history.write().await.push(rig::message::Message::Assistant {
-                                    id: None,
+                                    id: message_id(&agent),
                                     content: OneOrMany::one(AssistantContent::Reasoning(Reasoning {
                                         reasoning: reasoning.clone(), id
                                     }))
@@ -268,13 +536,18 @@ where
                                     if let Some(ref hook) = req.hook {
                                         hook.on_stream_completion_response_finish(&prompt, &final_resp).await;
                                     }
-                                    yield Ok(MultiTurnStreamItem::text("\n"));
+                                    if buffer_output {
+                                        turn_text_ended = true;
+                                    } else {
+                                        yield Ok(MultiTurnStreamItem::text("\n"));
+                                    }
                                     is_text_response = false;
                                 }
-                                if let Some(usage) = final_resp.token_usage() { aggregated_usage += usage; };
-                                // Do nothing here, since at the moment the final generic is actually unreachable.
-                                // We need to implement a trait that aggregates token usage.
-                                // TODO: Add a way to aggregate token responses from the generic variant
+                                if let Some(usage) = final_resp.token_usage() {
+                                    telemetry::record_usage(&chat_span, &usage);
+                                    aggregated_usage += usage;
+                                    yield Ok(MultiTurnStreamItem::Usage(usage));
+                                };
                             }
                             Err(e) => {
                                 yield Err(e.into());
@@ -283,43 +556,74 @@ where
                         }
                     }
 
+                    if buffer_output && !turn_text.is_empty() {
+                        let text = if agent.citations {
+                            let (text, turn_citations) = citations::parse(&turn_text, &request.documents);
+                            // The turn text is the end of the response text
+                            last_text_response.truncate(last_text_response.len() - turn_text.len());
+                            last_text_response.push_str(&text);
+                            response_citations = turn_citations;
+                            text
+                        } else {
+                            turn_text.clone()
+                        };
+                        if moderate_output && let Err(e) = moderate(&agent, &text).await {
+                            yield Err(e.into());
+                            break 'outer;
+                        }
+                        yield Ok(MultiTurnStreamItem::text(&text));
+                        if turn_text_ended {
+                            yield Ok(MultiTurnStreamItem::text("\n"));
+                        }
+                    }
+                    yield Ok(MultiTurnStreamItem::TurnEnd);
+
+                    let turn_text = (!turn_text.is_empty()).then(|| AssistantContent::text(turn_text));
+                    log_response(&agent, &turn_text.into_iter().chain(tool_calls.iter().cloned()).collect::<Vec<_>>());
+
                     // Add (parallel) tool calls to chat history
-                    if !tool_calls.is_empty() {
+                    if let Ok(content) = OneOrMany::many(tool_calls.clone()) {
                         chat_history.write().await.push(Message::Assistant {
-                            id: None,
-                            content: OneOrMany::many(tool_calls.clone()).expect("Impossible EmptyListError"),
+                            id: message_id(&agent),
+                            content,
                         });
                     }
 
+                    // Like `run`, stop with the tool calls awaiting a result from the caller
+                    if tool_results.iter().any(|(_, output)| output.is_none()) {
+                        yield Err(PromptError::PendingToolCalls(Box::new(PendingToolCalls {
+                            chat_history: chat_history.read().await.clone(),
+                            results: tool_results,
+                        })).into());
+                        break 'outer;
+                    }
+
                     // Add tool results to chat history
-                    for (id, call_id, tool_result) in tool_results {
-                        if let Some(call_id) = call_id {
-                            chat_history.write().await.push(Message::User {
-                                content: OneOrMany::one(UserContent::tool_result_with_call_id(
-                                    &id,
-                                    call_id.clone(),
-                                    OneOrMany::one(ToolResultContent::text(&tool_result)),
-                                )),
-                            });
-                        } else {
+                    for (tool_call, output) in tool_results {
+                        if let Some(output) = output {
                             chat_history.write().await.push(Message::User {
-                                content: OneOrMany::one(UserContent::tool_result(
-                                    &id,
-                                    OneOrMany::one(ToolResultContent::text(&tool_result)),
-                                )),
+                                content: OneOrMany::one(tool_result(&tool_call, output)),
                             });
                         }
-
                     }
 
                     // Set the current prompt to the last message in the chat history
-                    current_prompt = match chat_history.write().await.pop() {
-                        Some(prompt) => prompt,
-                        None => unreachable!("Chat history should never be empty at this point"),
+                    let Some(prompt) = chat_history.write().await.pop() else {
+                        yield Err(PromptError::EmptyChatHistory.into());
+                        break 'outer;
                     };
+                    current_prompt = prompt;
 
                     if !did_call_tool {
-                        yield Ok(MultiTurnStreamItem::final_response(&last_text_response, aggregated_usage));
+                        yield Ok(MultiTurnStreamItem::FinalResponse(
+                            FinalResponse {
+                                response: last_text_response.clone(),
+                                aggregated_usage,
+                                estimated_cost: None,
+                                citations: std::mem::take(&mut response_citations),
+                            }
+                            .with_pricing(agent.model.price_per_1k_tokens()),
+                        ));
                         break;
                     }
                 }
@@ -354,26 +658,58 @@ where
     }
 }
 
-/// helper function to stream a completion request to stdout
-pub async fn stream_to_stdout(
+/// Write the text of a streamed prompt response to `writer` as it is generated, flushing it
+/// after each chunk, and return the final response.
+///
+/// The texts of successive turns (e.g.: before and after tool calls) are written on separate
+/// lines, and the output ends with a newline. Writing stops at the first error of the stream.
+pub async fn stream_to_writer(
     stream: &mut StreamingResult,
-) -> Result<FinalResponse, std::io::Error> {
+    writer: &mut impl std::io::Write,
+) -> Result<FinalResponse, StreamingError> {
     let mut final_res = FinalResponse::empty();
-    print!("Response: ");
+    // Whether the last line written is unterminated, and whether its turn has ended
+    let mut open_line = false;
+    let mut turn_ended = false;
+
     while let Some(content) = stream.next().await {
-        match content {
-            Ok(MultiTurnStreamItem::Text(Text { text })) => {
-                print!("{text}");
-                std::io::Write::flush(&mut std::io::stdout())?;
-            }
-            Ok(MultiTurnStreamItem::FinalResponse(res)) => {
-                final_res = res;
-            }
-            Err(err) => {
-                eprintln!("Error: {err}");
+        match content? {
+            MultiTurnStreamItem::Text(Text { text }) if !text.is_empty() => {
+                if open_line && turn_ended {
+                    writeln!(writer)?;
+                }
+                write!(writer, "{text}")?;
+                writer.flush()?;
+                open_line = !text.ends_with('\n');
+                turn_ended = false;
             }
+            MultiTurnStreamItem::Text(_) | MultiTurnStreamItem::Usage(_) => {}
+            MultiTurnStreamItem::TurnEnd => turn_ended = true,
+            MultiTurnStreamItem::FinalResponse(res) => final_res = res,
         }
     }
 
+    if open_line {
+        writeln!(writer)?;
+        writer.flush()?;
+    }
+
     Ok(final_res)
 }
+
+/// helper function to stream a completion request to stdout (see [stream_to_writer])
+///
+/// Errors of the stream other than I/O errors are printed to stderr.
+pub async fn stream_to_stdout(
+    stream: &mut StreamingResult,
+) -> Result<FinalResponse, std::io::Error> {
+    print!("Response: ");
+    match stream_to_writer(stream, &mut std::io::stdout()).await {
+        Ok(final_res) => Ok(final_res),
+        Err(StreamingError::Io(e)) => Err(e),
+        Err(err) => {
+            eprintln!("Error: {err}");
+            Ok(FinalResponse::empty())
+        }
+    }
+}
@@ -0,0 +1,334 @@
+use serde::Serialize;
+
+use super::{Agent, AgentBuilder};
+use crate::{
+    completion::CompletionModel,
+    embeddings::{
+        Embed, EmbedError, EmbeddingError, EmbeddingModel, EmbeddingsBuilder, TextEmbedder,
+        to_texts,
+    },
+    vector_store::{VectorStoreIndexDyn, in_memory_store::InMemoryVectorStore},
+};
+
+/// Default number of documents retrieved for each prompt by agents built with a [RagAgentBuilder].
+pub const DEFAULT_RAG_SAMPLES: usize = 3;
+
+/// Words of the greetings, thanks and acknowledgements which do not warrant retrieving documents.
+const SMALL_TALK: &str = "\
+    hi hello hey yo thanks thank you thx ty cheers ok okay k bye goodbye see ya good morning \
+    afternoon evening night great cool nice awesome perfect sure yes no yep nope got it much \
+    very so there all a lot";
+
+/// A simple heuristic deciding whether a query warrants retrieving documents, to be used with
+/// [AgentBuilder::should_retrieve]: queries made only of greetings, thanks or
+/// acknowledgements (e.g.: "Hi!", "thanks a lot", "ok, got it") and queries without any
+/// alphanumeric character are considered trivial.
+pub fn is_substantive_query(query: &str) -> bool {
+    query
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|word| !word.is_empty())
+        .any(|word| {
+            !SMALL_TALK
+                .split_whitespace()
+                .any(|small_talk| small_talk.eq_ignore_ascii_case(word))
+        })
+}
+
+/// A builder for RAG agents, i.e.: agents whose prompts are augmented with the most relevant
+/// documents of one or more vector indexes (see [AgentBuilder::dynamic_context]).
+///
+/// Documents added with [RagAgentBuilder::documents] are embedded with the builder's embedding
+/// model and stored in an [InMemoryVectorStore] when the agent is built, which packages the whole
+/// embed → index → retrieve flow in a single builder. Existing vector indexes can be added with
+/// [RagAgentBuilder::index].
+///
+/// # Example
+/// ```no_run
+/// use rig::{
+///     agent::RagAgentBuilder,
+///     client::{CompletionClient, EmbeddingsClient},
+///     completion::Prompt,
+///     providers::openai,
+/// };
+///
+/// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// let openai = openai::Client::new("your-open-ai-api-key");
+///
+/// let agent = RagAgentBuilder::new(
+///     openai.completion_model(openai::GPT_4O),
+///     openai.embedding_model(openai::TEXT_EMBEDDING_3_SMALL),
+/// )
+/// .preamble("You are a dictionary assistant. Use the word definitions below to answer.")
+/// .documents([
+///     "Definition of a *flurbo*: A flurbo is a green alien that lives on cold planets",
+///     "Definition of a *glarb-glarb*: A glarb-glarb is a ancient tool used to farm the land.",
+/// ])?
+/// .samples(1)
+/// .build()
+/// .await?;
+///
+/// let response = agent.prompt("What does \"glarb-glarb\" mean?").await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct RagAgentBuilder<M: CompletionModel, E: EmbeddingModel> {
+    agent: AgentBuilder<M>,
+    embedding_model: E,
+    samples: usize,
+    documents: Vec<RagDocument>,
+    indexes: Vec<Box<dyn VectorStoreIndexDyn>>,
+}
+
+impl<M: CompletionModel, E: EmbeddingModel + 'static> RagAgentBuilder<M, E> {
+    pub fn new(model: M, embedding_model: E) -> Self {
+        Self {
+            agent: AgentBuilder::new(model),
+            embedding_model,
+            samples: DEFAULT_RAG_SAMPLES,
+            documents: vec![],
+            indexes: vec![],
+        }
+    }
+
+    /// Set the preamble (system prompt) of the agent.
+    pub fn preamble(mut self, preamble: &str) -> Self {
+        self.agent = self.agent.preamble(preamble);
+        self
+    }
+
+    /// Set the number of documents retrieved from each index for each prompt
+    /// (defaults to [DEFAULT_RAG_SAMPLES]).
+    pub fn samples(mut self, samples: usize) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// Add a document to embed and index when the agent is built. The document is embedded
+    /// according to its [Embed] implementation and its JSON serialization is what the agent
+    /// receives as context.
+    pub fn document(mut self, document: impl Embed + Serialize) -> Result<Self, EmbedError> {
+        let texts = to_texts(&document)?
+            .into_iter()
+            .filter(|text| !text.trim().is_empty())
+            .collect();
+        let document = serde_json::to_value(&document).map_err(EmbedError::new)?;
+
+        self.documents.push(RagDocument { document, texts });
+        Ok(self)
+    }
+
+    /// Add multiple documents to embed and index when the agent is built.
+    pub fn documents(
+        self,
+        documents: impl IntoIterator<Item = impl Embed + Serialize>,
+    ) -> Result<Self, EmbedError> {
+        documents
+            .into_iter()
+            .try_fold(self, |builder, document| builder.document(document))
+    }
+
+    /// Add an existing vector index to retrieve documents from. The index must use an
+    /// embedding model compatible with its stored embeddings.
+    pub fn index(mut self, index: impl VectorStoreIndexDyn + 'static) -> Self {
+        self.indexes.push(Box::new(index));
+        self
+    }
+
+    /// Configure the rest of the agent (e.g.: temperature, tools, hooks), e.g.:
+    /// `.agent(|agent| agent.temperature(0.0).max_tokens(1024))`.
+    pub fn agent(mut self, f: impl FnOnce(AgentBuilder<M>) -> AgentBuilder<M>) -> Self {
+        self.agent = f(self.agent);
+        self
+    }
+
+    /// Embed and index the documents of the builder, then build the agent.
+    pub async fn build(self) -> Result<Agent<M>, EmbeddingError> {
+        let mut agent = self.agent;
+
+        if !self.documents.is_empty() {
+            let embeddings = EmbeddingsBuilder::new(self.embedding_model.clone())
+                .documents(self.documents)
+                .map_err(|e| EmbeddingError::DocumentError(e.into()))?
+                .build()
+                .await?
+                .into_iter()
+                .map(|(document, embeddings)| (document.document, embeddings));
+
+            let index = InMemoryVectorStore::from_documents(embeddings).index(self.embedding_model);
+            agent = agent.dynamic_context(self.samples, index);
+        }
+
+        for index in self.indexes {
+            agent = agent.boxed_dynamic_context(self.samples, index);
+        }
+
+        Ok(agent.build())
+    }
+}
+
+/// A document of a [RagAgentBuilder], with the texts to embed extracted from the original document.
+struct RagDocument {
+    document: serde_json::Value,
+    texts: Vec<String>,
+}
+
+impl Embed for RagDocument {
+    fn embed(&self, embedder: &mut TextEmbedder) -> Result<(), EmbedError> {
+        self.texts
+            .iter()
+            .for_each(|text| embedder.embed(text.clone()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RagAgentBuilder, is_substantive_query};
+    use crate::{
+        completion::Prompt,
+        test_utils::{MockCompletionModel, MockEmbeddingModel},
+        vector_store::in_memory_store::InMemoryVectorStore,
+    };
+
+    /// A model embedding texts by whether they mention rig or ships.
+    fn embedding_model() -> MockEmbeddingModel {
+        MockEmbeddingModel::from_fn(2, |text| {
+            vec![
+                text.contains("rig") as u8 as f64,
+                text.contains("ship") as u8 as f64,
+            ]
+        })
+    }
+
+    #[tokio::test]
+    async fn test_rag_agent() {
+        let model = MockCompletionModel::new().with_text("rig is a Rust library");
+        let agent = RagAgentBuilder::new(model.clone(), embedding_model())
+            .preamble("Answer using the documents below.")
+            .documents([
+                "A ship is a large watercraft",
+                "rig is a Rust library for LLM applications",
+            ])
+            .unwrap()
+            .samples(1)
+            .build()
+            .await
+            .unwrap();
+
+        let response = agent.prompt("What is rig?").await.unwrap();
+
+        assert_eq!(response, "rig is a Rust library");
+        let requests = model.requests();
+        assert_eq!(
+            requests[0].preamble.as_deref(),
+            Some("Answer using the documents below.")
+        );
+        assert_eq!(requests[0].documents.len(), 1);
+        assert!(requests[0].documents[0].text.contains("Rust library"));
+    }
+
+    #[tokio::test]
+    async fn test_should_retrieve() {
+        let model = MockCompletionModel::new()
+            .with_text("Hello! How can I help?")
+            .with_text("rig is a Rust library");
+        let agent = RagAgentBuilder::new(model.clone(), embedding_model())
+            .documents(["rig is a Rust library for LLM applications"])
+            .unwrap()
+            .agent(|agent| agent.should_retrieve(is_substantive_query))
+            .build()
+            .await
+            .unwrap();
+
+        agent.prompt("Hi!").await.unwrap();
+        agent.prompt("What is rig?").await.unwrap();
+
+        let requests = model.requests();
+        assert!(requests[0].documents.is_empty());
+        assert_eq!(requests[1].documents.len(), 1);
+    }
+
+    #[test]
+    fn test_is_substantive_query() {
+        for query in [
+            "hi",
+            "Hello there!",
+            "thanks a lot",
+            "Ok, got it.",
+            "  ",
+            "👍",
+        ] {
+            assert!(!is_substantive_query(query), "{query:?}");
+        }
+        for query in [
+            "What is rig?",
+            "hi, what's a flurbo?",
+            "thanks, and the price?",
+        ] {
+            assert!(is_substantive_query(query), "{query:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retrieval_trace() {
+        let model = MockCompletionModel::new().with_text("rig is a Rust library");
+        let agent = RagAgentBuilder::new(model, embedding_model())
+            .documents([
+                "A ship is a large watercraft",
+                "rig is a Rust library for LLM applications",
+            ])
+            .unwrap()
+            .samples(1)
+            .agent(|agent| agent.context("rig is open source"))
+            .build()
+            .await
+            .unwrap();
+
+        let (response, trace) = agent
+            .prompt_with_retrieval_trace("What is rig?")
+            .await
+            .unwrap();
+
+        assert_eq!(response, "rig is a Rust library");
+        assert_eq!(trace.documents.len(), 1);
+        assert_eq!(trace.documents[0].score, 1.0);
+        assert_eq!(
+            trace.documents[0].document,
+            "rig is a Rust library for LLM applications"
+        );
+        // The context contains both the static and the retrieved documents
+        assert!(trace.context.contains("rig is open source"));
+        assert!(trace.context.contains(&format!(
+            "<file id: {}>\n\"rig is a Rust library for LLM applications\"\n</file>",
+            trace.documents[0].id
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_rag_agent_with_index() {
+        let embeddings = crate::embeddings::EmbeddingsBuilder::new(embedding_model())
+            .documents(["The ship sailed away".to_string()])
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        let index = InMemoryVectorStore::from_documents(embeddings).index(embedding_model());
+
+        let model = MockCompletionModel::new().with_text("It sailed away");
+        let agent = RagAgentBuilder::new(model.clone(), embedding_model())
+            .documents(["rig is a Rust library"])
+            .unwrap()
+            .index(index)
+            .agent(|agent| agent.temperature(0.0))
+            .build()
+            .await
+            .unwrap();
+
+        agent.prompt("Where did the ship go?").await.unwrap();
+
+        let requests = model.requests();
+        assert_eq!(requests[0].temperature, Some(0.0));
+        // Documents are retrieved from both the builder's documents and the index
+        assert_eq!(requests[0].documents.len(), 2);
+    }
+}
@@ -0,0 +1,374 @@
+//! This module implements the ReAct emulation of tool calls (see [ToolCallStyle::ReAct]), for
+//! models and providers without native tool calling.
+//!
+//! The tools of the agent are described in the preamble, and the model is instructed to call
+//! them with text of the form:
+//! ```text
+//! Thought: I need to look up the weather in Paris.
+//! Action: weather
+//! Action Input: {"city": "Paris"}
+//! ```
+//! Responses of this form are turned into regular tool calls, so the chat history of the agent
+//! is the same as with native tool calls, and the results of the tools are sent back to the
+//! model as `Observation: ...` text.
+//!
+//! # Example
+//! ```rust,ignore
+//! use rig::{agent::ToolCallStyle, completion::Prompt};
+//!
+//! let agent = ollama.agent("llama2")
+//!     .tool(Weather)
+//!     .tool_call_style(ToolCallStyle::ReAct)
+//!     .build();
+//!
+//! let answer = agent.prompt("What's the weather in Paris?").multi_turn(5).await?;
+//! ```
+use serde_json::json;
+
+use crate::{
+    OneOrMany,
+    completion::{CompletionError, CompletionRequest, Message, ToolChoice, ToolDefinition},
+    message::{AssistantContent, UserContent},
+};
+
+use super::prompt_request::tool_result_text;
+
+/// Stop sequence of the requests of a [ToolCallStyle::ReAct] agent, so that the model stops
+/// generating after calling a tool instead of making up its result. It is added to the `stop`
+/// additional parameter (e.g.: OpenAI-compatible providers, Ollama), unless this parameter is
+/// set to a string; providers with another name for stop sequences need it to be configured
+/// with [AgentBuilder::additional_params](super::AgentBuilder::additional_params). The text
+/// following an action is ignored either way.
+pub const REACT_STOP_SEQUENCE: &str = "\nObservation:";
+
+const THOUGHT: &str = "Thought:";
+const ACTION: &str = "Action:";
+const ACTION_INPUT: &str = "Action Input:";
+const OBSERVATION: &str = "Observation:";
+const FINAL_ANSWER: &str = "Final Answer:";
+
+/// How an agent lets the model call its tools (see
+/// [AgentBuilder::tool_call_style](super::AgentBuilder::tool_call_style)).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ToolCallStyle {
+    /// The tools are sent to the provider, and the model calls them with the native tool calls
+    /// of the provider (default).
+    #[default]
+    Native,
+    /// The tools are described in the preamble, and the model calls them by writing
+    /// `Action: <tool>` and `Action Input: <arguments>` lines (ReAct prompting), for models
+    /// without native tool calling. Streamed prompts always use native tool calls.
+    ReAct,
+}
+
+/// Rewrite `request` for ReAct tool calls: its tools are described at the end of the preamble
+/// instead of being sent to the provider (with [REACT_STOP_SEQUENCE] as stop sequence), and the
+/// tool calls and results of its chat history are turned into text.
+pub(crate) fn prepare_request(request: &mut CompletionRequest) {
+    let tools = std::mem::take(&mut request.tools);
+    // The tools cannot be called (e.g.: on the final answer turn), so they are not described
+    if !tools.is_empty() && request.tool_choice != Some(ToolChoice::None) {
+        let instructions = instructions(&tools);
+        request.preamble = Some(match request.preamble.take() {
+            Some(preamble) if !preamble.is_empty() => format!("{preamble}\n\n{instructions}"),
+            _ => instructions,
+        });
+
+        if let Some(params) = request
+            .additional_params
+            .get_or_insert_with(|| json!({}))
+            .as_object_mut()
+            && let Some(stop) = params
+                .entry("stop")
+                .or_insert_with(|| json!([]))
+                .as_array_mut()
+            && !stop.contains(&json!(REACT_STOP_SEQUENCE))
+        {
+            stop.push(json!(REACT_STOP_SEQUENCE));
+        }
+    }
+
+    request.chat_history.iter_mut().for_each(to_text);
+}
+
+/// The instructions describing `tools` and how to call them.
+fn instructions(tools: &[ToolDefinition]) -> String {
+    let descriptions = tools
+        .iter()
+        .map(|tool| {
+            format!(
+                "{}: {}\nArguments schema: {}",
+                tool.name, tool.description, tool.parameters
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let names = tools
+        .iter()
+        .map(|tool| tool.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        "You have access to the following tools:\n\n\
+        {descriptions}\n\n\
+        To use a tool, respond with the following format, then stop:\n\n\
+        {THOUGHT} <your reasoning>\n\
+        {ACTION} <the name of the tool, one of [{names}]>\n\
+        {ACTION_INPUT} <the arguments of the tool, as JSON>\n\n\
+        The result of the tool will then be given to you as:\n\n\
+        {OBSERVATION} <the result of the tool>\n\n\
+        When you know the answer, respond with the following format:\n\n\
+        {THOUGHT} <your reasoning>\n\
+        {FINAL_ANSWER} <your answer>"
+    )
+}
+
+/// Turn the tool calls and tool results of `message` into text (with the thoughts preceding the
+/// tool calls).
+fn to_text(message: &mut Message) {
+    match message {
+        Message::User { content } => {
+            for content in content.iter_mut() {
+                if let UserContent::ToolResult(result) = content {
+                    let text = format!("{OBSERVATION} {}", tool_result_text(&result.content));
+                    *content = UserContent::text(text);
+                }
+            }
+        }
+        Message::Assistant { content, .. } => {
+            let has_tool_calls = content
+                .iter()
+                .any(|content| matches!(content, AssistantContent::ToolCall(_)));
+            for content in content.iter_mut() {
+                if has_tool_calls && let AssistantContent::Text(text) = content {
+                    text.text = format!("{THOUGHT} {}", text.text);
+                }
+                if let AssistantContent::ToolCall(tool_call) = content {
+                    let text = format!(
+                        "{ACTION} {}\n{ACTION_INPUT} {}",
+                        tool_call.function.name, tool_call.function.arguments
+                    );
+                    *content = AssistantContent::text(text);
+                }
+            }
+        }
+    }
+}
+
+/// A step of the model, parsed from its text.
+#[derive(Debug, PartialEq)]
+enum Step {
+    Action {
+        thought: String,
+        name: String,
+        arguments: serde_json::Value,
+    },
+    FinalAnswer(String),
+}
+
+/// Parse the action or the final answer of `text`, whichever comes first.
+fn parse(text: &str) -> Option<Step> {
+    let action = text.find(ACTION);
+    let final_answer = text.find(FINAL_ANSWER);
+
+    match (action, final_answer) {
+        (Some(action), final_answer) if final_answer.is_none_or(|answer| action < answer) => {
+            let thought = text[..action].trim();
+            let thought = thought.strip_prefix(THOUGHT).unwrap_or(thought).trim();
+            let text = &text[action + ACTION.len()..];
+            let input = text.find(ACTION_INPUT)?;
+            let name = text[..input].trim();
+            let arguments = text[input + ACTION_INPUT.len()..]
+                .split(OBSERVATION)
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .trim_start_matches("```json")
+                .trim_matches('`')
+                .trim();
+
+            Some(Step::Action {
+                thought: thought.to_string(),
+                name: name.to_string(),
+                arguments: serde_json::from_str(arguments)
+                    .unwrap_or_else(|_| serde_json::Value::String(arguments.to_string())),
+            })
+        }
+        (_, Some(answer)) => Some(Step::FinalAnswer(
+            text[answer + FINAL_ANSWER.len()..].trim().to_string(),
+        )),
+        _ => None,
+    }
+}
+
+/// Turn the text of the response `choice` into a tool call with id `id` if the model called a
+/// tool (preceded by the thought of the model, if any), or into the final answer of the model.
+/// Responses without any action or final answer (or with native tool calls) are left as is.
+pub(crate) fn parse_response(
+    choice: OneOrMany<AssistantContent>,
+    id: String,
+) -> Result<OneOrMany<AssistantContent>, CompletionError> {
+    if choice
+        .iter()
+        .any(|content| matches!(content, AssistantContent::ToolCall(_)))
+    {
+        return Ok(choice);
+    }
+
+    let text = choice
+        .iter()
+        .filter_map(|content| match content {
+            AssistantContent::Text(text) => Some(text.text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let Some(step) = parse(&text) else {
+        return Ok(choice);
+    };
+
+    let mut content = choice
+        .into_iter()
+        .filter(|content| !matches!(content, AssistantContent::Text(_)))
+        .collect::<Vec<_>>();
+    match step {
+        Step::Action {
+            thought,
+            name,
+            arguments,
+        } => {
+            if !thought.is_empty() {
+                content.push(AssistantContent::text(thought));
+            }
+            content.push(AssistantContent::tool_call(id, name, arguments));
+        }
+        Step::FinalAnswer(answer) => content.push(AssistantContent::text(answer)),
+    }
+
+    OneOrMany::many(content)
+        .map_err(|_| CompletionError::ResponseError("The ReAct response has no content".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{REACT_STOP_SEQUENCE, Step, ToolCallStyle, parse};
+    use crate::{
+        agent::AgentBuilder,
+        completion::{Message, Prompt, ToolDefinition},
+        message::{AssistantContent, UserContent},
+        test_utils::MockCompletionModel,
+        tool::Tool,
+    };
+
+    #[test]
+    fn test_parse() {
+        let step = parse(
+            "Thought: I need the weather.\nAction: weather\nAction Input: {\"city\": \"Paris\"}\n\
+            Observation: Sunny",
+        );
+        assert_eq!(
+            step,
+            Some(Step::Action {
+                thought: "I need the weather.".to_string(),
+                name: "weather".to_string(),
+                arguments: serde_json::json!({"city": "Paris"}),
+            })
+        );
+
+        let step = parse("Action: weather\nAction Input: ```json\n{\"city\": \"Paris\"}\n```");
+        assert!(matches!(
+            step,
+            Some(Step::Action { arguments, .. }) if arguments == serde_json::json!({"city": "Paris"})
+        ));
+
+        let step = parse("Thought: I know the answer.\nFinal Answer: It is sunny.");
+        assert_eq!(step, Some(Step::FinalAnswer("It is sunny.".to_string())));
+
+        assert_eq!(parse("It is sunny."), None);
+    }
+
+    struct Weather;
+
+    impl Tool for Weather {
+        const NAME: &'static str = "weather";
+        type Error = std::convert::Infallible;
+        type Args = serde_json::Value;
+        type Output = String;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: Self::NAME.to_string(),
+                description: "Get the weather of a city".to_string(),
+                parameters: serde_json::json!({"type": "object"}),
+            }
+        }
+
+        async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+            Ok(format!(
+                "Sunny in {}",
+                args["city"].as_str().unwrap_or_default()
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_react_agent() {
+        let model = MockCompletionModel::new()
+            .with_text(
+                "Thought: I need the weather.\nAction: weather\nAction Input: {\"city\": \"Paris\"}",
+            )
+            .with_text("Thought: I know the answer.\nFinal Answer: It is sunny in Paris.");
+        let agent = AgentBuilder::new(model.clone())
+            .preamble("You are a helpful assistant.")
+            .tool(Weather)
+            .tool_call_style(ToolCallStyle::ReAct)
+            .build();
+
+        let answer = agent
+            .prompt("What's the weather in Paris?")
+            .multi_turn(2)
+            .await
+            .unwrap();
+        assert_eq!(answer, "It is sunny in Paris.");
+
+        let requests = model.requests();
+        // The tools are described in the preamble instead of being sent to the provider
+        assert!(requests[0].tools.is_empty());
+        let preamble = requests[0].preamble.as_deref().unwrap();
+        assert!(preamble.starts_with("You are a helpful assistant.\n\n"));
+        assert!(preamble.contains("weather: Get the weather of a city"));
+        // The model stops before making up the result of the tool
+        assert_eq!(
+            requests[0].additional_params,
+            Some(serde_json::json!({ "stop": [REACT_STOP_SEQUENCE] }))
+        );
+
+        // The tool call and its result are sent back as text
+        let chat_history = requests[1].chat_history.iter().collect::<Vec<_>>();
+        let Message::Assistant { content, .. } = chat_history[1] else {
+            panic!("Expected an assistant message");
+        };
+        let texts = content
+            .iter()
+            .map(|content| match content {
+                AssistantContent::Text(text) => text.text.as_str(),
+                _ => panic!("Expected text"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            vec![
+                "Thought: I need the weather.",
+                "Action: weather\nAction Input: {\"city\":\"Paris\"}"
+            ]
+        );
+        let Message::User { content } = chat_history[2] else {
+            panic!("Expected a user message");
+        };
+        assert_eq!(
+            content.first(),
+            UserContent::text("Observation: \"Sunny in Paris\"")
+        );
+    }
+}
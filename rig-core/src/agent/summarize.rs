@@ -0,0 +1,167 @@
+use super::Agent;
+use crate::{
+    completion::{CompletionModel, Message, PromptError},
+    message::{AssistantContent, ToolResultContent, UserContent},
+    redact::redact_message,
+};
+
+/// Instructions sent as the preamble of the requests of [Agent::summarize].
+pub const SUMMARIZE_PREAMBLE: &str = "\
+You summarize conversations between a user and an AI assistant. The summary replaces the \
+conversation in the assistant's context, so it must preserve everything needed to continue it: \
+key facts (names, numbers, preferences), decisions made, results of tool calls, and open \
+questions or pending tasks. Be concise: omit greetings, pleasantries and repetitions. \
+Answer with the summary only.";
+
+impl<M: CompletionModel> Agent<M> {
+    /// Summarize a conversation, e.g.: to replace its older turns in a long chat history and
+    /// keep it within the model's context window.
+    ///
+    /// The conversation is sent to the agent's model as a transcript, with [SUMMARIZE_PREAMBLE]
+    /// as the preamble (instead of the agent's preamble, context and tools). The agent's
+    /// redactor, if any, is applied to the transcript.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// // Replace everything but the last 4 messages with a summary
+    /// let split = history.len().saturating_sub(4);
+    /// let summary = agent.summarize(&history[..split]).await?;
+    ///
+    /// history.splice(
+    ///     ..split,
+    ///     [Message::user(format!("Summary of the conversation so far:\n{summary}"))],
+    /// );
+    /// ```
+    pub async fn summarize(&self, history: &[Message]) -> Result<String, PromptError> {
+        let transcript = history
+            .iter()
+            .cloned()
+            .map(|message| match &self.redactor {
+                Some(redactor) => {
+                    redact_message(redactor.as_ref(), message, self.redact_tool_results)
+                }
+                None => message,
+            })
+            .map(|message| transcript(&message))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let response = self
+            .model
+            .completion_request(Message::user(format!(
+                "Summarize the following conversation:\n\n{transcript}"
+            )))
+            .preamble(SUMMARIZE_PREAMBLE.to_string())
+            .max_tokens_opt(self.max_tokens)
+            .send()
+            .await?;
+
+        Ok(response
+            .choice
+            .iter()
+            .filter_map(|content| match content {
+                AssistantContent::Text(text) => Some(text.text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+}
+
+/// Render a message as lines of a plain text transcript.
+pub(crate) fn transcript(message: &Message) -> String {
+    match message {
+        Message::User { content } => content
+            .iter()
+            .map(|content| match content {
+                UserContent::Text(text) => format!("User: {}", text.text),
+                UserContent::ToolResult(tool_result) => {
+                    let result = tool_result
+                        .content
+                        .iter()
+                        .map(|content| match content {
+                            ToolResultContent::Text(text) => text.text.as_str(),
+                            ToolResultContent::Image(_) => "[image]",
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    format!("Tool result ({}): {result}", tool_result.id)
+                }
+                UserContent::Image(_) => "User: [image]".to_string(),
+                UserContent::Audio(_) => "User: [audio]".to_string(),
+                UserContent::Video(_) => "User: [video]".to_string(),
+                UserContent::Document(_) => "User: [document]".to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Message::Assistant { content, .. } => content
+            .iter()
+            .filter_map(|content| match content {
+                AssistantContent::Text(text) => Some(format!("Assistant: {}", text.text)),
+                AssistantContent::Refusal(refusal) => {
+                    Some(format!("Assistant refused: {}", refusal.refusal))
+                }
+                AssistantContent::ToolCall(tool_call) => Some(format!(
+                    "Assistant called tool {} ({}): {}",
+                    tool_call.function.name, tool_call.id, tool_call.function.arguments
+                )),
+                // Reasoning is not part of the conversation
+                AssistantContent::Reasoning(_) => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SUMMARIZE_PREAMBLE;
+    use crate::{
+        OneOrMany,
+        agent::AgentBuilder,
+        completion::Message,
+        message::{AssistantContent, UserContent},
+        test_utils::MockCompletionModel,
+    };
+
+    #[tokio::test]
+    async fn test_summarize() {
+        let model = MockCompletionModel::new().with_text("The user asked about rig.");
+        let agent = AgentBuilder::new(model.clone())
+            .preamble("You are a helpful assistant.")
+            .redactor(|text: &str| text.replace("secret", "[REDACTED]"))
+            .build();
+        let history = vec![
+            Message::user("What is rig? My password is secret"),
+            Message::Assistant {
+                id: None,
+                content: OneOrMany::one(AssistantContent::tool_call(
+                    "call_1",
+                    "search",
+                    serde_json::json!({"query": "rig"}),
+                )),
+            },
+            Message::tool_result("call_1", "rig is a Rust library"),
+            Message::assistant("rig is a Rust library for LLM applications."),
+        ];
+
+        let summary = agent.summarize(&history).await.unwrap();
+
+        assert_eq!(summary, "The user asked about rig.");
+        let requests = model.requests();
+        assert_eq!(requests[0].preamble.as_deref(), Some(SUMMARIZE_PREAMBLE));
+        assert!(requests[0].tools.is_empty());
+        let Some(Message::User { content }) = requests[0].chat_history.iter().last() else {
+            panic!("Expected a user message");
+        };
+        let UserContent::Text(prompt) = content.first() else {
+            panic!("Expected a text prompt");
+        };
+        assert!(prompt.text.ends_with(
+            "User: What is rig? My password is [REDACTED]\n\
+             Assistant called tool search (call_1): {\"query\":\"rig\"}\n\
+             Tool result (call_1): rig is a Rust library\n\
+             Assistant: rig is a Rust library for LLM applications."
+        ));
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use crate::{
     agent::Agent,
     completion::{CompletionModel, Prompt, PromptError, ToolDefinition},
@@ -42,3 +44,266 @@ impl<M: CompletionModel> Tool for Agent<M> {
         Self::NAME.to_string()
     }
 }
+
+/// Wrap `agent` as a tool named `name`, so that a supervisor agent can delegate tasks to it
+/// (see [AgentTool]).
+///
+/// # Example
+/// ```rust,ignore
+/// let researcher = openai.agent(openai::GPT_4O)
+///     .preamble("You research topics on the web.")
+///     .tool(WebSearch)
+///     .build();
+///
+/// let supervisor = openai.agent(openai::GPT_4O)
+///     .preamble("You write reports, delegating the research to the researcher.")
+///     .tool(
+///         agent_as_tool(researcher, "researcher", "Research a topic and summarize the findings")
+///             .multi_turn(5)
+///             .max_duration(Duration::from_secs(60)),
+///     )
+///     .build();
+/// ```
+pub fn agent_as_tool<M: CompletionModel>(
+    agent: Agent<M>,
+    name: &str,
+    description: &str,
+) -> AgentTool<M> {
+    AgentTool {
+        agent,
+        name: name.to_string(),
+        description: description.to_string(),
+        max_depth: 0,
+        max_duration: None,
+    }
+}
+
+/// A sub-agent called as a tool (see [agent_as_tool]): the prompt argument of the tool call is
+/// sent to the sub-agent, and its final answer is the result of the tool.
+///
+/// The sub-agent runs within the tool call of its parent, so it is cancelled with it, e.g.: when
+/// the deadline of the parent prompt is reached (see
+/// [PromptRequest::deadline](crate::agent::PromptRequest::deadline)). Errors of the sub-agent
+/// (including exceeding its own maximum duration) are returned as tool errors.
+pub struct AgentTool<M: CompletionModel> {
+    agent: Agent<M>,
+    name: String,
+    description: String,
+    max_depth: usize,
+    max_duration: Option<Duration>,
+}
+
+impl<M: CompletionModel> AgentTool<M> {
+    /// Set the maximum number of turns of the sub-agent for each call (see
+    /// [PromptRequest::multi_turn](crate::agent::PromptRequest::multi_turn)), defaults to a
+    /// single turn.
+    pub fn multi_turn(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Set the maximum duration of each call of the sub-agent (see
+    /// [PromptRequest::max_duration](crate::agent::PromptRequest::max_duration)).
+    pub fn max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+}
+
+impl<M: CompletionModel> Tool for AgentTool<M> {
+    const NAME: &'static str = "agent_tool";
+
+    type Error = PromptError;
+    type Args = AgentToolArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            parameters: serde_json::to_value(schema_for!(AgentToolArgs))
+                .expect("converting JSON schema to JSON value should never fail"),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let request = self.agent.prompt(args.prompt).multi_turn(self.max_depth);
+        match self.max_duration {
+            Some(max_duration) => request.max_duration(max_duration).await,
+            None => request.await,
+        }
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+        time::Duration,
+    };
+
+    use super::agent_as_tool;
+    use crate::{
+        agent::{Agent, AgentBuilder},
+        completion::{Message, Prompt, PromptError, ToolDefinition},
+        message::{ToolResultContent, UserContent},
+        test_utils::MockCompletionModel,
+        tool::Tool,
+    };
+
+    #[tokio::test]
+    async fn test_agent_as_tool() {
+        let sub_model = MockCompletionModel::new().with_text("Rust was first released in 2015.");
+        let researcher = AgentBuilder::new(sub_model.clone())
+            .preamble("You research topics.")
+            .build();
+
+        let model = MockCompletionModel::new()
+            .with_tool_call(
+                "call_1",
+                "researcher",
+                serde_json::json!({"prompt": "When was Rust released?"}),
+            )
+            .with_text("Rust was released in 2015.");
+        let supervisor = AgentBuilder::new(model.clone())
+            .tool(agent_as_tool(researcher, "researcher", "Research a topic"))
+            .build();
+
+        let answer = supervisor
+            .prompt("Write a report on Rust.")
+            .multi_turn(2)
+            .await
+            .unwrap();
+        assert_eq!(answer, "Rust was released in 2015.");
+
+        let requests = model.requests();
+        assert_eq!(requests[0].tools[0].name, "researcher");
+        assert_eq!(requests[0].tools[0].description, "Research a topic");
+
+        // The sub-agent is prompted with the arguments of the tool call
+        let sub_requests = sub_model.requests();
+        assert_eq!(
+            sub_requests[0].chat_history.iter().last().cloned(),
+            Some(Message::user("When was Rust released?"))
+        );
+
+        // Its answer is the result of the tool call
+        let Some(Message::User { content }) = requests[1].chat_history.iter().last() else {
+            panic!("Expected a user message");
+        };
+        let UserContent::ToolResult(result) = content.first() else {
+            panic!("Expected a tool result");
+        };
+        assert_eq!(
+            result.content.first(),
+            ToolResultContent::text("\"Rust was first released in 2015.\"")
+        );
+    }
+
+    /// A tool sleeping for 10 seconds, recording whether its call started and whether it was
+    /// cancelled (i.e.: dropped before completing).
+    #[derive(Clone, Default)]
+    struct Sleep {
+        started: Arc<AtomicBool>,
+        cancelled: Arc<AtomicBool>,
+    }
+
+    struct SetOnDrop(Arc<AtomicBool>);
+
+    impl Drop for SetOnDrop {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    impl Tool for Sleep {
+        const NAME: &'static str = "sleep";
+        type Error = std::convert::Infallible;
+        type Args = serde_json::Value;
+        type Output = String;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: Self::NAME.to_string(),
+                description: "Sleep for a while".to_string(),
+                parameters: serde_json::json!({"type": "object"}),
+            }
+        }
+
+        async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
+            self.started.store(true, Ordering::SeqCst);
+            let guard = SetOnDrop(self.cancelled.clone());
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            std::mem::forget(guard);
+            Ok("Done".to_string())
+        }
+    }
+
+    /// A supervisor delegating to a researcher which sleeps (see [Sleep]).
+    fn sleeping_supervisor(sleep: Sleep) -> (Agent<MockCompletionModel>, MockCompletionModel) {
+        let sub_model = MockCompletionModel::new()
+            .with_tool_call("sub_call_1", "sleep", serde_json::json!({}))
+            .with_text("Done researching.");
+        let researcher = AgentBuilder::new(sub_model.clone()).tool(sleep).build();
+
+        let model = MockCompletionModel::new()
+            .with_tool_call(
+                "call_1",
+                "researcher",
+                serde_json::json!({"prompt": "Research Rust"}),
+            )
+            .with_text("Done.");
+        let supervisor = AgentBuilder::new(model)
+            .tool(agent_as_tool(researcher, "researcher", "Research a topic").multi_turn(2))
+            .build();
+
+        (supervisor, sub_model)
+    }
+
+    #[tokio::test]
+    async fn test_agent_as_tool_parent_deadline() {
+        let sleep = Sleep::default();
+        let (supervisor, sub_model) = sleeping_supervisor(sleep.clone());
+
+        let start = std::time::Instant::now();
+        let result = supervisor
+            .prompt("Write a report on Rust.")
+            .multi_turn(2)
+            .max_duration(Duration::from_millis(50))
+            .await;
+
+        // The deadline of the parent cancels the sub-agent in the middle of its tool call
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(
+            matches!(result, Err(PromptError::DeadlineExceeded { .. })),
+            "{result:?}"
+        );
+        assert!(sleep.started.load(Ordering::SeqCst));
+        assert!(sleep.cancelled.load(Ordering::SeqCst));
+        assert_eq!(sub_model.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_agent_as_tool_parent_dropped() {
+        let sleep = Sleep::default();
+        let (supervisor, sub_model) = sleeping_supervisor(sleep.clone());
+
+        // Dropping the prompt of the parent cancels the sub-agent in the middle of its tool call
+        let result = tokio::time::timeout(
+            Duration::from_millis(50),
+            supervisor.prompt("Write a report on Rust.").multi_turn(2),
+        )
+        .await;
+        assert!(result.is_err());
+        assert!(sleep.started.load(Ordering::SeqCst));
+        assert!(sleep.cancelled.load(Ordering::SeqCst));
+        assert_eq!(sub_model.requests().len(), 1);
+    }
+}
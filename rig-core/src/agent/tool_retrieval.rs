@@ -0,0 +1,212 @@
+//! This module provides [ToolRetrieval], used by agents with many tools to only advertise the
+//! tools most relevant to each prompt (see
+//! [AgentBuilder::tool_retrieval](super::AgentBuilder::tool_retrieval)).
+//!
+//! # Example
+//! ```rust,ignore
+//! use rig::providers::openai;
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .tools(github_tools)
+//!     // Only advertise the 10 tools whose description is the most similar to the prompt
+//!     .tool_retrieval(openai.embedding_model(openai::TEXT_EMBEDDING_3_SMALL), 10)
+//!     .build();
+//! ```
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::{
+    completion::ToolDefinition,
+    embeddings::{
+        Embedding, EmbeddingError, EmbeddingModel, distance::VectorDistance,
+        embedding::EmbeddingModelDyn,
+    },
+};
+
+/// Selection of the `top_k` static tools of an agent whose description is the most similar to
+/// the prompt, by cosine similarity of their embeddings.
+///
+/// The descriptions of the tools are embedded once, the first time the tools are selected, and
+/// the RAG text of each prompt (see [Message::rag_text](crate::completion::Message::rag_text))
+/// is embedded as a query.
+pub struct ToolRetrieval {
+    model: Box<dyn EmbeddingModelDyn>,
+    top_k: usize,
+    /// Embeddings of the tool descriptions, by tool name
+    embeddings: Mutex<HashMap<String, Embedding>>,
+}
+
+impl ToolRetrieval {
+    /// Select the `top_k` tools most relevant to each prompt with the embeddings of `model`.
+    pub fn new(model: impl EmbeddingModel + 'static, top_k: usize) -> Self {
+        Self {
+            model: Box::new(model),
+            top_k,
+            embeddings: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Keep the `top_k` of `tools` whose description is the most similar to `query`, in their
+    /// original order. No embedding is computed if there are at most `top_k` tools.
+    pub async fn select(
+        &self,
+        query: &str,
+        tools: Vec<ToolDefinition>,
+    ) -> Result<Vec<ToolDefinition>, EmbeddingError> {
+        if tools.len() <= self.top_k {
+            return Ok(tools);
+        }
+
+        let missing = {
+            let embeddings = self.lock();
+            tools
+                .iter()
+                .filter(|tool| !embeddings.contains_key(&tool.name))
+                .collect::<Vec<_>>()
+        };
+        if !missing.is_empty() {
+            let texts = missing
+                .iter()
+                .map(|tool| format!("{}: {}", tool.name, tool.description))
+                .collect();
+            let embeddings = self.model.embed_texts(texts).await?;
+            self.lock()
+                .extend(missing.iter().map(|tool| tool.name.clone()).zip(embeddings));
+        }
+
+        let query = self.model.embed_query(query).await?;
+        let mut scores = {
+            let embeddings = self.lock();
+            tools
+                .iter()
+                .enumerate()
+                .map(|(i, tool)| {
+                    let score = embeddings.get(&tool.name).map_or(f64::MIN, |embedding| {
+                        embedding.cosine_similarity(&query, false)
+                    });
+                    (i, score)
+                })
+                .collect::<Vec<_>>()
+        };
+        scores.sort_by(|(_, score_1), (_, score_2)| score_2.total_cmp(score_1));
+        let mut selected = scores
+            .into_iter()
+            .take(self.top_k)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        selected.sort();
+
+        let mut tools = tools.into_iter().map(Some).collect::<Vec<_>>();
+        Ok(selected
+            .into_iter()
+            .filter_map(|i| tools[i].take())
+            .collect())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Embedding>> {
+        self.embeddings
+            .lock()
+            .expect("The tool embeddings should not be poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ToolRetrieval;
+    use crate::{
+        agent::AgentBuilder,
+        completion::{Prompt, ToolDefinition},
+        test_utils::{MockCompletionModel, MockEmbeddingModel},
+        tool::Tool,
+    };
+
+    /// A model embedding texts by the presence of the words `weather`, `stock` and `news`.
+    fn keyword_model() -> MockEmbeddingModel {
+        MockEmbeddingModel::from_fn(3, |text| {
+            ["weather", "stock", "news"]
+                .iter()
+                .map(|word| f64::from(u8::from(text.contains(word))) + 0.01)
+                .collect()
+        })
+    }
+
+    /// The number of texts embedded by `model`.
+    fn embedded_texts(model: &MockEmbeddingModel) -> usize {
+        model.requests().iter().map(Vec::len).sum()
+    }
+
+    struct Named(&'static str, &'static str);
+
+    impl Tool for Named {
+        const NAME: &'static str = "named";
+        type Error = std::convert::Infallible;
+        type Args = serde_json::Value;
+        type Output = String;
+
+        fn name(&self) -> String {
+            self.0.to_string()
+        }
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: self.0.to_string(),
+                description: self.1.to_string(),
+                parameters: serde_json::json!({"type": "object"}),
+            }
+        }
+
+        async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
+            Ok(self.0.to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_retrieval() {
+        let embedding_model = keyword_model();
+        let model = MockCompletionModel::new()
+            .with_text("Sunny")
+            .with_tool_call("call_1", "quote", serde_json::json!({}))
+            .with_text("Up 2%");
+        let agent = AgentBuilder::new(model.clone())
+            .tool(Named("forecast", "Get the weather forecast"))
+            .tool(Named("quote", "Get the price of a stock"))
+            .tool(Named("headlines", "Get the latest news"))
+            .tool_retrieval(embedding_model.clone(), 1)
+            .build();
+
+        agent.prompt("What's the weather like?").await.unwrap();
+        // The tool descriptions are embedded once, with the prompt
+        assert_eq!(embedded_texts(&embedding_model), 4);
+
+        // The model can still call a tool that was not advertised
+        agent.prompt("Any news?").multi_turn(1).await.unwrap();
+        assert_eq!(embedded_texts(&embedding_model), 6);
+
+        let requests = model.requests();
+        let tools = |i: usize| {
+            requests[i]
+                .tools
+                .iter()
+                .map(|tool| tool.name.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(tools(0), vec!["forecast"]);
+        assert_eq!(tools(1), vec!["headlines"]);
+        assert_eq!(requests.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_select_few_tools() {
+        let embedding_model = keyword_model();
+        let retrieval = ToolRetrieval::new(embedding_model.clone(), 5);
+        let tools = vec![ToolDefinition {
+            name: "forecast".to_string(),
+            description: "Get the weather forecast".to_string(),
+            parameters: serde_json::json!({"type": "object"}),
+        }];
+
+        // There are no more tools than `top_k`, so nothing is embedded
+        let selected = retrieval.select("news", tools).await.unwrap();
+        assert_eq!(selected.len(), 1);
+        assert_eq!(embedded_texts(&embedding_model), 0);
+    }
+}
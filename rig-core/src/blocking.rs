@@ -0,0 +1,61 @@
+//! This module provides [block_on], used by the blocking counterparts of Rig's async methods
+//! (e.g.: [Agent::prompt_blocking](crate::agent::Agent::prompt_blocking),
+//! [EmbeddingsBuilder::build_blocking](crate::embeddings::EmbeddingsBuilder::build_blocking)) so
+//! that Rig can be used from synchronous code (e.g.: CLI tools, scripts) without managing an
+//! async runtime. Requires the `blocking` feature.
+//!
+//! # Runtime nesting
+//! Each call starts a new single-threaded tokio runtime and blocks the current thread until the
+//! future completes. Blocking calls therefore **panic** when made from within an async runtime
+//! (e.g.: in a `#[tokio::main]` function): async callers should use the async methods instead
+//! (or call the blocking methods from `tokio::task::spawn_blocking`).
+//!
+//! # Example
+//! ```no_run
+//! use rig::{client::CompletionClient, providers::openai};
+//!
+//! fn main() {
+//!     let openai = openai::Client::new("your-open-ai-api-key");
+//!     let agent = openai.agent(openai::GPT_4O).build();
+//!
+//!     let answer = agent
+//!         .prompt_blocking("What is the capital of France?")
+//!         .expect("Failed to prompt the agent");
+//! }
+//! ```
+use std::future::Future;
+
+/// Run `future` to completion on a new single-threaded tokio runtime, blocking the current thread.
+///
+/// # Panics
+/// - If called from within an async runtime (see the [module documentation](self)).
+/// - If the runtime cannot be started.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    if tokio::runtime::Handle::try_current().is_ok() {
+        panic!(
+            "Rig's blocking methods cannot be called from within an async runtime, use their async counterparts instead"
+        );
+    }
+
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("tokio runtime should build")
+        .block_on(future)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::block_on;
+
+    #[test]
+    fn test_block_on() {
+        assert_eq!(block_on(async { 1 + 1 }), 2);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "cannot be called from within an async runtime")]
+    async fn test_block_on_in_runtime() {
+        block_on(async {});
+    }
+}
@@ -2,7 +2,7 @@ use crate::agent::AgentBuilder;
 use crate::client::{AsCompletion, ProviderClient};
 use crate::completion::{
     CompletionError, CompletionModel, CompletionModelDyn, CompletionRequest, CompletionResponse,
-    GetTokenUsage,
+    GetTokenUsage, Pricing,
 };
 use crate::extractor::ExtractorBuilder;
 use crate::streaming::StreamingCompletionResponse;
@@ -85,6 +85,18 @@ impl CompletionModel for CompletionModelHandle<'_> {
     > + Send {
         self.inner.stream(request)
     }
+
+    fn price_per_1k_tokens(&self) -> Option<Pricing> {
+        self.inner.price_per_1k_tokens()
+    }
+
+    fn model_name(&self) -> Option<&str> {
+        self.inner.model_name()
+    }
+
+    fn context_window(&self) -> Option<usize> {
+        self.inner.context_window()
+    }
 }
 
 pub trait CompletionClientDyn: ProviderClient {
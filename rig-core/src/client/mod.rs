@@ -12,7 +12,7 @@ pub mod verify;
 
 #[cfg(feature = "derive")]
 pub use rig_derive::ProviderClient;
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc, time::Duration};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -28,6 +28,52 @@ pub enum ClientBuilderError {
     InvalidProperty(&'static str),
 }
 
+/// Interval of the TCP keep-alive probes of the HTTP clients created by the provider clients,
+/// which keep long-lived connections (e.g.: of streamed responses) open through NATs and proxies.
+pub const DEFAULT_TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+
+/// The HTTP client of the provider clients built without a custom client (see e.g.:
+/// [openai::ClientBuilder::custom_client](crate::providers::openai::ClientBuilder::custom_client)):
+/// connections are pooled and reused across requests (including streaming requests), and kept
+/// alive with TCP keep-alive probes every [DEFAULT_TCP_KEEPALIVE].
+pub(crate) fn default_http_client() -> reqwest::Result<reqwest::Client> {
+    let builder = reqwest::Client::builder();
+    #[cfg(not(target_arch = "wasm32"))]
+    let builder = builder.tcp_keepalive(DEFAULT_TCP_KEEPALIVE);
+    builder.build()
+}
+
+/// Header carrying the id of each request sent by a client with a [RequestIdGenerator].
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// A function generating the id of each request sent by a client (e.g.: a UUID, or an id
+/// derived from the current trace), sent as the [REQUEST_ID_HEADER] header of the request.
+pub type RequestIdGenerator = Arc<dyn Fn() -> String + Send + Sync>;
+
+/// Identify a request sent by a client with its `User-Agent` and a new request id, if configured.
+///
+/// The request id is also logged at the `DEBUG` level of the `rig` target, within the current
+/// span, so that the requests of an agent can be matched with the provider's logs.
+pub(crate) fn identify_request(
+    request: reqwest::RequestBuilder,
+    user_agent: Option<&str>,
+    request_id: Option<&RequestIdGenerator>,
+) -> reqwest::RequestBuilder {
+    let request = match user_agent {
+        Some(user_agent) => request.header(reqwest::header::USER_AGENT, user_agent),
+        None => request,
+    };
+
+    match request_id {
+        Some(request_id) => {
+            let request_id = request_id();
+            tracing::debug!(target: "rig", request_id = %request_id, "Sending request");
+            request.header(REQUEST_ID_HEADER, request_id)
+        }
+        None => request,
+    }
+}
+
 /// The base ProviderClient trait, facilitates conversion between client types
 /// and creating a client from the environment.
 ///
@@ -603,6 +649,8 @@ mod tests {
             documents: vec![],
             temperature: None,
             max_tokens: None,
+            parallel_tool_calls: None,
+            tool_choice: None,
             additional_params: None,
             chat_history: OneOrMany::one(Message::user("What is the capital of France?")),
         });
@@ -631,6 +679,9 @@ mod tests {
                         config.name
                     );
                 }
+                AssistantContent::Refusal(refusal) => {
+                    panic!("[{}]: Refused to answer: {}", config.name, refusal.refusal)
+                }
                 AssistantContent::ToolCall(_) => {}
                 AssistantContent::Reasoning(_) => {}
             }
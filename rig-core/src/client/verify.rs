@@ -1,19 +1,38 @@
+use std::time::Duration;
+
 use crate::client::{AsVerify, ProviderClient};
 use futures::future::BoxFuture;
+use reqwest::StatusCode;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum VerifyError {
     #[error("invalid authentication")]
     InvalidAuthentication,
     #[error("provider error: {0}")]
     ProviderError(String),
+    /// The provider could not be reached (e.g.: DNS, connection or TLS failure)
+    #[error("provider unreachable: {0}")]
+    Unreachable(#[source] reqwest::Error),
+    /// The provider did not answer within the timeout of [VerifyClient::health_check]
+    #[error("provider did not respond within {0:?}")]
+    Timeout(Duration),
+    /// The provider has no way to check the client without a model-specific request
+    #[error("health check unsupported: {0}")]
+    Unsupported(String),
     #[error("http error: {0}")]
-    HttpError(
-        #[from]
-        #[source]
-        reqwest::Error,
-    ),
+    HttpError(#[source] reqwest::Error),
+}
+
+impl From<reqwest::Error> for VerifyError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_connect() || err.is_timeout() {
+            VerifyError::Unreachable(err)
+        } else {
+            VerifyError::HttpError(err)
+        }
+    }
 }
 
 /// A provider client that can verify the configuration.
@@ -21,17 +40,76 @@ pub enum VerifyError {
 pub trait VerifyClient: ProviderClient + Clone {
     /// Verify the configuration.
     fn verify(&self) -> impl Future<Output = Result<(), VerifyError>> + Send;
+
+    /// Check that the provider is reachable and accepts the credentials of the client, with a
+    /// cheap request (see [VerifyClient::verify]), e.g.: in the readiness probe of a service,
+    /// before serving traffic.
+    ///
+    /// Providers without an endpoint to verify the credentials send a minimal completion or
+    /// embeddings request instead, and fail with [VerifyError::Unsupported] if there is no such
+    /// request (e.g.: Azure OpenAI, where every request targets a deployment).
+    ///
+    /// Fails with [VerifyError::Timeout] if the provider does not respond within `timeout`.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use std::time::Duration;
+    /// use rig::{client::{VerifyClient, VerifyError}, providers::openai};
+    ///
+    /// let openai = openai::Client::from_env();
+    /// match openai.health_check(Duration::from_secs(5)).await {
+    ///     Ok(()) => println!("ready"),
+    ///     Err(VerifyError::InvalidAuthentication) => eprintln!("invalid API key"),
+    ///     Err(e) => eprintln!("not ready: {e}"),
+    /// }
+    /// ```
+    fn health_check(
+        &self,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<(), VerifyError>> + Send {
+        with_timeout(self.verify(), timeout)
+    }
 }
 
 pub trait VerifyClientDyn: ProviderClient {
     /// Verify the configuration.
     fn verify(&self) -> BoxFuture<'_, Result<(), VerifyError>>;
+
+    /// Check that the provider is reachable and accepts the credentials of the client (see
+    /// [VerifyClient::health_check]).
+    fn health_check(&self, timeout: Duration) -> BoxFuture<'_, Result<(), VerifyError>> {
+        Box::pin(with_timeout(self.verify(), timeout))
+    }
+}
+
+pub(crate) async fn with_timeout(
+    verify: impl Future<Output = Result<(), VerifyError>>,
+    timeout: Duration,
+) -> Result<(), VerifyError> {
+    tokio::time::timeout(timeout, verify)
+        .await
+        .map_err(|_| VerifyError::Timeout(timeout))?
+}
+
+/// Send `request` and map its response status, for the health check of providers without a
+/// verify endpoint.
+pub(crate) async fn probe(request: reqwest::RequestBuilder) -> Result<(), VerifyError> {
+    let response = request.send().await?;
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(VerifyError::InvalidAuthentication),
+        _ => Err(VerifyError::ProviderError(response.text().await?)),
+    }
 }
 
 impl<T: VerifyClient> VerifyClientDyn for T {
     fn verify(&self) -> BoxFuture<'_, Result<(), VerifyError>> {
         Box::pin(self.verify())
     }
+
+    fn health_check(&self, timeout: Duration) -> BoxFuture<'_, Result<(), VerifyError>> {
+        Box::pin(VerifyClient::health_check(self, timeout))
+    }
 }
 
 impl<T: VerifyClientDyn + Clone + 'static> AsVerify for T {
@@ -39,3 +117,82 @@ impl<T: VerifyClientDyn + Clone + 'static> AsVerify for T {
         Some(Box::new(self.clone()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::{VerifyClient, VerifyError};
+    use crate::providers::{azure, openai, perplexity};
+
+    #[tokio::test]
+    async fn test_health_check_unreachable() {
+        // Nothing listens on port 1
+        let client = openai::Client::builder("invalid-key")
+            .base_url("http://127.0.0.1:1")
+            .build()
+            .unwrap();
+
+        let result = client.health_check(Duration::from_secs(5)).await;
+        assert!(
+            matches!(result, Err(VerifyError::Unreachable(_))),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_health_check_timeout() {
+        // The connection is accepted by the listener, but the request is never answered
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let client = openai::Client::builder("invalid-key")
+            .base_url(&base_url)
+            .build()
+            .unwrap();
+
+        let result = client.health_check(Duration::from_millis(200)).await;
+        assert!(matches!(result, Err(VerifyError::Timeout(_))), "{result:?}");
+    }
+
+    #[tokio::test]
+    async fn test_health_check_probe_invalid_authentication() {
+        // Answer the probe of a provider without a verify endpoint with 401
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 4096];
+            let _ = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 401 Unauthorized\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        });
+        let client = perplexity::Client::builder("invalid-key")
+            .base_url(&base_url)
+            .build()
+            .unwrap();
+
+        let result = client.health_check(Duration::from_secs(5)).await;
+        assert!(
+            matches!(result, Err(VerifyError::InvalidAuthentication)),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_health_check_unsupported() {
+        let client = azure::Client::new(
+            azure::AzureOpenAIAuth::ApiKey("invalid-key".into()),
+            "http://127.0.0.1:1",
+        );
+
+        let result = client.health_check(Duration::from_secs(5)).await;
+        assert!(
+            matches!(result, Err(VerifyError::Unsupported(_))),
+            "{result:?}"
+        );
+    }
+}
@@ -0,0 +1,291 @@
+//! Helpers to manipulate chat histories, e.g.: to fork a conversation at a given turn and
+//! explore several continuations of it (tree-of-thought, "regenerate from turn N", etc.).
+//!
+//! # Example
+//! ```rust
+//! use rig::completion::{Message, history};
+//!
+//! let chat_history = vec![
+//!     Message::user("What is the capital of France?"),
+//!     Message::assistant("Paris."),
+//!     Message::user("And of Italy?"),
+//!     Message::assistant("Milan."),
+//! ];
+//!
+//! // Regenerate the answer to the second prompt
+//! let turns = history::turns(&chat_history);
+//! let fork = history::fork_at(&chat_history, turns[1] + 1);
+//! assert_eq!(fork.len(), 3);
+//! ```
+use std::{
+    collections::{BTreeMap, HashSet},
+    ops::Range,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::message::{AssistantContent, Message, UserContent};
+
+/// Returns an independent copy of the first `index` messages of `history` (or all of it, if
+/// `index` is greater than its length).
+///
+/// The fork never ends in the middle of a tool call: if an assistant message of the fork
+/// contains tool calls whose results are not part of the fork, the fork is cut right before
+/// that message, so providers never receive tool calls without their results.
+pub fn fork_at(history: &[Message], index: usize) -> Vec<Message> {
+    let mut fork = history[..index.min(history.len())].to_vec();
+
+    while let Some(position) = first_unanswered_tool_call(&fork) {
+        fork.truncate(position);
+    }
+
+    fork
+}
+
+/// Returns the index of every prompt of `history`, i.e.: of the user messages that are not
+/// only made of tool results. `turns(history)[n]` is the index of the message starting the
+/// n-th turn of the conversation.
+pub fn turns(history: &[Message]) -> Vec<usize> {
+    history
+        .iter()
+        .enumerate()
+        .filter_map(|(index, message)| match message {
+            Message::User { content }
+                if content
+                    .iter()
+                    .any(|content| !matches!(content, UserContent::ToolResult(_))) =>
+            {
+                Some(index)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Arbitrary metadata (e.g.: tags, sources, trace ids) attached to the messages of a chat history,
+/// by index.
+///
+/// The metadata is kept alongside the history instead of in the messages, so it is never sent
+/// to providers. Prompt requests only append messages to the chat history, so the metadata of
+/// the existing messages stays attached to the right messages across turns.
+///
+/// # Example
+/// ```rust
+/// use rig::completion::{Message, history::MessageMetadata};
+///
+/// let mut chat_history = vec![Message::user("Hello!")];
+/// let mut metadata = MessageMetadata::new();
+/// metadata.insert(0, serde_json::json!({"source": "web"}));
+///
+/// // ... prompt the agent with the chat history, then tag the messages it added
+/// let start = chat_history.len();
+/// chat_history.push(Message::assistant("Hi!"));
+/// metadata.insert_range(start..chat_history.len(), serde_json::json!({"trace_id": "abc"}));
+///
+/// assert_eq!(metadata.get(1), Some(&serde_json::json!({"trace_id": "abc"})));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MessageMetadata(BTreeMap<usize, serde_json::Value>);
+
+impl MessageMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attach `metadata` to the message at `index`, returning its previous metadata.
+    pub fn insert(
+        &mut self,
+        index: usize,
+        metadata: serde_json::Value,
+    ) -> Option<serde_json::Value> {
+        self.0.insert(index, metadata)
+    }
+
+    /// Attach `metadata` to every message in `range` (e.g.: the messages added by a prompt).
+    pub fn insert_range(&mut self, range: Range<usize>, metadata: serde_json::Value) {
+        for index in range {
+            self.0.insert(index, metadata.clone());
+        }
+    }
+
+    /// The metadata of the message at `index`, if any.
+    pub fn get(&self, index: usize) -> Option<&serde_json::Value> {
+        self.0.get(&index)
+    }
+
+    /// Remove the metadata of the message at `index`, returning it.
+    pub fn remove(&mut self, index: usize) -> Option<serde_json::Value> {
+        self.0.remove(&index)
+    }
+
+    /// The metadata of the first `index` messages, to go with a [fork_at] of the history.
+    pub fn fork_at(&self, index: usize) -> Self {
+        Self(
+            self.0
+                .range(..index)
+                .map(|(i, v)| (*i, v.clone()))
+                .collect(),
+        )
+    }
+
+    /// Iterate over the metadata, by increasing message index.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &serde_json::Value)> {
+        self.0.iter().map(|(index, metadata)| (*index, metadata))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Returns the index of the first assistant message with a tool call that has no result in
+/// `history`, if any.
+fn first_unanswered_tool_call(history: &[Message]) -> Option<usize> {
+    let results = history
+        .iter()
+        .flat_map(|message| match message {
+            Message::User { content } => content
+                .iter()
+                .filter_map(|content| match content {
+                    UserContent::ToolResult(result) => Some(result.id.as_str()),
+                    _ => None,
+                })
+                .collect(),
+            Message::Assistant { .. } => vec![],
+        })
+        .collect::<HashSet<_>>();
+
+    history.iter().position(|message| match message {
+        Message::Assistant { content, .. } => content.iter().any(|content| {
+            matches!(content, AssistantContent::ToolCall(tool_call) if !results.contains(tool_call.id.as_str()))
+        }),
+        Message::User { .. } => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OneOrMany;
+
+    fn tool_calls(ids: &[&str]) -> Message {
+        Message::Assistant {
+            id: None,
+            content: OneOrMany::many(
+                ids.iter()
+                    .map(|id| AssistantContent::tool_call(*id, "search", serde_json::json!({}))),
+            )
+            .unwrap(),
+        }
+    }
+
+    fn history() -> Vec<Message> {
+        vec![
+            Message::user("Search for rig"),
+            tool_calls(&["call_1", "call_2"]),
+            Message::tool_result("call_1", "rig is a Rust library"),
+            Message::tool_result("call_2", "rig is also a ship"),
+            Message::assistant("rig is a Rust library"),
+            Message::user("Thanks!"),
+            Message::assistant("You're welcome!"),
+        ]
+    }
+
+    #[test]
+    fn test_fork_at() {
+        let history = history();
+
+        assert_eq!(fork_at(&history, 0), vec![]);
+        assert_eq!(fork_at(&history, 1), history[..1]);
+        assert_eq!(fork_at(&history, 5), history[..5]);
+        assert_eq!(fork_at(&history, 7), history);
+        assert_eq!(fork_at(&history, 100), history);
+    }
+
+    #[test]
+    fn test_fork_at_unanswered_tool_calls() {
+        let history = history();
+
+        // Forking between a tool call and (all of) its results drops the tool call
+        assert_eq!(fork_at(&history, 2), history[..1]);
+        assert_eq!(fork_at(&history, 3), history[..1]);
+        // Once every result is in the fork, the tool call is kept
+        assert_eq!(fork_at(&history, 4), history[..4]);
+    }
+
+    #[test]
+    fn test_fork_is_independent() {
+        let history = history();
+        let mut fork = fork_at(&history, 5);
+        fork.push(Message::user("Tell me more"));
+
+        assert_eq!(history.len(), 7);
+        assert_eq!(fork.len(), 6);
+    }
+
+    #[test]
+    fn test_message_metadata() {
+        let history = history();
+        let mut metadata = MessageMetadata::new();
+        metadata.insert(0, serde_json::json!({"source": "web"}));
+        metadata.insert_range(1..5, serde_json::json!("tool_use"));
+
+        assert_eq!(metadata.len(), 5);
+        assert_eq!(metadata.get(3), Some(&serde_json::json!("tool_use")));
+        assert_eq!(metadata.get(5), None);
+        assert_eq!(metadata.fork_at(2).len(), 2);
+
+        // The metadata survives a serialization round-trip alongside the history
+        let serialized = serde_json::to_string(&(&history, &metadata)).unwrap();
+        let (history_2, metadata_2): (Vec<Message>, MessageMetadata) =
+            serde_json::from_str(&serialized).unwrap();
+        assert_eq!(history_2, history);
+        assert_eq!(metadata_2, metadata);
+    }
+
+    #[tokio::test]
+    async fn test_message_metadata_across_prompts() {
+        use crate::{agent::AgentBuilder, completion::Prompt, test_utils::MockCompletionModel};
+
+        let model = MockCompletionModel::new().with_text("rig is a Rust library");
+        let agent = AgentBuilder::new(model.clone()).build();
+        let mut chat_history = vec![Message::user("Hello!"), Message::assistant("Hi!")];
+        let mut metadata = MessageMetadata::new();
+        metadata.insert(1, serde_json::json!({"trace_id": "abc"}));
+
+        let start = chat_history.len();
+        agent
+            .prompt("What is rig?")
+            .with_history(&mut chat_history)
+            .await
+            .unwrap();
+        metadata.insert_range(
+            start..chat_history.len(),
+            serde_json::json!({"trace_id": "def"}),
+        );
+
+        assert_eq!(chat_history[1], Message::assistant("Hi!"));
+        assert_eq!(
+            metadata.get(1),
+            Some(&serde_json::json!({"trace_id": "abc"}))
+        );
+        assert_eq!(metadata.len(), chat_history.len() - 1);
+        // The metadata is not sent to the provider
+        assert!(
+            !serde_json::to_string(&model.requests()[0].chat_history)
+                .unwrap()
+                .contains("trace_id")
+        );
+    }
+
+    #[test]
+    fn test_turns() {
+        assert_eq!(turns(&history()), vec![0, 5]);
+        assert_eq!(turns(&[]), Vec::<usize>::new());
+    }
+}
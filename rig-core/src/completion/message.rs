@@ -1,6 +1,7 @@
 use std::{convert::Infallible, str::FromStr};
 
 use crate::OneOrMany;
+use base64::{Engine, prelude::BASE64_STANDARD};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -54,6 +55,14 @@ pub enum AssistantContent {
     Text(Text),
     ToolCall(ToolCall),
     Reasoning(Reasoning),
+    /// The model refused to answer (e.g.: OpenAI's `refusal`)
+    Refusal(Refusal),
+}
+
+/// A refusal of the model to answer, with its explanation.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Refusal {
+    pub refusal: String,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
@@ -305,6 +314,74 @@ impl Message {
         }
     }
 
+    /// Helper constructor to make creating user messages with text and other content (e.g.:
+    /// the images the text refers to) easier.
+    ///
+    /// # Example
+    /// ```rust
+    /// use rig::message::{ImageMediaType, Message, UserContent};
+    ///
+    /// let message = Message::user_with_attachments(
+    ///     "What is in this image?",
+    ///     [UserContent::image("https://example.com/cat.png", None, Some(ImageMediaType::PNG), None)],
+    /// );
+    /// ```
+    pub fn user_with_attachments(
+        text: impl Into<String>,
+        attachments: impl IntoIterator<Item = UserContent>,
+    ) -> Self {
+        let mut content = OneOrMany::one(UserContent::text(text));
+        for attachment in attachments {
+            content.push(attachment);
+        }
+        Message::User { content }
+    }
+
+    /// Helper constructor to make creating user image messages easier.
+    pub fn image(
+        data: impl Into<String>,
+        format: Option<ContentFormat>,
+        media_type: Option<ImageMediaType>,
+        detail: Option<ImageDetail>,
+    ) -> Self {
+        Message::User {
+            content: OneOrMany::one(UserContent::image(data, format, media_type, detail)),
+        }
+    }
+
+    /// Helper constructor to make creating user audio messages easier.
+    pub fn audio(
+        data: impl Into<String>,
+        format: Option<ContentFormat>,
+        media_type: Option<AudioMediaType>,
+    ) -> Self {
+        Message::User {
+            content: OneOrMany::one(UserContent::audio(data, format, media_type)),
+        }
+    }
+
+    /// Helper constructor to make creating user video messages easier.
+    pub fn video(
+        data: impl Into<String>,
+        format: Option<ContentFormat>,
+        media_type: Option<VideoMediaType>,
+    ) -> Self {
+        Message::User {
+            content: OneOrMany::one(UserContent::video(data, format, media_type)),
+        }
+    }
+
+    /// Helper constructor to make creating user document messages easier.
+    pub fn document(
+        data: impl Into<String>,
+        format: Option<ContentFormat>,
+        media_type: Option<DocumentMediaType>,
+    ) -> Self {
+        Message::User {
+            content: OneOrMany::one(UserContent::document(data, format, media_type)),
+        }
+    }
+
     /// Helper constructor to make creating assistant messages easier.
     pub fn assistant(text: impl Into<String>) -> Self {
         Message::Assistant {
@@ -345,6 +422,30 @@ impl Message {
             })),
         }
     }
+
+    /// Convert a conversation in the JSON format of the OpenAI Chat Completions API into rig
+    /// messages (see [crate::providers::openai::messages_from_json]).
+    pub fn from_openai_json(messages: serde_json::Value) -> Result<Vec<Message>, MessageError> {
+        crate::providers::openai::messages_from_json(messages)
+    }
+
+    /// Convert rig messages into the JSON format of the OpenAI Chat Completions API (see
+    /// [crate::providers::openai::messages_to_json]).
+    pub fn to_openai_json(messages: &[Message]) -> Result<serde_json::Value, MessageError> {
+        crate::providers::openai::messages_to_json(messages)
+    }
+
+    /// Convert a conversation in the JSON format of the Anthropic Messages API into rig
+    /// messages (see [crate::providers::anthropic::completion::messages_from_json]).
+    pub fn from_anthropic_json(messages: serde_json::Value) -> Result<Vec<Message>, MessageError> {
+        crate::providers::anthropic::completion::messages_from_json(messages)
+    }
+
+    /// Convert rig messages into the JSON format of the Anthropic Messages API (see
+    /// [crate::providers::anthropic::completion::messages_to_json]).
+    pub fn to_anthropic_json(messages: &[Message]) -> Result<serde_json::Value, MessageError> {
+        crate::providers::anthropic::completion::messages_to_json(messages)
+    }
 }
 
 impl UserContent {
@@ -383,6 +484,20 @@ impl UserContent {
         })
     }
 
+    /// Helper constructor to make creating user video content easier.
+    pub fn video(
+        data: impl Into<String>,
+        format: Option<ContentFormat>,
+        media_type: Option<VideoMediaType>,
+    ) -> Self {
+        UserContent::Video(Video {
+            data: data.into(),
+            format,
+            media_type,
+            additional_params: None,
+        })
+    }
+
     /// Helper constructor to make creating user document content easier.
     pub fn document(
         data: impl Into<String>,
@@ -426,6 +541,13 @@ impl AssistantContent {
         AssistantContent::Text(text.into().into())
     }
 
+    /// Helper constructor to make creating assistant refusal content easier.
+    pub fn refusal(refusal: impl Into<String>) -> Self {
+        AssistantContent::Refusal(Refusal {
+            refusal: refusal.into(),
+        })
+    }
+
     /// Helper constructor to make creating assistant tool call content easier.
     pub fn tool_call(
         id: impl Into<String>,
@@ -480,6 +602,25 @@ impl ToolResultContent {
             additional_params: None,
         })
     }
+
+    /// Helper constructor to make creating tool result content from binary data (e.g.: the
+    /// output of a tool reading a file) easier.
+    ///
+    /// Images (of a supported [ImageMediaType]) are returned as base64 encoded image content.
+    /// Other data is returned as text: its MIME type and size, followed by its base64 encoding.
+    pub fn binary(data: &[u8], mime_type: &str) -> Self {
+        let encoded = BASE64_STANDARD.encode(data);
+
+        match ImageMediaType::from_mime_type(mime_type) {
+            Some(media_type) => {
+                Self::image(encoded, Some(ContentFormat::Base64), Some(media_type), None)
+            }
+            None => Self::text(format!(
+                "[Binary data: {mime_type}, {} bytes, base64 encoded]\n{encoded}",
+                data.len()
+            )),
+        }
+    }
 }
 
 /// Trait for converting between MIME types and media types.
@@ -709,6 +850,14 @@ impl From<Audio> for Message {
     }
 }
 
+impl From<Video> for Message {
+    fn from(video: Video) -> Self {
+        Message::User {
+            content: OneOrMany::one(UserContent::Video(video)),
+        }
+    }
+}
+
 impl From<Document> for Message {
     fn from(document: Document) -> Self {
         Message::User {
@@ -809,3 +958,74 @@ impl From<MessageError> for CompletionError {
         CompletionError::RequestError(error.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_with_attachments() {
+        let image = UserContent::image("aGVsbG8=", Some(ContentFormat::Base64), None, None);
+        let message = Message::user_with_attachments("Describe this image", [image.clone()]);
+
+        assert_eq!(
+            message,
+            Message::User {
+                content: OneOrMany::many(vec![UserContent::text("Describe this image"), image])
+                    .unwrap(),
+            }
+        );
+        assert_eq!(
+            Message::user_with_attachments("Hello", []),
+            Message::user("Hello")
+        );
+    }
+
+    #[test]
+    fn test_binary_tool_result() {
+        assert_eq!(
+            ToolResultContent::binary(b"\x89PNG", "image/png"),
+            ToolResultContent::image(
+                "iVBORw==",
+                Some(ContentFormat::Base64),
+                Some(ImageMediaType::PNG),
+                None
+            )
+        );
+        assert_eq!(
+            ToolResultContent::binary(&[0xff, 0xfe, 0x00], "application/octet-stream"),
+            ToolResultContent::text(
+                "[Binary data: application/octet-stream, 3 bytes, base64 encoded]\n//4A"
+            )
+        );
+    }
+
+    #[test]
+    fn test_media_messages() {
+        let message = Message::video(
+            "https://example.com/video.mp4",
+            None,
+            Some(VideoMediaType::MP4),
+        );
+
+        assert_eq!(
+            message,
+            Message::from(Video {
+                data: "https://example.com/video.mp4".to_string(),
+                format: None,
+                media_type: Some(VideoMediaType::MP4),
+                additional_params: None,
+            })
+        );
+        assert_eq!(
+            Message::document("Hello", Some(ContentFormat::String), None),
+            Message::User {
+                content: OneOrMany::one(UserContent::document(
+                    "Hello",
+                    Some(ContentFormat::String),
+                    None
+                )),
+            }
+        );
+    }
+}
@@ -1,5 +1,10 @@
+pub mod history;
 pub mod message;
 pub mod request;
+pub mod retry;
+pub mod tool_call_id;
 
 pub use message::{AssistantContent, Message, MessageError};
 pub use request::*;
+pub use retry::RetryPolicy;
+pub use tool_call_id::{IdCharset, ToolCallIdFormat};
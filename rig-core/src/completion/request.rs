@@ -70,7 +70,7 @@ use crate::{OneOrMany, streaming};
 use crate::{
     json_utils,
     message::{Message, UserContent},
-    tool::ToolSetError,
+    tool::{ToolLimits, ToolSetError},
 };
 use futures::future::BoxFuture;
 use serde::de::DeserializeOwned;
@@ -82,6 +82,7 @@ use thiserror::Error;
 
 // Errors
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum CompletionError {
     /// Http error (e.g.: connection error, timeout, etc.)
     #[error("HttpError: {0}")]
@@ -106,6 +107,68 @@ pub enum CompletionError {
     /// Error returned by the completion model provider
     #[error("ProviderError: {0}")]
     ProviderError(String),
+
+    /// Error response of the completion model provider, with its HTTP status
+    #[error("ProviderError: {status}: {message}")]
+    ProviderStatusError {
+        status: reqwest::StatusCode,
+        message: String,
+    },
+
+    /// The connection was closed while streaming the response (e.g.: a network failure or the
+    /// idle timeout of a proxy). The response is incomplete, and the request can be sent again.
+    #[error("ConnectionReset: {0}")]
+    ConnectionReset(String),
+}
+
+impl CompletionError {
+    /// The error of a provider `response` with an unsuccessful status, with the body of the
+    /// response as message.
+    pub(crate) async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status();
+        match response.text().await {
+            Ok(message) => CompletionError::ProviderStatusError { status, message },
+            Err(err) => err.into(),
+        }
+    }
+
+    /// Whether the error is likely transient, i.e.: the same request may succeed if retried
+    /// (see [RetryPolicy](super::RetryPolicy)): network errors (including connections reset
+    /// while streaming), rate limiting (HTTP 429) and server errors (HTTP 5xx). Other errors (e.g.: invalid requests, authentication errors)
+    /// are not retryable.
+    ///
+    /// Error responses are classified on their HTTP status. Provider errors without a status
+    /// (e.g.: errors sent within a streamed response) are matched on the type of error in
+    /// their message (e.g.: "rate_limit_error", "overloaded").
+    pub fn is_retryable(&self) -> bool {
+        let is_transient = |status: reqwest::StatusCode| {
+            status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+        };
+
+        match self {
+            CompletionError::HttpError(err) => {
+                err.is_timeout() || err.is_connect() || err.status().is_some_and(is_transient)
+            }
+            CompletionError::ProviderStatusError { status, .. } => is_transient(*status),
+            CompletionError::ConnectionReset(_) => true,
+            CompletionError::ProviderError(message) => {
+                let message = message.to_lowercase();
+                [
+                    "rate limit",
+                    "rate_limit",
+                    "too many requests",
+                    "internal server error",
+                    "bad gateway",
+                    "service unavailable",
+                    "gateway timeout",
+                    "overloaded",
+                ]
+                .iter()
+                .any(|pattern| message.contains(pattern))
+            }
+            _ => false,
+        }
+    }
 }
 
 /// Prompt errors
@@ -128,6 +191,48 @@ pub enum PromptError {
         chat_history: Vec<Message>,
         prompt: Message,
     },
+
+    /// The agent did not call its final answer tool (e.g.: it answered with text instead), or
+    /// the arguments of the call could not be deserialized into the expected answer type.
+    #[error("FinalAnswerError: {0}")]
+    FinalAnswerError(String),
+
+    /// The prompt (or the response, see [crate::agent::AgentBuilder::moderate_output]) was
+    /// flagged by the agent's moderator (see [crate::agent::AgentBuilder::moderator]).
+    #[error("Moderated: flagged for {}", categories.join(", "))]
+    Moderated { categories: Vec<String> },
+
+    #[error("ModerationError: {0}")]
+    ModerationError(#[from] crate::moderation::ModerationError),
+
+    /// The model refused to answer the prompt (see [crate::message::AssistantContent::Refusal]).
+    #[error("Refused: {reason}")]
+    Refused { reason: String },
+
+    /// There is no message to respond to: the prompt request has no prompt and an empty chat history.
+    #[error("EmptyChatHistory: there is no prompt and the chat history is empty")]
+    EmptyChatHistory,
+
+    /// The model returned multiple tool calls with the same id in a single turn
+    /// (see [crate::agent::DuplicateToolCallIds]).
+    #[error("DuplicateToolCallId: the model returned multiple tool calls with id `{0}`")]
+    DuplicateToolCallId(String),
+
+    /// The prompt request did not complete before its deadline (see
+    /// [crate::agent::PromptRequest::deadline]). The chat history contains the turns that
+    /// completed before the deadline.
+    #[error("DeadlineExceeded: the prompt request did not complete before its deadline")]
+    DeadlineExceeded { chat_history: Vec<Message> },
+
+    /// The model called tools whose result must be provided by the caller (see
+    /// [crate::agent::AgentBuilder::pause_on_tool]).
+    #[error("PendingToolCalls: {} tool call(s) awaiting a result", .0.tool_calls().len())]
+    PendingToolCalls(Box<crate::agent::PendingToolCalls>),
+
+    /// The callback of the prompt request receiving each message of the chat history (see
+    /// [crate::agent::PromptRequest::on_message]) failed.
+    #[error("MessageHandlerError: {0}")]
+    MessageHandlerError(Box<dyn std::error::Error + Send + Sync>),
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -167,6 +272,19 @@ pub struct ToolDefinition {
     pub parameters: serde_json::Value,
 }
 
+/// Whether (and how) the model may call the tools of a completion request.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolChoice {
+    /// The model decides whether to call the tools
+    #[default]
+    Auto,
+    /// The model must not call any tool, but may still refer to them (e.g.: in a final answer)
+    None,
+    /// The model must call at least one tool
+    Required,
+}
+
 // ================================================================
 // Implementations
 // ================================================================
@@ -228,7 +346,12 @@ pub trait Completion<M: CompletionModel> {
 #[derive(Debug)]
 pub struct CompletionResponse<T> {
     /// The completion choice (represented by one or more assistant message content)
-    /// returned by the completion model provider
+    /// returned by the completion model provider.
+    ///
+    /// The contents are the parts of a single assistant message (e.g.: its text, reasoning and
+    /// tool calls), which is why agents concatenate its texts and run all of its tool calls.
+    /// When a provider returns several alternative completions (e.g.: OpenAI's `choices`),
+    /// only the first one is used and the others are left in [CompletionResponse::raw_response].
     pub choice: OneOrMany<AssistantContent>,
     /// Tokens used during prompting and responding
     pub usage: Usage,
@@ -298,6 +421,36 @@ impl AddAssign for Usage {
     }
 }
 
+impl Usage {
+    /// Estimates the cost (in dollars) of the tokens used given the pricing of a model.
+    pub fn estimated_cost(&self, pricing: &Pricing) -> f64 {
+        pricing.cost(self)
+    }
+}
+
+/// Struct representing the pricing of a completion model, in dollars per 1k tokens.
+/// Used to estimate the cost of a request from its [Usage].
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct Pricing {
+    /// The price (in dollars) of 1k input ("prompt") tokens.
+    pub prompt: f64,
+    /// The price (in dollars) of 1k output ("completion") tokens.
+    pub completion: f64,
+}
+
+impl Pricing {
+    /// Creates a new instance of `Pricing` from the price of 1k prompt and completion tokens.
+    pub fn new(prompt: f64, completion: f64) -> Self {
+        Self { prompt, completion }
+    }
+
+    /// Returns the estimated cost (in dollars) of the given token usage.
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        (usage.input_tokens as f64 * self.prompt + usage.output_tokens as f64 * self.completion)
+            / 1000.0
+    }
+}
+
 /// Trait defining a completion model that can be used to generate completion responses.
 /// This trait is meant to be implemented by the user to define a custom completion model,
 /// either from a third party provider (e.g.: OpenAI) or a local model.
@@ -332,6 +485,31 @@ pub trait CompletionModel: Clone + Send + Sync {
     fn completion_request(&self, prompt: impl Into<Message>) -> CompletionRequestBuilder<Self> {
         CompletionRequestBuilder::new(self.clone(), prompt)
     }
+
+    /// The price of 1k prompt and completion tokens for this model, if known.
+    /// Used to estimate the cost of requests (see [Usage::estimated_cost]).
+    fn price_per_1k_tokens(&self) -> Option<Pricing> {
+        None
+    }
+
+    /// The name of the model (e.g.: `gpt-4o`), if known.
+    fn model_name(&self) -> Option<&str> {
+        None
+    }
+
+    /// The limits of the provider on the tools of a request, if known (no limits by default).
+    /// Used to check the tools of an agent before sending requests (see
+    /// [Agent::validate_tools_for](crate::agent::Agent::validate_tools_for)).
+    fn tool_limits(&self) -> ToolLimits {
+        ToolLimits::default()
+    }
+
+    /// The size of the context window of the model in tokens (prompt and response), if known.
+    /// Used to check whether a request will fit before sending it (see
+    /// [Agent::will_fit](crate::agent::Agent::will_fit)).
+    fn context_window(&self) -> Option<usize> {
+        None
+    }
 }
 pub trait CompletionModelDyn: Send + Sync {
     fn completion(
@@ -348,6 +526,12 @@ pub trait CompletionModelDyn: Send + Sync {
         &self,
         prompt: Message,
     ) -> CompletionRequestBuilder<CompletionModelHandle<'_>>;
+
+    fn price_per_1k_tokens(&self) -> Option<Pricing>;
+
+    fn model_name(&self) -> Option<&str>;
+
+    fn context_window(&self) -> Option<usize>;
 }
 
 impl<T, R> CompletionModelDyn for T
@@ -398,10 +582,25 @@ where
             prompt,
         )
     }
+
+    fn price_per_1k_tokens(&self) -> Option<Pricing> {
+        CompletionModel::price_per_1k_tokens(self)
+    }
+
+    fn model_name(&self) -> Option<&str> {
+        CompletionModel::model_name(self)
+    }
+
+    fn context_window(&self) -> Option<usize> {
+        CompletionModel::context_window(self)
+    }
 }
 
 /// Struct representing a general completion request that can be sent to a completion model provider.
+///
+/// New fields may be added to the request: use [CompletionRequestBuilder] to create one.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct CompletionRequest {
     /// The preamble to be sent to the completion model provider
     pub preamble: Option<String>,
@@ -416,6 +615,12 @@ pub struct CompletionRequest {
     pub temperature: Option<f64>,
     /// The max tokens to be sent to the completion model provider
     pub max_tokens: Option<u64>,
+    /// Whether the model may call multiple tools in a single turn. Only forwarded to providers
+    /// that support it (e.g.: OpenAI, Anthropic); `None` uses the provider's default.
+    pub parallel_tool_calls: Option<bool>,
+    /// Whether the model may call the tools. Only forwarded to providers that support it (e.g.:
+    /// OpenAI, Anthropic); `None` uses the provider's default (usually [ToolChoice::Auto]).
+    pub tool_choice: Option<ToolChoice>,
     /// Additional provider-specific parameters to be sent to the completion model provider
     pub additional_params: Option<serde_json::Value>,
 }
@@ -504,6 +709,8 @@ pub struct CompletionRequestBuilder<M: CompletionModel> {
     tools: Vec<ToolDefinition>,
     temperature: Option<f64>,
     max_tokens: Option<u64>,
+    parallel_tool_calls: Option<bool>,
+    tool_choice: Option<ToolChoice>,
     additional_params: Option<serde_json::Value>,
 }
 
@@ -518,6 +725,8 @@ impl<M: CompletionModel> CompletionRequestBuilder<M> {
             tools: Vec::new(),
             temperature: None,
             max_tokens: None,
+            parallel_tool_calls: None,
+            tool_choice: None,
             additional_params: None,
         }
     }
@@ -620,6 +829,35 @@ impl<M: CompletionModel> CompletionRequestBuilder<M> {
         self
     }
 
+    /// Sets whether the model may call multiple tools in a single turn.
+    /// Setting it to `false` forces the model to call at most one tool per turn.
+    /// Note: This is only supported by some providers (e.g.: OpenAI, Anthropic)
+    pub fn parallel_tool_calls(mut self, parallel_tool_calls: bool) -> Self {
+        self.parallel_tool_calls = Some(parallel_tool_calls);
+        self
+    }
+
+    /// Sets whether the model may call multiple tools in a single turn.
+    /// Note: This is only supported by some providers (e.g.: OpenAI, Anthropic)
+    pub fn parallel_tool_calls_opt(mut self, parallel_tool_calls: Option<bool>) -> Self {
+        self.parallel_tool_calls = parallel_tool_calls;
+        self
+    }
+
+    /// Sets whether the model may call the tools of the request.
+    /// Note: This is only supported by some providers (e.g.: OpenAI, Anthropic)
+    pub fn tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.tool_choice = Some(tool_choice);
+        self
+    }
+
+    /// Sets whether the model may call the tools of the request.
+    /// Note: This is only supported by some providers (e.g.: OpenAI, Anthropic)
+    pub fn tool_choice_opt(mut self, tool_choice: Option<ToolChoice>) -> Self {
+        self.tool_choice = tool_choice;
+        self
+    }
+
     /// Builds the completion request.
     pub fn build(self) -> CompletionRequest {
         let chat_history = OneOrMany::many([self.chat_history, vec![self.prompt]].concat())
@@ -632,6 +870,8 @@ impl<M: CompletionModel> CompletionRequestBuilder<M> {
             tools: self.tools,
             temperature: self.temperature,
             max_tokens: self.max_tokens,
+            parallel_tool_calls: self.parallel_tool_calls,
+            tool_choice: self.tool_choice,
             additional_params: self.additional_params,
         }
     }
@@ -714,6 +954,8 @@ mod tests {
             tools: Vec::new(),
             temperature: None,
             max_tokens: None,
+            parallel_tool_calls: None,
+            tool_choice: None,
             additional_params: None,
         };
 
@@ -736,6 +978,19 @@ mod tests {
         assert_eq!(request.normalized_documents(), Some(expected));
     }
 
+    #[test]
+    fn test_usage_estimated_cost() {
+        let usage = Usage {
+            input_tokens: 2000,
+            output_tokens: 500,
+            total_tokens: 2500,
+        };
+
+        let pricing = Pricing::new(0.0025, 0.01);
+
+        assert!((usage.estimated_cost(&pricing) - 0.01).abs() < f64::EPSILON);
+    }
+
     #[test]
     fn test_normalize_documents_without_documents() {
         let request = CompletionRequest {
@@ -745,6 +1000,8 @@ mod tests {
             tools: Vec::new(),
             temperature: None,
             max_tokens: None,
+            parallel_tool_calls: None,
+            tool_choice: None,
             additional_params: None,
         };
 
@@ -0,0 +1,185 @@
+//! This module provides [RetryPolicy], used to retry the completion requests of agents that
+//! fail with transient errors (see [CompletionError::is_retryable]), so that a single network
+//! blip or overloaded provider does not abort a long multi-turn prompt.
+//!
+//! # Example
+//! ```rust
+//! use std::time::Duration;
+//! use rig::{client::CompletionClient, completion::RetryPolicy, providers::openai};
+//!
+//! let openai = openai::Client::new("your-open-ai-api-key");
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You are a helpful assistant.")
+//!     // Retry each completion request up to 5 times, waiting 1s, 2s, 4s, 8s then 10s
+//!     .completion_retry(
+//!         RetryPolicy::new(5)
+//!             .initial_backoff(Duration::from_secs(1))
+//!             .max_backoff(Duration::from_secs(10)),
+//!     )
+//!     .build();
+//! ```
+use std::{future::Future, time::Duration};
+
+use super::CompletionError;
+
+/// Default maximum number of retries of a [RetryPolicy].
+pub const DEFAULT_MAX_RETRIES: usize = 3;
+/// Default delay before the first retry of a [RetryPolicy].
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+/// Default maximum delay between two retries of a [RetryPolicy].
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How failed completion requests are retried: up to `max_retries` times, with an exponential
+/// backoff (the delay doubles after each retry, up to `max_backoff`).
+///
+/// Only retryable errors are retried (see [CompletionError::is_retryable]): other errors (e.g.:
+/// invalid requests, authentication errors) fail immediately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_retries: usize,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_RETRIES)
+    }
+}
+
+impl RetryPolicy {
+    /// Retry failed requests up to `max_retries` times (in addition to the first attempt).
+    pub fn new(max_retries: usize) -> Self {
+        Self {
+            max_retries,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+        }
+    }
+
+    /// Set the delay before the first retry (defaults to [DEFAULT_INITIAL_BACKOFF]).
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Set the maximum delay between two retries (defaults to [DEFAULT_MAX_BACKOFF]).
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    pub fn max_retries(&self) -> usize {
+        self.max_retries
+    }
+
+    /// The delay before the `retry`-th retry (starting at 0).
+    pub fn backoff(&self, retry: usize) -> Duration {
+        let factor = 2u32.saturating_pow(retry.min(u32::MAX as usize) as u32);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
+    /// Run `f` until it succeeds, fails with an error that is not retryable, or the retries
+    /// are exhausted (in which case the last error is returned).
+    pub async fn retry<T, F, Fut>(&self, mut f: F) -> Result<T, CompletionError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, CompletionError>>,
+    {
+        let mut retry = 0;
+        loop {
+            match f().await {
+                Err(err) if err.is_retryable() && retry < self.max_retries => {
+                    let backoff = self.backoff(retry);
+                    retry += 1;
+                    tracing::warn!(
+                        "Completion request failed, retrying in {backoff:?} ({retry}/{}): {err}",
+                        self.max_retries
+                    );
+                    tokio::time::sleep(backoff).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::RetryPolicy;
+    use crate::completion::CompletionError;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::new(10)
+            .initial_backoff(Duration::from_secs(1))
+            .max_backoff(Duration::from_secs(5));
+
+        assert_eq!(policy.backoff(0), Duration::from_secs(1));
+        assert_eq!(policy.backoff(1), Duration::from_secs(2));
+        assert_eq!(policy.backoff(2), Duration::from_secs(4));
+        assert_eq!(policy.backoff(3), Duration::from_secs(5));
+        assert_eq!(policy.backoff(100), Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let policy = RetryPolicy::new(2).initial_backoff(Duration::from_millis(1));
+        let attempts = AtomicUsize::new(0);
+
+        // Transient errors are retried until the request succeeds
+        let result = policy
+            .retry(|| async {
+                match attempts.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err(CompletionError::ProviderStatusError {
+                        status: reqwest::StatusCode::SERVICE_UNAVAILABLE,
+                        message: "Service Unavailable".into(),
+                    }),
+                    _ => Ok("Hello!"),
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), "Hello!");
+        assert_eq!(attempts.swap(0, Ordering::SeqCst), 2);
+
+        // ... up to `max_retries` times
+        let result = policy
+            .retry(|| async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(CompletionError::ProviderError("Rate limit exceeded".into()))
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.swap(0, Ordering::SeqCst), 3);
+
+        // Other errors fail immediately, whatever their message
+        let result = policy
+            .retry(|| async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(CompletionError::ProviderStatusError {
+                    status: reqwest::StatusCode::BAD_REQUEST,
+                    message: "The prompt exceeds the limit of 500 tokens".into(),
+                })
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.swap(0, Ordering::SeqCst), 1);
+
+        let result = policy
+            .retry(|| async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(CompletionError::ProviderError("Invalid API key".into()))
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,260 @@
+//! This module provides [ToolCallIdFormat], used by providers to normalize the ids of the tool
+//! calls (and of their results) sent back to them in the chat history.
+//!
+//! Some providers reject the ids they did not generate themselves (e.g.: ids generated by another
+//! provider, or by the application) when they are too long or contain unexpected characters,
+//! which only surfaces mid-conversation, once a tool result is sent back. The ids which do not fit
+//! are rewritten deterministically, so that each tool call and its result keep the same id.
+//!
+//! # Example
+//! ```rust
+//! use rig::{client::CompletionClient, completion::ToolCallIdFormat, providers::anthropic};
+//!
+//! let anthropic = anthropic::Client::new("your-anthropic-api-key");
+//!
+//! // Limit the ids of the tool calls sent to the model to 40 characters
+//! let model = anthropic
+//!     .completion_model(anthropic::CLAUDE_3_7_SONNET)
+//!     .with_tool_call_ids(ToolCallIdFormat::anthropic().max_len(40));
+//! ```
+use std::borrow::Cow;
+
+use super::message::{AssistantContent, Message, UserContent};
+
+/// Length of the hash appended to the ids rewritten by [ToolCallIdFormat::normalize].
+const HASH_LEN: usize = 16;
+
+/// The characters allowed in the ids of a [ToolCallIdFormat].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdCharset {
+    /// Any character
+    #[default]
+    Any,
+    /// ASCII letters and digits, `_` and `-`
+    AlphanumericDashUnderscore,
+    /// ASCII letters and digits
+    Alphanumeric,
+}
+
+impl IdCharset {
+    pub fn allows(&self, c: char) -> bool {
+        match self {
+            IdCharset::Any => true,
+            IdCharset::AlphanumericDashUnderscore => {
+                c.is_ascii_alphanumeric() || c == '_' || c == '-'
+            }
+            IdCharset::Alphanumeric => c.is_ascii_alphanumeric(),
+        }
+    }
+}
+
+/// The constraints of a provider on the ids of the tool calls (and tool results) it accepts.
+///
+/// The default format accepts any id, which is then sent as is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ToolCallIdFormat {
+    max_len: Option<usize>,
+    charset: IdCharset,
+}
+
+impl ToolCallIdFormat {
+    /// Accept any id.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The format of the ids accepted by Anthropic: ASCII letters and digits, `_` and `-`.
+    pub fn anthropic() -> Self {
+        Self::new().charset(IdCharset::AlphanumericDashUnderscore)
+    }
+
+    /// The format of the ids accepted by the OpenAI Chat Completions API: at most 40 characters.
+    pub fn openai() -> Self {
+        Self::new().max_len(40)
+    }
+
+    /// Set the maximum length of the ids, in characters.
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+
+    /// Set the characters allowed in the ids.
+    pub fn charset(mut self, charset: IdCharset) -> Self {
+        self.charset = charset;
+        self
+    }
+
+    /// Whether `id` is accepted as is.
+    pub fn fits(&self, id: &str) -> bool {
+        self.max_len
+            .is_none_or(|max_len| id.chars().count() <= max_len)
+            && id.chars().all(|c| self.charset.allows(c))
+    }
+
+    /// Rewrite `id` to fit the format, if needed.
+    ///
+    /// Ids which do not fit are sanitized (disallowed characters are replaced with `_`, or dropped
+    /// if `_` is not allowed), truncated, and suffixed with a hash of the original id, so that
+    /// distinct ids stay distinct. The result only depends on `id`: a tool call and its result
+    /// are always given the same id.
+    pub fn normalize<'a>(&self, id: &'a str) -> Cow<'a, str> {
+        if self.fits(id) {
+            return Cow::Borrowed(id);
+        }
+
+        let hash = format!("{:0width$x}", fnv1a(id), width = HASH_LEN);
+        let max_len = self.max_len.unwrap_or(usize::MAX);
+        if max_len <= HASH_LEN {
+            return Cow::Owned(hash[..max_len].to_string());
+        }
+
+        let separator = if self.charset.allows('_') { "_" } else { "" };
+        let prefix = id
+            .chars()
+            .filter_map(|c| match c {
+                c if self.charset.allows(c) => Some(c),
+                _ if !separator.is_empty() => Some('_'),
+                _ => None,
+            })
+            .take(max_len - HASH_LEN - separator.len())
+            .collect::<String>();
+
+        Cow::Owned(if prefix.is_empty() {
+            hash
+        } else {
+            format!("{prefix}{separator}{hash}")
+        })
+    }
+
+    /// Normalize the ids of the tool calls and tool results of `messages` (see
+    /// [ToolCallIdFormat::normalize]).
+    pub fn normalize_messages<'a>(&self, messages: impl IntoIterator<Item = &'a mut Message>) {
+        if *self == Self::default() {
+            return;
+        }
+
+        let normalize = |id: &mut String| {
+            if let Cow::Owned(normalized) = self.normalize(id) {
+                *id = normalized;
+            }
+        };
+        for message in messages {
+            match message {
+                Message::User { content } => {
+                    for content in content.iter_mut() {
+                        if let UserContent::ToolResult(result) = content {
+                            normalize(&mut result.id);
+                        }
+                    }
+                }
+                Message::Assistant { content, .. } => {
+                    for content in content.iter_mut() {
+                        if let AssistantContent::ToolCall(call) = content {
+                            normalize(&mut call.id);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The 64-bit FNV-1a hash of `id`, which is stable across platforms and Rust versions, unlike
+/// [std::hash::DefaultHasher].
+fn fnv1a(id: &str) -> u64 {
+    id.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IdCharset, ToolCallIdFormat};
+    use crate::completion::{AssistantContent, Message, message::UserContent};
+
+    #[test]
+    fn test_normalize() {
+        let format = ToolCallIdFormat::anthropic().max_len(40);
+
+        // Valid ids are kept as is
+        assert_eq!(
+            format.normalize("toolu_01A09q90qw90lq917835lq9"),
+            "toolu_01A09q90qw90lq917835lq9"
+        );
+
+        // Invalid characters are replaced, long ids are truncated, and both stay distinct
+        let long = "call_".to_string() + &"a".repeat(60);
+        for id in [
+            "call.1:weather",
+            "call.1/weather",
+            long.as_str(),
+            &(long.clone() + "b"),
+        ] {
+            let normalized = format.normalize(id);
+            assert!(format.fits(&normalized), "{normalized}");
+            assert!(normalized.len() <= 40);
+            assert_eq!(format.normalize(id), normalized);
+            assert_eq!(format.normalize(&normalized), normalized);
+        }
+        assert_ne!(
+            format.normalize("call.1:weather"),
+            format.normalize("call.1/weather")
+        );
+        assert_ne!(
+            format.normalize(&long),
+            format.normalize(&(long.clone() + "b"))
+        );
+        assert!(
+            format
+                .normalize("call.1:weather")
+                .starts_with("call_1_weather_")
+        );
+
+        // Without `_`, invalid characters are dropped
+        let format = ToolCallIdFormat::new()
+            .charset(IdCharset::Alphanumeric)
+            .max_len(9);
+        let normalized = format.normalize("call-1");
+        assert_eq!(normalized.len(), 9);
+        assert!(normalized.chars().all(|c| c.is_ascii_alphanumeric()));
+    }
+
+    #[test]
+    fn test_normalize_messages() {
+        let id = "functions.get_weather:0|".to_string() + &"x".repeat(40);
+        let mut messages = vec![
+            Message::Assistant {
+                id: None,
+                content: crate::OneOrMany::one(AssistantContent::tool_call(
+                    &id,
+                    "get_weather",
+                    serde_json::json!({}),
+                )),
+            },
+            Message::tool_result(&id, "Sunny"),
+        ];
+
+        ToolCallIdFormat::anthropic()
+            .max_len(40)
+            .normalize_messages(&mut messages);
+
+        let Message::Assistant { content, .. } = &messages[0] else {
+            panic!("Expected an assistant message");
+        };
+        let AssistantContent::ToolCall(call) = content.first() else {
+            panic!("Expected a tool call");
+        };
+        let Message::User { content } = &messages[1] else {
+            panic!("Expected a user message");
+        };
+        let UserContent::ToolResult(result) = content.first() else {
+            panic!("Expected a tool result");
+        };
+
+        // The tool call and its result still correlate
+        assert_ne!(call.id, id);
+        assert_eq!(call.id, result.id);
+        assert!(ToolCallIdFormat::anthropic().max_len(40).fits(&call.id));
+    }
+}
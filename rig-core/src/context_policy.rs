@@ -0,0 +1,289 @@
+//! This module provides [ContextPolicy], used to keep the chat history of agents within a token
+//! budget by dropping messages before each completion request (see
+//! [AgentBuilder::context_policy](crate::agent::AgentBuilder::context_policy)).
+//!
+//! The preamble of the agent is not part of the chat history and is never dropped, neither is
+//! the prompt. A tool call and its result are always dropped together.
+//!
+//! # Example
+//! ```rust
+//! use rig::{client::CompletionClient, context_policy::ContextPolicy, providers::openai};
+//!
+//! let openai = openai::Client::new("your-open-ai-api-key");
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You are a helpful assistant.")
+//!     // Keep the first exchange (e.g.: the task) and the 10 most recent messages
+//!     .context_policy(ContextPolicy::KeepFirstAndLast {
+//!         max_tokens: 100_000,
+//!         first_n: 2,
+//!         last_n: 10,
+//!     })
+//!     .build();
+//! ```
+use crate::{completion::Message, message::UserContent};
+
+/// Strategy used to trim the chat history when the prompt and the chat history exceed
+/// `max_tokens` tokens (estimated with [estimate_tokens]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextPolicy {
+    /// Drop the oldest messages until the chat history fits the budget.
+    DropOldest { max_tokens: usize },
+    /// Always keep the first `first_n` messages (e.g.: the instructions of the conversation)
+    /// and the last `last_n` messages (the most recent turns), and drop the messages in
+    /// between, oldest first, until the chat history fits the budget.
+    ///
+    /// The kept messages may exceed the budget.
+    KeepFirstAndLast {
+        max_tokens: usize,
+        first_n: usize,
+        last_n: usize,
+    },
+}
+
+impl ContextPolicy {
+    /// The token budget of the prompt and the chat history.
+    pub fn max_tokens(&self) -> usize {
+        match self {
+            ContextPolicy::DropOldest { max_tokens }
+            | ContextPolicy::KeepFirstAndLast { max_tokens, .. } => *max_tokens,
+        }
+    }
+
+    /// Trim `chat_history` (preceding `prompt`) according to the policy.
+    pub fn apply(&self, mut chat_history: Vec<Message>, prompt: &Message) -> Vec<Message> {
+        let max_tokens = self.max_tokens();
+        let mut tokens =
+            estimate_tokens(prompt) + chat_history.iter().map(estimate_tokens).sum::<usize>();
+        if tokens <= max_tokens {
+            return chat_history;
+        }
+
+        let (first_n, last_n) = match self {
+            ContextPolicy::DropOldest { .. } => (0, 0),
+            ContextPolicy::KeepFirstAndLast {
+                first_n, last_n, ..
+            } => (*first_n, *last_n),
+        };
+        // The tool calls answered by the prompt are kept with it
+        let last_n = last_n.max(usize::from(is_tool_result(prompt)));
+
+        let len = chat_history.len();
+        let start = turn_boundary(&chat_history, first_n.min(len));
+        let end = turn_boundary(&chat_history, len.saturating_sub(last_n)).max(start);
+
+        let mut dropped = start;
+        while dropped < end && tokens > max_tokens {
+            tokens -= estimate_tokens(&chat_history[dropped]);
+            dropped += 1;
+        }
+        // Don't keep the results of dropped tool calls
+        let dropped = turn_boundary(&chat_history, dropped);
+
+        if dropped > start {
+            tracing::debug!(
+                target: "rig",
+                "Dropping {} messages of the chat history to fit {max_tokens} tokens",
+                dropped - start
+            );
+            chat_history.drain(start..dropped);
+        }
+        chat_history
+    }
+}
+
+/// A rough estimate of the number of tokens of `message`: 4 characters per token of its JSON
+/// serialization.
+pub fn estimate_tokens(message: &Message) -> usize {
+    serde_json::to_string(message)
+        .map(|json| estimate_text_tokens(&json))
+        .unwrap_or_default()
+}
+
+/// A rough estimate of the number of tokens of `text`: 4 characters per token.
+pub fn estimate_text_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+/// Whether `message` contains tool results, i.e.: answers tool calls of the previous message.
+fn is_tool_result(message: &Message) -> bool {
+    matches!(
+        message,
+        Message::User { content }
+            if content.iter().any(|content| matches!(content, UserContent::ToolResult(_)))
+    )
+}
+
+/// The first index from `index` which does not separate tool calls from their results.
+fn turn_boundary(chat_history: &[Message], mut index: usize) -> usize {
+    while index > 0 && index < chat_history.len() && is_tool_result(&chat_history[index]) {
+        index += 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContextPolicy, estimate_tokens};
+    use crate::{
+        OneOrMany,
+        agent::AgentBuilder,
+        completion::{Chat, Message},
+        message::{AssistantContent, ToolResultContent, UserContent},
+        test_utils::MockCompletionModel,
+    };
+
+    fn history() -> Vec<Message> {
+        (0..6)
+            .map(|i| {
+                if i % 2 == 0 {
+                    Message::user(format!("Question {i}"))
+                } else {
+                    Message::assistant(format!("Answer {i}"))
+                }
+            })
+            .collect()
+    }
+
+    /// The text (or tool call id) of each message.
+    fn texts(chat_history: &[Message]) -> Vec<String> {
+        chat_history
+            .iter()
+            .map(|message| match message {
+                Message::User { content } => match content.first() {
+                    UserContent::Text(text) => text.text,
+                    UserContent::ToolResult(result) => match result.content.first() {
+                        ToolResultContent::Text(text) => text.text,
+                        _ => unreachable!(),
+                    },
+                    _ => unreachable!(),
+                },
+                Message::Assistant { content, .. } => match content.first() {
+                    AssistantContent::Text(text) => text.text,
+                    AssistantContent::ToolCall(tool_call) => tool_call.id,
+                    _ => unreachable!(),
+                },
+            })
+            .collect()
+    }
+
+    /// The tokens of the prompt and the first `n` messages of `history()`.
+    fn budget(n: usize) -> usize {
+        estimate_tokens(&Message::user("Prompt"))
+            + history()[..n].iter().map(estimate_tokens).sum::<usize>()
+    }
+
+    #[test]
+    fn test_within_budget() {
+        let policy = ContextPolicy::DropOldest {
+            max_tokens: budget(6),
+        };
+        assert_eq!(policy.apply(history(), &Message::user("Prompt")), history());
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        // Messages are about the same size, so 2 messages must be dropped to fit 4
+        let policy = ContextPolicy::DropOldest {
+            max_tokens: budget(4),
+        };
+        let trimmed = policy.apply(history(), &Message::user("Prompt"));
+
+        assert_eq!(
+            texts(&trimmed),
+            vec!["Question 2", "Answer 3", "Question 4", "Answer 5"]
+        );
+    }
+
+    #[test]
+    fn test_keep_first_and_last() {
+        let policy = ContextPolicy::KeepFirstAndLast {
+            max_tokens: budget(4),
+            first_n: 1,
+            last_n: 2,
+        };
+        let trimmed = policy.apply(history(), &Message::user("Prompt"));
+
+        assert_eq!(
+            texts(&trimmed),
+            vec!["Question 0", "Answer 3", "Question 4", "Answer 5"]
+        );
+
+        // The first and last messages are kept even if they exceed the budget
+        let policy = ContextPolicy::KeepFirstAndLast {
+            max_tokens: 0,
+            first_n: 1,
+            last_n: 2,
+        };
+        let trimmed = policy.apply(history(), &Message::user("Prompt"));
+
+        assert_eq!(
+            texts(&trimmed),
+            vec!["Question 0", "Question 4", "Answer 5"]
+        );
+    }
+
+    #[test]
+    fn test_tool_calls_are_dropped_with_their_results() {
+        let tool_call = |id: &str| Message::Assistant {
+            id: None,
+            content: OneOrMany::one(AssistantContent::tool_call(
+                id,
+                "search",
+                serde_json::json!({}),
+            )),
+        };
+        let chat_history = vec![
+            Message::user("Question 0"),
+            tool_call("call_1"),
+            Message::tool_result("call_1", "Result 1"),
+            Message::assistant("Answer 3"),
+            tool_call("call_4"),
+        ];
+
+        // Dropping the first tool call also drops its result, and the last tool call is kept
+        // with its result (the prompt)
+        let policy = ContextPolicy::DropOldest { max_tokens: 0 };
+        let trimmed = policy.apply(
+            chat_history.clone(),
+            &Message::tool_result("call_4", "Result 4"),
+        );
+        assert_eq!(trimmed, vec![tool_call("call_4")]);
+
+        // The first messages are kept with the results of their tool calls
+        let policy = ContextPolicy::KeepFirstAndLast {
+            max_tokens: 0,
+            first_n: 2,
+            last_n: 0,
+        };
+        let trimmed = policy.apply(chat_history, &Message::user("Prompt"));
+        assert_eq!(texts(&trimmed), vec!["Question 0", "call_1", "Result 1"]);
+    }
+
+    #[tokio::test]
+    async fn test_agent_context_policy() {
+        let model = MockCompletionModel::new().with_text("Answer 6");
+        let agent = AgentBuilder::new(model.clone())
+            .preamble("You are a helpful assistant.")
+            .context_policy(ContextPolicy::KeepFirstAndLast {
+                max_tokens: budget(4),
+                first_n: 2,
+                last_n: 1,
+            })
+            .build();
+
+        agent.chat("Prompt", history()).await.unwrap();
+
+        let requests = model.requests();
+        assert_eq!(
+            requests[0].preamble.as_deref(),
+            Some("You are a helpful assistant.")
+        );
+        // The prompt is the last message of the request
+        let chat_history = requests[0].chat_history.iter().cloned().collect::<Vec<_>>();
+        assert_eq!(
+            texts(&chat_history),
+            vec!["Question 0", "Answer 1", "Question 4", "Answer 5", "Prompt"]
+        );
+    }
+}
@@ -2,20 +2,37 @@
 //! and batch generates the embeddings for each object when built.
 //! Only types that implement the [Embed] trait can be added to the [EmbeddingsBuilder].
 
-use std::{cmp::max, collections::HashMap};
+use std::{
+    cmp::max,
+    collections::{HashMap, VecDeque},
+    hash::{BuildHasher, RandomState},
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
 
-use futures::{StreamExt, stream};
+use futures::{Stream, StreamExt, stream, stream::FuturesUnordered};
 
 use crate::{
     OneOrMany,
+    completion::RetryPolicy,
     embeddings::{
-        Embed, EmbedError, Embedding, EmbeddingError, EmbeddingModel, embed::TextEmbedder,
+        Embed, EmbedError, EmbedKind, Embedding, EmbeddingError, EmbeddingInput, EmbeddingModel,
+        Quantization,
+        embed::TextEmbedder,
+        embedding::{EmbeddingModelDyn, batch_size},
     },
 };
 
 /// Builder for creating embeddings from one or more documents of type `T`.
 /// Note: `T` can be any type that implements the [Embed] trait.
 ///
+/// The documents are embedded as [EmbedKind::Document].
+///
 /// Using the builder is preferred over using [EmbeddingModel::embed_text] directly as
 /// it will batch the documents in a single request to the model provider.
 ///
@@ -49,7 +66,31 @@ use crate::{
 /// ```
 pub struct EmbeddingsBuilder<M: EmbeddingModel, T: Embed> {
     model: M,
+    model_name: String,
+    additional_models: Vec<(String, Box<dyn EmbeddingModelDyn>)>,
     documents: Vec<(T, Vec<String>)>,
+    empty_documents: EmptyDocuments,
+    adaptive_concurrency: Option<AdaptiveConcurrency>,
+    quantization: Option<Quantization>,
+    token_counter: Option<TokenCounter>,
+    dedupe_inputs: bool,
+}
+
+/// Function counting the tokens of a text (see [EmbeddingsBuilder::token_counter]).
+type TokenCounter = Arc<dyn Fn(&str) -> usize + Send + Sync>;
+
+/// What [EmbeddingsBuilder::build] does with documents that have no content to embed
+/// (i.e.: all their embeddable texts are empty or whitespace).
+///
+/// Empty or whitespace texts are never sent to the embedding model, as they waste a request
+/// and some providers reject them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmptyDocuments {
+    /// Omit the document from the result and log a warning (default).
+    #[default]
+    Skip,
+    /// Return the document with a single zero vector embedding of the model's dimensions.
+    ZeroEmbedding,
 }
 
 impl<M: EmbeddingModel, T: Embed> EmbeddingsBuilder<M, T> {
@@ -57,16 +98,99 @@ impl<M: EmbeddingModel, T: Embed> EmbeddingsBuilder<M, T> {
     pub fn new(model: M) -> Self {
         Self {
             model,
+            model_name: DEFAULT_MODEL_NAME.to_string(),
+            additional_models: vec![],
             documents: vec![],
+            empty_documents: EmptyDocuments::default(),
+            adaptive_concurrency: None,
+            quantization: None,
+            token_counter: None,
+            dedupe_inputs: false,
         }
     }
 
+    /// Set the name under which the embeddings of the builder's model are returned by
+    /// [EmbeddingsBuilder::build_per_model] (defaults to `"default"`).
+    pub fn model_name(mut self, name: &str) -> Self {
+        self.model_name = name.to_string();
+        self
+    }
+
+    /// Add another embedding model, whose embeddings are returned under `name` by
+    /// [EmbeddingsBuilder::build_per_model] (e.g.: to compare the retrieval quality of
+    /// different models on the same documents without going through them twice).
+    ///
+    /// Note: additional models are ignored by [EmbeddingsBuilder::build].
+    pub fn additional_model(mut self, name: &str, model: impl EmbeddingModel + 'static) -> Self {
+        self.additional_models
+            .push((name.to_string(), Box::new(model)));
+        self
+    }
+
+    /// Set what to do with documents that have no content to embed (see [EmptyDocuments]).
+    pub fn empty_documents(mut self, empty_documents: EmptyDocuments) -> Self {
+        self.empty_documents = empty_documents;
+        self
+    }
+
+    /// Adapt the number of concurrent embedding requests to the provider's rate limits.
+    ///
+    /// The builder starts with `min` concurrent requests and adds one each time a full
+    /// window of requests succeeds (up to `max`). When the provider rate limits a request
+    /// (see [EmbeddingError::is_rate_limited]), the concurrency is halved (down to `min`)
+    /// and the batch is retried after an exponential backoff with jitter (like the default
+    /// [RetryPolicy]), up to 5 times.
+    ///
+    /// By default, up to `1024 / batch size` requests are sent concurrently (see
+    /// [EmbeddingModel::max_batch_size]).
+    pub fn adaptive_concurrency(mut self, min: usize, max: usize) -> Self {
+        let min = min.max(1);
+        self.adaptive_concurrency = Some(AdaptiveConcurrency {
+            min,
+            max: max.max(min),
+        });
+        self
+    }
+
+    /// Quantize the generated embeddings (see [Embedding::quantize]).
+    ///
+    /// Note: the embeddings are still returned as `f64` vectors, vector stores supporting
+    /// quantized vectors (e.g.: MongoDB) need to be configured with the same quantization to
+    /// store them in their compact form.
+    pub fn quantize(mut self, quantization: Quantization) -> Self {
+        self.quantization = Some(quantization);
+        self
+    }
+
+    /// Count the tokens of the texts to embed with `count_tokens` (e.g.: the tokenizer of the
+    /// embedding model), so that texts exceeding the [EmbeddingModel::max_input_tokens] of the
+    /// model fail with [EmbeddingError::InvalidInput] before any request is sent.
+    ///
+    /// Without a token counter (or if the limit of the model is unknown), texts are sent as is.
+    pub fn token_counter(
+        mut self,
+        count_tokens: impl Fn(&str) -> usize + Send + Sync + 'static,
+    ) -> Self {
+        self.token_counter = Some(Arc::new(count_tokens));
+        self
+    }
+
+    /// Embed each distinct text only once, even if several documents (or texts of a document)
+    /// share it (e.g.: boilerplate sections), and give its embedding to all of them. The
+    /// documents and their embeddings are returned as without deduplication.
+    ///
+    /// Disabled by default, since the texts have to be hashed.
+    pub fn dedupe_inputs(mut self, dedupe_inputs: bool) -> Self {
+        self.dedupe_inputs = dedupe_inputs;
+        self
+    }
+
     /// Add a document to be embedded to the builder. `document` must implement the [Embed] trait.
     pub fn document(mut self, document: T) -> Result<Self, EmbedError> {
         let mut embedder = TextEmbedder::default();
         document.embed(&mut embedder)?;
 
-        self.documents.push((document, embedder.texts));
+        self.documents.push((document, embeddable_texts(embedder)));
 
         Ok(self)
     }
@@ -85,93 +209,861 @@ impl<M: EmbeddingModel, T: Embed> EmbeddingsBuilder<M, T> {
 impl<M: EmbeddingModel, T: Embed + Send> EmbeddingsBuilder<M, T> {
     /// Generate embeddings for all documents in the builder.
     /// Returns a vector of tuples, where the first element is the document and the second element is the embeddings (either one embedding or many).
+    ///
+    /// The documents are returned in the order they were added to the builder, and the
+    /// embeddings of each document in the order of its texts, regardless of the order in which
+    /// the concurrent embedding requests complete.
     pub async fn build(self) -> Result<Vec<(T, OneOrMany<Embedding>)>, EmbeddingError> {
-        use stream::TryStreamExt;
+        let (docs, texts) = split_documents(self.documents);
+        check_input_tokens(&self.model, &texts, self.token_counter.as_ref())?;
 
-        // Store the documents and their texts in a HashMap for easy access.
-        let mut docs = HashMap::new();
-        let mut texts = Vec::new();
+        // Compute the embeddings.
+        let mut embeddings = embed_documents(
+            &self.model,
+            &texts,
+            self.adaptive_concurrency,
+            self.dedupe_inputs,
+        )
+        .await?;
+        quantize(&mut embeddings, self.quantization);
+
+        // Merge the embeddings with their respective documents
+        Ok(docs
+            .into_iter()
+            .filter_map(|(i, doc)| match embeddings.remove(&i) {
+                Some(embeddings) => Some((doc, embeddings)),
+                // The document had no content to embed
+                None => self
+                    .empty_documents
+                    .embeddings(i, EmbeddingModel::ndims(&self.model))
+                    .map(|embeddings| (doc, embeddings)),
+            })
+            .collect())
+    }
 
-        // Iterate over all documents in the builder and insert their docs and texts into the lookup stores.
-        for (i, (doc, doc_texts)) in self.documents.into_iter().enumerate() {
-            docs.insert(i, doc);
-            texts.push((i, doc_texts));
+    /// Blocking version of [EmbeddingsBuilder::build] (see [blocking](crate::blocking) for the
+    /// runtime nesting caveats).
+    #[cfg_attr(docsrs, doc(cfg(feature = "blocking")))]
+    #[cfg(feature = "blocking")]
+    pub fn build_blocking(self) -> Result<Vec<(T, OneOrMany<Embedding>)>, EmbeddingError> {
+        crate::blocking::block_on(self.build())
+    }
+
+    /// Generate embeddings for all documents in the builder, as a stream of documents with
+    /// their embeddings. Each document is yielded as soon as all of its embeddings are generated,
+    /// so the documents are **not** yielded in the order they were added to the builder.
+    ///
+    /// Embedding requests are only sent while the stream is polled, with the concurrency of
+    /// [EmbeddingsBuilder::adaptive_concurrency] if set (rate limited batches are retried, like
+    /// with [EmbeddingsBuilder::build]), or the default concurrency otherwise.
+    ///
+    /// Dropping the stream or calling [EmbeddingsStream::cancel] stops sending new embedding
+    /// requests. When cancelled, the stream still yields the documents of the requests already
+    /// in flight, then ends. Note that requests already dispatched may still complete (and be
+    /// billed) on the provider's side, even when the stream is dropped.
+    ///
+    /// The stream ends after the first error.
+    ///
+    /// # Example
+    /// ```rust
+    /// use futures::StreamExt;
+    /// use rig::{
+    ///     client::EmbeddingsClient,
+    ///     embeddings::EmbeddingsBuilder,
+    ///     providers::openai::{Client, TEXT_EMBEDDING_3_SMALL},
+    /// };
+    ///
+    /// # async fn run(documents: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let model = Client::new("your-open-ai-api-key").embedding_model(TEXT_EMBEDDING_3_SMALL);
+    ///
+    /// let mut stream = EmbeddingsBuilder::new(model)
+    ///     .documents(documents)?
+    ///     .build_stream();
+    ///
+    /// let mut embedded = 0;
+    /// while let Some(result) = stream.next().await {
+    ///     let (document, embeddings) = result?;
+    ///     embedded += 1;
+    ///     if embedded == 1000 {
+    ///         // Stop sending requests, the documents already being embedded are still yielded
+    ///         stream.cancel();
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn build_stream<'a>(self) -> EmbeddingsStream<'a, T>
+    where
+        M: 'a,
+        T: 'a,
+    {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let concurrency = ConcurrencyLimit::new(
+            self.adaptive_concurrency,
+            max(1, 1024 / batch_size(&self.model)),
+        );
+        let inner = embed_documents_stream(
+            self.model,
+            self.documents,
+            self.empty_documents,
+            self.quantization,
+            self.token_counter,
+            self.dedupe_inputs,
+            concurrency,
+            cancelled.clone(),
+        );
+
+        EmbeddingsStream {
+            inner: Box::pin(inner),
+            cancelled,
         }
+    }
 
-        // Compute the embeddings.
-        let mut embeddings = stream::iter(texts.into_iter())
-            // Merge the texts of each document into a single list of texts.
-            .flat_map(|(i, texts)| stream::iter(texts.into_iter().map(move |text| (i, text))))
-            // Chunk them into batches. Each batch size is at most the embedding API limit per request.
-            .chunks(M::MAX_DOCUMENTS)
-            // Generate the embeddings for each batch.
-            .map(|text| async {
-                let (ids, docs): (Vec<_>, Vec<_>) = text.into_iter().unzip();
-
-                let embeddings = self.model.embed_texts(docs).await?;
-                Ok::<_, EmbeddingError>(ids.into_iter().zip(embeddings).collect::<Vec<_>>())
-            })
-            // Parallelize the embeddings generation over 10 concurrent requests
-            .buffer_unordered(max(1, 1024 / M::MAX_DOCUMENTS))
-            // Collect the embeddings into a HashMap.
-            .try_fold(
-                HashMap::new(),
-                |mut acc: HashMap<_, OneOrMany<Embedding>>, embeddings| async move {
-                    embeddings.into_iter().for_each(|(i, embedding)| {
-                        acc.entry(i)
-                            .and_modify(|embeddings| embeddings.push(embedding.clone()))
-                            .or_insert(OneOrMany::one(embedding.clone()));
-                    });
-
-                    Ok(acc)
-                },
+    /// Generate embeddings for the documents in the builder, then for the documents of
+    /// `documents` (e.g.: read from a database cursor), as a stream of documents with their
+    /// embeddings, like [EmbeddingsBuilder::build_stream].
+    ///
+    /// The documents of `documents` are only pulled when the next batch of texts can be sent, so
+    /// memory stays bounded regardless of the size of the corpus: at most the texts of the
+    /// requests in flight, and of the documents whose embeddings are not complete yet, are held.
+    /// The documents can then be written to a vector store as they are yielded.
+    ///
+    /// Note: since the texts are not known in advance, [EmbeddingsBuilder::dedupe_inputs] is
+    /// ignored, and the texts exceeding the input token limit of the model (see
+    /// [EmbeddingsBuilder::token_counter]) are only detected when their document is pulled.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use futures::{StreamExt, TryStreamExt};
+    ///
+    /// let documents = collection.find(doc! {}).await?.filter_map(|doc| async { doc.ok() });
+    ///
+    /// let mut stream = EmbeddingsBuilder::new(model).build_from_stream(documents);
+    /// while let Some((document, embeddings)) = stream.try_next().await? {
+    ///     index.insert_documents(vec![(document, embeddings)]).await?;
+    /// }
+    /// ```
+    pub fn build_from_stream<'a>(
+        self,
+        documents: impl Stream<Item = T> + Send + 'a,
+    ) -> EmbeddingsStream<'a, T>
+    where
+        M: 'a,
+        T: 'a,
+    {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let concurrency = ConcurrencyLimit::new(
+            self.adaptive_concurrency,
+            max(1, 1024 / batch_size(&self.model)),
+        );
+        let documents =
+            stream::iter(self.documents.into_iter().map(Ok)).chain(documents.map(|document| {
+                let mut embedder = TextEmbedder::default();
+                document.embed(&mut embedder)?;
+                Ok((document, embeddable_texts(embedder)))
+            }));
+        let inner = embed_lazy_stream(
+            self.model,
+            documents,
+            self.empty_documents,
+            self.quantization,
+            self.token_counter,
+            concurrency,
+            cancelled.clone(),
+        );
+
+        EmbeddingsStream {
+            inner: Box::pin(inner),
+            cancelled,
+        }
+    }
+
+    /// Generate embeddings for all documents in the builder with the builder's model and each
+    /// additional model (see [EmbeddingsBuilder::additional_model]).
+    /// Returns a vector of tuples, where the first element is the document and the second element
+    /// maps the name of each model to the embeddings it generated for the document.
+    ///
+    /// As with [EmbeddingsBuilder::build], the documents are returned in the order they were
+    /// added to the builder.
+    pub async fn build_per_model(
+        self,
+    ) -> Result<Vec<(T, HashMap<String, OneOrMany<Embedding>>)>, EmbeddingError> {
+        let (docs, texts) = split_documents(self.documents);
+
+        let models = std::iter::once((self.model_name, &self.model as &dyn EmbeddingModelDyn))
+            .chain(
+                self.additional_models
+                    .iter()
+                    .map(|(name, model)| (name.clone(), model.as_ref())),
             )
-            .await?;
+            .collect::<Vec<_>>();
+        for (_, model) in models.iter() {
+            check_input_tokens(*model, &texts, self.token_counter.as_ref())?;
+        }
 
-        // Merge the embeddings with their respective documents
+        // Compute the embeddings of every model concurrently.
+        let mut embeddings = futures::future::try_join_all(models.iter().map(|(_, model)| {
+            embed_documents(
+                *model,
+                &texts,
+                self.adaptive_concurrency,
+                self.dedupe_inputs,
+            )
+        }))
+        .await?;
+        for embeddings in embeddings.iter_mut() {
+            quantize(embeddings, self.quantization);
+        }
+
+        // Merge the embeddings of each model with their respective documents
         Ok(docs
             .into_iter()
-            .map(|(i, doc)| {
-                (
-                    doc,
-                    embeddings.remove(&i).expect("Document should be present"),
-                )
+            .filter_map(|(i, doc)| {
+                let doc_embeddings = models
+                    .iter()
+                    .zip(embeddings.iter_mut())
+                    .filter_map(|((name, model), embeddings)| {
+                        embeddings
+                            .remove(&i)
+                            // The document had no content to embed
+                            .or_else(|| self.empty_documents.embeddings(i, model.ndims()))
+                            .map(|embeddings| (name.clone(), embeddings))
+                    })
+                    .collect::<HashMap<_, _>>();
+
+                (!doc_embeddings.is_empty()).then_some((doc, doc_embeddings))
             })
             .collect())
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::{
-        Embed,
-        embeddings::{Embedding, EmbeddingModel, embed::EmbedError, embed::TextEmbedder},
+/// Quantize the embeddings of each document, if a quantization is set.
+fn quantize(
+    embeddings: &mut HashMap<usize, OneOrMany<Embedding>>,
+    quantization: Option<Quantization>,
+) {
+    if let Some(quantization) = quantization {
+        embeddings
+            .values_mut()
+            .flat_map(|embeddings| embeddings.iter_mut())
+            .for_each(|embedding| embedding.quantize(quantization));
+    }
+}
+
+/// Name of the builder's model in the result of [EmbeddingsBuilder::build_per_model], unless set
+/// with [EmbeddingsBuilder::model_name].
+const DEFAULT_MODEL_NAME: &str = "default";
+
+impl EmptyDocuments {
+    /// The embeddings of the document `i`, which has no content to embed, for a model with `ndims` dimensions.
+    fn embeddings(self, i: usize, ndims: usize) -> Option<OneOrMany<Embedding>> {
+        match self {
+            EmptyDocuments::Skip => {
+                tracing::warn!("Skipping document {i}: it has no content to embed");
+                None
+            }
+            EmptyDocuments::ZeroEmbedding => Some(OneOrMany::one(Embedding {
+                document: String::new(),
+                vec: vec![0.0; ndims],
+            })),
+        }
+    }
+}
+
+/// The texts of `embedder` which are valid [EmbeddingInput]s. The others (i.e.: empty or only
+/// whitespace) are dropped before any request is sent, and documents left without texts are
+/// handled as set with [EmbeddingsBuilder::empty_documents].
+fn embeddable_texts(embedder: TextEmbedder) -> Vec<String> {
+    embedder
+        .texts
+        .into_iter()
+        .filter_map(|text| EmbeddingInput::new(text).ok())
+        .map(EmbeddingInput::into_inner)
+        .collect()
+}
+
+/// Split the documents of the builder into the list of the documents and the list of their
+/// texts, by index (in the order the documents were added).
+#[allow(clippy::type_complexity)]
+fn split_documents<T>(
+    documents: Vec<(T, Vec<String>)>,
+) -> (Vec<(usize, T)>, Vec<(usize, Vec<String>)>) {
+    documents
+        .into_iter()
+        .enumerate()
+        .map(|(i, (doc, texts))| ((i, doc), (i, texts)))
+        .unzip()
+}
+
+/// Check that none of the texts of the documents exceeds the input token limit of `model`, if
+/// both the limit and a token counter are known.
+fn check_input_tokens<E: EmbeddingModelDyn + ?Sized>(
+    model: &E,
+    texts: &[(usize, Vec<String>)],
+    token_counter: Option<&TokenCounter>,
+) -> Result<(), EmbeddingError> {
+    let (Some(max_tokens), Some(count_tokens)) = (model.max_input_tokens(), token_counter) else {
+        return Ok(());
     };
 
-    use super::EmbeddingsBuilder;
+    for (i, texts) in texts {
+        for text in texts {
+            let tokens = count_tokens(text);
+            if tokens > max_tokens {
+                return Err(EmbeddingError::InvalidInput(format!(
+                    "A text of document {i} is {tokens} tokens long, which exceeds the limit of \
+                     {max_tokens} tokens of the model"
+                )));
+            }
+        }
+    }
 
-    #[derive(Clone)]
-    struct Model;
+    Ok(())
+}
 
-    impl EmbeddingModel for Model {
-        const MAX_DOCUMENTS: usize = 5;
+/// The inputs to send to the embedding model for `texts` (without duplicates if `dedupe`), with
+/// the positions of the texts sharing each input.
+fn inputs(texts: impl Iterator<Item = String>, dedupe: bool) -> (Vec<String>, Vec<Vec<usize>>) {
+    if !dedupe {
+        return texts
+            .enumerate()
+            .map(|(position, text)| (text, vec![position]))
+            .unzip();
+    }
 
-        fn ndims(&self) -> usize {
-            10
+    let mut inputs = vec![];
+    let mut positions: Vec<Vec<usize>> = vec![];
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for (position, text) in texts.enumerate() {
+        match seen.get(&text) {
+            Some(&input) => positions[input].push(position),
+            None => {
+                seen.insert(text.clone(), inputs.len());
+                inputs.push(text);
+                positions.push(vec![position]);
+            }
         }
+    }
+    (inputs, positions)
+}
 
-        async fn embed_texts(
-            &self,
-            documents: impl IntoIterator<Item = String> + Send,
-        ) -> Result<Vec<crate::embeddings::Embedding>, crate::embeddings::EmbeddingError> {
-            Ok(documents
-                .into_iter()
-                .map(|doc| Embedding {
-                    document: doc.to_string(),
-                    vec: vec![0.0, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9],
+/// Give the `embedding` of an input to the texts at `positions`.
+fn fan_out(embedding: Embedding, positions: &[usize]) -> Vec<(usize, Embedding)> {
+    let (last, others) = positions
+        .split_last()
+        .expect("Each input is shared by at least one text");
+    let mut embeddings = others
+        .iter()
+        .map(|position| (*position, embedding.clone()))
+        .collect::<Vec<_>>();
+    embeddings.push((*last, embedding));
+    embeddings
+}
+
+/// Generate the embeddings of the texts of the documents with `model`, by document index.
+async fn embed_documents<E: EmbeddingModelDyn + ?Sized>(
+    model: &E,
+    texts: &[(usize, Vec<String>)],
+    adaptive_concurrency: Option<AdaptiveConcurrency>,
+    dedupe: bool,
+) -> Result<HashMap<usize, OneOrMany<Embedding>>, EmbeddingError> {
+    use stream::TryStreamExt;
+
+    let max_documents = model.max_documents();
+
+    // Merge the texts of each document into a single list of texts, and chunk them into batches.
+    // Each batch size is at most the embedding API limit per request.
+    // The texts are identified by their position in the list, so that their embeddings can be
+    // put back in order whatever the order in which the batches complete.
+    let documents = texts
+        .iter()
+        .flat_map(|(i, texts)| texts.iter().map(move |_| *i))
+        .collect::<Vec<_>>();
+    let (inputs, positions) = inputs(
+        texts.iter().flat_map(|(_, texts)| texts.iter().cloned()),
+        dedupe,
+    );
+    let batches = inputs
+        .into_iter()
+        .enumerate()
+        .collect::<Vec<_>>()
+        .chunks(max_documents)
+        .map(<[_]>::to_vec)
+        .collect::<VecDeque<_>>();
+
+    let embeddings = match adaptive_concurrency {
+        Some(concurrency) => concurrency.embed(model, batches).await?,
+        None => {
+            stream::iter(batches)
+                // Generate the embeddings for each batch.
+                .map(|batch| async {
+                    let (positions, docs): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+
+                    let embeddings = model
+                        .embed_texts_with_kind(docs, EmbedKind::Document)
+                        .await?;
+                    Ok::<_, EmbeddingError>(
+                        positions.into_iter().zip(embeddings).collect::<Vec<_>>(),
+                    )
                 })
-                .collect())
+                // Parallelize the embeddings generation over 10 concurrent requests
+                .buffer_unordered(max(1, 1024 / max_documents))
+                .try_concat()
+                .await?
         }
+    };
+
+    let mut embeddings = embeddings
+        .into_iter()
+        .flat_map(|(input, embedding)| fan_out(embedding, &positions[input]))
+        .collect::<Vec<_>>();
+    embeddings.sort_by_key(|(position, _)| *position);
+
+    // Collect the embeddings into a HashMap.
+    Ok(embeddings.into_iter().fold(
+        HashMap::new(),
+        |mut acc: HashMap<_, OneOrMany<Embedding>>, (position, embedding)| {
+            let i = documents[position];
+            match acc.get_mut(&i) {
+                Some(embeddings) => embeddings.push(embedding),
+                None => {
+                    acc.insert(i, OneOrMany::one(embedding));
+                }
+            }
+            acc
+        },
+    ))
+}
+
+/// A stream of documents with their embeddings, returned by [EmbeddingsBuilder::build_stream].
+pub struct EmbeddingsStream<'a, T> {
+    #[allow(clippy::type_complexity)]
+    inner:
+        Pin<Box<dyn Stream<Item = Result<(T, OneOrMany<Embedding>), EmbeddingError>> + Send + 'a>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl<T> EmbeddingsStream<'_, T> {
+    /// Stop sending new embedding requests. The documents of the requests already in flight are
+    /// still yielded before the stream ends.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+impl<T> Stream for EmbeddingsStream<'_, T> {
+    type Item = Result<(T, OneOrMany<Embedding>), EmbeddingError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.as_mut().poll_next(cx)
+    }
+}
+
+/// Stream the embeddings of the documents, yielding each document once all of its texts are
+/// embedded, and sending new requests only while `cancelled` is not set.
+#[allow(clippy::too_many_arguments)]
+fn embed_documents_stream<'a, M: EmbeddingModel + 'a, T: Send + 'a>(
+    model: M,
+    documents: Vec<(T, Vec<String>)>,
+    empty_documents: EmptyDocuments,
+    quantization: Option<Quantization>,
+    token_counter: Option<TokenCounter>,
+    dedupe: bool,
+    mut concurrency: ConcurrencyLimit,
+    cancelled: Arc<AtomicBool>,
+) -> impl Stream<Item = Result<(T, OneOrMany<Embedding>), EmbeddingError>> + Send + 'a {
+    async_stream::stream! {
+        let (docs, texts) = split_documents(documents);
+        if let Err(err) = check_input_tokens(&model, &texts, token_counter.as_ref()) {
+            yield Err(err);
+            return;
+        }
+        let mut docs = docs.into_iter().map(|(_, doc)| Some(doc)).collect::<Vec<_>>();
+        // Number of texts of each document that are not embedded yet.
+        let mut remaining = texts.iter().map(|(_, texts)| texts.len()).collect::<Vec<_>>();
+        let mut embeddings: HashMap<usize, Vec<(usize, Embedding)>> = HashMap::new();
+
+        // Documents without content to embed are handled upfront.
+        for (i, _) in texts.iter().filter(|(_, texts)| texts.is_empty()) {
+            if let Some(embeddings) = empty_documents.embeddings(*i, EmbeddingModel::ndims(&model))
+                && let Some(doc) = docs[*i].take()
+            {
+                yield Ok((doc, embeddings));
+            }
+        }
+
+        // Batches of texts, identified by their position (see [embed_documents]), with the
+        // number of times they were retried.
+        let documents = texts
+            .iter()
+            .flat_map(|(i, texts)| texts.iter().map(move |_| *i))
+            .collect::<Vec<_>>();
+        let (inputs, positions) = inputs(texts.into_iter().flat_map(|(_, texts)| texts), dedupe);
+        let mut batches = inputs
+            .into_iter()
+            .enumerate()
+            .collect::<Vec<_>>()
+            .chunks(batch_size(&model))
+            .map(|batch| (batch.to_vec(), 0))
+            .collect::<VecDeque<_>>();
+        let mut in_flight = FuturesUnordered::new();
+
+        loop {
+            while in_flight.len() < concurrency.limit() && !cancelled.load(Ordering::SeqCst) {
+                let Some((batch, retries)) = batches.pop_front() else {
+                    break;
+                };
+                in_flight.push(AdaptiveConcurrency::embed_batch(&model, batch, retries));
+            }
+
+            let Some((batch, retries, result)) = in_flight.next().await else {
+                break;
+            };
+
+            let batch_embeddings = match result {
+                Ok(batch_embeddings) => {
+                    concurrency.succeeded();
+                    batch_embeddings
+                }
+                Err(err) if concurrency.retry(&err, retries) => {
+                    batches.push_front((batch, retries + 1));
+                    continue;
+                }
+                Err(err) => {
+                    yield Err(err);
+                    break;
+                }
+            };
+
+            let batch_embeddings = batch
+                .into_iter()
+                .zip(batch_embeddings)
+                .flat_map(|((input, _), embedding)| fan_out(embedding, &positions[input]));
+            for (position, mut embedding) in batch_embeddings {
+                let i = documents[position];
+                if let Some(quantization) = quantization {
+                    embedding.quantize(quantization);
+                }
+                embeddings.entry(i).or_default().push((position, embedding));
+                remaining[i] -= 1;
+
+                if remaining[i] == 0
+                    && let (Some(doc), Some(mut doc_embeddings)) =
+                        (docs[i].take(), embeddings.remove(&i))
+                {
+                    doc_embeddings.sort_by_key(|(position, _)| *position);
+                    let doc_embeddings = OneOrMany::many(
+                        doc_embeddings.into_iter().map(|(_, embedding)| embedding),
+                    )
+                    .expect("Documents with embeddings have at least one embedding");
+                    yield Ok((doc, doc_embeddings));
+                }
+            }
+        }
+    }
+}
+
+/// Stream the embeddings of the documents of `documents`, pulling documents only when their
+/// texts are needed to fill the next batch, and sending new requests only while `cancelled` is
+/// not set. Each document is yielded once all of its texts are embedded.
+fn embed_lazy_stream<'a, M: EmbeddingModel + 'a, T: Send + 'a>(
+    model: M,
+    documents: impl Stream<Item = Result<(T, Vec<String>), EmbedError>> + Send + 'a,
+    empty_documents: EmptyDocuments,
+    quantization: Option<Quantization>,
+    token_counter: Option<TokenCounter>,
+    mut concurrency: ConcurrencyLimit,
+    cancelled: Arc<AtomicBool>,
+) -> impl Stream<Item = Result<(T, OneOrMany<Embedding>), EmbeddingError>> + Send + 'a {
+    async_stream::stream! {
+        let mut documents = Box::pin(documents);
+        let mut exhausted = false;
+        let batch_size = batch_size(&model);
+
+        // The documents being embedded, by index, with the number of their texts that are not
+        // embedded yet and the embeddings of the others (by position, see [embed_documents]).
+        let mut docs = HashMap::new();
+        let mut remaining: HashMap<usize, usize> = HashMap::new();
+        let mut embeddings: HashMap<usize, Vec<(usize, Embedding)>> = HashMap::new();
+        // The texts which are not sent yet, the rate limited batches to send again (with the
+        // number of times they were retried), and the document of each text being embedded.
+        let mut pending = VecDeque::new();
+        let mut retried = VecDeque::new();
+        let mut text_documents = HashMap::new();
+        let (mut next_document, mut next_position) = (0, 0);
+        let mut in_flight = FuturesUnordered::new();
+
+        loop {
+            while in_flight.len() < concurrency.limit() && !cancelled.load(Ordering::SeqCst) {
+                if let Some((batch, retries)) = retried.pop_front() {
+                    in_flight.push(AdaptiveConcurrency::embed_batch(&model, batch, retries));
+                } else if pending.len() < batch_size && !exhausted {
+                    let (doc, texts) = match documents.next().await {
+                        Some(Ok(document)) => document,
+                        Some(Err(err)) => {
+                            yield Err(EmbeddingError::DocumentError(Box::new(err)));
+                            return;
+                        }
+                        None => {
+                            exhausted = true;
+                            continue;
+                        }
+                    };
+                    let i = next_document;
+                    next_document += 1;
+                    let texts = vec![(i, texts)];
+                    if let Err(err) = check_input_tokens(&model, &texts, token_counter.as_ref()) {
+                        yield Err(err);
+                        return;
+                    }
+                    let texts = texts.into_iter().flat_map(|(_, texts)| texts).collect::<Vec<_>>();
+
+                    // Documents without content to embed are handled right away.
+                    if texts.is_empty() {
+                        if let Some(embeddings) = empty_documents.embeddings(i, EmbeddingModel::ndims(&model)) {
+                            yield Ok((doc, embeddings));
+                        }
+                        continue;
+                    }
+
+                    docs.insert(i, doc);
+                    remaining.insert(i, texts.len());
+                    for text in texts {
+                        text_documents.insert(next_position, i);
+                        pending.push_back((next_position, text));
+                        next_position += 1;
+                    }
+                } else if !pending.is_empty() {
+                    let batch = pending.drain(..batch_size.min(pending.len())).collect();
+                    in_flight.push(AdaptiveConcurrency::embed_batch(&model, batch, 0));
+                } else {
+                    break;
+                }
+            }
+
+            let Some((batch, retries, result)) = in_flight.next().await else {
+                break;
+            };
+
+            let batch_embeddings = match result {
+                Ok(batch_embeddings) => {
+                    concurrency.succeeded();
+                    batch_embeddings
+                }
+                Err(err) if concurrency.retry(&err, retries) => {
+                    retried.push_back((batch, retries + 1));
+                    continue;
+                }
+                Err(err) => {
+                    yield Err(err);
+                    break;
+                }
+            };
+
+            for ((position, _), mut embedding) in batch.into_iter().zip(batch_embeddings) {
+                let Some(i) = text_documents.remove(&position) else {
+                    continue;
+                };
+                if let Some(quantization) = quantization {
+                    embedding.quantize(quantization);
+                }
+                embeddings.entry(i).or_default().push((position, embedding));
+                let Some(count) = remaining.get_mut(&i) else {
+                    continue;
+                };
+                *count -= 1;
+
+                if *count == 0
+                    && let (Some(doc), Some(mut doc_embeddings)) =
+                        (docs.remove(&i), embeddings.remove(&i))
+                {
+                    remaining.remove(&i);
+                    doc_embeddings.sort_by_key(|(position, _)| *position);
+                    let doc_embeddings = OneOrMany::many(
+                        doc_embeddings.into_iter().map(|(_, embedding)| embedding),
+                    )
+                    .expect("Documents with embeddings have at least one embedding");
+                    yield Ok((doc, doc_embeddings));
+                }
+            }
+        }
+    }
+}
+
+/// Maximum number of times a rate limited batch is retried before the error is returned.
+const MAX_RATE_LIMIT_RETRIES: usize = 5;
+
+/// The delay before the `retries`-th retry (starting at 1) of a rate limited batch: the
+/// exponential backoff of the default [RetryPolicy], shortened by a random jitter of up to half
+/// of it, so that the batches rate limited together are not all sent again at once.
+fn rate_limit_backoff(retries: usize) -> Duration {
+    let backoff = RetryPolicy::default().backoff(retries.saturating_sub(1));
+    backoff.mul_f64(1.0 - jitter() / 2.0)
+}
+
+/// A pseudo-random number in `[0, 1)`, drawn from the random keys of [RandomState].
+fn jitter() -> f64 {
+    let random = RandomState::new().hash_one(0u8);
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Additive increase / multiplicative decrease (AIMD) control of the number of concurrent
+/// embedding requests (see [EmbeddingsBuilder::adaptive_concurrency]).
+#[derive(Debug, Clone, Copy)]
+struct AdaptiveConcurrency {
+    min: usize,
+    max: usize,
+}
+
+impl AdaptiveConcurrency {
+    async fn embed<E: EmbeddingModelDyn + ?Sized>(
+        self,
+        model: &E,
+        batches: VecDeque<Vec<(usize, String)>>,
+    ) -> Result<Vec<(usize, Embedding)>, EmbeddingError> {
+        // Pending batches, with the number of times they were retried.
+        let mut batches = batches
+            .into_iter()
+            .map(|batch| (batch, 0))
+            .collect::<VecDeque<_>>();
+        let mut concurrency = ConcurrencyLimit::new(Some(self), self.min);
+        let mut in_flight = FuturesUnordered::new();
+        let mut embeddings = Vec::new();
+
+        loop {
+            while in_flight.len() < concurrency.limit() {
+                let Some((batch, retries)) = batches.pop_front() else {
+                    break;
+                };
+                in_flight.push(Self::embed_batch(model, batch, retries));
+            }
+
+            let Some((batch, retries, result)) = in_flight.next().await else {
+                break;
+            };
+
+            match result {
+                Ok(batch_embeddings) => {
+                    embeddings.extend(batch.into_iter().map(|(i, _)| i).zip(batch_embeddings));
+                    concurrency.succeeded();
+                }
+                Err(err) if concurrency.retry(&err, retries) => {
+                    batches.push_front((batch, retries + 1));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(embeddings)
+    }
+
+    async fn embed_batch<E: EmbeddingModelDyn + ?Sized>(
+        model: &E,
+        batch: Vec<(usize, String)>,
+        retries: usize,
+    ) -> (
+        Vec<(usize, String)>,
+        usize,
+        Result<Vec<Embedding>, EmbeddingError>,
+    ) {
+        if retries > 0 {
+            tokio::time::sleep(rate_limit_backoff(retries)).await;
+        }
+
+        let texts = batch
+            .iter()
+            .map(|(_, text)| text.clone())
+            .collect::<Vec<_>>();
+        let result = model
+            .embed_texts_with_kind(texts, EmbedKind::Document)
+            .await;
+        (batch, retries, result)
+    }
+}
+
+/// The current limit of concurrent embedding requests: fixed, or controlled by an
+/// [AdaptiveConcurrency].
+#[derive(Debug, Clone, Copy)]
+struct ConcurrencyLimit {
+    adaptive: Option<AdaptiveConcurrency>,
+    limit: usize,
+    /// Number of successful requests since the limit last changed.
+    successes: usize,
+}
+
+impl ConcurrencyLimit {
+    /// Start at the `min` of `adaptive` if set, or at the fixed `limit` otherwise.
+    fn new(adaptive: Option<AdaptiveConcurrency>, limit: usize) -> Self {
+        Self {
+            adaptive,
+            limit: adaptive.map_or(limit, |adaptive| adaptive.min),
+            successes: 0,
+        }
+    }
+
+    fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Additive increase: one more concurrent request per window of successful requests.
+    fn succeeded(&mut self) {
+        let Some(adaptive) = self.adaptive else {
+            return;
+        };
+        self.successes += 1;
+        if self.successes >= self.limit {
+            self.limit = (self.limit + 1).min(adaptive.max);
+            self.successes = 0;
+        }
+    }
+
+    /// Whether a batch that failed with `err` after `retries` retries should be retried, with
+    /// a multiplicative decrease of the limit. Only rate limited batches are retried, and only
+    /// when the concurrency is adaptive.
+    fn retry(&mut self, err: &EmbeddingError, retries: usize) -> bool {
+        let Some(adaptive) = self.adaptive else {
+            return false;
+        };
+        if !err.is_rate_limited() || retries >= MAX_RATE_LIMIT_RETRIES {
+            return false;
+        }
+        self.limit = (self.limit / 2).max(adaptive.min);
+        self.successes = 0;
+        tracing::warn!(
+            "Embedding request rate limited, reducing concurrency to {}: {err}",
+            self.limit
+        );
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+
+    use crate::{
+        Embed,
+        embeddings::{
+            Embedding, EmbeddingError, Quantization, QuantizedVector, embed::EmbedError,
+            embed::TextEmbedder,
+        },
+        test_utils::MockEmbeddingModel,
+    };
+
+    use super::{EmbeddingsBuilder, EmptyDocuments, rate_limit_backoff};
+
+    fn model() -> MockEmbeddingModel {
+        MockEmbeddingModel::new(vec![0.0, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9])
     }
 
     #[derive(Clone, Debug)]
@@ -251,7 +1143,7 @@ mod tests {
     async fn test_build_multiple_text() {
         let fake_definitions = definitions_multiple_text();
 
-        let fake_model = Model;
+        let fake_model = model();
         let mut result = EmbeddingsBuilder::new(fake_model)
             .documents(fake_definitions)
             .unwrap()
@@ -285,7 +1177,7 @@ mod tests {
     async fn test_build_single_text() {
         let fake_definitions = definitions_single_text();
 
-        let fake_model = Model;
+        let fake_model = model();
         let mut result = EmbeddingsBuilder::new(fake_model)
             .documents(fake_definitions)
             .unwrap()
@@ -320,7 +1212,7 @@ mod tests {
         let fake_definitions = definitions_multiple_text();
         let fake_definitions_single = definitions_multiple_text_2();
 
-        let fake_model = Model;
+        let fake_model = model();
         let mut result = EmbeddingsBuilder::new(fake_model)
             .documents(fake_definitions)
             .unwrap()
@@ -357,7 +1249,7 @@ mod tests {
         let bindings = definitions_multiple_text();
         let fake_definitions = bindings.iter().map(|def| def.definitions.clone());
 
-        let fake_model = Model;
+        let fake_model = model();
         let mut result = EmbeddingsBuilder::new(fake_model)
             .documents(fake_definitions)
             .unwrap()
@@ -384,4 +1276,494 @@ mod tests {
             second_definition.1.rest()[0].document, "A fictional creature found in the distant, swampy marshlands of the planet Glibbo in the Andromeda galaxy.".to_string()
         )
     }
+
+    fn definitions_with_empty_text() -> Vec<WordDefinition> {
+        vec![
+            WordDefinition {
+                id: "doc0".to_string(),
+                definitions: vec![
+                    "A green alien that lives on cold planets.".to_string(),
+                    "   ".to_string(),
+                ],
+            },
+            WordDefinition {
+                id: "doc1".to_string(),
+                definitions: vec!["".to_string()],
+            },
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_build_skip_empty_documents() {
+        let model = model();
+        let result = EmbeddingsBuilder::new(model.clone())
+            .documents(definitions_with_empty_text())
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+
+        assert_eq!(result.len(), 1);
+        // The empty texts are never sent to the model
+        assert_eq!(
+            model.requests(),
+            vec![vec![
+                "A green alien that lives on cold planets.".to_string()
+            ]]
+        );
+
+        let (definition, embeddings) = &result[0];
+        assert_eq!(definition.id, "doc0");
+        assert_eq!(embeddings.len(), 1);
+        assert_eq!(
+            embeddings.first().document,
+            "A green alien that lives on cold planets.".to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_build_quantized() {
+        let build = |quantization| {
+            EmbeddingsBuilder::new(model())
+                .quantize(quantization)
+                .documents(definitions_single_text())
+                .unwrap()
+                .build()
+        };
+
+        let result = build(Quantization::Int8 { scale: 10.0 }).await.unwrap();
+        assert_eq!(
+            result[0].1.first().vec,
+            vec![0.0, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9]
+        );
+        let result = build(Quantization::Int8 { scale: 2.0 }).await.unwrap();
+        assert_eq!(
+            result[0].1.first().vec,
+            vec![0.0, 0.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.5, 1.0, 1.0]
+        );
+        let result = build(Quantization::Float32).await.unwrap();
+        assert_eq!(result[0].1.first().vec[1], f64::from(0.1f32));
+
+        // Values out of range are clamped
+        assert_eq!(
+            Quantization::Int8 { scale: 127.0 }.quantize(&[-2.0, -1.0, 0.5, 2.0]),
+            QuantizedVector::Int8(vec![-128, -127, 64, 127])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_build_zero_embedding_empty_documents() {
+        let mut result = EmbeddingsBuilder::new(model())
+            .empty_documents(EmptyDocuments::ZeroEmbedding)
+            .documents(definitions_with_empty_text())
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+
+        result.sort_by(|(fake_definition_1, _), (fake_definition_2, _)| {
+            fake_definition_1.id.cmp(&fake_definition_2.id)
+        });
+
+        assert_eq!(result.len(), 2);
+
+        let (definition, embeddings) = &result[1];
+        assert_eq!(definition.id, "doc1");
+        assert_eq!(embeddings.len(), 1);
+        assert_eq!(embeddings.first().vec, vec![0.0; 10]);
+    }
+
+    #[tokio::test]
+    async fn test_build_per_model() {
+        let mut result = EmbeddingsBuilder::new(model())
+            .model_name("model")
+            .additional_model("other_model", MockEmbeddingModel::new(vec![1.0]))
+            .documents(definitions_multiple_text())
+            .unwrap()
+            .build_per_model()
+            .await
+            .unwrap();
+
+        result.sort_by(|(fake_definition_1, _), (fake_definition_2, _)| {
+            fake_definition_1.id.cmp(&fake_definition_2.id)
+        });
+
+        assert_eq!(result.len(), 2);
+
+        let (definition, embeddings) = &result[1];
+        assert_eq!(definition.id, "doc1");
+        assert_eq!(embeddings.len(), 2);
+        assert_eq!(embeddings["model"].len(), 2);
+        assert_eq!(embeddings["model"].first().vec.len(), 10);
+        assert_eq!(embeddings["other_model"].len(), 2);
+        assert_eq!(embeddings["other_model"].first().vec, vec![1.0]);
+    }
+
+    /// A model embedding texts as the number in their text, whose requests complete in the
+    /// reverse order of that number.
+    fn reverse_order_model() -> MockEmbeddingModel {
+        fn number(text: &str) -> u64 {
+            text.rsplit(' ').next().unwrap().parse().unwrap()
+        }
+
+        MockEmbeddingModel::from_fn(1, |text| vec![number(text) as f64])
+            .with_max_batch_size(1)
+            .with_latency(|texts| Duration::from_millis(5 * (20 - number(&texts[0]))))
+    }
+
+    fn numbered_definitions() -> Vec<WordDefinition> {
+        (0..5)
+            .map(|i| WordDefinition {
+                id: format!("doc{i}"),
+                definitions: (0..2).map(|j| format!("text {}", i * 2 + j)).collect(),
+            })
+            .collect()
+    }
+
+    fn assert_in_order(result: &[(WordDefinition, crate::OneOrMany<Embedding>)]) {
+        assert_eq!(
+            result
+                .iter()
+                .map(|(doc, embeddings)| (
+                    doc.id.as_str(),
+                    embeddings.iter().map(|e| e.vec[0]).collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>(),
+            (0..5)
+                .map(|i| (
+                    ["doc0", "doc1", "doc2", "doc3", "doc4"][i],
+                    vec![(i * 2) as f64, (i * 2 + 1) as f64]
+                ))
+                .collect::<Vec<_>>()
+        );
+        for (doc, embeddings) in result {
+            assert_eq!(
+                embeddings
+                    .iter()
+                    .map(|e| e.document.clone())
+                    .collect::<Vec<_>>(),
+                doc.definitions
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_build_preserves_order() {
+        // The embedding requests complete in the reverse order of the texts
+        let result = EmbeddingsBuilder::new(reverse_order_model())
+            .documents(numbered_definitions())
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        assert_in_order(&result);
+
+        let result = EmbeddingsBuilder::new(reverse_order_model())
+            .adaptive_concurrency(10, 10)
+            .documents(numbered_definitions())
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        assert_in_order(&result);
+
+        let result = EmbeddingsBuilder::new(reverse_order_model())
+            .model_name("model")
+            .documents(numbered_definitions())
+            .unwrap()
+            .build_per_model()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(doc, mut embeddings)| (doc, embeddings.remove("model").unwrap()))
+            .collect::<Vec<_>>();
+        assert_in_order(&result);
+    }
+
+    /// A model rate limiting requests when more than `capacity` of them are in flight.
+    fn rate_limited_model(capacity: usize) -> MockEmbeddingModel {
+        MockEmbeddingModel::new(vec![1.0])
+            .with_max_batch_size(1)
+            .with_rate_limit(capacity)
+    }
+
+    #[test]
+    fn test_rate_limit_backoff() {
+        // The backoff doubles after each retry, shortened by up to half
+        for (retries, backoff) in [(1, 500), (2, 1000), (3, 2000)] {
+            let backoff = Duration::from_millis(backoff);
+            let delays = (0..20)
+                .map(|_| rate_limit_backoff(retries))
+                .collect::<Vec<_>>();
+            assert!(
+                delays
+                    .iter()
+                    .all(|delay| *delay > backoff / 2 && *delay <= backoff)
+            );
+            // ...by a random jitter
+            assert!(delays.iter().any(|delay| *delay != delays[0]));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_build_adaptive_concurrency() {
+        let model = rate_limited_model(2);
+        let documents = (0..20).map(|i| format!("document {i}")).collect::<Vec<_>>();
+
+        let result = EmbeddingsBuilder::new(model.clone())
+            .adaptive_concurrency(1, 8)
+            .documents(documents)
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+
+        // Every document is embedded despite the rate limiting
+        assert_eq!(result.len(), 20);
+        assert!(result.iter().all(|(_, embeddings)| embeddings.len() == 1));
+        // The concurrency ramped up past the capacity and backed off
+        assert!(model.rate_limited() > 0);
+        assert!(model.peak_in_flight() <= 8);
+    }
+
+    #[tokio::test]
+    async fn test_build_streams_adaptive_concurrency() {
+        use futures::{StreamExt, TryStreamExt, stream};
+
+        let documents = (0..20).map(|i| format!("document {i}")).collect::<Vec<_>>();
+
+        let model = rate_limited_model(2);
+        let result = EmbeddingsBuilder::new(model.clone())
+            .adaptive_concurrency(1, 8)
+            .documents(documents.clone())
+            .unwrap()
+            .build_stream()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 20);
+        assert!(model.rate_limited() > 0);
+
+        let model = rate_limited_model(2);
+        let result = EmbeddingsBuilder::new(model.clone())
+            .adaptive_concurrency(1, 8)
+            .build_from_stream(stream::iter(documents))
+            .map(|result| result.map(|(document, _)| document))
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 20);
+        assert!(model.rate_limited() > 0);
+    }
+
+    /// A model embedding texts as their length, two texts per request.
+    fn counting_model() -> MockEmbeddingModel {
+        MockEmbeddingModel::from_fn(1, |text| vec![text.len() as f64]).with_max_batch_size(2)
+    }
+
+    #[tokio::test]
+    async fn test_build_stream() {
+        use futures::StreamExt;
+
+        let documents = (0..9)
+            .map(|i| match i {
+                // Documents with several texts, whose embeddings may span multiple batches
+                0 | 4 => vec![format!("document {i}"), "a".repeat(i + 1)],
+                _ => vec![format!("document {i}")],
+            })
+            .chain([vec![]])
+            .collect::<Vec<_>>();
+        let builder = || {
+            EmbeddingsBuilder::new(counting_model())
+                .empty_documents(EmptyDocuments::ZeroEmbedding)
+                .documents(documents.clone())
+                .unwrap()
+        };
+
+        let mut streamed = builder()
+            .build_stream()
+            .map(|result| result.unwrap())
+            .collect::<Vec<_>>()
+            .await;
+        streamed.sort_by_key(|(texts, _)| documents.iter().position(|doc| doc == texts));
+
+        assert_eq!(streamed, builder().build().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_build_from_stream() {
+        use futures::{StreamExt, stream};
+
+        let documents = (0..20)
+            .map(|i| match i {
+                0 | 7 => vec![format!("document {i}"), "a".repeat(i + 1)],
+                13 => vec![],
+                _ => vec![format!("document {i}")],
+            })
+            .collect::<Vec<_>>();
+        let builder = || {
+            EmbeddingsBuilder::new(counting_model())
+                .empty_documents(EmptyDocuments::ZeroEmbedding)
+                .adaptive_concurrency(1, 1)
+        };
+
+        // The documents are pulled as the embeddings are requested, not upfront
+        let pulled = Arc::new(AtomicUsize::new(0));
+        let mut stream = builder().build_from_stream(stream::iter(documents.clone()).inspect({
+            let pulled = pulled.clone();
+            move |_| {
+                pulled.fetch_add(1, Ordering::SeqCst);
+            }
+        }));
+        let first = stream.next().await.unwrap().unwrap();
+        assert!(pulled.load(Ordering::SeqCst) < documents.len());
+
+        let mut streamed = vec![first];
+        streamed.extend(
+            stream
+                .map(|result| result.unwrap())
+                .collect::<Vec<_>>()
+                .await,
+        );
+        streamed.sort_by_key(|(texts, _)| documents.iter().position(|doc| doc == texts));
+
+        let built = builder()
+            .documents(documents.clone())
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(streamed, built);
+    }
+
+    #[tokio::test]
+    async fn test_build_dedupe_inputs() {
+        use futures::StreamExt;
+
+        // 6 texts, of which 2 are distinct
+        let documents = vec![
+            vec!["header".to_string(), "body 1".to_string()],
+            vec!["header".to_string(), "body 1".to_string()],
+            vec!["header".to_string()],
+            vec!["body 1".to_string()],
+        ];
+        let builder = |model: MockEmbeddingModel, dedupe_inputs| {
+            EmbeddingsBuilder::new(model)
+                .dedupe_inputs(dedupe_inputs)
+                .documents(documents.clone())
+                .unwrap()
+        };
+
+        let model = counting_model();
+        let result = builder(model.clone(), false).build().await.unwrap();
+        assert_eq!(model.requests().len(), 3);
+
+        // Each distinct text is embedded once, and the result is the same
+        let model = counting_model();
+        let deduped = builder(model.clone(), true).build().await.unwrap();
+        assert_eq!(model.requests().len(), 1);
+        assert_eq!(deduped, result);
+
+        let model = counting_model();
+        let streamed = builder(model.clone(), true)
+            .build_stream()
+            .map(|result| result.unwrap())
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(model.requests().len(), 1);
+        assert_eq!(streamed.len(), 4);
+        assert!(
+            streamed
+                .iter()
+                .all(|(texts, embeddings)| result.contains(&(texts.clone(), embeddings.clone())))
+        );
+
+        let model = counting_model();
+        let per_model = builder(model.clone(), true)
+            .build_per_model()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(doc, mut embeddings)| (doc, embeddings.remove("default").unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(model.requests().len(), 1);
+        assert_eq!(per_model, result);
+    }
+
+    #[tokio::test]
+    async fn test_build_stream_cancel() {
+        use futures::StreamExt;
+
+        let model = counting_model();
+        let documents = (0..100)
+            .map(|i| format!("document {i}"))
+            .collect::<Vec<_>>();
+
+        let mut stream = EmbeddingsBuilder::new(model.clone())
+            .adaptive_concurrency(1, 2)
+            .documents(documents)
+            .unwrap()
+            .build_stream();
+
+        stream.next().await.unwrap().unwrap();
+        stream.cancel();
+        let yielded = 1 + stream.count().await;
+
+        // Only the requests in flight when the stream was cancelled are completed
+        let requests = model.requests().len();
+        assert!(requests <= 3, "{requests} requests were sent");
+        assert_eq!(yielded, requests * 2);
+    }
+
+    /// A model embedding a single text per request, with a limit of 3 tokens per text.
+    fn limited_model() -> MockEmbeddingModel {
+        counting_model()
+            .with_max_batch_size(1)
+            .with_max_input_tokens(3)
+    }
+
+    #[tokio::test]
+    async fn test_build_model_limits() {
+        use futures::StreamExt;
+
+        let count_words = |text: &str| text.split_whitespace().count();
+
+        // Texts are batched according to the model's batch size
+        let model = limited_model();
+        let result = EmbeddingsBuilder::new(model.clone())
+            .token_counter(count_words)
+            .documents(["first document", "second document", "third document"])
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 3);
+        assert_eq!(model.requests().len(), 3);
+
+        // Texts over the model's token limit fail before any request is sent
+        let model = limited_model();
+        let builder = || {
+            EmbeddingsBuilder::new(model.clone())
+                .token_counter(count_words)
+                .documents(["a short document", "a document that is too long"])
+                .unwrap()
+        };
+        assert!(matches!(
+            builder().build().await,
+            Err(EmbeddingError::InvalidInput(message)) if message.contains("document 1 is 6 tokens")
+        ));
+        assert!(matches!(
+            builder().build_stream().next().await,
+            Some(Err(EmbeddingError::InvalidInput(_)))
+        ));
+        assert_eq!(model.requests().len(), 0);
+
+        // Without a token counter, texts are sent as is
+        let result = EmbeddingsBuilder::new(model.clone())
+            .documents(["a document that is too long"])
+            .unwrap()
+            .build()
+            .await;
+        assert!(result.is_ok());
+    }
 }
@@ -60,6 +60,12 @@ impl EmbedError {
 ///
 /// assert_eq!(embeddings::to_texts(fake_definition).unwrap(), vec!["a fruit", " a tech company"]);
 /// ```
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` does not implement `Embed`",
+    label = "`#[embed]` requires the field type to implement `Embed`",
+    note = "`Embed` is implemented for strings, numbers, booleans, chars, `serde_json::Value` and `Vec`s of them",
+    note = "derive or implement `Embed` for `{Self}`, or use `#[embed(embed_with = \"...\")]` to embed the field with a custom function"
+)]
 pub trait Embed {
     fn embed(&self, embedder: &mut TextEmbedder) -> Result<(), EmbedError>;
 }
@@ -33,6 +33,67 @@ pub enum EmbeddingError {
     /// Error returned by the embedding model provider
     #[error("ProviderError: {0}")]
     ProviderError(String),
+
+    /// Error response of the embedding model provider, with its HTTP status
+    #[error("ProviderError: {status}: {message}")]
+    ProviderStatusError {
+        status: reqwest::StatusCode,
+        message: String,
+    },
+
+    /// The input is empty or too long to be embedded (see [EmbeddingInput])
+    #[error("InvalidInput: {0}")]
+    InvalidInput(String),
+}
+
+impl EmbeddingError {
+    /// The error of a provider `response` with an unsuccessful status, with the body of the
+    /// response as message.
+    pub(crate) async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status();
+        match response.text().await {
+            Ok(message) => EmbeddingError::ProviderStatusError { status, message },
+            Err(err) => err.into(),
+        }
+    }
+
+    /// Whether the error was caused by the provider rate limiting requests (i.e.: HTTP 429).
+    ///
+    /// Error responses are classified on their HTTP status. Provider errors without a status
+    /// (e.g.: errors in the body of a successful response) are matched on the type of error in
+    /// their message (e.g.: "rate_limit_exceeded").
+    pub fn is_rate_limited(&self) -> bool {
+        match self {
+            EmbeddingError::HttpError(err) => {
+                err.status() == Some(reqwest::StatusCode::TOO_MANY_REQUESTS)
+            }
+            EmbeddingError::ProviderStatusError { status, .. } => {
+                *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            EmbeddingError::ProviderError(message) => {
+                let message = message.to_lowercase();
+                ["rate limit", "rate_limit", "too many requests"]
+                    .iter()
+                    .any(|pattern| message.contains(pattern))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Whether texts are embedded as search queries or as documents to be retrieved.
+///
+/// Retrieval-optimized embedding models (e.g.: Voyage AI, Jina AI, Cohere) produce asymmetric
+/// embeddings: queries and documents are embedded differently, and mixing the two degrades the
+/// retrieval quality. [EmbeddingsBuilder](crate::embeddings::EmbeddingsBuilder) embeds documents
+/// as [EmbedKind::Document] and vector store indexes embed queries as [EmbedKind::Query].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmbedKind {
+    /// Search query
+    Query,
+    /// Document to be retrieved (default)
+    #[default]
+    Document,
 }
 
 /// Trait for embedding models that can generate embeddings for documents.
@@ -43,6 +104,19 @@ pub trait EmbeddingModel: Clone + Sync + Send {
     /// The number of dimensions in the embedding vector.
     fn ndims(&self) -> usize;
 
+    /// The maximum number of tokens of a single input text, if known for the model. Longer
+    /// inputs are rejected (or truncated) by most providers.
+    fn max_input_tokens(&self) -> Option<usize> {
+        None
+    }
+
+    /// The maximum number of input texts embedded in a single request, if known for the model
+    /// (defaults to [EmbeddingModel::MAX_DOCUMENTS]). Texts are batched accordingly by the
+    /// [EmbeddingsBuilder](crate::embeddings::EmbeddingsBuilder).
+    fn max_batch_size(&self) -> Option<usize> {
+        Some(Self::MAX_DOCUMENTS)
+    }
+
     /// Embed multiple text documents in a single request
     fn embed_texts(
         &self,
@@ -62,21 +136,199 @@ pub trait EmbeddingModel: Clone + Sync + Send {
                 .expect("There should be at least one embedding"))
         }
     }
+
+    /// Embed multiple texts of the given kind in a single request (see [EmbedKind]).
+    ///
+    /// Models that do not distinguish search queries from documents embed them the same way
+    /// (i.e.: the default implementation ignores `kind`).
+    fn embed_texts_with_kind(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+        kind: EmbedKind,
+    ) -> impl std::future::Future<Output = Result<Vec<Embedding>, EmbeddingError>> + Send {
+        let _ = kind;
+        self.embed_texts(texts)
+    }
+
+    /// Embed multiple search queries (see [EmbedKind::Query]), in as few requests as possible
+    /// (i.e.: in batches of [EmbeddingModel::max_batch_size] queries).
+    fn embed_queries(
+        &self,
+        queries: Vec<String>,
+    ) -> impl std::future::Future<Output = Result<Vec<Embedding>, EmbeddingError>> + Send {
+        async move {
+            let mut embeddings = Vec::with_capacity(queries.len());
+            for batch in queries.chunks(batch_size(self)) {
+                embeddings.extend(
+                    self.embed_texts_with_kind(batch.to_vec(), EmbedKind::Query)
+                        .await?,
+                );
+            }
+            Ok(embeddings)
+        }
+    }
+
+    /// Embed a single search query (see [EmbedKind::Query]).
+    fn embed_query(
+        &self,
+        query: &str,
+    ) -> impl std::future::Future<Output = Result<Embedding, EmbeddingError>> + Send {
+        async {
+            Ok(self
+                .embed_texts_with_kind(vec![query.to_string()], EmbedKind::Query)
+                .await?
+                .pop()
+                .expect("There should be at least one embedding"))
+        }
+    }
+
+    /// Embed a single validated input (see [EmbeddingInput]), e.g.:
+    /// `model.embed_input("Hello, world!")`.
+    ///
+    /// Invalid inputs (e.g.: empty texts) fail with [EmbeddingError::InvalidInput] without
+    /// sending a request to the provider.
+    fn embed_input(
+        &self,
+        input: impl TryInto<EmbeddingInput, Error = EmbeddingError> + Send,
+    ) -> impl std::future::Future<Output = Result<Embedding, EmbeddingError>> + Send {
+        async {
+            let input = input.try_into()?;
+            self.embed_text(input.as_str()).await
+        }
+    }
+
+    /// Embed multiple validated inputs (see [EmbeddingInput]) in a single request.
+    fn embed_inputs(
+        &self,
+        inputs: Vec<EmbeddingInput>,
+    ) -> impl std::future::Future<Output = Result<Vec<Embedding>, EmbeddingError>> + Send {
+        self.embed_texts(inputs.into_iter().map(EmbeddingInput::into_inner))
+    }
+}
+
+/// A text that can be embedded: not empty (nor only whitespace) and, if checked with
+/// [EmbeddingInput::with_max_tokens], within the token limit of the model.
+///
+/// Validating inputs when they are created surfaces problems before a request to the provider
+/// is wasted on them (most providers reject empty inputs and inputs over their context length).
+///
+/// # Example
+/// ```rust
+/// use rig::embeddings::{EmbeddingError, EmbeddingInput};
+///
+/// let input = EmbeddingInput::new("Hello, world!")?;
+/// assert_eq!(input.as_str(), "Hello, world!");
+///
+/// assert!(EmbeddingInput::new("  ").is_err());
+///
+/// // Count tokens with the model's tokenizer (here, approximated by words)
+/// let count = |text: &str| text.split_whitespace().count();
+/// assert!(EmbeddingInput::with_max_tokens("Hello, world!", 1, count).is_err());
+/// # Ok::<(), EmbeddingError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EmbeddingInput(String);
+
+impl EmbeddingInput {
+    /// Validate that `text` is not empty or only whitespace.
+    pub fn new(text: impl Into<String>) -> Result<Self, EmbeddingError> {
+        let text = text.into();
+        if text.trim().is_empty() {
+            return Err(EmbeddingError::InvalidInput(
+                "Input is empty or only whitespace".to_string(),
+            ));
+        }
+
+        Ok(Self(text))
+    }
+
+    /// Validate that `text` is not empty and that it is at most `max_tokens` tokens long,
+    /// according to `count_tokens` (e.g.: the tokenizer of the embedding model).
+    pub fn with_max_tokens(
+        text: impl Into<String>,
+        max_tokens: usize,
+        count_tokens: impl FnOnce(&str) -> usize,
+    ) -> Result<Self, EmbeddingError> {
+        let input = Self::new(text)?;
+        let tokens = count_tokens(&input.0);
+        if tokens > max_tokens {
+            return Err(EmbeddingError::InvalidInput(format!(
+                "Input is {tokens} tokens long, which exceeds the limit of {max_tokens} tokens"
+            )));
+        }
+
+        Ok(input)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl TryFrom<&str> for EmbeddingInput {
+    type Error = EmbeddingError;
+
+    fn try_from(text: &str) -> Result<Self, Self::Error> {
+        Self::new(text)
+    }
+}
+
+impl TryFrom<String> for EmbeddingInput {
+    type Error = EmbeddingError;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        Self::new(text)
+    }
+}
+
+impl AsRef<str> for EmbeddingInput {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for EmbeddingInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The number of texts embedded in each request by `model` (see [EmbeddingModel::max_batch_size]).
+pub(crate) fn batch_size<M: EmbeddingModel>(model: &M) -> usize {
+    model.max_batch_size().unwrap_or(M::MAX_DOCUMENTS).max(1)
 }
 
 pub trait EmbeddingModelDyn: Sync + Send {
     fn max_documents(&self) -> usize;
+    fn max_input_tokens(&self) -> Option<usize>;
     fn ndims(&self) -> usize;
     fn embed_text<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Embedding, EmbeddingError>>;
     fn embed_texts(
         &self,
         texts: Vec<String>,
     ) -> BoxFuture<'_, Result<Vec<Embedding>, EmbeddingError>>;
+    fn embed_texts_with_kind(
+        &self,
+        texts: Vec<String>,
+        kind: EmbedKind,
+    ) -> BoxFuture<'_, Result<Vec<Embedding>, EmbeddingError>>;
+    fn embed_query<'a>(
+        &'a self,
+        query: &'a str,
+    ) -> BoxFuture<'a, Result<Embedding, EmbeddingError>>;
 }
 
 impl<T: EmbeddingModel> EmbeddingModelDyn for T {
     fn max_documents(&self) -> usize {
-        T::MAX_DOCUMENTS
+        batch_size(self)
+    }
+
+    fn max_input_tokens(&self) -> Option<usize> {
+        self.max_input_tokens()
     }
 
     fn ndims(&self) -> usize {
@@ -91,7 +343,22 @@ impl<T: EmbeddingModel> EmbeddingModelDyn for T {
         &self,
         texts: Vec<String>,
     ) -> BoxFuture<'_, Result<Vec<Embedding>, EmbeddingError>> {
-        Box::pin(self.embed_texts(texts.into_iter().collect::<Vec<_>>()))
+        Box::pin(self.embed_texts(texts))
+    }
+
+    fn embed_texts_with_kind(
+        &self,
+        texts: Vec<String>,
+        kind: EmbedKind,
+    ) -> BoxFuture<'_, Result<Vec<Embedding>, EmbeddingError>> {
+        Box::pin(self.embed_texts_with_kind(texts, kind))
+    }
+
+    fn embed_query<'a>(
+        &'a self,
+        query: &'a str,
+    ) -> BoxFuture<'a, Result<Embedding, EmbeddingError>> {
+        Box::pin(self.embed_query(query))
     }
 }
 
@@ -140,3 +407,134 @@ impl PartialEq for Embedding {
 }
 
 impl Eq for Embedding {}
+
+impl Embedding {
+    /// Reduce the precision of the embedding vector to the given quantization, i.e.: replace
+    /// it with its dequantized (see [Quantization::dequantize]) value.
+    pub fn quantize(&mut self, quantization: Quantization) {
+        self.vec = quantization.dequantize(&quantization.quantize(&self.vec));
+    }
+}
+
+/// Lower precision representation of embedding vectors, to reduce their storage size
+/// (2x for [Quantization::Float32], 8x for [Quantization::Int8]).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Quantization {
+    /// 32-bit floats.
+    Float32,
+    /// 8-bit integers: each value is multiplied by `scale`, then rounded and clamped to
+    /// `[-128, 127]` (e.g.: a scale of `127.0` for normalized embeddings, whose values are in `[-1, 1]`).
+    Int8 { scale: f64 },
+}
+
+/// An embedding vector quantized with a [Quantization].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum QuantizedVector {
+    Float32(Vec<f32>),
+    Int8(Vec<i8>),
+}
+
+impl Quantization {
+    /// Quantize an embedding vector.
+    pub fn quantize(&self, vec: &[f64]) -> QuantizedVector {
+        match self {
+            Quantization::Float32 => {
+                QuantizedVector::Float32(vec.iter().map(|value| *value as f32).collect())
+            }
+            Quantization::Int8 { scale } => QuantizedVector::Int8(
+                vec.iter()
+                    .map(|value| (value * scale).round().clamp(-128.0, 127.0) as i8)
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Convert a quantized vector back to an (approximation of the original) embedding vector.
+    pub fn dequantize(&self, vec: &QuantizedVector) -> Vec<f64> {
+        match (self, vec) {
+            (Quantization::Int8 { scale }, QuantizedVector::Int8(values)) => values
+                .iter()
+                .map(|value| f64::from(*value) / scale)
+                .collect(),
+            (_, QuantizedVector::Int8(values)) => {
+                values.iter().map(|value| f64::from(*value)).collect()
+            }
+            (_, QuantizedVector::Float32(values)) => {
+                values.iter().map(|value| f64::from(*value)).collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EmbeddingError, EmbeddingInput, EmbeddingModel};
+    use crate::test_utils::MockEmbeddingModel;
+
+    #[test]
+    fn test_embedding_input() {
+        assert_eq!(
+            EmbeddingInput::new("Hello, world!").unwrap().as_str(),
+            "Hello, world!"
+        );
+        assert!(matches!(
+            EmbeddingInput::new(""),
+            Err(EmbeddingError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            EmbeddingInput::try_from(" \n\t"),
+            Err(EmbeddingError::InvalidInput(_))
+        ));
+
+        let count_words = |text: &str| text.split_whitespace().count();
+        assert!(EmbeddingInput::with_max_tokens("Hello, world!", 2, count_words).is_ok());
+        assert!(matches!(
+            EmbeddingInput::with_max_tokens("Hello, world!", 1, count_words),
+            Err(EmbeddingError::InvalidInput(message)) if message.contains("2 tokens")
+        ));
+    }
+
+    #[test]
+    fn test_is_rate_limited() {
+        let status_error = |status: u16, message: &str| EmbeddingError::ProviderStatusError {
+            status: reqwest::StatusCode::from_u16(status).unwrap(),
+            message: message.to_string(),
+        };
+
+        // Error responses are classified on their status, not on their message
+        assert!(status_error(429, "Slow down").is_rate_limited());
+        assert!(!status_error(400, "Input 429 is too long").is_rate_limited());
+        assert!(!status_error(500, "Rate limiter unavailable").is_rate_limited());
+
+        assert!(
+            EmbeddingError::ProviderError("Rate limit reached for requests".to_string())
+                .is_rate_limited()
+        );
+        assert!(
+            !EmbeddingError::ProviderError("Document 429 is too long".to_string())
+                .is_rate_limited()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_embed_inputs() {
+        let model = MockEmbeddingModel::new(vec![1.0, 0.0]);
+
+        let inputs = ["first", "second"]
+            .into_iter()
+            .map(EmbeddingInput::new)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let embeddings = model.embed_inputs(inputs).await.unwrap();
+        assert_eq!(embeddings.len(), 2);
+        assert_eq!(embeddings[1].document, "second");
+        assert_eq!(model.requests(), vec![vec!["first", "second"]]);
+
+        // Invalid inputs fail before any request is sent
+        assert!(matches!(
+            model.embed_input("  ").await,
+            Err(EmbeddingError::InvalidInput(_))
+        ));
+        assert_eq!(model.requests().len(), 1);
+    }
+}
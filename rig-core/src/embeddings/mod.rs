@@ -9,7 +9,10 @@ pub mod embedding;
 pub mod tool;
 
 pub mod distance;
-pub use builder::EmbeddingsBuilder;
+pub use builder::{EmbeddingsBuilder, EmbeddingsStream, EmptyDocuments};
 pub use embed::{Embed, EmbedError, TextEmbedder, to_texts};
-pub use embedding::{Embedding, EmbeddingError, EmbeddingModel};
+pub use embedding::{
+    EmbedKind, Embedding, EmbeddingError, EmbeddingInput, EmbeddingModel, Quantization,
+    QuantizedVector,
+};
 pub use tool::ToolSchema;
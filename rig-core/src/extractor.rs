@@ -28,8 +28,9 @@
 //!     .expect("Failed to extract data from text");
 //! ```
 
-use std::marker::PhantomData;
+use std::{marker::PhantomData, sync::Arc};
 
+use futures::{Stream, StreamExt};
 use schemars::{JsonSchema, schema_for};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -37,7 +38,10 @@ use serde_json::json;
 use crate::{
     agent::{Agent, AgentBuilder},
     completion::{Completion, CompletionError, CompletionModel, ToolDefinition},
+    json_utils,
     message::{AssistantContent, Message, ToolCall, ToolFunction},
+    schema::{SchemaValidationError, SchemaValidator},
+    streaming::{StreamedAssistantContent, StreamingCompletion},
     tool::Tool,
 };
 
@@ -53,6 +57,21 @@ pub enum ExtractionError {
 
     #[error("CompletionError: {0}")]
     CompletionError(#[from] CompletionError),
+
+    /// The extracted data does not match the schema of the data to extract
+    /// (see [ExtractorBuilder::schema_validator])
+    #[error("SchemaValidationError: {0}")]
+    SchemaValidationError(#[from] SchemaValidationError),
+}
+
+/// An update of a streaming extraction (see [Extractor::extract_stream]).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExtractionEvent<T> {
+    /// The (partial) data extracted so far, e.g.: to render fields as they arrive.
+    /// Strings may be truncated and fields missing, so it is not validated against `T`.
+    Partial(serde_json::Value),
+    /// The complete extracted data. Always the last event of a successful extraction.
+    Final(T),
 }
 
 /// Extractor for structured data from text
@@ -60,6 +79,7 @@ pub struct Extractor<M: CompletionModel, T: JsonSchema + for<'a> Deserialize<'a>
     agent: Agent<M>,
     _t: PhantomData<T>,
     retries: u64,
+    schema_validator: Option<Arc<dyn SchemaValidator>>,
 }
 
 impl<T: JsonSchema + for<'a> Deserialize<'a> + Send + Sync, M: CompletionModel> Extractor<M, T>
@@ -147,7 +167,65 @@ where
             return Err(ExtractionError::NoData);
         };
 
-        Ok(serde_json::from_value(raw_data)?)
+        to_data(self.schema_validator.as_deref(), raw_data)
+    }
+
+    /// Extract data from the given text, streaming the partially extracted data as it is
+    /// generated by the model. The stream yields [ExtractionEvent::Partial] updates as the JSON
+    /// of the data accumulates, then the validated data as [ExtractionEvent::Final].
+    ///
+    /// Note: unlike [Extractor::extract], failed extractions are not retried. Partial updates
+    /// are only available if the provider streams the arguments of tool calls.
+    pub async fn extract_stream(
+        &self,
+        text: impl Into<Message> + Send,
+    ) -> Result<impl Stream<Item = Result<ExtractionEvent<T>, ExtractionError>>, ExtractionError>
+    {
+        let mut stream = self
+            .agent
+            .stream_completion(text, vec![])
+            .await?
+            .stream()
+            .await?;
+        let schema_validator = self.schema_validator.clone();
+
+        Ok(async_stream::stream! {
+            let mut arguments = String::new();
+            let mut last_partial = None;
+
+            while let Some(content) = stream.next().await {
+                match content {
+                    Ok(StreamedAssistantContent::ToolCallDelta { name, delta, .. })
+                        if name == SUBMIT_TOOL_NAME =>
+                    {
+                        arguments.push_str(&delta);
+                        if let Some(partial) = json_utils::parse_partial(&arguments)
+                            .filter(|partial| last_partial.as_ref() != Some(partial))
+                        {
+                            last_partial = Some(partial.clone());
+                            yield Ok(ExtractionEvent::Partial(partial));
+                        }
+                    }
+                    Ok(StreamedAssistantContent::ToolCall(tool_call))
+                        if tool_call.function.name == SUBMIT_TOOL_NAME =>
+                    {
+                        yield to_data(schema_validator.as_deref(), tool_call.function.arguments)
+                            .map(ExtractionEvent::Final);
+                        return;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        yield Err(e.into());
+                        return;
+                    }
+                }
+            }
+
+            tracing::warn!(
+                "The submit tool was not called. If this happens more than once, please ensure the model you are using is powerful enough to reliably call tools."
+            );
+            yield Err(ExtractionError::NoData);
+        })
     }
 
     pub async fn get_inner(&self) -> &Agent<M> {
@@ -167,6 +245,7 @@ pub struct ExtractorBuilder<
     agent_builder: AgentBuilder<M>,
     _t: PhantomData<T>,
     retries: Option<u64>,
+    schema_validator: Option<Arc<dyn SchemaValidator>>,
 }
 
 impl<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync, M: CompletionModel>
@@ -183,6 +262,7 @@ impl<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync, M: Compl
                 ")
                 .tool(SubmitTool::<T> {_t: PhantomData}),
             retries: None,
+            schema_validator: None,
             _t: PhantomData,
         }
     }
@@ -218,16 +298,38 @@ impl<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync, M: Compl
         self
     }
 
+    /// Validate the extracted data against the JSON schema of `T` with `validator` (e.g.:
+    /// [BasicSchemaValidator](crate::schema::BasicSchemaValidator)) before deserializing it,
+    /// e.g.: to enforce constraints that the deserialization of `T` does not check. Invalid
+    /// data fails the attempt with an [ExtractionError::SchemaValidationError].
+    pub fn schema_validator(mut self, validator: impl SchemaValidator + 'static) -> Self {
+        self.schema_validator = Some(Arc::new(validator));
+        self
+    }
+
     /// Build the Extractor
     pub fn build(self) -> Extractor<M, T> {
         Extractor {
             agent: self.agent_builder.build(),
             _t: PhantomData,
             retries: self.retries.unwrap_or(0),
+            schema_validator: self.schema_validator,
         }
     }
 }
 
+/// Validate the extracted `data` with `validator` (if any), then deserialize it.
+fn to_data<T: JsonSchema + for<'a> Deserialize<'a>>(
+    validator: Option<&dyn SchemaValidator>,
+    data: serde_json::Value,
+) -> Result<T, ExtractionError> {
+    if let Some(validator) = validator {
+        validator.validate(&json!(schema_for!(T)), &data)?;
+    }
+
+    Ok(serde_json::from_value(data)?)
+}
+
 #[derive(Deserialize, Serialize)]
 struct SubmitTool<T: JsonSchema + for<'a> Deserialize<'a> + Send + Sync> {
     _t: PhantomData<T>,
@@ -256,3 +358,102 @@ impl<T: JsonSchema + for<'a> Deserialize<'a> + Serialize + Send + Sync> Tool for
         Ok(data)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use serde_json::json;
+
+    use super::{ExtractionError, ExtractionEvent, ExtractorBuilder};
+    use crate::{schema::BasicSchemaValidator, test_utils::MockCompletionModel};
+
+    #[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+    struct Person {
+        name: String,
+        age: u8,
+    }
+
+    #[tokio::test]
+    async fn test_extract_stream() {
+        let model = MockCompletionModel::new().with_tool_call(
+            "call_1",
+            "submit",
+            json!({"name": "John Doe", "age": 30}),
+        );
+        let extractor = ExtractorBuilder::<Person, _>::new(model).build();
+
+        let events = extractor
+            .extract_stream("John Doe is 30 years old.")
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        // Partial updates grow until the final, validated, data
+        let partials = events
+            .iter()
+            .filter_map(|event| match event {
+                ExtractionEvent::Partial(partial) => Some(partial.clone()),
+                ExtractionEvent::Final(_) => None,
+            })
+            .collect::<Vec<_>>();
+        assert!(partials.len() > 1);
+        // The order of the fields in the streamed arguments depends on serde_json's features
+        assert!(
+            partials
+                .iter()
+                .any(|partial| partial.as_object().is_some_and(|fields| fields.len() == 1))
+        );
+        assert_eq!(
+            partials.last(),
+            Some(&json!({"name": "John Doe", "age": 30}))
+        );
+        assert_eq!(
+            events.last(),
+            Some(&ExtractionEvent::Final(Person {
+                name: "John Doe".to_string(),
+                age: 30,
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_schema_validator() {
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "submit", json!({"name": "John Doe", "age": "30"}))
+            .with_tool_call("call_2", "submit", json!({"name": "John Doe", "age": 30}));
+        let extractor = ExtractorBuilder::<Person, _>::new(model)
+            .schema_validator(BasicSchemaValidator)
+            .build();
+
+        let result = extractor.extract("John Doe is 30 years old.").await;
+        let Err(ExtractionError::SchemaValidationError(error)) = result else {
+            panic!("Expected a schema validation error");
+        };
+        assert_eq!(error.errors, vec!["$.age: expected integer, got string"]);
+
+        let person = extractor
+            .extract("John Doe is 30 years old.")
+            .await
+            .unwrap();
+        assert_eq!(person.age, 30);
+    }
+
+    #[tokio::test]
+    async fn test_extract_stream_no_data() {
+        let model = MockCompletionModel::new().with_text("I don't know.");
+        let extractor = ExtractorBuilder::<Person, _>::new(model).build();
+
+        let events = extractor
+            .extract_stream("Who is John Doe?")
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+
+        assert!(matches!(events[..], [Err(ExtractionError::NoData)]));
+    }
+}
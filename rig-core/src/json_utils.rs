@@ -140,6 +140,72 @@ where
     deserializer.deserialize_any(NullOrVec(PhantomData))
 }
 
+/// Tolerantly parse a truncated JSON document (e.g.: the JSON generated so far by a streaming
+/// model) by closing its open strings, arrays and objects. Trailing content that cannot be
+/// completed (e.g.: a key without value, a partial `true`) is dropped.
+/// Returns `None` if nothing can be parsed yet.
+pub fn parse_partial(json: &str) -> Option<serde_json::Value> {
+    let mut json = json.trim_end();
+
+    loop {
+        let (completed, cut) = complete_partial(json);
+        if let Ok(value) = serde_json::from_str(&completed) {
+            return Some(value);
+        }
+
+        // Drop the last (incomplete) element and try again
+        json = match cut {
+            Some(cut) if cut < json.len() => json[..cut].trim_end(),
+            _ => return None,
+        };
+    }
+}
+
+/// Close the open strings, arrays and objects of a truncated JSON document. Also returns the
+/// position right before the last element of the innermost open array or object, if any.
+fn complete_partial(json: &str) -> (String, Option<usize>) {
+    let mut closers = vec![];
+    let mut cut = None;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, c) in json.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => {
+                closers.push(if c == '{' { '}' } else { ']' });
+                cut = Some(i + 1);
+            }
+            '}' | ']' => {
+                closers.pop();
+            }
+            ',' => cut = Some(i),
+            _ => {}
+        }
+    }
+
+    let mut completed = json.to_string();
+    if in_string {
+        if escaped {
+            completed.pop();
+        }
+        completed.push('"');
+    }
+    completed.extend(closers.into_iter().rev());
+
+    (completed, cut)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -169,6 +235,41 @@ mod tests {
         assert_eq!(a, expected);
     }
 
+    #[test]
+    fn test_parse_partial() {
+        let json = r#"{"name": "John \"Doe\"", "tags": ["a", "b"], "address": {"city": "Paris"}, "age": 30}"#;
+
+        // Every prefix of a valid document can be parsed
+        for i in 0..=json.len() {
+            parse_partial(&json[..i]);
+        }
+
+        assert_eq!(parse_partial(""), None);
+        assert_eq!(parse_partial("{"), Some(serde_json::json!({})));
+        assert_eq!(parse_partial(r#"{"na"#), Some(serde_json::json!({})));
+        assert_eq!(parse_partial(r#"{"name": "#), Some(serde_json::json!({})));
+        assert_eq!(
+            parse_partial(r#"{"name": "John \"#),
+            Some(serde_json::json!({"name": "John "}))
+        );
+        assert_eq!(
+            parse_partial(r#"{"name": "John \"Doe\"", "tags": ["a", "#),
+            Some(serde_json::json!({"name": "John \"Doe\"", "tags": ["a"]}))
+        );
+        assert_eq!(
+            parse_partial(r#"{"name": "John", "address": {"city": "Par"#),
+            Some(serde_json::json!({"name": "John", "address": {"city": "Par"}}))
+        );
+        assert_eq!(
+            parse_partial(r#"{"name": "John", "active": tr"#),
+            Some(serde_json::json!({"name": "John"}))
+        );
+        assert_eq!(
+            parse_partial(json),
+            Some(serde_json::from_str::<serde_json::Value>(json).unwrap())
+        );
+    }
+
     #[test]
     fn test_stringified_json_serialize() {
         let dummy = Dummy {
@@ -86,20 +86,33 @@ extern crate self as rig;
 pub mod agent;
 #[cfg(feature = "audio")]
 pub mod audio_generation;
+#[cfg(feature = "blocking")]
+#[cfg_attr(docsrs, doc(cfg(feature = "blocking")))]
+pub mod blocking;
 pub mod cli_chatbot;
 pub mod client;
 pub mod completion;
+pub mod context_policy;
 pub mod embeddings;
 pub mod extractor;
 #[cfg(feature = "image")]
 pub mod image_generation;
 pub(crate) mod json_utils;
 pub mod loaders;
+pub mod media_limits;
+pub mod moderation;
 pub mod one_or_many;
 pub mod pipeline;
 pub mod prelude;
 pub mod providers;
+pub mod redact;
+pub mod schema;
 pub mod streaming;
+pub mod telemetry;
+#[cfg(any(test, feature = "test-utils"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-utils")))]
+pub mod test_utils;
+pub mod text;
 pub mod tool;
 pub mod transcription;
 pub mod vector_store;
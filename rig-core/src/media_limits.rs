@@ -0,0 +1,333 @@
+//! This module provides [MediaLimits], used to keep the images and documents of the prompt and
+//! chat history within the size limits of model providers (see
+//! [AgentBuilder::media_limits](crate::agent::AgentBuilder::media_limits)).
+//!
+//! Images larger than the configured maximum dimension are downscaled (requires the
+//! `image-resize` feature), and images and documents that are still over the configured byte
+//! limits result in a [MediaLimitError] instead of being sent to the provider.
+//!
+//! # Example
+//! ```rust
+//! use rig::{client::CompletionClient, media_limits::MediaLimits, providers::openai};
+//!
+//! let openai = openai::Client::new("your-open-ai-api-key");
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .preamble("You are a helpful assistant.")
+//!     // Reject images over 5MB and documents over 32MB
+//!     .media_limits(
+//!         MediaLimits::new()
+//!             .max_image_bytes(5 * 1024 * 1024)
+//!             .max_document_bytes(32 * 1024 * 1024),
+//!     )
+//!     .build();
+//! ```
+use base64::{Engine, prelude::BASE64_STANDARD};
+
+use crate::{
+    completion::Message,
+    message::{ContentFormat, Image, ToolResultContent, UserContent},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum MediaLimitError {
+    /// The image is still over the byte limit after downscaling
+    #[error("Image is {size} bytes, which exceeds the limit of {limit} bytes")]
+    ImageTooLarge { size: usize, limit: usize },
+
+    /// The document is over the byte limit
+    #[error("Document is {size} bytes, which exceeds the limit of {limit} bytes")]
+    DocumentTooLarge { size: usize, limit: usize },
+
+    /// The base64 data of the image or document could not be decoded
+    #[error("Base64Error: {0}")]
+    Base64(#[from] base64::DecodeError),
+
+    /// The image could not be decoded or re-encoded while downscaling it
+    #[cfg(feature = "image-resize")]
+    #[error("ImageError: {0}")]
+    Image(#[from] image::ImageError),
+}
+
+/// Size limits applied to the images and documents of messages before they are sent to a
+/// model provider. Every limit is disabled by default.
+///
+/// Only base64 content is checked: content referenced by URL is left untouched, since its size
+/// is only known to the provider.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaLimits {
+    max_image_dimension: Option<u32>,
+    max_image_bytes: Option<usize>,
+    max_document_bytes: Option<usize>,
+}
+
+impl MediaLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Downscale images whose width or height exceeds `max_image_dimension` pixels, preserving
+    /// their aspect ratio. Downscaled images are re-encoded in their original format (only the
+    /// first frame of animated GIFs is kept).
+    #[cfg(feature = "image-resize")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "image-resize")))]
+    pub fn max_image_dimension(mut self, max_image_dimension: u32) -> Self {
+        self.max_image_dimension = Some(max_image_dimension);
+        self
+    }
+
+    /// Reject images over `max_image_bytes` bytes (decoded size, after downscaling).
+    pub fn max_image_bytes(mut self, max_image_bytes: usize) -> Self {
+        self.max_image_bytes = Some(max_image_bytes);
+        self
+    }
+
+    /// Reject documents over `max_document_bytes` bytes (decoded size).
+    pub fn max_document_bytes(mut self, max_document_bytes: usize) -> Self {
+        self.max_document_bytes = Some(max_document_bytes);
+        self
+    }
+
+    /// Apply the limits to the images (including images of tool results) and documents of
+    /// `message`.
+    pub fn apply(&self, message: Message) -> Result<Message, MediaLimitError> {
+        match message {
+            Message::User { mut content } => {
+                for content in content.iter_mut() {
+                    match content {
+                        UserContent::Image(image) => self.apply_to_image(image)?,
+                        UserContent::Document(document) => {
+                            if let (Some(limit), Some(data)) = (
+                                self.max_document_bytes,
+                                base64_data(&document.data, &document.format)?,
+                            ) && data.len() > limit
+                            {
+                                return Err(MediaLimitError::DocumentTooLarge {
+                                    size: data.len(),
+                                    limit,
+                                });
+                            }
+                        }
+                        UserContent::ToolResult(tool_result) => {
+                            for content in tool_result.content.iter_mut() {
+                                if let ToolResultContent::Image(image) = content {
+                                    self.apply_to_image(image)?;
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+
+                Ok(Message::User { content })
+            }
+            message => Ok(message),
+        }
+    }
+
+    fn apply_to_image(&self, image: &mut Image) -> Result<(), MediaLimitError> {
+        if self.max_image_dimension.is_none() && self.max_image_bytes.is_none() {
+            return Ok(());
+        }
+
+        let Some(data) = base64_data(&image.data, &image.format)? else {
+            return Ok(());
+        };
+
+        #[cfg(feature = "image-resize")]
+        let data = match self.max_image_dimension {
+            Some(max_dimension) => match downscale(&data, max_dimension)? {
+                Some(downscaled) => {
+                    image.data = BASE64_STANDARD.encode(&downscaled);
+                    image.format = Some(ContentFormat::Base64);
+                    downscaled
+                }
+                None => data,
+            },
+            None => data,
+        };
+
+        if let Some(limit) = self.max_image_bytes
+            && data.len() > limit
+        {
+            return Err(MediaLimitError::ImageTooLarge {
+                size: data.len(),
+                limit,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// The decoded data of base64 content, or `None` if the content is a URL.
+///
+/// Content without an explicit format that is not valid base64 is assumed to be a URL.
+fn base64_data(
+    data: &str,
+    format: &Option<ContentFormat>,
+) -> Result<Option<Vec<u8>>, MediaLimitError> {
+    match format {
+        Some(ContentFormat::String) => Ok(None),
+        Some(ContentFormat::Base64) => Ok(Some(BASE64_STANDARD.decode(data)?)),
+        None => Ok(BASE64_STANDARD.decode(data).ok()),
+    }
+}
+
+/// Downscale the encoded image `data` so that neither of its dimensions exceeds
+/// `max_dimension`, returning `None` if it is already small enough.
+#[cfg(feature = "image-resize")]
+fn downscale(data: &[u8], max_dimension: u32) -> Result<Option<Vec<u8>>, MediaLimitError> {
+    let format = image::guess_format(data)?;
+    let decoded = image::load_from_memory_with_format(data, format)?;
+
+    if decoded.width() <= max_dimension && decoded.height() <= max_dimension {
+        return Ok(None);
+    }
+
+    // `resize` preserves the aspect ratio of the image
+    let resized = decoded.resize(
+        max_dimension,
+        max_dimension,
+        image::imageops::FilterType::Triangle,
+    );
+
+    let mut encoded = std::io::Cursor::new(vec![]);
+    resized.write_to(&mut encoded, format)?;
+
+    Ok(Some(encoded.into_inner()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{OneOrMany, message::DocumentMediaType};
+
+    #[test]
+    fn test_document_too_large() {
+        let limits = MediaLimits::new().max_document_bytes(4);
+        let message = |data: &[u8]| {
+            Message::document(
+                BASE64_STANDARD.encode(data),
+                Some(ContentFormat::Base64),
+                Some(DocumentMediaType::PDF),
+            )
+        };
+
+        assert!(limits.apply(message(b"1234")).is_ok());
+        assert!(matches!(
+            limits.apply(message(b"12345")),
+            Err(MediaLimitError::DocumentTooLarge { size: 5, limit: 4 })
+        ));
+
+        // Text documents and URLs are not checked
+        let text = Message::document(
+            "123456789",
+            Some(ContentFormat::String),
+            Some(DocumentMediaType::TXT),
+        );
+        assert_eq!(limits.apply(text.clone()).unwrap(), text);
+    }
+
+    #[test]
+    fn test_image_too_large() {
+        let limits = MediaLimits::new().max_image_bytes(4);
+        let image = Message::User {
+            content: OneOrMany::one(UserContent::tool_result(
+                "call_1",
+                OneOrMany::one(ToolResultContent::image(
+                    BASE64_STANDARD.encode(b"12345"),
+                    Some(ContentFormat::Base64),
+                    None,
+                    None,
+                )),
+            )),
+        };
+
+        assert!(matches!(
+            limits.apply(image),
+            Err(MediaLimitError::ImageTooLarge { size: 5, limit: 4 })
+        ));
+
+        let url = Message::image("https://example.com/image.png", None, None, None);
+        assert_eq!(limits.apply(url.clone()).unwrap(), url);
+    }
+
+    #[tokio::test]
+    async fn test_agent_media_limits() {
+        use crate::{
+            agent::AgentBuilder,
+            completion::{CompletionError, Prompt, PromptError},
+            test_utils::MockCompletionModel,
+        };
+
+        let model = MockCompletionModel::new().with_text("It's a PDF.");
+        let agent = AgentBuilder::new(model.clone())
+            .media_limits(MediaLimits::new().max_document_bytes(4))
+            .build();
+        let document = Message::user_with_attachments(
+            "What is this?",
+            [UserContent::document(
+                BASE64_STANDARD.encode(b"12345"),
+                Some(ContentFormat::Base64),
+                Some(DocumentMediaType::PDF),
+            )],
+        );
+
+        let result = agent.prompt(document).await;
+
+        assert!(matches!(
+            result,
+            Err(PromptError::CompletionError(CompletionError::RequestError(
+                _
+            )))
+        ));
+        assert!(model.requests().is_empty());
+    }
+
+    #[cfg(feature = "image-resize")]
+    #[test]
+    fn test_downscale_image() {
+        let png = |width, height| {
+            let mut data = std::io::Cursor::new(vec![]);
+            image::DynamicImage::new_rgb8(width, height)
+                .write_to(&mut data, image::ImageFormat::Png)
+                .unwrap();
+            data.into_inner()
+        };
+        let dimensions = |message: Message| {
+            let Message::User { content } = message else {
+                panic!("Expected a user message");
+            };
+            let UserContent::Image(image) = content.first() else {
+                panic!("Expected an image");
+            };
+            let data = BASE64_STANDARD.decode(image.data).unwrap();
+            let image = image::load_from_memory(&data).unwrap();
+            (image.width(), image.height())
+        };
+        let message = |width, height| {
+            Message::image(
+                BASE64_STANDARD.encode(png(width, height)),
+                Some(ContentFormat::Base64),
+                None,
+                None,
+            )
+        };
+        let limits = MediaLimits::new().max_image_dimension(100);
+
+        assert_eq!(
+            dimensions(limits.apply(message(400, 200)).unwrap()),
+            (100, 50)
+        );
+        assert_eq!(dimensions(limits.apply(message(50, 80)).unwrap()), (50, 80));
+
+        // The byte limit applies to the downscaled image
+        let limits = limits.max_image_bytes(png(100, 100).len());
+        assert!(limits.apply(message(1000, 1000)).is_ok());
+        assert!(matches!(
+            limits.max_image_bytes(10).apply(message(1000, 1000)),
+            Err(MediaLimitError::ImageTooLarge { limit: 10, .. })
+        ));
+    }
+}
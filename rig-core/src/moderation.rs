@@ -0,0 +1,117 @@
+//! This module provides the [Moderator] trait, used to check the prompts of agents (and
+//! optionally their responses) against a moderation endpoint and block disallowed content
+//! before it reaches the model (or the user).
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     client::CompletionClient,
+//!     moderation::ModerationResult,
+//!     providers::openai,
+//! };
+//!
+//! let openai = openai::Client::new("your-open-ai-api-key");
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     // Check prompts and responses with OpenAI's moderation endpoint
+//!     .moderator(openai.moderation_model(openai::OMNI_MODERATION_LATEST))
+//!     .moderate_output(true)
+//!     .build();
+//!
+//! // A custom moderator
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .moderator(|text: &str| {
+//!         if text.contains("forbidden") {
+//!             ModerationResult::flagged(["forbidden"])
+//!         } else {
+//!             ModerationResult::allowed()
+//!         }
+//!     })
+//!     .build();
+//! ```
+use std::future::Future;
+
+use futures::future::BoxFuture;
+
+/// The result of a moderation check.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModerationResult {
+    /// Whether the text is disallowed
+    pub flagged: bool,
+    /// The categories the text was flagged for (e.g.: `harassment`, `violence`)
+    pub categories: Vec<String>,
+}
+
+impl ModerationResult {
+    /// A result allowing the text.
+    pub fn allowed() -> Self {
+        Self::default()
+    }
+
+    /// A result flagging the text for `categories`.
+    pub fn flagged(categories: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            flagged: true,
+            categories: categories.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ModerationError {
+    /// Http error (e.g.: connection error, timeout, etc.)
+    #[error("HttpError: {0}")]
+    HttpError(#[from] reqwest::Error),
+
+    /// Json error (e.g.: serialization, deserialization)
+    #[error("JsonError: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    /// Error returned by the moderation provider
+    #[error("ProviderError: {0}")]
+    ProviderError(String),
+
+    /// Error parsing the moderation response
+    #[error("ResponseError: {0}")]
+    ResponseError(String),
+}
+
+/// Trait for checking text against a content policy.
+///
+/// When an agent is configured with a moderator (see
+/// [AgentBuilder::moderator](crate::agent::AgentBuilder::moderator)), the text of each prompt
+/// is checked before any completion request, and prompts that are flagged fail with
+/// [PromptError::Moderated](crate::completion::PromptError::Moderated).
+pub trait Moderator: Send + Sync {
+    /// Check `text`, returning whether it is flagged and for which categories.
+    fn check(
+        &self,
+        text: &str,
+    ) -> impl Future<Output = Result<ModerationResult, ModerationError>> + Send;
+}
+
+impl<F> Moderator for F
+where
+    F: Fn(&str) -> ModerationResult + Send + Sync,
+{
+    async fn check(&self, text: &str) -> Result<ModerationResult, ModerationError> {
+        Ok(self(text))
+    }
+}
+
+/// Object-safe version of [Moderator], used to store moderators in agents.
+pub trait ModeratorDyn: Send + Sync {
+    fn check<'a>(
+        &'a self,
+        text: &'a str,
+    ) -> BoxFuture<'a, Result<ModerationResult, ModerationError>>;
+}
+
+impl<M: Moderator> ModeratorDyn for M {
+    fn check<'a>(
+        &'a self,
+        text: &'a str,
+    ) -> BoxFuture<'a, Result<ModerationResult, ModerationError>> {
+        Box::pin(Moderator::check(self, text))
+    }
+}
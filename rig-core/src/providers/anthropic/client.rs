@@ -1,8 +1,8 @@
 //! Anthropic client api implementation
 use super::completion::{ANTHROPIC_VERSION_LATEST, CompletionModel};
 use crate::client::{
-    ClientBuilderError, CompletionClient, ProviderClient, ProviderValue, VerifyClient, VerifyError,
-    impl_conversion_traits,
+    ClientBuilderError, CompletionClient, ProviderClient, ProviderValue, RequestIdGenerator,
+    VerifyClient, VerifyError, identify_request, impl_conversion_traits,
 };
 
 // ================================================================
@@ -15,6 +15,8 @@ pub struct ClientBuilder<'a> {
     base_url: &'a str,
     anthropic_version: &'a str,
     anthropic_betas: Option<Vec<&'a str>>,
+    user_agent: Option<&'a str>,
+    request_id: Option<RequestIdGenerator>,
     http_client: Option<reqwest::Client>,
 }
 
@@ -37,6 +39,8 @@ impl<'a> ClientBuilder<'a> {
             base_url: ANTHROPIC_API_BASE_URL,
             anthropic_version: ANTHROPIC_VERSION_LATEST,
             anthropic_betas: None,
+            user_agent: None,
+            request_id: None,
             http_client: None,
         }
     }
@@ -61,6 +65,19 @@ impl<'a> ClientBuilder<'a> {
         self
     }
 
+    /// Set the `User-Agent` header of the requests sent by the client (e.g.: `my-app/1.0`).
+    pub fn user_agent(mut self, user_agent: &'a str) -> Self {
+        self.user_agent = Some(user_agent);
+        self
+    }
+
+    /// Generate an id for each request sent by the client, sent as its `X-Request-Id` header
+    /// (see [crate::client::REQUEST_ID_HEADER]) and logged in rig's traces.
+    pub fn request_id(mut self, request_id: impl Fn() -> String + Send + Sync + 'static) -> Self {
+        self.request_id = Some(std::sync::Arc::new(request_id));
+        self
+    }
+
     pub fn custom_client(mut self, client: reqwest::Client) -> Self {
         self.http_client = Some(client);
         self
@@ -87,13 +104,15 @@ impl<'a> ClientBuilder<'a> {
         let http_client = if let Some(http_client) = self.http_client {
             http_client
         } else {
-            reqwest::Client::builder().build()?
+            crate::client::default_http_client()?
         };
 
         Ok(Client {
             base_url: self.base_url.to_string(),
             api_key: self.api_key.to_string(),
             default_headers,
+            user_agent: self.user_agent.map(str::to_string),
+            request_id: self.request_id,
             http_client,
         })
     }
@@ -109,6 +128,10 @@ pub struct Client {
     http_client: reqwest::Client,
     /// Default headers that will be automatically added to any given request with this client (API key, Anthropic Version and any betas that have been added)
     default_headers: reqwest::header::HeaderMap,
+    /// The `User-Agent` header of the requests
+    user_agent: Option<String>,
+    /// The generator of the `X-Request-Id` header of each request
+    request_id: Option<RequestIdGenerator>,
 }
 
 impl std::fmt::Debug for Client {
@@ -118,6 +141,8 @@ impl std::fmt::Debug for Client {
             .field("http_client", &self.http_client)
             .field("api_key", &"<REDACTED>")
             .field("default_headers", &self.default_headers)
+            .field("user_agent", &self.user_agent)
+            .field("request_id", &self.request_id.as_ref().map(|_| "<fn>"))
             .finish()
     }
 }
@@ -152,18 +177,30 @@ impl Client {
 
     pub(crate) fn post(&self, path: &str) -> reqwest::RequestBuilder {
         let url = format!("{}/{}", self.base_url, path).replace("//", "/");
-        self.http_client
-            .post(url)
-            .header("X-Api-Key", &self.api_key)
-            .headers(self.default_headers.clone())
+        self.identify(
+            self.http_client
+                .post(url)
+                .header("X-Api-Key", &self.api_key)
+                .headers(self.default_headers.clone()),
+        )
     }
 
     pub(crate) fn get(&self, path: &str) -> reqwest::RequestBuilder {
         let url = format!("{}/{}", self.base_url, path).replace("//", "/");
-        self.http_client
-            .get(url)
-            .header("X-Api-Key", &self.api_key)
-            .headers(self.default_headers.clone())
+        self.identify(
+            self.http_client
+                .get(url)
+                .header("X-Api-Key", &self.api_key)
+                .headers(self.default_headers.clone()),
+        )
+    }
+
+    fn identify(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        identify_request(
+            request,
+            self.user_agent.as_deref(),
+            self.request_id.as_ref(),
+        )
     }
 }
 
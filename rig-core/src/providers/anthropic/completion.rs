@@ -10,7 +10,7 @@ use crate::{
 use std::{convert::Infallible, str::FromStr};
 
 use super::client::Client;
-use crate::completion::CompletionRequest;
+use crate::completion::{CompletionRequest, ToolCallIdFormat};
 use crate::providers::anthropic::streaming::StreamingCompletionResponse;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -43,6 +43,9 @@ pub const CLAUDE_3_SONNET: &str = "claude-3-sonnet-20240229";
 /// `claude-3-haiku-20240307` completion model
 pub const CLAUDE_3_HAIKU: &str = "claude-3-haiku-20240307";
 
+/// Context window (in tokens) of the Claude 3 and Claude 4 models
+pub const CLAUDE_CONTEXT_WINDOW: usize = 200_000;
+
 pub const ANTHROPIC_VERSION_2023_01_01: &str = "2023-01-01";
 pub const ANTHROPIC_VERSION_2023_06_01: &str = "2023-06-01";
 pub const ANTHROPIC_VERSION_LATEST: &str = ANTHROPIC_VERSION_2023_06_01;
@@ -328,7 +331,10 @@ impl TryFrom<DocumentMediaType> for DocumentFormat {
 impl From<message::AssistantContent> for Content {
     fn from(text: message::AssistantContent) -> Self {
         match text {
-            message::AssistantContent::Text(message::Text { text }) => Content::Text { text },
+            message::AssistantContent::Text(message::Text { text })
+            | message::AssistantContent::Refusal(message::Refusal { refusal: text }) => {
+                Content::Text { text }
+            }
             message::AssistantContent::ToolCall(message::ToolCall { id, function, .. }) => {
                 Content::ToolUse {
                     id,
@@ -541,11 +547,37 @@ impl TryFrom<Message> for message::Message {
     }
 }
 
+/// Convert a conversation in the JSON format of the Anthropic Messages API (an array of
+/// messages, e.g.: the `messages` of a request) into rig messages, e.g.: to import a chat
+/// history created with another library.
+pub fn messages_from_json(
+    messages: serde_json::Value,
+) -> Result<Vec<message::Message>, MessageError> {
+    let messages: Vec<Message> = serde_json::from_value(messages)
+        .map_err(|e| MessageError::ConversionError(e.to_string()))?;
+
+    messages.into_iter().map(TryInto::try_into).collect()
+}
+
+/// Convert rig messages into the JSON format of the Anthropic Messages API (an array of
+/// messages), the reverse of [messages_from_json].
+pub fn messages_to_json(messages: &[message::Message]) -> Result<serde_json::Value, MessageError> {
+    let messages = messages
+        .iter()
+        .cloned()
+        .map(Message::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    serde_json::to_value(messages).map_err(|e| MessageError::ConversionError(e.to_string()))
+}
+
 #[derive(Clone)]
 pub struct CompletionModel {
     pub(crate) client: Client,
     pub model: String,
     pub default_max_tokens: Option<u64>,
+    /// Format of the ids of the tool calls sent in the chat history
+    pub(crate) tool_call_ids: ToolCallIdFormat,
 }
 
 impl CompletionModel {
@@ -554,8 +586,17 @@ impl CompletionModel {
             client,
             model: model.to_string(),
             default_max_tokens: calculate_max_tokens(model),
+            tool_call_ids: ToolCallIdFormat::anthropic(),
         }
     }
+
+    /// Set the format the ids of the tool calls and tool results of the chat history are
+    /// normalized to (defaults to [ToolCallIdFormat::anthropic]), e.g.: when the history comes
+    /// from another provider.
+    pub fn with_tool_call_ids(mut self, format: ToolCallIdFormat) -> Self {
+        self.tool_call_ids = format;
+        self
+    }
 }
 
 /// Anthropic requires a `max_tokens` parameter to be set, which is dependent on the model. If not
@@ -594,12 +635,34 @@ pub enum ToolChoice {
     Tool {
         name: String,
     },
+    None,
+}
+
+impl From<completion::ToolChoice> for ToolChoice {
+    fn from(tool_choice: completion::ToolChoice) -> Self {
+        match tool_choice {
+            completion::ToolChoice::Auto => ToolChoice::Auto,
+            completion::ToolChoice::None => ToolChoice::None,
+            completion::ToolChoice::Required => ToolChoice::Any,
+        }
+    }
 }
 
 impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
     type StreamingResponse = StreamingCompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn context_window(&self) -> Option<usize> {
+        ["claude-3", "claude-opus-4", "claude-sonnet-4"]
+            .iter()
+            .any(|prefix| self.model.starts_with(prefix))
+            .then_some(CLAUDE_CONTEXT_WINDOW)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
@@ -625,6 +688,7 @@ impl completion::CompletionModel for CompletionModel {
             full_history.push(docs);
         }
         full_history.extend(completion_request.chat_history);
+        self.tool_call_ids.normalize_messages(&mut full_history);
 
         let full_history = full_history
             .into_iter()
@@ -643,6 +707,19 @@ impl completion::CompletionModel for CompletionModel {
         }
 
         if !completion_request.tools.is_empty() {
+            let tool_choice = ToolChoice::from(completion_request.tool_choice.unwrap_or_default());
+            // Parallel tool use cannot be configured when the tools cannot be called
+            let parallel_tool_calls = completion_request
+                .parallel_tool_calls
+                .filter(|_| !matches!(tool_choice, ToolChoice::None));
+            let mut tool_choice = json!(tool_choice);
+            if let Some(parallel_tool_calls) = parallel_tool_calls {
+                json_utils::merge_inplace(
+                    &mut tool_choice,
+                    json!({ "disable_parallel_tool_use": !parallel_tool_calls }),
+                );
+            }
+
             json_utils::merge_inplace(
                 &mut request,
                 json!({
@@ -655,7 +732,7 @@ impl completion::CompletionModel for CompletionModel {
                             input_schema: tool.parameters,
                         })
                         .collect::<Vec<_>>(),
-                    "tool_choice": ToolChoice::Auto,
+                    "tool_choice": tool_choice,
                 }),
             );
         }
@@ -685,7 +762,7 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Error(error) => Err(CompletionError::ProviderError(error.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from_response(response).await)
         }
     }
 
@@ -1023,4 +1100,71 @@ mod tests {
         assert_eq!(assistant_message, original_assistant_message);
         assert_eq!(tool_message, original_tool_message);
     }
+
+    #[test]
+    fn test_messages_json_round_trip() {
+        let json = serde_json::json!([
+            {"role": "user", "content": "What is the weather in Paris?"},
+            {
+                "role": "assistant",
+                "content": [
+                    {"type": "thinking", "thinking": "I should check the weather.", "signature": "sig"},
+                    {"type": "tool_use", "id": "toolu_1", "name": "weather", "input": {"city": "Paris"}}
+                ]
+            },
+            {
+                "role": "user",
+                "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_1", "content": "Sunny"}
+                ]
+            },
+            {"role": "assistant", "content": [{"type": "text", "text": "It is sunny in Paris."}]}
+        ]);
+
+        let messages = message::Message::from_anthropic_json(json.clone()).unwrap();
+
+        assert_eq!(messages.len(), 4);
+        assert_eq!(
+            messages[1],
+            message::Message::Assistant {
+                id: None,
+                content: OneOrMany::many([
+                    message::AssistantContent::Reasoning(
+                        Reasoning::new("I should check the weather.").with_id("sig".to_string())
+                    ),
+                    message::AssistantContent::tool_call(
+                        "toolu_1",
+                        "weather",
+                        serde_json::json!({"city": "Paris"})
+                    ),
+                ])
+                .unwrap(),
+            }
+        );
+        assert_eq!(
+            messages[2],
+            message::Message::tool_result("toolu_1", "Sunny")
+        );
+
+        // Anthropic -> rig -> Anthropic
+        let converted = message::Message::to_anthropic_json(&messages).unwrap();
+        assert_eq!(converted[1]["content"][1]["type"], "tool_use");
+        assert_eq!(converted[2]["content"][0]["tool_use_id"], "toolu_1");
+        assert_eq!(
+            message::Message::from_anthropic_json(converted).unwrap(),
+            messages
+        );
+    }
+
+    #[test]
+    fn test_tool_choice() {
+        assert_eq!(
+            json!(ToolChoice::from(completion::ToolChoice::None)),
+            json!({"type": "none"})
+        );
+        assert_eq!(
+            json!(ToolChoice::from(completion::ToolChoice::Required)),
+            json!({"type": "any"})
+        );
+    }
 }
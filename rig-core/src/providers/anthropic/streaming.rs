@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 use super::completion::{CompletionModel, Content, Message, ToolChoice, ToolDefinition, Usage};
-use super::decoders::sse::from_response as sse_from_response;
+use super::decoders::sse::{SSEDecoderError, ServerSentEvent, from_response as sse_from_response};
 use crate::completion::{CompletionError, CompletionRequest, GetTokenUsage};
 use crate::json_utils::merge_inplace;
 use crate::streaming;
@@ -113,6 +113,7 @@ impl CompletionModel {
             full_history.push(docs);
         }
         full_history.extend(completion_request.chat_history);
+        self.tool_call_ids.normalize_messages(&mut full_history);
 
         let full_history = full_history
             .into_iter()
@@ -132,6 +133,19 @@ impl CompletionModel {
         }
 
         if !completion_request.tools.is_empty() {
+            let tool_choice = ToolChoice::from(completion_request.tool_choice.unwrap_or_default());
+            // Parallel tool use cannot be configured when the tools cannot be called
+            let parallel_tool_calls = completion_request
+                .parallel_tool_calls
+                .filter(|_| !matches!(tool_choice, ToolChoice::None));
+            let mut tool_choice = json!(tool_choice);
+            if let Some(parallel_tool_calls) = parallel_tool_calls {
+                merge_inplace(
+                    &mut tool_choice,
+                    json!({ "disable_parallel_tool_use": !parallel_tool_calls }),
+                );
+            }
+
             merge_inplace(
                 &mut request,
                 json!({
@@ -144,7 +158,7 @@ impl CompletionModel {
                             input_schema: tool.parameters,
                         })
                         .collect::<Vec<_>>(),
-                    "tool_choice": ToolChoice::Auto,
+                    "tool_choice": tool_choice,
                 }),
             );
         }
@@ -161,64 +175,77 @@ impl CompletionModel {
             .await?;
 
         if !response.status().is_success() {
-            return Err(CompletionError::ProviderError(response.text().await?));
+            return Err(CompletionError::from_response(response).await);
         }
 
         // Use our SSE decoder to directly handle Server-Sent Events format
         let sse_stream = sse_from_response(response);
 
-        let stream: StreamingResult<StreamingCompletionResponse> = Box::pin(stream! {
-            let mut current_tool_call: Option<ToolCallState> = None;
-            let mut sse_stream = Box::pin(sse_stream);
-            let mut input_tokens = 0;
-
-            while let Some(sse_result) = sse_stream.next().await {
-                match sse_result {
-                    Ok(sse) => {
-                        // Parse the SSE data as a StreamingEvent
-                        match serde_json::from_str::<StreamingEvent>(&sse.data) {
-                            Ok(event) => {
-                                match &event {
-                                    StreamingEvent::MessageStart { message } => {
-                                        input_tokens = message.usage.input_tokens;
-                                    },
-                                    StreamingEvent::MessageDelta { delta, usage } => {
-                                        if delta.stop_reason.is_some() {
-
-                                            yield Ok(RawStreamingChoice::FinalResponse(StreamingCompletionResponse {
-                                                usage: PartialUsage {
-                                                    output_tokens: usage.output_tokens,
-                                                    input_tokens: Some(input_tokens.try_into().expect("Failed to convert input_tokens to usize")),
-                                                }
-                                            }))
-                                        }
+        Ok(streaming::StreamingCompletionResponse::stream(
+            stream_events(sse_stream),
+        ))
+    }
+}
+
+/// Convert the server-sent events of a response into the streamed choices of the response.
+fn stream_events<S>(sse_stream: S) -> StreamingResult<StreamingCompletionResponse>
+where
+    S: futures::Stream<Item = Result<ServerSentEvent, SSEDecoderError>> + Send + 'static,
+{
+    Box::pin(stream! {
+        let mut current_tool_call: Option<ToolCallState> = None;
+        let mut sse_stream = Box::pin(sse_stream);
+        let mut input_tokens = 0;
+
+        while let Some(sse_result) = sse_stream.next().await {
+            match sse_result {
+                Ok(sse) => {
+                    // Parse the SSE data as a StreamingEvent
+                    match serde_json::from_str::<StreamingEvent>(&sse.data) {
+                        Ok(event) => {
+                            match &event {
+                                StreamingEvent::MessageStart { message } => {
+                                    input_tokens = message.usage.input_tokens;
+                                },
+                                StreamingEvent::MessageDelta { delta, usage } => {
+                                    if delta.stop_reason.is_some() {
+
+                                        yield Ok(RawStreamingChoice::FinalResponse(StreamingCompletionResponse {
+                                            usage: PartialUsage {
+                                                output_tokens: usage.output_tokens,
+                                                input_tokens: Some(input_tokens.try_into().expect("Failed to convert input_tokens to usize")),
+                                            }
+                                        }))
                                     }
-                                    _ => {}
                                 }
+                                _ => {}
+                            }
 
-                                if let Some(result) = handle_event(&event, &mut current_tool_call) {
-                                    yield result;
-                                }
-                            },
-                            Err(e) => {
-                                if !sse.data.trim().is_empty() {
-                                    yield Err(CompletionError::ResponseError(
-                                        format!("Failed to parse JSON: {} (Data: {})", e, sse.data)
-                                    ));
-                                }
+                            if let Some(result) = handle_event(&event, &mut current_tool_call) {
+                                yield result;
+                            }
+                        },
+                        Err(e) => {
+                            if !sse.data.trim().is_empty() {
+                                yield Err(CompletionError::ResponseError(
+                                    format!("Failed to parse JSON: {} (Data: {})", e, sse.data)
+                                ));
                             }
                         }
-                    },
-                    Err(e) => {
-                        yield Err(CompletionError::ResponseError(format!("SSE Error: {e}")));
-                        break;
                     }
+                },
+                // The body of the response failed to be read: the connection was reset
+                Err(SSEDecoderError::IoError(e)) => {
+                    yield Err(CompletionError::ConnectionReset(e.to_string()));
+                    break;
+                }
+                Err(e) => {
+                    yield Err(CompletionError::ResponseError(format!("SSE Error: {e}")));
+                    break;
                 }
             }
-        });
-
-        Ok(streaming::StreamingCompletionResponse::stream(stream))
-    }
+        }
+    })
 }
 
 fn handle_event(
@@ -234,10 +261,14 @@ fn handle_event(
                 None
             }
             ContentDelta::InputJsonDelta { partial_json } => {
-                if let Some(tool_call) = current_tool_call {
-                    tool_call.input_json.push_str(partial_json);
-                }
-                None
+                let tool_call = current_tool_call.as_mut()?;
+                tool_call.input_json.push_str(partial_json);
+
+                Some(Ok(RawStreamingChoice::ToolCallDelta {
+                    id: tool_call.id.clone(),
+                    name: tool_call.name.clone(),
+                    delta: partial_json.clone(),
+                }))
             }
         },
         StreamingEvent::ContentBlockStart { content_block, .. } => match content_block {
@@ -280,3 +311,33 @@ fn handle_event(
         | StreamingEvent::Unknown => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::{StreamExt, stream};
+
+    use super::stream_events;
+    use crate::{
+        completion::CompletionError, providers::anthropic::decoders::sse::iter_sse_messages,
+        streaming::RawStreamingChoice,
+    };
+
+    #[tokio::test]
+    async fn test_stream_connection_reset() {
+        let chunks = stream::iter([
+            Ok(b"event: content_block_delta\ndata: {\"type\": \"content_block_delta\", \"index\": 0, \"delta\": {\"type\": \"text_delta\", \"text\": \"Hello\"}}\n\n".to_vec()),
+            Err(std::io::Error::other("error decoding response body")),
+        ]);
+
+        let choices = stream_events(iter_sse_messages(chunks))
+            .collect::<Vec<_>>()
+            .await;
+
+        assert!(matches!(&choices[0], Ok(RawStreamingChoice::Message(text)) if text == "Hello"));
+        assert!(matches!(
+            &choices[1],
+            Err(err @ CompletionError::ConnectionReset(_)) if err.is_retryable()
+        ));
+        assert_eq!(choices.len(), 2);
+    }
+}
@@ -19,12 +19,14 @@ use crate::{
     embeddings::{self, EmbeddingError},
     json_utils,
     providers::openai,
+    telemetry,
     transcription::{self, TranscriptionError},
 };
 use reqwest::header::AUTHORIZATION;
 use reqwest::multipart::Part;
 use serde::Deserialize;
 use serde_json::json;
+use std::time::Duration;
 // ================================================================
 // Main Azure OpenAI Client
 // ================================================================
@@ -69,7 +71,7 @@ impl<'a> ClientBuilder<'a> {
         let http_client = if let Some(http_client) = self.http_client {
             http_client
         } else {
-            reqwest::Client::builder().build()?
+            crate::client::default_http_client()?
         };
 
         let api_version = self.api_version.unwrap_or(DEFAULT_API_VERSION);
@@ -414,6 +416,10 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
         self.ndims
     }
 
+    fn max_input_tokens(&self) -> Option<usize> {
+        crate::providers::openai::embedding::max_input_tokens(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn embed_texts(
         &self,
@@ -421,44 +427,51 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
         let documents = documents.into_iter().collect::<Vec<_>>();
 
-        let response = self
-            .client
-            .post_embedding(&self.model)
-            .json(&json!({
-                "input": documents,
-            }))
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            match response.json::<ApiResponse<EmbeddingResponse>>().await? {
-                ApiResponse::Ok(response) => {
-                    tracing::info!(target: "rig",
-                        "Azure embedding token usage: {}",
-                        response.usage
-                    );
-
-                    if response.data.len() != documents.len() {
-                        return Err(EmbeddingError::ResponseError(
-                            "Response data length does not match input length".into(),
-                        ));
+        telemetry::traced(
+            telemetry::embeddings_span(&self.model, documents.len()),
+            async {
+                let response = self
+                    .client
+                    .post_embedding(&self.model)
+                    .json(&json!({
+                        "input": documents,
+                    }))
+                    .send()
+                    .await?;
+
+                if response.status().is_success() {
+                    match response.json::<ApiResponse<EmbeddingResponse>>().await? {
+                        ApiResponse::Ok(response) => {
+                            tracing::info!(target: "rig",
+                                "Azure embedding token usage: {}",
+                                response.usage
+                            );
+                            telemetry::record_input_tokens(response.usage.prompt_tokens as u64);
+
+                            if response.data.len() != documents.len() {
+                                return Err(EmbeddingError::ResponseError(
+                                    "Response data length does not match input length".into(),
+                                ));
+                            }
+
+                            Ok(response
+                                .data
+                                .into_iter()
+                                .zip(documents.into_iter())
+                                .map(|(embedding, document)| embeddings::Embedding {
+                                    document,
+                                    vec: embedding.embedding,
+                                })
+                                .collect())
+                        }
+                        ApiResponse::Err(err) => Err(EmbeddingError::ProviderError(err.message)),
                     }
-
-                    Ok(response
-                        .data
-                        .into_iter()
-                        .zip(documents.into_iter())
-                        .map(|(embedding, document)| embeddings::Embedding {
-                            document,
-                            vec: embedding.embedding,
-                        })
-                        .collect())
+                } else {
+                    Err(EmbeddingError::from_response(response).await)
                 }
-                ApiResponse::Err(err) => Err(EmbeddingError::ProviderError(err.message)),
-            }
-        } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
-        }
+            },
+        )
+        .await
     }
 }
 
@@ -552,7 +565,7 @@ impl CompletionModel {
                 "messages": full_history,
                 "temperature": completion_request.temperature,
                 "tools": completion_request.tools.into_iter().map(openai::ToolDefinition::from).collect::<Vec<_>>(),
-                "tool_choice": "auto",
+                "tool_choice": completion_request.tool_choice.unwrap_or_default(),
             })
         };
 
@@ -570,6 +583,14 @@ impl completion::CompletionModel for CompletionModel {
     type Response = openai::CompletionResponse;
     type StreamingResponse = openai::StreamingCompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn tool_limits(&self) -> crate::tool::ToolLimits {
+        crate::tool::ToolLimits::new().max_tools(openai::MAX_TOOLS)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
@@ -599,7 +620,7 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from_response(response).await)
         }
     }
 
@@ -858,6 +879,13 @@ impl VerifyClient for Client {
         // consuming tokens
         Ok(())
     }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn health_check(&self, _timeout: Duration) -> Result<(), VerifyError> {
+        Err(VerifyError::Unsupported(
+            "Azure OpenAI requests target a deployment, use a completion request instead".into(),
+        ))
+    }
 }
 
 #[cfg(test)]
@@ -898,6 +926,8 @@ mod azure_tests {
                 max_tokens: Some(100),
                 temperature: Some(0.0),
                 tools: vec![],
+                parallel_tool_calls: None,
+                tool_choice: None,
                 additional_params: None,
             })
             .await
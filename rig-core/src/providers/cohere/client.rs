@@ -56,7 +56,7 @@ impl<'a> ClientBuilder<'a> {
         let http_client = if let Some(http_client) = self.http_client {
             http_client
         } else {
-            reqwest::Client::builder().build()?
+            crate::client::default_http_client()?
         };
 
         Ok(Client {
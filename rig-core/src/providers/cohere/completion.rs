@@ -327,7 +327,8 @@ impl TryFrom<message::Message> for Vec<Message> {
                 let mut text_content = vec![];
                 let mut tool_calls = vec![];
                 content.into_iter().for_each(|content| match content {
-                    message::AssistantContent::Text(message::Text { text }) => {
+                    message::AssistantContent::Text(message::Text { text })
+                    | message::AssistantContent::Refusal(message::Refusal { refusal: text }) => {
                         text_content.push(AssistantContent::Text { text });
                     }
                     message::AssistantContent::ToolCall(message::ToolCall {
@@ -505,6 +506,10 @@ impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
     type StreamingResponse = StreamingCompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
@@ -527,7 +532,7 @@ impl completion::CompletionModel for CompletionModel {
                 json_response.try_into()?;
             Ok(completion)
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from_response(response).await)
         }
     }
 
@@ -1,6 +1,7 @@
 use super::{Client, client::ApiResponse};
 
-use crate::embeddings::{self, EmbeddingError};
+use crate::embeddings::{self, EmbedKind, EmbeddingError};
+use crate::telemetry;
 
 use serde::Deserialize;
 use serde_json::json;
@@ -70,63 +71,106 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
         self.ndims
     }
 
+    fn max_input_tokens(&self) -> Option<usize> {
+        // All of Cohere's embedding models (v2 and v3) truncate inputs at 512 tokens
+        Some(512)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn embed_texts(
         &self,
         documents: impl IntoIterator<Item = String>,
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
-        let documents = documents.into_iter().collect::<Vec<_>>();
-
-        let response = self
-            .client
-            .post("/v1/embed")
-            .json(&json!({
-                "model": self.model,
-                "texts": documents,
-                "input_type": self.input_type,
-            }))
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            match response.json::<ApiResponse<EmbeddingResponse>>().await? {
-                ApiResponse::Ok(response) => {
-                    match response.meta {
-                        Some(meta) => tracing::info!(target: "rig",
-                            "Cohere embeddings billed units: {}",
-                            meta.billed_units,
-                        ),
-                        None => tracing::info!(target: "rig",
-                            "Cohere embeddings billed units: n/a",
-                        ),
-                    };
-
-                    if response.embeddings.len() != documents.len() {
-                        return Err(EmbeddingError::DocumentError(
-                            format!(
-                                "Expected {} embeddings, got {}",
-                                documents.len(),
-                                response.embeddings.len()
-                            )
-                            .into(),
-                        ));
-                    }
+        self.embed(documents.into_iter().collect(), &self.input_type)
+            .await
+    }
 
-                    Ok(response
-                        .embeddings
-                        .into_iter()
-                        .zip(documents.into_iter())
-                        .map(|(embedding, document)| embeddings::Embedding {
-                            document,
-                            vec: embedding,
-                        })
-                        .collect())
+    /// Embeds search queries as `search_query` if the input type of the model is `search_document`.
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn embed_texts_with_kind(
+        &self,
+        documents: impl IntoIterator<Item = String> + Send,
+        kind: EmbedKind,
+    ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        let input_type = match kind {
+            EmbedKind::Query if self.input_type == "search_document" => "search_query",
+            _ => &self.input_type,
+        };
+
+        self.embed(documents.into_iter().collect(), input_type)
+            .await
+    }
+}
+
+impl EmbeddingModel {
+    async fn embed(
+        &self,
+        documents: Vec<String>,
+        input_type: &str,
+    ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        telemetry::traced(
+            telemetry::embeddings_span(&self.model, documents.len()),
+            async {
+                let response = self
+                    .client
+                    .post("/v1/embed")
+                    .json(&json!({
+                        "model": self.model,
+                        "texts": documents,
+                        "input_type": input_type,
+                    }))
+                    .send()
+                    .await?;
+
+                if response.status().is_success() {
+                    match response.json::<ApiResponse<EmbeddingResponse>>().await? {
+                        ApiResponse::Ok(response) => {
+                            match response.meta {
+                                Some(meta) => {
+                                    tracing::info!(target: "rig",
+                                        "Cohere embeddings billed units: {}",
+                                        meta.billed_units,
+                                    );
+                                    telemetry::record_input_tokens(
+                                        meta.billed_units.input_tokens as u64,
+                                    );
+                                }
+                                None => tracing::info!(target: "rig",
+                                    "Cohere embeddings billed units: n/a",
+                                ),
+                            };
+
+                            if response.embeddings.len() != documents.len() {
+                                return Err(EmbeddingError::DocumentError(
+                                    format!(
+                                        "Expected {} embeddings, got {}",
+                                        documents.len(),
+                                        response.embeddings.len()
+                                    )
+                                    .into(),
+                                ));
+                            }
+
+                            Ok(response
+                                .embeddings
+                                .into_iter()
+                                .zip(documents.into_iter())
+                                .map(|(embedding, document)| embeddings::Embedding {
+                                    document,
+                                    vec: embedding,
+                                })
+                                .collect())
+                        }
+                        ApiResponse::Err(error) => {
+                            Err(EmbeddingError::ProviderError(error.message))
+                        }
+                    }
+                } else {
+                    Err(EmbeddingError::from_response(response).await)
                 }
-                ApiResponse::Err(error) => Err(EmbeddingError::ProviderError(error.message)),
-            }
-        } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
-        }
+            },
+        )
+        .await
     }
 }
 
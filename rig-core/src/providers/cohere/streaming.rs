@@ -101,11 +101,7 @@ impl CompletionModel {
         let response = self.client.post("/v2/chat").json(&request).send().await?;
 
         if !response.status().is_success() {
-            return Err(CompletionError::ProviderError(format!(
-                "{}: {}",
-                response.status(),
-                response.text().await?
-            )));
+            return Err(CompletionError::from_response(response).await);
         }
 
         let stream = Box::pin(stream! {
@@ -63,7 +63,7 @@ impl<'a> ClientBuilder<'a> {
         let http_client = if let Some(http_client) = self.http_client {
             http_client
         } else {
-            reqwest::Client::builder().build()?
+            crate::client::default_http_client()?
         };
 
         Ok(Client {
@@ -556,7 +556,7 @@ impl CompletionModel {
                 "messages": full_history,
                 "temperature": completion_request.temperature,
                 "tools": completion_request.tools.into_iter().map(ToolDefinition::from).collect::<Vec<_>>(),
-                "tool_choice": "auto",
+                "tool_choice": completion_request.tool_choice.unwrap_or_default(),
             })
         };
 
@@ -574,6 +574,10 @@ impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
     type StreamingResponse = StreamingCompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
@@ -602,7 +606,7 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from_response(response).await)
         }
     }
 
@@ -671,11 +675,7 @@ pub async fn send_compatible_streaming_request(
     let response = request_builder.send().await?;
 
     if !response.status().is_success() {
-        return Err(CompletionError::ProviderError(format!(
-            "{}: {}",
-            response.status(),
-            response.text().await?
-        )));
+        return Err(CompletionError::from_response(response).await);
     }
 
     // Handle OpenAI Compatible SSE chunks
@@ -13,6 +13,7 @@
 use super::openai;
 use crate::client::{
     ClientBuilderError, CompletionClient, ProviderClient, VerifyClient, VerifyError,
+    verify::{probe, with_timeout},
 };
 use crate::json_utils::merge;
 use crate::message::MessageError;
@@ -25,6 +26,7 @@ use crate::{
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
+use std::time::Duration;
 
 // ================================================================
 // Main Galadriel Client
@@ -67,7 +69,7 @@ impl<'a> ClientBuilder<'a> {
         let http_client = if let Some(http_client) = self.http_client {
             http_client
         } else {
-            reqwest::Client::builder().build()?
+            crate::client::default_http_client()?
         };
 
         Ok(Client {
@@ -186,6 +188,17 @@ impl VerifyClient for Client {
         // Could not find an API endpoint to verify the API key
         Ok(())
     }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn health_check(&self, timeout: Duration) -> Result<(), VerifyError> {
+        // Without a verify endpoint, send a completion request limited to a single token
+        let body = json!({
+            "model": GPT_35_TURBO,
+            "messages": [{ "role": "user", "content": "ping" }],
+            "max_tokens": 1,
+        });
+        with_timeout(probe(self.post("/chat/completions").json(&body)), timeout).await
+    }
 }
 
 impl_conversion_traits!(
@@ -412,15 +425,16 @@ impl TryFrom<message::Message> for Message {
 
                 for c in content.iter() {
                     match c {
-                        message::AssistantContent::Text(text) => {
+                        message::AssistantContent::Text(message::Text { text })
+                        | message::AssistantContent::Refusal(message::Refusal { refusal: text }) => {
                             text_content = Some(
                                 text_content
                                     .map(|mut existing| {
                                         existing.push('\n');
-                                        existing.push_str(&text.text);
+                                        existing.push_str(text);
                                         existing
                                     })
-                                    .unwrap_or_else(|| text.text.clone()),
+                                    .unwrap_or_else(|| text.clone()),
                             );
                         }
                         message::AssistantContent::ToolCall(tool_call) => {
@@ -514,7 +528,7 @@ impl CompletionModel {
                 "messages": full_history,
                 "temperature": completion_request.temperature,
                 "tools": completion_request.tools.into_iter().map(ToolDefinition::from).collect::<Vec<_>>(),
-                "tool_choice": "auto",
+                "tool_choice": completion_request.tool_choice.unwrap_or_default(),
             })
         };
 
@@ -541,6 +555,10 @@ impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
     type StreamingResponse = openai::StreamingCompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
@@ -570,7 +588,7 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from_response(response).await)
         }
     }
 
@@ -67,6 +67,10 @@ impl completion::CompletionModel for CompletionModel {
     type Response = GenerateContentResponse;
     type StreamingResponse = StreamingCompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
@@ -102,7 +106,7 @@ impl completion::CompletionModel for CompletionModel {
 
             Ok(completion::CompletionResponse::try_from(response))
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from_response(response).await)
         }?
     }
 
@@ -713,7 +717,10 @@ pub mod gemini_api_types {
     impl From<message::AssistantContent> for Part {
         fn from(content: message::AssistantContent) -> Self {
             match content {
-                message::AssistantContent::Text(message::Text { text }) => text.into(),
+                message::AssistantContent::Text(message::Text { text })
+                | message::AssistantContent::Refusal(message::Refusal { refusal: text }) => {
+                    text.into()
+                }
                 message::AssistantContent::ToolCall(tool_call) => tool_call.into(),
                 message::AssistantContent::Reasoning(message::Reasoning { reasoning, .. }) => {
                     Part {
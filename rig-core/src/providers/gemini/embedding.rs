@@ -5,7 +5,8 @@
 
 use serde_json::json;
 
-use crate::embeddings::{self, EmbeddingError};
+use crate::embeddings::{self, EmbedKind, EmbeddingError};
+use crate::telemetry;
 
 use super::{Client, client::ApiResponse};
 
@@ -40,19 +41,55 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
         }
     }
 
-    /// <https://ai.google.dev/api/embeddings#batch_embed_contents-SHELL>
+    fn max_input_tokens(&self) -> Option<usize> {
+        match self.model.as_str() {
+            EMBEDDING_001 | EMBEDDING_004 => Some(2048),
+            _ => None,
+        }
+    }
+
+    fn max_batch_size(&self) -> Option<usize> {
+        // `batchEmbedContents` accepts at most 100 requests
+        Some(100)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn embed_texts(
         &self,
         documents: impl IntoIterator<Item = String> + Send,
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
-        let documents: Vec<String> = documents.into_iter().collect();
+        self.embed(documents.into_iter().collect(), None).await
+    }
+
+    /// Embeds the texts with the `RETRIEVAL_QUERY` or `RETRIEVAL_DOCUMENT` task type.
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn embed_texts_with_kind(
+        &self,
+        documents: impl IntoIterator<Item = String> + Send,
+        kind: EmbedKind,
+    ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        let task_type = match kind {
+            EmbedKind::Query => "RETRIEVAL_QUERY",
+            EmbedKind::Document => "RETRIEVAL_DOCUMENT",
+        };
 
+        self.embed(documents.into_iter().collect(), Some(task_type))
+            .await
+    }
+}
+
+impl EmbeddingModel {
+    /// <https://ai.google.dev/api/embeddings#batch_embed_contents-SHELL>
+    async fn embed(
+        &self,
+        documents: Vec<String>,
+        task_type: Option<&str>,
+    ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
         // Google batch embed requests. See docstrings for API ref link.
         let requests: Vec<_> = documents
             .iter()
             .map(|doc| {
-                json!({
+                let mut request = json!({
                     "model": format!("models/{}", self.model),
                     "content": json!({
                         "parts": [json!({
@@ -60,7 +97,13 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
                         })]
                     }),
                     "output_dimensionality": self.ndims,
-                })
+                });
+
+                if let Some(task_type) = task_type {
+                    request["task_type"] = json!(task_type);
+                }
+
+                request
             })
             .collect();
 
@@ -68,31 +111,37 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
 
         tracing::info!("{}", serde_json::to_string_pretty(&request_body).unwrap());
 
-        let response = self
-            .client
-            .post(&format!("/v1beta/models/{}:batchEmbedContents", self.model))
-            .json(&request_body)
-            .send()
-            .await?
-            .error_for_status()?
-            .json::<ApiResponse<gemini_api_types::EmbeddingResponse>>()
-            .await?;
-
-        match response {
-            ApiResponse::Ok(response) => {
-                let docs = documents
-                    .into_iter()
-                    .zip(response.embeddings)
-                    .map(|(document, embedding)| embeddings::Embedding {
-                        document,
-                        vec: embedding.values,
-                    })
-                    .collect();
-
-                Ok(docs)
-            }
-            ApiResponse::Err(err) => Err(EmbeddingError::ProviderError(err.message)),
-        }
+        telemetry::traced(
+            telemetry::embeddings_span(&self.model, documents.len()),
+            async {
+                let response = self
+                    .client
+                    .post(&format!("/v1beta/models/{}:batchEmbedContents", self.model))
+                    .json(&request_body)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json::<ApiResponse<gemini_api_types::EmbeddingResponse>>()
+                    .await?;
+
+                match response {
+                    ApiResponse::Ok(response) => {
+                        let docs = documents
+                            .into_iter()
+                            .zip(response.embeddings)
+                            .map(|(document, embedding)| embeddings::Embedding {
+                                document,
+                                vec: embedding.values,
+                            })
+                            .collect();
+
+                        Ok(docs)
+                    }
+                    ApiResponse::Err(err) => Err(EmbeddingError::ProviderError(err.message)),
+                }
+            },
+        )
+        .await
     }
 }
 
@@ -139,6 +139,11 @@ impl CompletionModel {
                     Err(reqwest_eventsource::Error::StreamEnded) => {
                         break;
                     }
+                    Err(reqwest_eventsource::Error::Transport(error)) => {
+                        tracing::error!(?error, "SSE transport error");
+                        yield Err(CompletionError::HttpError(error));
+                        break;
+                    }
                     Err(error) => {
                         tracing::error!(?error, "SSE error");
                         yield Err(CompletionError::ResponseError(error.to_string()));
@@ -312,6 +312,9 @@ impl TryFrom<message::Message> for Vec<Message> {
                     |(mut texts, mut tools), content| {
                         match content {
                             message::AssistantContent::Text(text) => texts.push(text),
+                            message::AssistantContent::Refusal(message::Refusal { refusal }) => {
+                                texts.push(message::Text { text: refusal })
+                            }
                             message::AssistantContent::ToolCall(tool_call) => tools.push(tool_call),
                             message::AssistantContent::Reasoning(_) => {
                                 unimplemented!("Reasoning is not supported on HuggingFace via Rig");
@@ -545,7 +548,7 @@ impl CompletionModel {
                 "messages": full_history,
                 "temperature": completion_request.temperature,
                 "tools": completion_request.tools.clone().into_iter().map(ToolDefinition::from).collect::<Vec<_>>(),
-                "tool_choice": "auto",
+                "tool_choice": completion_request.tool_choice.unwrap_or_default(),
             })
         };
         Ok(request)
@@ -556,6 +559,10 @@ impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
     type StreamingResponse = StreamingCompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
@@ -588,11 +595,7 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.to_string())),
             }
         } else {
-            Err(CompletionError::ProviderError(format!(
-                "{}: {}",
-                response.status(),
-                response.text().await?
-            )))
+            Err(CompletionError::from_response(response).await)
         }
     }
 
@@ -0,0 +1,484 @@
+//! Jina AI API client and Rig integration
+//!
+//! # Example
+//! ```
+//! use rig::providers::jina;
+//!
+//! let client = jina::Client::new("YOUR_API_KEY");
+//!
+//! let embedding_model = client
+//!     .embedding_model(jina::JINA_EMBEDDINGS_V3)
+//!     .task(jina::Task::RetrievalPassage);
+//! ```
+use crate::client::{
+    ClientBuilderError, EmbeddingsClient, ProviderClient, VerifyClient, VerifyError,
+    verify::{probe, with_timeout},
+};
+use crate::embeddings::{EmbedKind, EmbeddingError};
+use crate::{embeddings, impl_conversion_traits, telemetry};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+
+// ================================================================
+// Main Jina AI Client
+// ================================================================
+const JINA_API_BASE_URL: &str = "https://api.jina.ai/v1";
+
+pub struct ClientBuilder<'a> {
+    api_key: &'a str,
+    base_url: &'a str,
+    http_client: Option<reqwest::Client>,
+}
+
+impl<'a> ClientBuilder<'a> {
+    pub fn new(api_key: &'a str) -> Self {
+        Self {
+            api_key,
+            base_url: JINA_API_BASE_URL,
+            http_client: None,
+        }
+    }
+
+    pub fn base_url(mut self, base_url: &'a str) -> Self {
+        self.base_url = base_url;
+        self
+    }
+
+    pub fn custom_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    pub fn build(self) -> Result<Client, ClientBuilderError> {
+        let http_client = if let Some(http_client) = self.http_client {
+            http_client
+        } else {
+            crate::client::default_http_client()?
+        };
+
+        Ok(Client {
+            base_url: self.base_url.to_string(),
+            api_key: self.api_key.to_string(),
+            http_client,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct Client {
+    base_url: String,
+    api_key: String,
+    http_client: reqwest::Client,
+}
+
+impl std::fmt::Debug for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Client")
+            .field("base_url", &self.base_url)
+            .field("http_client", &self.http_client)
+            .field("api_key", &"<REDACTED>")
+            .finish()
+    }
+}
+
+impl Client {
+    /// Create a new Jina AI client builder.
+    ///
+    /// # Example
+    /// ```
+    /// use rig::providers::jina::{ClientBuilder, self};
+    ///
+    /// // Initialize the Jina AI client
+    /// let jina = Client::builder("your-jina-api-key")
+    ///    .build()
+    /// ```
+    pub fn builder(api_key: &str) -> ClientBuilder<'_> {
+        ClientBuilder::new(api_key)
+    }
+
+    /// Create a new Jina AI client. For more control, use the `builder` method.
+    ///
+    /// # Panics
+    /// - If the reqwest client cannot be built (if the TLS backend cannot be initialized).
+    pub fn new(api_key: &str) -> Self {
+        Self::builder(api_key)
+            .build()
+            .expect("Jina AI client should build")
+    }
+
+    pub(crate) fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        let url = format!("{}/{}", self.base_url, path).replace("//", "/");
+        self.http_client.post(url).bearer_auth(&self.api_key)
+    }
+}
+
+impl VerifyClient for Client {
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn verify(&self) -> Result<(), VerifyError> {
+        // No API endpoint to verify the API key
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn health_check(&self, timeout: Duration) -> Result<(), VerifyError> {
+        // Without a verify endpoint, embed a single word
+        let body = json!({
+            "model": JINA_EMBEDDINGS_V2_BASE_EN,
+            "input": ["ping"],
+        });
+        with_timeout(probe(self.post("/embeddings").json(&body)), timeout).await
+    }
+}
+
+impl_conversion_traits!(
+    AsCompletion,
+    AsTranscription,
+    AsImageGeneration,
+    AsAudioGeneration for Client
+);
+
+impl ProviderClient for Client {
+    /// Create a new Jina AI client from the `JINA_API_KEY` environment variable.
+    /// Panics if the environment variable is not set.
+    fn from_env() -> Self {
+        let api_key = std::env::var("JINA_API_KEY").expect("JINA_API_KEY not set");
+        Self::new(&api_key)
+    }
+
+    fn from_val(input: crate::client::ProviderValue) -> Self {
+        let crate::client::ProviderValue::Simple(api_key) = input else {
+            panic!("Incorrect provider value type")
+        };
+        Self::new(&api_key)
+    }
+}
+
+/// See Jina AI's documentation: <https://jina.ai/embeddings>
+impl EmbeddingsClient for Client {
+    type EmbeddingModel = EmbeddingModel;
+
+    /// # Panics
+    /// - If the number of dimensions of `model` is unknown: the number of dimensions of other
+    ///   models must be set with [EmbeddingsClient::embedding_model_with_ndims].
+    fn embedding_model(&self, model: &str) -> Self::EmbeddingModel {
+        let ndims = match model {
+            JINA_EMBEDDINGS_V4 => 2048,
+            JINA_EMBEDDINGS_V3 | JINA_CLIP_V2 => 1024,
+            JINA_EMBEDDINGS_V2_BASE_EN | JINA_EMBEDDINGS_V2_BASE_CODE => 768,
+            _ => panic!(
+                "Unknown number of dimensions of Jina AI model `{model}`, use `embedding_model_with_ndims`"
+            ),
+        };
+        EmbeddingModel::new(self.clone(), model, ndims)
+    }
+
+    fn embedding_model_with_ndims(&self, model: &str, ndims: usize) -> Self::EmbeddingModel {
+        EmbeddingModel::new(self.clone(), model, ndims)
+    }
+}
+
+impl EmbeddingModel {
+    pub fn new(client: Client, model: &str, ndims: usize) -> Self {
+        Self {
+            client,
+            model: model.to_string(),
+            ndims,
+            task: None,
+        }
+    }
+
+    /// Set the task the embeddings are generated for (only supported by `jina-embeddings-v3`
+    /// and later). For retrieval, documents should be embedded with [Task::RetrievalPassage]
+    /// and search queries with [Task::RetrievalQuery].
+    ///
+    /// Note: [EmbeddingsBuilder](crate::embeddings::EmbeddingsBuilder) and vector store indexes
+    /// set the retrieval task of their texts themselves (see [EmbedKind]).
+    pub fn task(mut self, task: Task) -> Self {
+        self.task = Some(task);
+        self
+    }
+}
+
+// ================================================================
+// Jina AI Embedding API
+// ================================================================
+/// `jina-embeddings-v4` embedding model (Jina AI)
+pub const JINA_EMBEDDINGS_V4: &str = "jina-embeddings-v4";
+/// `jina-embeddings-v3` embedding model (Jina AI)
+pub const JINA_EMBEDDINGS_V3: &str = "jina-embeddings-v3";
+/// `jina-clip-v2` embedding model (Jina AI)
+pub const JINA_CLIP_V2: &str = "jina-clip-v2";
+/// `jina-embeddings-v2-base-en` embedding model (Jina AI)
+pub const JINA_EMBEDDINGS_V2_BASE_EN: &str = "jina-embeddings-v2-base-en";
+/// `jina-embeddings-v2-base-code` embedding model (Jina AI)
+pub const JINA_EMBEDDINGS_V2_BASE_CODE: &str = "jina-embeddings-v2-base-code";
+
+/// Task the embeddings are generated for (see [EmbeddingModel::task]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Task {
+    /// Search query
+    #[serde(rename = "retrieval.query")]
+    RetrievalQuery,
+    /// Document to be retrieved
+    #[serde(rename = "retrieval.passage")]
+    RetrievalPassage,
+    /// Symmetric similarity (e.g.: deduplication)
+    #[serde(rename = "text-matching")]
+    TextMatching,
+    /// Classification
+    #[serde(rename = "classification")]
+    Classification,
+    /// Clustering
+    #[serde(rename = "separation")]
+    Separation,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingResponse {
+    pub object: String,
+    pub data: Vec<EmbeddingData>,
+    pub model: String,
+    pub usage: Usage,
+}
+
+impl EmbeddingResponse {
+    /// The embeddings of `documents`, matched with the data of the response by their index.
+    fn embeddings(
+        self,
+        documents: Vec<String>,
+    ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        if self.data.len() != documents.len() {
+            return Err(EmbeddingError::ResponseError(
+                "Response data length does not match input length".into(),
+            ));
+        }
+
+        let mut data = self.data;
+        data.sort_by_key(|embedding| embedding.index);
+        Ok(data
+            .into_iter()
+            .zip(documents)
+            .map(|(embedding, document)| embeddings::Embedding {
+                document,
+                vec: embedding.embedding,
+            })
+            .collect())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: usize,
+    pub total_tokens: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiErrorResponse {
+    pub(crate) detail: String,
+}
+
+impl From<ApiErrorResponse> for EmbeddingError {
+    fn from(err: ApiErrorResponse) -> Self {
+        EmbeddingError::ProviderError(err.detail)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum ApiResponse<T> {
+    Ok(T),
+    Err(ApiErrorResponse),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingData {
+    pub object: String,
+    pub embedding: Vec<f64>,
+    pub index: usize,
+}
+
+#[derive(Clone)]
+pub struct EmbeddingModel {
+    client: Client,
+    pub model: String,
+    ndims: usize,
+    /// Task the embeddings are generated for, if any (see [EmbeddingModel::task])
+    pub task: Option<Task>,
+}
+
+impl embeddings::EmbeddingModel for EmbeddingModel {
+    const MAX_DOCUMENTS: usize = 1024;
+
+    fn ndims(&self) -> usize {
+        self.ndims
+    }
+
+    fn max_input_tokens(&self) -> Option<usize> {
+        match self.model.as_str() {
+            JINA_EMBEDDINGS_V4 => Some(32_768),
+            JINA_EMBEDDINGS_V3
+            | JINA_CLIP_V2
+            | JINA_EMBEDDINGS_V2_BASE_EN
+            | JINA_EMBEDDINGS_V2_BASE_CODE => Some(8192),
+            _ => None,
+        }
+    }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn embed_texts(
+        &self,
+        documents: impl IntoIterator<Item = String>,
+    ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        self.embed(documents.into_iter().collect(), self.task).await
+    }
+
+    /// Embeds the texts with the retrieval task matching `kind`, unless [EmbeddingModel::task]
+    /// is set to a non-retrieval task or the model does not support tasks.
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn embed_texts_with_kind(
+        &self,
+        documents: impl IntoIterator<Item = String> + Send,
+        kind: EmbedKind,
+    ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        let task = match self.task {
+            None | Some(Task::RetrievalQuery | Task::RetrievalPassage) if self.supports_tasks() => {
+                Some(match kind {
+                    EmbedKind::Query => Task::RetrievalQuery,
+                    EmbedKind::Document => Task::RetrievalPassage,
+                })
+            }
+            task => task,
+        };
+
+        self.embed(documents.into_iter().collect(), task).await
+    }
+}
+
+impl EmbeddingModel {
+    /// Whether the model supports tasks (i.e.: all models but the `jina-embeddings-v2` family).
+    fn supports_tasks(&self) -> bool {
+        !self.model.starts_with("jina-embeddings-v2")
+    }
+
+    async fn embed(
+        &self,
+        documents: Vec<String>,
+        task: Option<Task>,
+    ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        telemetry::traced(
+            telemetry::embeddings_span(&self.model, documents.len()),
+            async {
+                let mut body = json!({
+                    "model": self.model,
+                    "input": documents,
+                });
+
+                if let Some(task) = task {
+                    body["task"] = json!(task);
+                }
+
+                let response = self.client.post("/embeddings").json(&body).send().await?;
+
+                if response.status().is_success() {
+                    match response.json::<ApiResponse<EmbeddingResponse>>().await? {
+                        ApiResponse::Ok(response) => {
+                            tracing::info!(target: "rig",
+                                "Jina AI embedding token usage: {}",
+                                response.usage.total_tokens
+                            );
+                            telemetry::record_input_tokens(response.usage.prompt_tokens as u64);
+
+                            response.embeddings(documents)
+                        }
+                        ApiResponse::Err(err) => Err(err.into()),
+                    }
+                } else {
+                    Err(EmbeddingError::from_response(response).await)
+                }
+            },
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::EmbeddingsClient;
+
+    #[test]
+    fn test_embedding_response() {
+        // The data of the response is not necessarily in the order of the inputs
+        let response = json!({
+            "model": "jina-embeddings-v3",
+            "object": "list",
+            "usage": { "total_tokens": 8, "prompt_tokens": 8 },
+            "data": [
+                { "object": "embedding", "index": 1, "embedding": [0.3, 0.4] },
+                { "object": "embedding", "index": 0, "embedding": [0.1, 0.2] }
+            ]
+        });
+
+        let ApiResponse::Ok(response) =
+            serde_json::from_value::<ApiResponse<EmbeddingResponse>>(response).unwrap()
+        else {
+            panic!("Expected an embedding response");
+        };
+        assert_eq!(response.usage.prompt_tokens, 8);
+
+        let embeddings = response
+            .embeddings(vec!["first".to_string(), "second".to_string()])
+            .unwrap();
+        assert_eq!(embeddings[0].document, "first");
+        assert_eq!(embeddings[0].vec, vec![0.1, 0.2]);
+        assert_eq!(embeddings[1].document, "second");
+        assert_eq!(embeddings[1].vec, vec![0.3, 0.4]);
+    }
+
+    #[test]
+    fn test_embedding_response_length_mismatch() {
+        let response = serde_json::from_value::<EmbeddingResponse>(json!({
+            "model": "jina-embeddings-v3",
+            "object": "list",
+            "usage": { "total_tokens": 4, "prompt_tokens": 4 },
+            "data": [{ "object": "embedding", "index": 0, "embedding": [0.1, 0.2] }]
+        }))
+        .unwrap();
+
+        let result = response.embeddings(vec!["first".to_string(), "second".to_string()]);
+        assert!(matches!(result, Err(EmbeddingError::ResponseError(_))));
+    }
+
+    #[test]
+    fn test_error_response() {
+        let response = serde_json::from_value::<ApiResponse<EmbeddingResponse>>(json!({
+            "detail": "Invalid API key"
+        }))
+        .unwrap();
+
+        let ApiResponse::Err(err) = response else {
+            panic!("Expected an error response");
+        };
+        assert!(matches!(
+            EmbeddingError::from(err),
+            EmbeddingError::ProviderError(detail) if detail == "Invalid API key"
+        ));
+    }
+
+    #[test]
+    fn test_embedding_model_ndims() {
+        let client = Client::new("invalid-key");
+        let model = client.embedding_model(JINA_EMBEDDINGS_V3);
+        assert_eq!(embeddings::EmbeddingModel::ndims(&model), 1024);
+
+        let model = client.embedding_model_with_ndims("jina-embeddings-v5", 512);
+        assert_eq!(embeddings::EmbeddingModel::ndims(&model), 512);
+    }
+
+    #[test]
+    #[should_panic(expected = "embedding_model_with_ndims")]
+    fn test_embedding_model_unknown_ndims() {
+        Client::new("invalid-key").embedding_model("jina-embeddings-v5");
+    }
+}
@@ -149,7 +149,7 @@ impl<'a> ClientBuilder<'a> {
         let http_client = if let Some(http_client) = self.http_client {
             http_client
         } else {
-            reqwest::Client::builder().build()?
+            crate::client::default_http_client()?
         };
 
         Ok(Client {
@@ -392,6 +392,10 @@ impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
     type StreamingResponse = openai::StreamingCompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
@@ -415,11 +419,7 @@ impl completion::CompletionModel for CompletionModel {
             .map_err(|e| CompletionError::ProviderError(e.to_string()))?;
 
         if !response.status().is_success() {
-            let status = response.status().as_u16();
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(CompletionError::ProviderError(format!(
-                "API error: {status} - {error_text}"
-            )));
+            return Err(CompletionError::from_response(response).await);
         }
 
         let response: CompletionResponse = response
@@ -118,6 +118,9 @@ impl TryFrom<message::Message> for Vec<Message> {
                     |(mut texts, mut tools), content| {
                         match content {
                             message::AssistantContent::Text(text) => texts.push(text),
+                            message::AssistantContent::Refusal(message::Refusal { refusal }) => {
+                                texts.push(message::Text { text: refusal })
+                            }
                             message::AssistantContent::ToolCall(tool_call) => tools.push(tool_call),
                             message::AssistantContent::Reasoning(_) => {
                                 unimplemented!("Reasoning content is not currently supported on Mistral via Rig");
@@ -300,7 +303,7 @@ impl CompletionModel {
                 "model": self.model,
                 "messages": full_history,
                 "tools": completion_request.tools.into_iter().map(ToolDefinition::from).collect::<Vec<_>>(),
-                "tool_choice": "auto",
+                "tool_choice": completion_request.tool_choice.unwrap_or_default(),
             })
         };
 
@@ -415,6 +418,10 @@ impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
     type StreamingResponse = CompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
@@ -442,7 +449,7 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from_response(response).await)
         }
     }
 
@@ -456,8 +463,9 @@ impl completion::CompletionModel for CompletionModel {
         let stream = Box::pin(stream! {
             for c in resp.choice.clone() {
                 match c {
-                    message::AssistantContent::Text(t) => {
-                        yield Ok(RawStreamingChoice::Message(t.text.clone()))
+                    message::AssistantContent::Text(message::Text { text })
+                    | message::AssistantContent::Refusal(message::Refusal { refusal: text }) => {
+                        yield Ok(RawStreamingChoice::Message(text))
                     }
                     message::AssistantContent::ToolCall(tc) => {
                         yield Ok(RawStreamingChoice::ToolCall {
@@ -2,6 +2,7 @@ use serde::Deserialize;
 use serde_json::json;
 
 use crate::embeddings::{self, EmbeddingError};
+use crate::telemetry;
 
 use super::client::{ApiResponse, Client, Usage};
 
@@ -35,6 +36,13 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
         self.ndims
     }
 
+    fn max_input_tokens(&self) -> Option<usize> {
+        match self.model.as_str() {
+            MISTRAL_EMBED => Some(8192),
+            _ => None,
+        }
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn embed_texts(
         &self,
@@ -42,45 +50,52 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
         let documents = documents.into_iter().collect::<Vec<_>>();
 
-        let response = self
-            .client
-            .post("v1/embeddings")
-            .json(&json!({
-                "model": self.model,
-                "input": documents,
-            }))
-            .send()
-            .await?;
+        telemetry::traced(
+            telemetry::embeddings_span(&self.model, documents.len()),
+            async {
+                let response = self
+                    .client
+                    .post("v1/embeddings")
+                    .json(&json!({
+                        "model": self.model,
+                        "input": documents,
+                    }))
+                    .send()
+                    .await?;
 
-        if response.status().is_success() {
-            match response.json::<ApiResponse<EmbeddingResponse>>().await? {
-                ApiResponse::Ok(response) => {
-                    tracing::debug!(target: "rig",
-                        "Mistral embedding token usage: {}",
-                        response.usage
-                    );
+                if response.status().is_success() {
+                    match response.json::<ApiResponse<EmbeddingResponse>>().await? {
+                        ApiResponse::Ok(response) => {
+                            tracing::debug!(target: "rig",
+                                "Mistral embedding token usage: {}",
+                                response.usage
+                            );
+                            telemetry::record_input_tokens(response.usage.prompt_tokens as u64);
 
-                    if response.data.len() != documents.len() {
-                        return Err(EmbeddingError::ResponseError(
-                            "Response data length does not match input length".into(),
-                        ));
-                    }
+                            if response.data.len() != documents.len() {
+                                return Err(EmbeddingError::ResponseError(
+                                    "Response data length does not match input length".into(),
+                                ));
+                            }
 
-                    Ok(response
-                        .data
-                        .into_iter()
-                        .zip(documents.into_iter())
-                        .map(|(embedding, document)| embeddings::Embedding {
-                            document,
-                            vec: embedding.embedding,
-                        })
-                        .collect())
+                            Ok(response
+                                .data
+                                .into_iter()
+                                .zip(documents.into_iter())
+                                .map(|(embedding, document)| embeddings::Embedding {
+                                    document,
+                                    vec: embedding.embedding,
+                                })
+                                .collect())
+                        }
+                        ApiResponse::Err(err) => Err(EmbeddingError::ProviderError(err.message)),
+                    }
+                } else {
+                    Err(EmbeddingError::from_response(response).await)
                 }
-                ApiResponse::Err(err) => Err(EmbeddingError::ProviderError(err.message)),
-            }
-        } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
-        }
+            },
+        )
+        .await
     }
 }
 
@@ -12,6 +12,10 @@
 //! - Azure OpenAI
 //! - Mira
 //!
+//! Providers with heavier dependencies are available as companion crates, e.g.: AWS Bedrock
+//! (`rig-bedrock`, which authenticates with the AWS SDK credential chain and calls the Bedrock
+//! Runtime `Converse` and `ConverseStream` APIs) and Fastembed (`rig-fastembed`).
+//!
 //! Each provider has its own module, which contains a `Client` implementation that can
 //! be used to initialize completion and embedding models and execute requests to those models.
 //!
@@ -54,6 +58,7 @@ pub mod gemini;
 pub mod groq;
 pub mod huggingface;
 pub mod hyperbolic;
+pub mod jina;
 pub mod mira;
 pub mod mistral;
 pub mod moonshot;
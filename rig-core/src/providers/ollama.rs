@@ -51,7 +51,7 @@ use crate::{
     embeddings::{self, EmbeddingError, EmbeddingsBuilder},
     impl_conversion_traits, json_utils, message,
     message::{ImageDetail, Text},
-    streaming,
+    streaming, telemetry,
 };
 use async_stream::stream;
 use futures::StreamExt;
@@ -92,7 +92,7 @@ impl<'a> ClientBuilder<'a> {
         let http_client = if let Some(http_client) = self.http_client {
             http_client
         } else {
-            reqwest::Client::builder().build()?
+            crate::client::default_http_client()?
         };
 
         Ok(Client {
@@ -292,32 +292,38 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
             "model": self.model,
             "input": docs,
         });
-        let response = self
-            .client
-            .post("api/embed")?
-            .json(&payload)
-            .send()
-            .await
-            .map_err(|e| EmbeddingError::ProviderError(e.to_string()))?;
-        if response.status().is_success() {
-            let api_resp: EmbeddingResponse = response
-                .json()
+        telemetry::traced(telemetry::embeddings_span(&self.model, docs.len()), async {
+            let response = self
+                .client
+                .post("api/embed")?
+                .json(&payload)
+                .send()
                 .await
                 .map_err(|e| EmbeddingError::ProviderError(e.to_string()))?;
-            if api_resp.embeddings.len() != docs.len() {
-                return Err(EmbeddingError::ResponseError(
-                    "Number of returned embeddings does not match input".into(),
-                ));
+            if response.status().is_success() {
+                let api_resp: EmbeddingResponse = response
+                    .json()
+                    .await
+                    .map_err(|e| EmbeddingError::ProviderError(e.to_string()))?;
+                if let Some(tokens) = api_resp.prompt_eval_count {
+                    telemetry::record_input_tokens(tokens);
+                }
+                if api_resp.embeddings.len() != docs.len() {
+                    return Err(EmbeddingError::ResponseError(
+                        "Number of returned embeddings does not match input".into(),
+                    ));
+                }
+                Ok(api_resp
+                    .embeddings
+                    .into_iter()
+                    .zip(docs.into_iter())
+                    .map(|(vec, document)| embeddings::Embedding { document, vec })
+                    .collect())
+            } else {
+                Err(EmbeddingError::from_response(response).await)
             }
-            Ok(api_resp
-                .embeddings
-                .into_iter()
-                .zip(docs.into_iter())
-                .map(|(vec, document)| embeddings::Embedding { document, vec })
-                .collect())
-        } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
-        }
+        })
+        .await
     }
 }
 
@@ -521,6 +527,10 @@ impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
     type StreamingResponse = StreamingCompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
@@ -546,11 +556,7 @@ impl completion::CompletionModel for CompletionModel {
             let conv: completion::CompletionResponse<CompletionResponse> = chat_resp.try_into()?;
             Ok(conv)
         } else {
-            let err_text = response
-                .text()
-                .await
-                .map_err(|e| CompletionError::ProviderError(e.to_string()))?;
-            Err(CompletionError::ProviderError(err_text))
+            Err(CompletionError::from_response(response).await)
         }
     }
 
@@ -572,11 +578,7 @@ impl completion::CompletionModel for CompletionModel {
             .map_err(|e| CompletionError::ProviderError(e.to_string()))?;
 
         if !response.status().is_success() {
-            let err_text = response
-                .text()
-                .await
-                .map_err(|e| CompletionError::ProviderError(e.to_string()))?;
-            return Err(CompletionError::ProviderError(err_text));
+            return Err(CompletionError::from_response(response).await);
         }
 
         let stream = Box::pin(stream! {
@@ -809,7 +811,12 @@ impl TryFrom<crate::message::Message> for Vec<Message> {
                     (Vec::new(), Vec::new()),
                     |(mut texts, mut tools), content| {
                         match content {
-                            crate::message::AssistantContent::Text(text) => texts.push(text.text),
+                            crate::message::AssistantContent::Text(crate::message::Text {
+                                text,
+                            })
+                            | crate::message::AssistantContent::Refusal(
+                                crate::message::Refusal { refusal: text },
+                            ) => texts.push(text),
                             crate::message::AssistantContent::ToolCall(tool_call) => {
                                 tools.push(tool_call)
                             }
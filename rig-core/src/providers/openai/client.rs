@@ -9,8 +9,8 @@ use super::image_generation::ImageGenerationModel;
 use super::transcription::TranscriptionModel;
 
 use crate::client::{
-    ClientBuilderError, CompletionClient, EmbeddingsClient, ProviderClient, TranscriptionClient,
-    VerifyClient, VerifyError,
+    ClientBuilderError, CompletionClient, EmbeddingsClient, ProviderClient, RequestIdGenerator,
+    TranscriptionClient, VerifyClient, VerifyError, identify_request,
 };
 
 #[cfg(feature = "audio")]
@@ -28,6 +28,8 @@ const OPENAI_API_BASE_URL: &str = "https://api.openai.com/v1";
 pub struct ClientBuilder<'a> {
     api_key: &'a str,
     base_url: &'a str,
+    user_agent: Option<&'a str>,
+    request_id: Option<RequestIdGenerator>,
     http_client: Option<reqwest::Client>,
 }
 
@@ -36,6 +38,8 @@ impl<'a> ClientBuilder<'a> {
         Self {
             api_key,
             base_url: OPENAI_API_BASE_URL,
+            user_agent: None,
+            request_id: None,
             http_client: None,
         }
     }
@@ -45,6 +49,19 @@ impl<'a> ClientBuilder<'a> {
         self
     }
 
+    /// Set the `User-Agent` header of the requests sent by the client (e.g.: `my-app/1.0`).
+    pub fn user_agent(mut self, user_agent: &'a str) -> Self {
+        self.user_agent = Some(user_agent);
+        self
+    }
+
+    /// Generate an id for each request sent by the client, sent as its `X-Request-Id` header
+    /// (see [crate::client::REQUEST_ID_HEADER]) and logged in rig's traces.
+    pub fn request_id(mut self, request_id: impl Fn() -> String + Send + Sync + 'static) -> Self {
+        self.request_id = Some(std::sync::Arc::new(request_id));
+        self
+    }
+
     pub fn custom_client(mut self, client: reqwest::Client) -> Self {
         self.http_client = Some(client);
         self
@@ -54,12 +71,14 @@ impl<'a> ClientBuilder<'a> {
         let http_client = if let Some(http_client) = self.http_client {
             http_client
         } else {
-            reqwest::Client::builder().build()?
+            crate::client::default_http_client()?
         };
 
         Ok(Client {
             base_url: self.base_url.to_string(),
             api_key: self.api_key.to_string(),
+            user_agent: self.user_agent.map(str::to_string),
+            request_id: self.request_id,
             http_client,
         })
     }
@@ -69,6 +88,8 @@ impl<'a> ClientBuilder<'a> {
 pub struct Client {
     base_url: String,
     api_key: String,
+    user_agent: Option<String>,
+    request_id: Option<RequestIdGenerator>,
     http_client: reqwest::Client,
 }
 
@@ -78,6 +99,8 @@ impl std::fmt::Debug for Client {
             .field("base_url", &self.base_url)
             .field("http_client", &self.http_client)
             .field("api_key", &"<REDACTED>")
+            .field("user_agent", &self.user_agent)
+            .field("request_id", &self.request_id.as_ref().map(|_| "<fn>"))
             .finish()
     }
 }
@@ -107,14 +130,38 @@ impl Client {
             .expect("OpenAI client should build")
     }
 
+    /// Create a moderation model with the given name, to check the prompts of agents (see
+    /// [AgentBuilder::moderator](crate::agent::AgentBuilder::moderator)).
+    ///
+    /// # Example
+    /// ```
+    /// use rig::providers::openai::{Client, self};
+    ///
+    /// // Initialize the OpenAI client
+    /// let openai = Client::new("your-open-ai-api-key");
+    ///
+    /// let moderation = openai.moderation_model(openai::OMNI_MODERATION_LATEST);
+    /// ```
+    pub fn moderation_model(&self, model: &str) -> super::ModerationModel {
+        super::ModerationModel::new(self.clone(), model)
+    }
+
     pub(crate) fn post(&self, path: &str) -> reqwest::RequestBuilder {
         let url = format!("{}/{}", self.base_url, path).replace("//", "/");
-        self.http_client.post(url).bearer_auth(&self.api_key)
+        self.identify(self.http_client.post(url).bearer_auth(&self.api_key))
     }
 
     pub(crate) fn get(&self, path: &str) -> reqwest::RequestBuilder {
         let url = format!("{}/{}", self.base_url, path).replace("//", "/");
-        self.http_client.get(url).bearer_auth(&self.api_key)
+        self.identify(self.http_client.get(url).bearer_auth(&self.api_key))
+    }
+
+    fn identify(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        identify_request(
+            request,
+            self.user_agent.as_deref(),
+            self.request_id.as_ref(),
+        )
     }
 }
 
@@ -553,4 +600,24 @@ mod tests {
         assert_eq!(original_user_message[0], user_message);
         assert_eq!(original_assistant_message[0], assistant_message);
     }
+
+    #[test]
+    fn test_request_identity() {
+        let client = crate::providers::openai::Client::builder("api-key")
+            .user_agent("my-app/1.0")
+            .request_id(|| "req-123".to_string())
+            .build()
+            .unwrap();
+
+        let request = client.post("/chat/completions").build().unwrap();
+        assert_eq!(request.headers()["user-agent"], "my-app/1.0");
+        assert_eq!(request.headers()["x-request-id"], "req-123");
+
+        // Neither header is set by default
+        let request = crate::providers::openai::Client::new("api-key")
+            .get("/models")
+            .build()
+            .unwrap();
+        assert!(!request.headers().contains_key("x-request-id"));
+    }
 }
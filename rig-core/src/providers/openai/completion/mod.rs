@@ -4,7 +4,7 @@
 
 use super::{ApiErrorResponse, ApiResponse, Client, streaming::StreamingCompletionResponse};
 use crate::completion::{CompletionError, CompletionRequest};
-use crate::message::{AudioMediaType, ImageDetail};
+use crate::message::{AudioMediaType, ImageDetail, MimeType};
 use crate::one_or_many::string_or_one_or_many;
 use crate::{OneOrMany, completion, json_utils, message};
 use serde::{Deserialize, Serialize};
@@ -16,6 +16,48 @@ use std::str::FromStr;
 
 pub mod streaming;
 
+/// Maximum number of tools in an OpenAI completion request
+pub const MAX_TOOLS: usize = 128;
+
+/// Known context windows (in tokens) as `(model, context window)`.
+/// More specific model names must come before the model names they are prefixed by.
+const CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("o4-mini", 200_000),
+    ("o3-mini", 200_000),
+    ("o3", 200_000),
+    ("o1-pro", 200_000),
+    ("o1-mini", 128_000),
+    ("o1-preview", 128_000),
+    ("o1", 200_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4.5-preview", 128_000),
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4-0125-preview", 128_000),
+    ("gpt-4-1106-preview", 128_000),
+    ("gpt-4-vision-preview", 128_000),
+    ("gpt-4-1106-vision-preview", 128_000),
+    ("gpt-4-32k", 32_768),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo-instruct", 4_096),
+    ("gpt-3.5-turbo", 16_385),
+];
+
+/// Returns the context window (in tokens) of the given OpenAI model, if known.
+/// Dated model snapshots (e.g.: `gpt-4o-mini-2024-07-18`) use the context window of their base
+/// model.
+pub fn context_window(model: &str) -> Option<usize> {
+    CONTEXT_WINDOWS
+        .iter()
+        .find(|(name, _)| {
+            model == *name
+                || model
+                    .strip_prefix(name)
+                    .is_some_and(|suffix| suffix.starts_with('-'))
+        })
+        .map(|(_, context_window)| *context_window)
+}
+
 /// `o4-mini-2025-04-16` completion model
 pub const O4_MINI_2025_04_16: &str = "o4-mini-2025-04-16";
 /// `o4-mini` completion model
@@ -133,6 +175,7 @@ pub enum Message {
     #[serde(rename = "tool")]
     ToolResult {
         tool_call_id: String,
+        #[serde(deserialize_with = "string_or_one_or_many")]
         content: OneOrMany<ToolResultContent>,
     },
 }
@@ -176,7 +219,7 @@ impl From<AssistantContent> for completion::AssistantContent {
     fn from(value: AssistantContent) -> Self {
         match value {
             AssistantContent::Text { text } => completion::AssistantContent::text(text),
-            AssistantContent::Refusal { refusal } => completion::AssistantContent::text(refusal),
+            AssistantContent::Refusal { refusal } => completion::AssistantContent::refusal(refusal),
         }
     }
 }
@@ -203,6 +246,22 @@ pub struct ImageUrl {
     pub detail: ImageDetail,
 }
 
+impl From<message::Image> for ImageUrl {
+    fn from(image: message::Image) -> Self {
+        let url = match (image.format, image.media_type) {
+            (Some(message::ContentFormat::Base64) | None, Some(media_type)) => {
+                format!("data:{};base64,{}", media_type.to_mime_type(), image.data)
+            }
+            _ => image.data,
+        };
+
+        ImageUrl {
+            url,
+            detail: image.detail.unwrap_or_default(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct InputAudio {
     pub data: String,
@@ -290,27 +349,42 @@ impl TryFrom<message::Message> for Vec<Message> {
                 // If there are messages with both tool results and user content, openai will only
                 //  handle tool results. It's unlikely that there will be both.
                 if !tool_results.is_empty() {
-                    tool_results
+                    // Tool messages only support text content: images returned by tools are
+                    //  sent in a user message following the tool results.
+                    let mut images = vec![];
+                    let mut messages = tool_results
                         .into_iter()
                         .map(|content| match content {
                             message::UserContent::ToolResult(message::ToolResult {
                                 id,
                                 content,
                                 ..
-                            }) => Ok::<_, message::MessageError>(Message::ToolResult {
+                            }) => Message::ToolResult {
                                 tool_call_id: id,
-                                content: content.try_map(|content| match content {
+                                content: content.map(|content| match content {
                                     message::ToolResultContent::Text(message::Text { text }) => {
-                                        Ok(text.into())
+                                        text.into()
                                     }
-                                    _ => Err(message::MessageError::ConversionError(
-                                        "Tool result content does not support non-text".into(),
-                                    )),
-                                })?,
-                            }),
+                                    message::ToolResultContent::Image(image) => {
+                                        images.push(UserContent::Image {
+                                            image_url: image.into(),
+                                        });
+                                        "[Image attached in the next message]".to_string().into()
+                                    }
+                                }),
+                            },
                             _ => unreachable!(),
                         })
-                        .collect::<Result<Vec<_>, _>>()
+                        .collect::<Vec<_>>();
+
+                    if let Ok(images) = OneOrMany::many(images) {
+                        messages.push(Message::User {
+                            content: images,
+                            name: None,
+                        });
+                    }
+
+                    Ok(messages)
                 } else {
                     let other_content = OneOrMany::many(other_content).expect(
                         "There must be other content here if there were no tool result content",
@@ -352,12 +426,16 @@ impl TryFrom<message::Message> for Vec<Message> {
                 }
             }
             message::Message::Assistant { content, .. } => {
+                let mut refusal = None;
                 let (text_content, tool_calls) = content.into_iter().fold(
                     (Vec::new(), Vec::new()),
                     |(mut texts, mut tools), content| {
                         match content {
                             message::AssistantContent::Text(text) => texts.push(text),
                             message::AssistantContent::ToolCall(tool_call) => tools.push(tool_call),
+                            message::AssistantContent::Refusal(message::Refusal {
+                                refusal: text,
+                            }) => refusal = Some(text),
                             message::AssistantContent::Reasoning(_) => {
                                 unimplemented!(
                                     "The OpenAI Completions API doesn't support reasoning!"
@@ -375,7 +453,7 @@ impl TryFrom<message::Message> for Vec<Message> {
                         .into_iter()
                         .map(|content| content.text.into())
                         .collect::<Vec<_>>(),
-                    refusal: None,
+                    refusal,
                     audio: None,
                     name: None,
                     tool_calls: tool_calls
@@ -477,6 +555,73 @@ impl TryFrom<Message> for message::Message {
     }
 }
 
+/// Convert a conversation in the JSON format of the OpenAI Chat Completions API (an array of
+/// messages, e.g.: the `messages` of a request) into rig messages, e.g.: to import a chat
+/// history created with another library.
+///
+/// The results of consecutive `tool` messages are grouped in a single user message, like rig
+/// does. System messages are skipped: in rig, they are the preamble of the agent.
+pub fn messages_from_json(
+    messages: serde_json::Value,
+) -> Result<Vec<message::Message>, message::MessageError> {
+    let messages: Vec<Message> = serde_json::from_value(messages)
+        .map_err(|e| message::MessageError::ConversionError(e.to_string()))?;
+
+    let mut converted: Vec<message::Message> = vec![];
+    let mut after_tool_result = false;
+    for message in messages {
+        let is_tool_result = matches!(message, Message::ToolResult { .. });
+        if matches!(message, Message::System { .. }) {
+            continue;
+        }
+
+        match (converted.last_mut(), message::Message::try_from(message)?) {
+            (
+                Some(message::Message::User { content }),
+                message::Message::User {
+                    content: tool_results,
+                },
+            ) if is_tool_result && after_tool_result => {
+                tool_results
+                    .into_iter()
+                    .for_each(|tool_result| content.push(tool_result));
+            }
+            (_, message) => converted.push(message),
+        }
+        after_tool_result = is_tool_result;
+    }
+
+    Ok(converted)
+}
+
+/// Convert rig messages into the JSON format of the OpenAI Chat Completions API (an array of
+/// messages), the reverse of [messages_from_json].
+///
+/// Reasoning is skipped, since the Chat Completions API does not support it.
+pub fn messages_to_json(
+    messages: &[message::Message],
+) -> Result<serde_json::Value, message::MessageError> {
+    let mut converted = vec![];
+    for message in messages.iter().cloned() {
+        let message = match message {
+            message::Message::Assistant { id, content } => {
+                let content = content
+                    .into_iter()
+                    .filter(|content| !matches!(content, message::AssistantContent::Reasoning(_)));
+                match OneOrMany::many(content) {
+                    Ok(content) => message::Message::Assistant { id, content },
+                    Err(_) => continue,
+                }
+            }
+            message => message,
+        };
+        converted.extend(Vec::<Message>::try_from(message)?);
+    }
+
+    serde_json::to_value(converted)
+        .map_err(|e| message::MessageError::ConversionError(e.to_string()))
+}
+
 impl From<UserContent> for message::UserContent {
     fn from(content: UserContent) -> Self {
         match content {
@@ -569,24 +714,28 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
         let content = match &choice.message {
             Message::Assistant {
                 content,
+                refusal,
                 tool_calls,
                 ..
             } => {
                 let mut content = content
                     .iter()
                     .filter_map(|c| {
-                        let s = match c {
-                            AssistantContent::Text { text } => text,
-                            AssistantContent::Refusal { refusal } => refusal,
-                        };
+                        let (AssistantContent::Text { text: s }
+                        | AssistantContent::Refusal { refusal: s }) = c;
                         if s.is_empty() {
                             None
                         } else {
-                            Some(completion::AssistantContent::text(s))
+                            Some(c.clone().into())
                         }
                     })
                     .collect::<Vec<_>>();
 
+                // Refusals are returned in their own field, without content
+                if let Some(refusal) = refusal.as_ref().filter(|refusal| !refusal.is_empty()) {
+                    content.push(completion::AssistantContent::refusal(refusal));
+                }
+
                 content.extend(
                     tool_calls
                         .iter()
@@ -662,6 +811,10 @@ pub struct CompletionModel {
     pub(crate) client: Client,
     /// Name of the model (e.g.: gpt-3.5-turbo-1106)
     pub model: String,
+    /// Price of the model, overriding the built-in price table
+    pub(crate) pricing: Option<completion::Pricing>,
+    /// Format of the ids of the tool calls sent in the chat history
+    pub(crate) tool_call_ids: completion::ToolCallIdFormat,
 }
 
 impl CompletionModel {
@@ -669,9 +822,26 @@ impl CompletionModel {
         Self {
             client,
             model: model.to_string(),
+            pricing: None,
+            tool_call_ids: completion::ToolCallIdFormat::openai(),
         }
     }
 
+    /// Set the format the ids of the tool calls and tool results of the chat history are
+    /// normalized to (defaults to [completion::ToolCallIdFormat::openai]), e.g.: when the history
+    /// comes from another provider.
+    pub fn with_tool_call_ids(mut self, format: completion::ToolCallIdFormat) -> Self {
+        self.tool_call_ids = format;
+        self
+    }
+
+    /// Set the price of the model (in dollars per 1k tokens), used to estimate the cost of requests.
+    /// This overrides the built-in price table (see the `pricing` feature).
+    pub fn with_pricing(mut self, pricing: completion::Pricing) -> Self {
+        self.pricing = Some(pricing);
+        self
+    }
+
     pub fn into_agent_builder(self) -> crate::agent::AgentBuilder<Self> {
         crate::agent::AgentBuilder::new(self)
     }
@@ -686,6 +856,7 @@ impl CompletionModel {
             partial_history.push(docs);
         }
         partial_history.extend(completion_request.chat_history);
+        self.tool_call_ids.normalize_messages(&mut partial_history);
 
         // Initialize full history with preamble (or empty if non-existent)
         let mut full_history: Vec<Message> = completion_request
@@ -710,12 +881,21 @@ impl CompletionModel {
 
             })
         } else {
-            json!({
+            let request = json!({
                 "model": self.model,
                 "messages": full_history,
                 "tools": completion_request.tools.into_iter().map(ToolDefinition::from).collect::<Vec<_>>(),
-                "tool_choice": "auto",
-            })
+                "tool_choice": completion_request.tool_choice.unwrap_or_default(),
+            });
+
+            // `parallel_tool_calls` is only allowed when tools are specified
+            match completion_request.parallel_tool_calls {
+                Some(parallel_tool_calls) => json_utils::merge(
+                    request,
+                    json!({ "parallel_tool_calls": parallel_tool_calls }),
+                ),
+                None => request,
+            }
         };
 
         // only include temperature if it exists
@@ -745,6 +925,28 @@ impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
     type StreamingResponse = StreamingCompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn tool_limits(&self) -> crate::tool::ToolLimits {
+        crate::tool::ToolLimits::new().max_tools(MAX_TOOLS)
+    }
+
+    fn context_window(&self) -> Option<usize> {
+        context_window(&self.model)
+    }
+
+    fn price_per_1k_tokens(&self) -> Option<completion::Pricing> {
+        #[cfg(feature = "pricing")]
+        return self
+            .pricing
+            .or_else(|| super::pricing::pricing(&self.model));
+
+        #[cfg(not(feature = "pricing"))]
+        self.pricing
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
@@ -779,7 +981,7 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from_response(response).await)
         }
     }
 
@@ -794,3 +996,249 @@ impl completion::CompletionModel for CompletionModel {
         CompletionModel::stream(self, request).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::OneOrMany;
+    use crate::client::CompletionClient;
+    use crate::completion::{CompletionModel as _, ToolChoice, ToolDefinition};
+    use crate::message::{self, ImageDetail};
+    use crate::providers::openai::{
+        Client, GPT_4_1_NANO, GPT_4_0613, GPT_4O, GPT_35_TURBO, context_window,
+    };
+
+    use super::{CompletionResponse, ImageUrl, Message, UserContent};
+
+    #[test]
+    fn test_response_with_multiple_choices() {
+        let response: CompletionResponse = serde_json::from_value(serde_json::json!({
+            "id": "chatcmpl-123",
+            "object": "chat.completion",
+            "created": 1_700_000_000,
+            "model": "gpt-4o",
+            "system_fingerprint": null,
+            "choices": [
+                {
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "Paris"},
+                    "logprobs": null,
+                    "finish_reason": "stop"
+                },
+                {
+                    "index": 1,
+                    "message": {"role": "assistant", "content": "Paris, France"},
+                    "logprobs": null,
+                    "finish_reason": "stop"
+                }
+            ],
+            "usage": null
+        }))
+        .unwrap();
+
+        // Alternative choices are not merged: only the first one is the completion choice
+        let response: crate::completion::CompletionResponse<_> = response.try_into().unwrap();
+        assert_eq!(
+            response.choice,
+            OneOrMany::one(message::AssistantContent::text("Paris"))
+        );
+        assert_eq!(response.raw_response.choices.len(), 2);
+    }
+
+    #[test]
+    fn test_context_window_lookup() {
+        assert_eq!(context_window(GPT_4O), Some(128_000));
+        assert_eq!(context_window("gpt-4o-mini-2024-07-18"), Some(128_000));
+        assert_eq!(context_window(GPT_4_0613), Some(8_192));
+        assert_eq!(context_window(GPT_4_1_NANO), Some(1_047_576));
+        assert_eq!(context_window("text-embedding-3-small"), None);
+
+        let model = Client::new("dummy-key").completion_model(GPT_35_TURBO);
+        assert_eq!(model.context_window(), Some(16_385));
+    }
+
+    #[test]
+    fn test_parallel_tool_calls() {
+        let model = Client::new("dummy-key")
+            .completion_model(GPT_4O)
+            .completions_api();
+        let tool = ToolDefinition {
+            name: "add".to_string(),
+            description: "Add two numbers".to_string(),
+            parameters: serde_json::json!({"type": "object"}),
+        };
+
+        let request = model
+            .completion_request("What is 2 + 2?")
+            .tool(tool.clone())
+            .parallel_tool_calls(false)
+            .build();
+        let request = model.create_completion_request(request).unwrap();
+        assert_eq!(request["parallel_tool_calls"], false);
+
+        // Without tools, the flag is not sent
+        let request = model
+            .completion_request("What is 2 + 2?")
+            .parallel_tool_calls(false)
+            .build();
+        let request = model.create_completion_request(request).unwrap();
+        assert!(request.get("parallel_tool_calls").is_none());
+
+        let request = model
+            .completion_request("What is 2 + 2?")
+            .tool(tool)
+            .build();
+        let request = model.create_completion_request(request).unwrap();
+        assert!(request.get("parallel_tool_calls").is_none());
+    }
+
+    #[test]
+    fn test_tool_choice() {
+        let model = Client::new("dummy-key")
+            .completion_model(GPT_4O)
+            .completions_api();
+        let tool = ToolDefinition {
+            name: "add".to_string(),
+            description: "Add two numbers".to_string(),
+            parameters: serde_json::json!({"type": "object"}),
+        };
+
+        let request = model
+            .completion_request("What is 2 + 2?")
+            .tool(tool.clone())
+            .build();
+        let request = model.create_completion_request(request).unwrap();
+        assert_eq!(request["tool_choice"], "auto");
+
+        // The tools are still sent, but cannot be called
+        let request = model
+            .completion_request("What is 2 + 2?")
+            .tool(tool)
+            .tool_choice(ToolChoice::None)
+            .build();
+        let request = model.create_completion_request(request).unwrap();
+        assert_eq!(request["tool_choice"], "none");
+        assert_eq!(request["tools"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_image_tool_result() {
+        let message = message::Message::User {
+            content: OneOrMany::one(message::UserContent::tool_result(
+                "call_1",
+                OneOrMany::many(vec![
+                    message::ToolResultContent::text("A chart of the results"),
+                    message::ToolResultContent::image(
+                        "aGVsbG8=",
+                        Some(message::ContentFormat::Base64),
+                        Some(message::ImageMediaType::PNG),
+                        None,
+                    ),
+                ])
+                .unwrap(),
+            )),
+        };
+
+        let messages: Vec<Message> = message.try_into().unwrap();
+        assert_eq!(messages.len(), 2);
+
+        let Message::ToolResult {
+            tool_call_id,
+            content,
+        } = &messages[0]
+        else {
+            panic!("Expected a tool result message");
+        };
+        assert_eq!(tool_call_id, "call_1");
+        assert_eq!(content.first().text, "A chart of the results");
+        assert_eq!(content.len(), 2);
+
+        let Message::User { content, .. } = &messages[1] else {
+            panic!("Expected a user message");
+        };
+        assert_eq!(
+            content.first(),
+            UserContent::Image {
+                image_url: ImageUrl {
+                    url: "data:image/png;base64,aGVsbG8=".to_string(),
+                    detail: ImageDetail::default(),
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_messages_json_round_trip() {
+        let json = serde_json::json!([
+            {"role": "system", "content": "You are a helpful assistant."},
+            {"role": "user", "content": "What is the weather in Paris and London?"},
+            {
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [
+                    {
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "weather", "arguments": "{\"city\":\"Paris\"}"}
+                    },
+                    {
+                        "id": "call_2",
+                        "type": "function",
+                        "function": {"name": "weather", "arguments": "{\"city\":\"London\"}"}
+                    }
+                ]
+            },
+            {"role": "tool", "tool_call_id": "call_1", "content": "Sunny"},
+            {"role": "tool", "tool_call_id": "call_2", "content": "Rainy"},
+            {"role": "assistant", "content": "It is sunny in Paris and rainy in London."}
+        ]);
+
+        let messages = message::Message::from_openai_json(json).unwrap();
+
+        // The system message is skipped, the tool results are grouped in a single message
+        let tool_results = message::Message::User {
+            content: OneOrMany::many([
+                message::UserContent::tool_result(
+                    "call_1",
+                    OneOrMany::one(message::ToolResultContent::text("Sunny")),
+                ),
+                message::UserContent::tool_result(
+                    "call_2",
+                    OneOrMany::one(message::ToolResultContent::text("Rainy")),
+                ),
+            ])
+            .unwrap(),
+        };
+        let tool_calls = message::Message::Assistant {
+            id: None,
+            content: OneOrMany::many([
+                message::AssistantContent::tool_call(
+                    "call_1",
+                    "weather",
+                    serde_json::json!({"city": "Paris"}),
+                ),
+                message::AssistantContent::tool_call(
+                    "call_2",
+                    "weather",
+                    serde_json::json!({"city": "London"}),
+                ),
+            ])
+            .unwrap(),
+        };
+        assert_eq!(
+            messages,
+            vec![
+                message::Message::user("What is the weather in Paris and London?"),
+                tool_calls,
+                tool_results,
+                message::Message::assistant("It is sunny in Paris and rainy in London."),
+            ]
+        );
+
+        // rig -> OpenAI -> rig
+        let json = message::Message::to_openai_json(&messages).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 5);
+        assert_eq!(json[2]["role"], "tool");
+        assert_eq!(json[3]["tool_call_id"], "call_2");
+        assert_eq!(message::Message::from_openai_json(json).unwrap(), messages);
+    }
+}
@@ -87,11 +87,7 @@ pub async fn send_compatible_streaming_request(
     let response = request_builder.send().await?;
 
     if !response.status().is_success() {
-        return Err(CompletionError::ProviderError(format!(
-            "{}: {}",
-            response.status(),
-            response.text().await?
-        )));
+        return Err(CompletionError::from_response(response).await);
     }
 
     // Handle OpenAI Compatible SSE chunks
@@ -187,8 +183,11 @@ pub async fn send_compatible_streaming_request(
 
                                 let new_arguments = &tool_call.function.arguments;
                                 let arguments = format!("{arguments}{new_arguments}");
+                                let (id, name) = (id.clone(), name.clone());
 
                                 calls.insert(tool_call.index, (id.clone(), name.clone(), arguments));
+
+                                yield Ok(streaming::RawStreamingChoice::ToolCallDelta { id, name, delta: new_arguments.clone() });
                             }
                             // Entire tool call
                             else {
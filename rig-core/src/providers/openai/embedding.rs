@@ -1,6 +1,7 @@
 use super::{ApiErrorResponse, ApiResponse, Client, completion::Usage};
 use crate::embeddings;
 use crate::embeddings::EmbeddingError;
+use crate::telemetry;
 use serde::Deserialize;
 use serde_json::json;
 
@@ -14,6 +15,14 @@ pub const TEXT_EMBEDDING_3_SMALL: &str = "text-embedding-3-small";
 /// `text-embedding-ada-002` embedding model
 pub const TEXT_EMBEDDING_ADA_002: &str = "text-embedding-ada-002";
 
+/// The maximum number of tokens of an input of the OpenAI embedding model `model`, if known.
+pub(crate) fn max_input_tokens(model: &str) -> Option<usize> {
+    match model {
+        TEXT_EMBEDDING_3_LARGE | TEXT_EMBEDDING_3_SMALL | TEXT_EMBEDDING_ADA_002 => Some(8191),
+        _ => None,
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct EmbeddingResponse {
     pub object: String,
@@ -58,6 +67,10 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
         self.ndims
     }
 
+    fn max_input_tokens(&self) -> Option<usize> {
+        max_input_tokens(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn embed_texts(
         &self,
@@ -65,45 +78,52 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
         let documents = documents.into_iter().collect::<Vec<_>>();
 
-        let response = self
-            .client
-            .post("/embeddings")
-            .json(&json!({
-                "model": self.model,
-                "input": documents,
-            }))
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            match response.json::<ApiResponse<EmbeddingResponse>>().await? {
-                ApiResponse::Ok(response) => {
-                    tracing::info!(target: "rig",
-                        "OpenAI embedding token usage: {:?}",
-                        response.usage
-                    );
-
-                    if response.data.len() != documents.len() {
-                        return Err(EmbeddingError::ResponseError(
-                            "Response data length does not match input length".into(),
-                        ));
-                    }
+        telemetry::traced(
+            telemetry::embeddings_span(&self.model, documents.len()),
+            async {
+                let response = self
+                    .client
+                    .post("/embeddings")
+                    .json(&json!({
+                        "model": self.model,
+                        "input": documents,
+                    }))
+                    .send()
+                    .await?;
+
+                if response.status().is_success() {
+                    match response.json::<ApiResponse<EmbeddingResponse>>().await? {
+                        ApiResponse::Ok(response) => {
+                            tracing::info!(target: "rig",
+                                "OpenAI embedding token usage: {:?}",
+                                response.usage
+                            );
+                            telemetry::record_input_tokens(response.usage.prompt_tokens as u64);
+
+                            if response.data.len() != documents.len() {
+                                return Err(EmbeddingError::ResponseError(
+                                    "Response data length does not match input length".into(),
+                                ));
+                            }
 
-                    Ok(response
-                        .data
-                        .into_iter()
-                        .zip(documents.into_iter())
-                        .map(|(embedding, document)| embeddings::Embedding {
-                            document,
-                            vec: embedding.embedding,
-                        })
-                        .collect())
+                            Ok(response
+                                .data
+                                .into_iter()
+                                .zip(documents.into_iter())
+                                .map(|(embedding, document)| embeddings::Embedding {
+                                    document,
+                                    vec: embedding.embedding,
+                                })
+                                .collect())
+                        }
+                        ApiResponse::Err(err) => Err(EmbeddingError::ProviderError(err.message)),
+                    }
+                } else {
+                    Err(EmbeddingError::from_response(response).await)
                 }
-                ApiResponse::Err(err) => Err(EmbeddingError::ProviderError(err.message)),
-            }
-        } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
-        }
+            },
+        )
+        .await
     }
 }
 
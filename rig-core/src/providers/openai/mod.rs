@@ -11,6 +11,9 @@
 pub mod client;
 pub mod completion;
 pub mod embedding;
+pub mod moderation;
+#[cfg(feature = "pricing")]
+pub mod pricing;
 pub mod responses_api;
 
 #[cfg(feature = "audio")]
@@ -23,6 +26,7 @@ pub mod transcription;
 pub use client::*;
 pub use completion::*;
 pub use embedding::*;
+pub use moderation::*;
 
 #[cfg(feature = "audio")]
 pub use audio_generation::{TTS_1, TTS_1_HD};
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{ApiResponse, Client};
+use crate::moderation::{self, ModerationError, ModerationResult};
+
+// ================================================================
+// OpenAI Moderation API
+// ================================================================
+/// `omni-moderation-latest` moderation model
+pub const OMNI_MODERATION_LATEST: &str = "omni-moderation-latest";
+/// `text-moderation-latest` moderation model
+pub const TEXT_MODERATION_LATEST: &str = "text-moderation-latest";
+
+#[derive(Debug, Deserialize)]
+pub struct ModerationResponse {
+    pub id: String,
+    pub model: String,
+    pub results: Vec<ModerationData>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModerationData {
+    pub flagged: bool,
+    pub categories: HashMap<String, bool>,
+    #[serde(default)]
+    pub category_scores: HashMap<String, f64>,
+}
+
+impl From<ModerationData> for ModerationResult {
+    fn from(data: ModerationData) -> Self {
+        let mut categories = data
+            .categories
+            .into_iter()
+            .filter_map(|(category, flagged)| flagged.then_some(category))
+            .collect::<Vec<_>>();
+        categories.sort();
+
+        ModerationResult {
+            flagged: data.flagged,
+            categories,
+        }
+    }
+}
+
+/// An OpenAI moderation model, usable as a [Moderator](moderation::Moderator).
+#[derive(Clone)]
+pub struct ModerationModel {
+    client: Client,
+    pub model: String,
+}
+
+impl ModerationModel {
+    pub fn new(client: Client, model: &str) -> Self {
+        Self {
+            client,
+            model: model.to_string(),
+        }
+    }
+}
+
+impl moderation::Moderator for ModerationModel {
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn check(&self, text: &str) -> Result<ModerationResult, ModerationError> {
+        let response = self
+            .client
+            .post("/moderations")
+            .json(&json!({
+                "model": self.model,
+                "input": text,
+            }))
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            match response.json::<ApiResponse<ModerationResponse>>().await? {
+                ApiResponse::Ok(response) => response
+                    .results
+                    .into_iter()
+                    .next()
+                    .map(ModerationResult::from)
+                    .ok_or_else(|| {
+                        ModerationError::ResponseError("Response contained no result".into())
+                    }),
+                ApiResponse::Err(err) => Err(ModerationError::ProviderError(err.message)),
+            }
+        } else {
+            Err(ModerationError::ProviderError(response.text().await?))
+        }
+    }
+}
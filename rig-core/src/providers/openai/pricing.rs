@@ -0,0 +1,111 @@
+//! Table of OpenAI completion model prices (in dollars per 1k tokens).
+//!
+//! Prices are subject to change: users can override the price of a model with
+//! [CompletionModel::with_pricing](super::CompletionModel::with_pricing) or
+//! [ResponsesCompletionModel::with_pricing](super::responses_api::ResponsesCompletionModel::with_pricing).
+//!
+//! See the [OpenAI pricing page](https://openai.com/api/pricing/) for the latest prices.
+
+use crate::completion::Pricing;
+
+/// Known model prices as `(model, prompt price per 1k tokens, completion price per 1k tokens)`.
+/// More specific model names must come before the model names they are prefixed by.
+const PRICES: &[(&str, f64, f64)] = &[
+    ("o4-mini", 0.0011, 0.0044),
+    ("o3-mini", 0.0011, 0.0044),
+    ("o3", 0.002, 0.008),
+    ("o1-pro", 0.15, 0.6),
+    ("o1-mini", 0.0011, 0.0044),
+    ("o1-preview", 0.015, 0.06),
+    ("o1", 0.015, 0.06),
+    ("gpt-4.1-mini", 0.0004, 0.0016),
+    ("gpt-4.1-nano", 0.0001, 0.0004),
+    ("gpt-4.1", 0.002, 0.008),
+    ("gpt-4.5-preview", 0.075, 0.15),
+    ("gpt-4o-mini", 0.00015, 0.0006),
+    ("gpt-4o-2024-05-13", 0.005, 0.015),
+    ("gpt-4o", 0.0025, 0.01),
+    ("gpt-4-turbo", 0.01, 0.03),
+    ("gpt-4-0125-preview", 0.01, 0.03),
+    ("gpt-4-1106-preview", 0.01, 0.03),
+    ("gpt-4-vision-preview", 0.01, 0.03),
+    ("gpt-4-1106-vision-preview", 0.01, 0.03),
+    ("gpt-4-32k", 0.06, 0.12),
+    ("gpt-4", 0.03, 0.06),
+    ("gpt-3.5-turbo-instruct", 0.0015, 0.002),
+    ("gpt-3.5-turbo-1106", 0.001, 0.002),
+    ("gpt-3.5-turbo", 0.0005, 0.0015),
+];
+
+/// Returns the price of the given OpenAI model, if known.
+/// Dated model snapshots (e.g.: `gpt-4o-mini-2024-07-18`, `gpt-4-0613`) use the price of their
+/// base model. Other variants (e.g.: `o3-pro`, `gpt-4o-audio-preview`) are priced differently
+/// from their base model, so they have no price unless they are part of the table.
+pub fn pricing(model: &str) -> Option<Pricing> {
+    PRICES
+        .iter()
+        .find(|(name, _, _)| {
+            model == *name
+                || model
+                    .strip_prefix(name)
+                    .and_then(|suffix| suffix.strip_prefix('-'))
+                    .is_some_and(is_snapshot_date)
+        })
+        .map(|(_, prompt, completion)| Pricing::new(*prompt, *completion))
+}
+
+/// Whether `suffix` is the date of a model snapshot: `YYYY-MM-DD` or `MMDD`.
+fn is_snapshot_date(suffix: &str) -> bool {
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    match suffix.split('-').collect::<Vec<_>>()[..] {
+        [year, month, day] => {
+            year.len() == 4
+                && month.len() == 2
+                && day.len() == 2
+                && [year, month, day].into_iter().all(digits)
+        }
+        [date] => {
+            date.len() == 4
+                && digits(date)
+                && (1..=12).contains(&date[..2].parse::<u8>().unwrap_or(0))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pricing_lookup() {
+        assert_eq!(pricing("gpt-4o"), Some(Pricing::new(0.0025, 0.01)));
+        assert_eq!(
+            pricing("gpt-4o-mini-2024-07-18"),
+            Some(Pricing::new(0.00015, 0.0006))
+        );
+        assert_eq!(
+            pricing("gpt-4o-2024-05-13"),
+            Some(Pricing::new(0.005, 0.015))
+        );
+        assert_eq!(pricing("gpt-4-0613"), Some(Pricing::new(0.03, 0.06)));
+        assert_eq!(pricing("gpt-4.1-nano"), Some(Pricing::new(0.0001, 0.0004)));
+        assert_eq!(
+            pricing("gpt-3.5-turbo-0125"),
+            Some(Pricing::new(0.0005, 0.0015))
+        );
+        assert_eq!(pricing("text-embedding-3-small"), None);
+
+        // Variants which are not dated snapshots are priced differently from their base model
+        for model in [
+            "o3-pro",
+            "gpt-4o-audio-preview",
+            "gpt-4o-realtime-preview",
+            "gpt-4o-search-preview",
+            "gpt-4o-transcribe-preview",
+            "gpt-4o-2024",
+        ] {
+            assert_eq!(pricing(model), None, "{model}");
+        }
+    }
+}
@@ -44,8 +44,9 @@ pub struct CompletionRequest {
     /// The temperature. Set higher (up to a max of 1.0) for more creative responses.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub temperature: Option<f64>,
-    // TODO: Fix this before opening a PR!
-    // tool_choice: Option<T>,
+    /// Whether the model may call the tools.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<completion::ToolChoice>,
     /// The tools you want to use. Currently this is limited to functions, but will be expanded on in future.
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub tools: Vec<ResponsesToolDefinition>,
@@ -271,6 +272,22 @@ impl TryFrom<crate::completion::Message> for Vec<InputItem> {
                                 }),
                             });
                         }
+                        crate::message::AssistantContent::Refusal(crate::message::Refusal {
+                            refusal,
+                        }) => {
+                            let id = id.as_ref().unwrap_or(&String::default()).clone();
+                            items.push(InputItem {
+                                role: Some(Role::Assistant),
+                                input: InputContent::Message(Message::Assistant {
+                                    content: OneOrMany::one(AssistantContentType::Text(
+                                        AssistantContent::Refusal { refusal },
+                                    )),
+                                    id,
+                                    name: None,
+                                    status: ToolStatus::Completed,
+                                }),
+                            });
+                        }
                         crate::message::AssistantContent::ToolCall(crate::message::ToolCall {
                             id: tool_id,
                             call_id,
@@ -572,13 +589,17 @@ impl TryFrom<(String, crate::completion::CompletionRequest)> for CompletionReque
             .unwrap_or(Value::Null)
             .as_bool();
 
-        let additional_parameters = if let Some(map) = req.additional_params {
+        let mut additional_parameters = if let Some(map) = req.additional_params {
             serde_json::from_value::<AdditionalParameters>(map).expect("Converting additional parameters to AdditionalParameters should never fail as every field is an Option")
         } else {
             // If there's no additional parameters, initialise an empty object
             AdditionalParameters::default()
         };
 
+        if req.parallel_tool_calls.is_some() {
+            additional_parameters.parallel_tool_calls = req.parallel_tool_calls;
+        }
+
         Ok(Self {
             input,
             model,
@@ -591,6 +612,7 @@ impl TryFrom<(String, crate::completion::CompletionRequest)> for CompletionReque
                 .map(ResponsesToolDefinition::from)
                 .collect(),
             temperature: req.temperature,
+            tool_choice: req.tool_choice,
             additional_parameters,
         })
     }
@@ -603,6 +625,8 @@ pub struct ResponsesCompletionModel {
     pub(crate) client: Client,
     /// Name of the model (e.g.: gpt-3.5-turbo-1106)
     pub model: String,
+    /// Price of the model, overriding the built-in price table
+    pub(crate) pricing: Option<completion::Pricing>,
 }
 
 impl ResponsesCompletionModel {
@@ -611,12 +635,26 @@ impl ResponsesCompletionModel {
         Self {
             client,
             model: model.to_string(),
+            pricing: None,
         }
     }
 
     /// Use the Completions API instead of Responses.
     pub fn completions_api(self) -> crate::providers::openai::completion::CompletionModel {
-        crate::providers::openai::completion::CompletionModel::new(self.client, &self.model)
+        let model =
+            crate::providers::openai::completion::CompletionModel::new(self.client, &self.model);
+
+        match self.pricing {
+            Some(pricing) => model.with_pricing(pricing),
+            None => model,
+        }
+    }
+
+    /// Set the price of the model (in dollars per 1k tokens), used to estimate the cost of requests.
+    /// This overrides the built-in price table (see the `pricing` feature).
+    pub fn with_pricing(mut self, pricing: completion::Pricing) -> Self {
+        self.pricing = Some(pricing);
+        self
     }
 
     /// Attempt to create a completion request from [`crate::completion::CompletionRequest`].
@@ -939,6 +977,28 @@ impl completion::CompletionModel for ResponsesCompletionModel {
     type Response = CompletionResponse;
     type StreamingResponse = StreamingCompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
+    fn tool_limits(&self) -> crate::tool::ToolLimits {
+        crate::tool::ToolLimits::new().max_tools(super::MAX_TOOLS)
+    }
+
+    fn context_window(&self) -> Option<usize> {
+        super::context_window(&self.model)
+    }
+
+    fn price_per_1k_tokens(&self) -> Option<completion::Pricing> {
+        #[cfg(feature = "pricing")]
+        return self
+            .pricing
+            .or_else(|| super::pricing::pricing(&self.model));
+
+        #[cfg(not(feature = "pricing"))]
+        self.pricing
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
@@ -958,7 +1018,7 @@ impl completion::CompletionModel for ResponsesCompletionModel {
             let response = serde_json::from_str::<Self::Response>(&t)?;
             response.try_into()
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from_response(response).await)
         }
     }
 
@@ -1076,9 +1136,7 @@ pub enum AssistantContent {
 impl From<AssistantContent> for completion::AssistantContent {
     fn from(value: AssistantContent) -> Self {
         match value {
-            AssistantContent::Refusal { refusal } => {
-                completion::AssistantContent::Text(Text { text: refusal })
-            }
+            AssistantContent::Refusal { refusal } => completion::AssistantContent::refusal(refusal),
             AssistantContent::OutputText(Text { text }) => {
                 completion::AssistantContent::Text(Text { text })
             }
@@ -1206,6 +1264,16 @@ impl TryFrom<message::Message> for Vec<Message> {
                             name: None,
                         }])
                     }
+                    crate::message::AssistantContent::Refusal(crate::message::Refusal {
+                        refusal,
+                    }) => Ok(vec![Message::Assistant {
+                        id: assistant_message_id.expect("The assistant message ID should exist"),
+                        status: ToolStatus::Completed,
+                        content: OneOrMany::one(AssistantContentType::Text(
+                            AssistantContent::Refusal { refusal },
+                        )),
+                        name: None,
+                    }]),
                     crate::message::AssistantContent::ToolCall(crate::message::ToolCall {
                         id,
                         call_id,
@@ -211,11 +211,7 @@ pub async fn send_compatible_streaming_request(
     let response = request_builder.send().await?;
 
     if !response.status().is_success() {
-        return Err(CompletionError::ProviderError(format!(
-            "{}: {}",
-            response.status(),
-            response.text().await?
-        )));
+        return Err(CompletionError::from_response(response).await);
     }
 
     // Handle OpenAI Compatible SSE chunks
@@ -65,7 +65,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
                     .map(|c| match c {
                         AssistantContent::Text { text } => completion::AssistantContent::text(text),
                         AssistantContent::Refusal { refusal } => {
-                            completion::AssistantContent::text(refusal)
+                            completion::AssistantContent::refusal(refusal)
                         }
                     })
                     .collect::<Vec<_>>();
@@ -186,6 +186,10 @@ impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
     type StreamingResponse = FinalCompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
@@ -214,7 +218,7 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from_response(response).await)
         }
     }
 
@@ -10,7 +10,10 @@
 //! ```
 use crate::{
     OneOrMany,
-    client::{VerifyClient, VerifyError},
+    client::{
+        VerifyClient, VerifyError,
+        verify::{probe, with_timeout},
+    },
     completion::{self, CompletionError, MessageError, message},
     impl_conversion_traits, json_utils,
 };
@@ -23,6 +26,7 @@ use crate::providers::openai::send_compatible_streaming_request;
 use crate::streaming::StreamingCompletionResponse;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
+use std::time::Duration;
 
 // ================================================================
 // Main Cohere Client
@@ -58,7 +62,7 @@ impl<'a> ClientBuilder<'a> {
         let http_client = if let Some(http_client) = self.http_client {
             http_client
         } else {
-            reqwest::Client::builder().build()?
+            crate::client::default_http_client()?
         };
 
         Ok(Client {
@@ -147,6 +151,17 @@ impl VerifyClient for Client {
         // No API endpoint to verify the API key
         Ok(())
     }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn health_check(&self, timeout: Duration) -> Result<(), VerifyError> {
+        // Without a verify endpoint, send a completion request limited to a single token
+        let body = json!({
+            "model": SONAR,
+            "messages": [{ "role": "user", "content": "ping" }],
+            "max_tokens": 1,
+        });
+        with_timeout(probe(self.post("/chat/completions").json(&body)), timeout).await
+    }
 }
 
 impl_conversion_traits!(
@@ -385,6 +400,10 @@ impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
     type StreamingResponse = openai::StreamingCompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
@@ -411,7 +430,7 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Err(error) => Err(CompletionError::ProviderError(error.message)),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from_response(response).await)
         }
     }
 
@@ -7,6 +7,7 @@ use serde::Deserialize;
 use serde_json::json;
 
 use crate::embeddings::{self, EmbeddingError};
+use crate::telemetry;
 
 use super::{
     Client,
@@ -83,40 +84,48 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
         let documents = documents.into_iter().collect::<Vec<_>>();
 
-        let response = self
-            .client
-            .post("/v1/embeddings")
-            .json(&json!({
-                "model": self.model,
-                "input": documents,
-            }))
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            match response.json::<ApiResponse<EmbeddingResponse>>().await? {
-                ApiResponse::Ok(response) => {
-                    if response.data.len() != documents.len() {
-                        return Err(EmbeddingError::ResponseError(
-                            "Response data length does not match input length".into(),
-                        ));
+        telemetry::traced(
+            telemetry::embeddings_span(&self.model, documents.len()),
+            async {
+                let response = self
+                    .client
+                    .post("/v1/embeddings")
+                    .json(&json!({
+                        "model": self.model,
+                        "input": documents,
+                    }))
+                    .send()
+                    .await?;
+
+                if response.status().is_success() {
+                    match response.json::<ApiResponse<EmbeddingResponse>>().await? {
+                        ApiResponse::Ok(response) => {
+                            if response.data.len() != documents.len() {
+                                return Err(EmbeddingError::ResponseError(
+                                    "Response data length does not match input length".into(),
+                                ));
+                            }
+
+                            Ok(response
+                                .data
+                                .into_iter()
+                                .zip(documents.into_iter())
+                                .map(|(embedding, document)| embeddings::Embedding {
+                                    document,
+                                    vec: embedding.embedding,
+                                })
+                                .collect())
+                        }
+                        ApiResponse::Error(err) => {
+                            Err(EmbeddingError::ProviderError(err.message()))
+                        }
                     }
-
-                    Ok(response
-                        .data
-                        .into_iter()
-                        .zip(documents.into_iter())
-                        .map(|(embedding, document)| embeddings::Embedding {
-                            document,
-                            vec: embedding.embedding,
-                        })
-                        .collect())
+                } else {
+                    Err(EmbeddingError::from_response(response).await)
                 }
-                ApiResponse::Error(err) => Err(EmbeddingError::ProviderError(err.message())),
-            }
-        } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
-        }
+            },
+        )
+        .await
     }
 }
 
@@ -1,10 +1,12 @@
 use crate::client::{
     ClientBuilderError, EmbeddingsClient, ProviderClient, VerifyClient, VerifyError,
+    verify::{probe, with_timeout},
 };
-use crate::embeddings::EmbeddingError;
-use crate::{embeddings, impl_conversion_traits};
-use serde::Deserialize;
+use crate::embeddings::{EmbedKind, EmbeddingError};
+use crate::{embeddings, impl_conversion_traits, telemetry};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::time::Duration;
 
 // ================================================================
 // Main Voyage AI Client
@@ -40,7 +42,7 @@ impl<'a> ClientBuilder<'a> {
         let http_client = if let Some(http_client) = self.http_client {
             http_client
         } else {
-            reqwest::Client::builder().build()?
+            crate::client::default_http_client()?
         };
 
         Ok(Client {
@@ -105,6 +107,16 @@ impl VerifyClient for Client {
         // No API endpoint to verify the API key
         Ok(())
     }
+
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn health_check(&self, timeout: Duration) -> Result<(), VerifyError> {
+        // Without a verify endpoint, embed a single word
+        let body = json!({
+            "model": VOYAGE_3_5_LITE,
+            "input": ["ping"],
+        });
+        with_timeout(probe(self.post("/embeddings").json(&body)), timeout).await
+    }
 }
 
 impl_conversion_traits!(
@@ -155,8 +167,20 @@ impl EmbeddingModel {
             client,
             model: model.to_string(),
             ndims,
+            input_type: None,
         }
     }
+
+    /// Set the type of the input texts. Voyage AI prepends a retrieval specific prompt to the
+    /// texts depending on their type, which improves the retrieval quality: documents should be
+    /// embedded as [InputType::Document] and search queries as [InputType::Query].
+    ///
+    /// Note: [EmbeddingsBuilder](crate::embeddings::EmbeddingsBuilder) and vector store indexes
+    /// set the input type of their texts themselves (see [EmbedKind]).
+    pub fn input_type(mut self, input_type: InputType) -> Self {
+        self.input_type = Some(input_type);
+        self
+    }
 }
 
 // ================================================================
@@ -167,7 +191,7 @@ pub const VOYAGE_3_LARGE: &str = "voyage-3-large";
 /// `voyage-3.5` embedding model (Voyage AI)
 pub const VOYAGE_3_5: &str = "voyage-3.5";
 /// `voyage-3.5-lite` embedding model (Voyage AI)
-pub const VOYAGE_3_5_LITE: &str = "voyage.3-5.lite";
+pub const VOYAGE_3_5_LITE: &str = "voyage-3.5-lite";
 /// `voyage-code-3` embedding model (Voyage AI)
 pub const VOYAGE_CODE_3: &str = "voyage-code-3";
 /// `voyage-finance-2` embedding model (Voyage AI)
@@ -177,6 +201,16 @@ pub const VOYAGE_LAW_2: &str = "voyage-law-2";
 /// `voyage-code-2` embedding model (Voyage AI)
 pub const VOYAGE_CODE_2: &str = "voyage-code-2";
 
+/// Type of the input texts of an embedding request (see [EmbeddingModel::input_type]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputType {
+    /// Search query
+    Query,
+    /// Document to be retrieved
+    Document,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct EmbeddingResponse {
     pub object: String,
@@ -230,6 +264,8 @@ pub struct EmbeddingModel {
     client: Client,
     pub model: String,
     ndims: usize,
+    /// Type of the input texts, if any (see [EmbeddingModel::input_type])
+    pub input_type: Option<InputType>,
 }
 
 impl embeddings::EmbeddingModel for EmbeddingModel {
@@ -239,51 +275,98 @@ impl embeddings::EmbeddingModel for EmbeddingModel {
         self.ndims
     }
 
+    fn max_input_tokens(&self) -> Option<usize> {
+        match self.model.as_str() {
+            VOYAGE_3_LARGE | VOYAGE_3_5 | VOYAGE_3_5_LITE | VOYAGE_CODE_3 | VOYAGE_FINANCE_2
+            | VOYAGE_LAW_2 => Some(32_000),
+            VOYAGE_CODE_2 => Some(16_000),
+            _ => None,
+        }
+    }
+
+    fn max_batch_size(&self) -> Option<usize> {
+        // Voyage AI accepts at most 1000 inputs per request
+        Some(1000)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn embed_texts(
         &self,
         documents: impl IntoIterator<Item = String>,
     ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
-        let documents = documents.into_iter().collect::<Vec<_>>();
-
-        let response = self
-            .client
-            .post("/embeddings")
-            .json(&json!({
-                "model": self.model,
-                "input": documents,
-            }))
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            match response.json::<ApiResponse<EmbeddingResponse>>().await? {
-                ApiResponse::Ok(response) => {
-                    tracing::info!(target: "rig",
-                        "VoyageAI embedding token usage: {}",
-                        response.usage.total_tokens
-                    );
-
-                    if response.data.len() != documents.len() {
-                        return Err(EmbeddingError::ResponseError(
-                            "Response data length does not match input length".into(),
-                        ));
-                    }
+        self.embed(documents.into_iter().collect(), self.input_type)
+            .await
+    }
 
-                    Ok(response
-                        .data
-                        .into_iter()
-                        .zip(documents.into_iter())
-                        .map(|(embedding, document)| embeddings::Embedding {
-                            document,
-                            vec: embedding.embedding,
-                        })
-                        .collect())
+    /// Embeds the texts with the input type matching `kind`, overriding [EmbeddingModel::input_type].
+    #[cfg_attr(feature = "worker", worker::send)]
+    async fn embed_texts_with_kind(
+        &self,
+        documents: impl IntoIterator<Item = String> + Send,
+        kind: EmbedKind,
+    ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        let input_type = match kind {
+            EmbedKind::Query => InputType::Query,
+            EmbedKind::Document => InputType::Document,
+        };
+
+        self.embed(documents.into_iter().collect(), Some(input_type))
+            .await
+    }
+}
+
+impl EmbeddingModel {
+    async fn embed(
+        &self,
+        documents: Vec<String>,
+        input_type: Option<InputType>,
+    ) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+        telemetry::traced(
+            telemetry::embeddings_span(&self.model, documents.len()),
+            async {
+                let mut body = json!({
+                    "model": self.model,
+                    "input": documents,
+                });
+
+                if let Some(input_type) = input_type {
+                    body["input_type"] = json!(input_type);
                 }
-                ApiResponse::Err(err) => Err(EmbeddingError::ProviderError(err.message)),
-            }
-        } else {
-            Err(EmbeddingError::ProviderError(response.text().await?))
-        }
+
+                let response = self.client.post("/embeddings").json(&body).send().await?;
+
+                if response.status().is_success() {
+                    match response.json::<ApiResponse<EmbeddingResponse>>().await? {
+                        ApiResponse::Ok(response) => {
+                            tracing::info!(target: "rig",
+                                "VoyageAI embedding token usage: {}",
+                                response.usage.total_tokens
+                            );
+                            telemetry::record_input_tokens(response.usage.prompt_tokens as u64);
+
+                            if response.data.len() != documents.len() {
+                                return Err(EmbeddingError::ResponseError(
+                                    "Response data length does not match input length".into(),
+                                ));
+                            }
+
+                            Ok(response
+                                .data
+                                .into_iter()
+                                .zip(documents.into_iter())
+                                .map(|(embedding, document)| embeddings::Embedding {
+                                    document,
+                                    vec: embedding.embedding,
+                                })
+                                .collect())
+                        }
+                        ApiResponse::Err(err) => Err(EmbeddingError::ProviderError(err.message)),
+                    }
+                } else {
+                    Err(EmbeddingError::from_response(response).await)
+                }
+            },
+        )
+        .await
     }
 }
@@ -83,7 +83,7 @@ impl CompletionModel {
                 "messages": full_history,
                 "temperature": completion_request.temperature,
                 "tools": completion_request.tools.into_iter().map(ToolDefinition::from).collect::<Vec<_>>(),
-                "tool_choice": "auto",
+                "tool_choice": completion_request.tool_choice.unwrap_or_default(),
             })
         };
 
@@ -107,6 +107,10 @@ impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
     type StreamingResponse = openai::StreamingCompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     #[cfg_attr(feature = "worker", worker::send)]
     async fn completion(
         &self,
@@ -127,7 +131,7 @@ impl completion::CompletionModel for CompletionModel {
                 ApiResponse::Error(error) => Err(CompletionError::ProviderError(error.message())),
             }
         } else {
-            Err(CompletionError::ProviderError(response.text().await?))
+            Err(CompletionError::from_response(response).await)
         }
     }
 
@@ -167,7 +171,7 @@ pub mod xai_api_types {
                                 completion::AssistantContent::text(text)
                             }
                             AssistantContent::Refusal { refusal } => {
-                                completion::AssistantContent::text(refusal)
+                                completion::AssistantContent::refusal(refusal)
                             }
                         })
                         .collect::<Vec<_>>();
@@ -0,0 +1,296 @@
+//! This module provides the [SchemaValidator] trait, used to validate JSON values (the arguments
+//! of tool calls, structured data returned by an [Extractor](crate::extractor::Extractor))
+//! against their JSON schema before they are deserialized.
+//!
+//! Without a validator, values are only checked by their deserialization (i.e.: by `serde`).
+//! [BasicSchemaValidator] implements the most common keywords of JSON Schema; a validator
+//! backed by a dedicated crate (e.g.: `jsonschema`) or supporting custom keywords and formats
+//! can be plugged in by implementing [SchemaValidator] (or with a closure).
+//!
+//! # Example
+//! ```rust
+//! use rig::{
+//!     client::CompletionClient,
+//!     providers::openai,
+//!     schema::{BasicSchemaValidator, SchemaValidationError},
+//! };
+//!
+//! let openai = openai::Client::new("your-open-ai-api-key");
+//!
+//! let agent = openai.agent(openai::GPT_4O)
+//!     // Send the schema errors of tool calls back to the model
+//!     .validate_tool_args(true)
+//!     .schema_validator(BasicSchemaValidator)
+//!     .build();
+//!
+//! // A custom validator, e.g.: wrapping another JSON schema crate
+//! let agent = openai.agent(openai::GPT_4O)
+//!     .schema_validator(|_schema: &serde_json::Value, value: &serde_json::Value| {
+//!         if value.is_null() {
+//!             return Err(SchemaValidationError::new(["value is null"]));
+//!         }
+//!         Ok(())
+//!     })
+//!     .build();
+//! ```
+use serde_json::Value;
+
+/// The errors found when validating a value against a JSON schema.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{}", errors.join("; "))]
+pub struct SchemaValidationError {
+    pub errors: Vec<String>,
+}
+
+impl SchemaValidationError {
+    pub fn new(errors: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            errors: errors.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// Trait for validating JSON values against a JSON schema.
+///
+/// When an agent is configured with a validator (see
+/// [AgentBuilder::schema_validator](crate::agent::AgentBuilder::schema_validator)), the
+/// arguments of tool calls are validated against the parameters schema of the tool before
+/// the tool is called.
+pub trait SchemaValidator: Send + Sync {
+    /// Validate `value` against `schema`.
+    fn validate(&self, schema: &Value, value: &Value) -> Result<(), SchemaValidationError>;
+}
+
+impl<F> SchemaValidator for F
+where
+    F: Fn(&Value, &Value) -> Result<(), SchemaValidationError> + Send + Sync,
+{
+    fn validate(&self, schema: &Value, value: &Value) -> Result<(), SchemaValidationError> {
+        self(schema, value)
+    }
+}
+
+/// A [SchemaValidator] supporting the keywords of JSON Schema generated by `schemars` for most
+/// types: `type`, `properties`, `required`, `additionalProperties`, `items`,
+/// `enum`, `const`, `anyOf`, `oneOf`, `allOf` and local `$ref`s (`#/definitions/...` and
+/// `#/$defs/...`). Other keywords (e.g.: `format`, `pattern`, `minimum`) are ignored.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BasicSchemaValidator;
+
+impl SchemaValidator for BasicSchemaValidator {
+    fn validate(&self, schema: &Value, value: &Value) -> Result<(), SchemaValidationError> {
+        let mut errors = vec![];
+        validate(schema, schema, value, "$", &mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(SchemaValidationError { errors })
+        }
+    }
+}
+
+/// Validate `value` (at `path`) against `schema`, a sub-schema of `root`.
+fn validate(root: &Value, schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let schema = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => {
+            errors.push(format!("{path}: no value is allowed"));
+            return;
+        }
+        Value::Object(schema) => schema,
+        _ => return,
+    };
+
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        match resolve(root, reference) {
+            Some(schema) => validate(root, schema, value, path, errors),
+            None => errors.push(format!("{path}: unresolved reference `{reference}`")),
+        }
+    }
+
+    if let Some(types) = schema.get("type") {
+        let types = match types {
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            Value::String(t) => vec![t.as_str()],
+            _ => vec![],
+        };
+        if !types.is_empty() && !types.iter().any(|t| has_type(value, t)) {
+            errors.push(format!(
+                "{path}: expected {}, got {}",
+                types.join(" or "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+
+    if let Some(Value::Array(values)) = schema.get("enum")
+        && !values.contains(value)
+    {
+        errors.push(format!(
+            "{path}: {value} is not one of {}",
+            Value::from(values.clone())
+        ));
+    }
+
+    if let Some(constant) = schema.get("const")
+        && constant != value
+    {
+        errors.push(format!("{path}: expected {constant}, got {value}"));
+    }
+
+    if let Some(Value::Array(schemas)) = schema.get("allOf") {
+        for schema in schemas {
+            validate(root, schema, value, path, errors);
+        }
+    }
+
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(Value::Array(schemas)) = schema.get(keyword) {
+            let valid = schemas
+                .iter()
+                .filter(|schema| {
+                    let mut errors = vec![];
+                    validate(root, schema, value, path, &mut errors);
+                    errors.is_empty()
+                })
+                .count();
+            if keyword == "anyOf" && valid == 0 {
+                errors.push(format!("{path}: does not match any of the `anyOf` schemas"));
+            } else if keyword == "oneOf" && valid != 1 {
+                errors.push(format!(
+                    "{path}: expected exactly one of the `oneOf` schemas to match, {valid} matched"
+                ));
+            }
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for property in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(property) {
+                        errors.push(format!("{path}: missing required property `{property}`"));
+                    }
+                }
+            }
+
+            for (key, value) in object {
+                let path = format!("{path}.{key}");
+                match properties.and_then(|properties| properties.get(key)) {
+                    Some(schema) => validate(root, schema, value, &path, errors),
+                    None => {
+                        if let Some(schema) = schema.get("additionalProperties") {
+                            validate(root, schema, value, &path, errors);
+                        }
+                    }
+                }
+            }
+        }
+        Value::Array(values) => {
+            if let Some(schema) = schema.get("items") {
+                for (i, value) in values.iter().enumerate() {
+                    validate(root, schema, value, &format!("{path}[{i}]"), errors);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Resolve a local reference (e.g.: `#/definitions/Address`) of the `root` schema.
+fn resolve<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    root.pointer(reference.strip_prefix('#')?)
+}
+
+fn has_type(value: &Value, t: &str) -> bool {
+    match t {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|x| x.fract() == 0.0)
+        }
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{BasicSchemaValidator, SchemaValidator};
+
+    #[test]
+    fn test_basic_schema_validator() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string"},
+                "age": {"type": ["integer", "null"]},
+                "unit": {"enum": ["metric", "imperial"]},
+                "address": {"$ref": "#/definitions/Address"},
+                "tags": {"type": "array", "items": {"type": "string"}}
+            },
+            "required": ["name"],
+            "additionalProperties": false,
+            "definitions": {
+                "Address": {
+                    "type": "object",
+                    "properties": {"city": {"type": "string"}},
+                    "required": ["city"]
+                }
+            }
+        });
+
+        let valid = json!({
+            "name": "Ada",
+            "age": null,
+            "unit": "metric",
+            "address": {"city": "London"},
+            "tags": ["math"]
+        });
+        assert!(BasicSchemaValidator.validate(&schema, &valid).is_ok());
+
+        let invalid = json!({
+            "age": 36.5,
+            "unit": "nautical",
+            "address": {},
+            "tags": ["math", 1],
+            "email": "ada@example.com"
+        });
+        let mut errors = BasicSchemaValidator
+            .validate(&schema, &invalid)
+            .unwrap_err()
+            .errors;
+        errors.sort();
+        assert_eq!(
+            errors,
+            vec![
+                "$.address: missing required property `city`",
+                "$.age: expected integer or null, got number",
+                "$.email: no value is allowed",
+                "$.tags[1]: expected string, got number",
+                "$.unit: \"nautical\" is not one of [\"metric\",\"imperial\"]",
+                "$: missing required property `name`",
+            ]
+        );
+    }
+}
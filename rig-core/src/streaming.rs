@@ -62,6 +62,7 @@ impl Default for PauseControl {
 
 /// Enum representing a streaming chunk from the model
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum RawStreamingChoice<R: Clone> {
     /// A text chunk from a message response
     Message(String),
@@ -73,6 +74,13 @@ pub enum RawStreamingChoice<R: Clone> {
         name: String,
         arguments: serde_json::Value,
     },
+    /// A fragment of the (JSON) arguments of a tool call, yielded while the arguments are
+    /// generated. The complete tool call is still yielded as [RawStreamingChoice::ToolCall].
+    ToolCallDelta {
+        id: String,
+        name: String,
+        delta: String,
+    },
     /// A reasoning chunk
     Reasoning {
         id: Option<String>,
@@ -202,7 +210,7 @@ where
                 {
                     return Poll::Ready(None); // Treat cancellation as stream termination
                 }
-                Poll::Ready(Some(Err(err)))
+                Poll::Ready(Some(Err(connection_reset(err))))
             }
             Poll::Ready(Some(Ok(choice))) => match choice {
                 RawStreamingChoice::Message(text) => {
@@ -246,6 +254,13 @@ where
                         ))))
                     }
                 }
+                RawStreamingChoice::ToolCallDelta { id, name, delta } => {
+                    Poll::Ready(Some(Ok(StreamedAssistantContent::ToolCallDelta {
+                        id,
+                        name,
+                        delta,
+                    })))
+                }
                 RawStreamingChoice::FinalResponse(response) => {
                     if stream
                         .final_response_yielded
@@ -267,6 +282,24 @@ where
     }
 }
 
+/// Surface the interruptions of the connection of a stream (e.g.: the body of the response
+/// failing to be read) as [CompletionError::ConnectionReset], so that callers can tell them
+/// apart from the errors of the provider and restart the request.
+fn connection_reset(err: CompletionError) -> CompletionError {
+    match err {
+        CompletionError::HttpError(e)
+            if e.is_body()
+                || e.is_decode()
+                || e.is_timeout()
+                || e.is_connect()
+                || e.is_request() =>
+        {
+            CompletionError::ConnectionReset(e.to_string())
+        }
+        err => err,
+    }
+}
+
 /// Trait for high-level streaming prompt interface
 pub trait StreamingPrompt<M, R>
 where
@@ -327,6 +360,13 @@ impl<R: Clone + Unpin> Stream for StreamingResultDyn<R> {
                 RawStreamingChoice::Reasoning { id, reasoning } => {
                     Poll::Ready(Some(Ok(RawStreamingChoice::Reasoning { id, reasoning })))
                 }
+                RawStreamingChoice::ToolCallDelta { id, name, delta } => {
+                    Poll::Ready(Some(Ok(RawStreamingChoice::ToolCallDelta {
+                        id,
+                        name,
+                        delta,
+                    })))
+                }
                 RawStreamingChoice::ToolCall {
                     id,
                     name,
@@ -371,6 +411,7 @@ pub async fn stream_to_stdout<M: CompletionModel>(
                     .map_err(|e| std::io::Error::other(e.to_string()))?;
                 println!("\nResult: {res}");
             }
+            Ok(StreamedAssistantContent::ToolCallDelta { .. }) => {}
             Ok(StreamedAssistantContent::Final(res)) => {
                 let json_res = serde_json::to_string_pretty(&res).unwrap();
                 println!();
@@ -462,6 +503,7 @@ mod tests {
                     println!("\nTool Call: {tc:?}");
                     chunk_count += 1;
                 }
+                Ok(StreamedAssistantContent::ToolCallDelta { .. }) => {}
                 Ok(StreamedAssistantContent::Final(res)) => {
                     println!("\nFinal response: {res:?}");
                 }
@@ -508,9 +550,16 @@ mod tests {
 /// Describes responses from a streamed provider response which is either text, a tool call or a final usage response.
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(untagged)]
+#[non_exhaustive]
 pub enum StreamedAssistantContent<R> {
     Text(Text),
     ToolCall(ToolCall),
+    /// A fragment of the arguments of a tool call (see [RawStreamingChoice::ToolCallDelta]).
+    ToolCallDelta {
+        id: String,
+        name: String,
+        delta: String,
+    },
     Reasoning(Reasoning),
     Final(R),
 }
@@ -0,0 +1,307 @@
+//! This module defines the spans emitted by Rig for prompt runs, turns, tool calls, completion
+//! requests and embedding requests. Their names and attributes follow the OpenTelemetry semantic
+//! conventions for generative AI (e.g.: `gen_ai.request.model`, `gen_ai.usage.input_tokens`), so
+//! that they can be exported to any OpenTelemetry pipeline as is.
+//!
+//! The spans are regular [tracing] spans, which cost (almost) nothing without a subscriber. With
+//! the `otel` feature, [otel_layer] bridges them to an OpenTelemetry tracer (e.g.: of an OTLP
+//! exporter): the latency of each operation is the duration of its span. The `opentelemetry` and
+//! `tracing-opentelemetry` crates are re-exported, so that their versions match Rig's.
+//!
+//! # Example
+//! ```rust,ignore
+//! use opentelemetry::trace::TracerProvider;
+//! use tracing_subscriber::prelude::*;
+//!
+//! let exporter = opentelemetry_otlp::SpanExporter::builder().with_http().build()?;
+//! let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+//!     .with_batch_exporter(exporter)
+//!     .build();
+//!
+//! tracing_subscriber::registry()
+//!     .with(rig::telemetry::otel_layer(provider.tracer("my-app")))
+//!     .init();
+//! ```
+//!
+//! See the `agent_with_otel` example for a complete setup.
+use std::{fmt::Display, future::Future};
+
+use tracing::{Instrument, Span, field::Empty};
+
+use crate::completion::{CompletionRequest, Usage};
+
+#[cfg(feature = "otel")]
+#[cfg_attr(docsrs, doc(cfg(feature = "otel")))]
+pub use {opentelemetry, tracing_opentelemetry};
+
+/// A [tracing_subscriber::Layer] exporting the spans of Rig (and of the application) to the
+/// OpenTelemetry `tracer`.
+#[cfg(feature = "otel")]
+#[cfg_attr(docsrs, doc(cfg(feature = "otel")))]
+pub fn otel_layer<S, T>(tracer: T) -> tracing_opentelemetry::OpenTelemetryLayer<S, T>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+    T: opentelemetry::trace::Tracer + 'static,
+    T::Span: Send + Sync,
+{
+    tracing_opentelemetry::layer().with_tracer(tracer)
+}
+
+/// The span of a prompt run of the agent `agent_name`, spanning all of its turns.
+pub(crate) fn agent_span(agent_name: &str) -> Span {
+    tracing::info_span!(
+        target: "rig",
+        "invoke_agent",
+        otel.name = format!("invoke_agent {agent_name}"),
+        agent_name,
+        gen_ai.operation.name = "invoke_agent",
+        gen_ai.agent.name = agent_name,
+        gen_ai.usage.input_tokens = Empty,
+        gen_ai.usage.output_tokens = Empty,
+        error.type = Empty,
+        otel.status_code = Empty,
+    )
+}
+
+/// The span of the `turn`-th turn (starting at 1) of a prompt run.
+pub(crate) fn turn_span(turn: usize) -> Span {
+    tracing::info_span!(target: "rig", "turn", rig.turn = turn)
+}
+
+/// The span of a completion `request` sent to `model`.
+pub(crate) fn chat_span(model: Option<&str>, request: &CompletionRequest) -> Span {
+    let model = model.unwrap_or("unknown");
+    tracing::info_span!(
+        target: "rig",
+        "chat",
+        otel.name = format!("chat {model}"),
+        otel.kind = "client",
+        gen_ai.operation.name = "chat",
+        gen_ai.request.model = model,
+        gen_ai.request.temperature = request.temperature,
+        gen_ai.request.max_tokens = request.max_tokens,
+        gen_ai.usage.input_tokens = Empty,
+        gen_ai.usage.output_tokens = Empty,
+        error.type = Empty,
+        otel.status_code = Empty,
+    )
+}
+
+/// The span of a call of the tool `tool_name`.
+pub(crate) fn tool_span(tool_name: &str) -> Span {
+    tracing::info_span!(
+        target: "rig",
+        "execute_tool",
+        otel.name = format!("execute_tool {tool_name}"),
+        gen_ai.operation.name = "execute_tool",
+        gen_ai.tool.name = tool_name,
+        error.type = Empty,
+        otel.status_code = Empty,
+    )
+}
+
+/// The span of an embedding request of `inputs` texts sent to `model`.
+pub(crate) fn embeddings_span(model: &str, inputs: usize) -> Span {
+    tracing::info_span!(
+        target: "rig",
+        "embeddings",
+        otel.name = format!("embeddings {model}"),
+        otel.kind = "client",
+        gen_ai.operation.name = "embeddings",
+        gen_ai.request.model = model,
+        rig.embeddings.inputs = inputs,
+        gen_ai.usage.input_tokens = Empty,
+        error.type = Empty,
+        otel.status_code = Empty,
+    )
+}
+
+/// Record the token `usage` of the operation of `span`.
+pub(crate) fn record_usage(span: &Span, usage: &Usage) {
+    span.record("gen_ai.usage.input_tokens", usage.input_tokens);
+    span.record("gen_ai.usage.output_tokens", usage.output_tokens);
+}
+
+/// Record the number of input tokens of the embedding request of the current span (see
+/// [embeddings_span]).
+pub(crate) fn record_input_tokens(tokens: u64) {
+    Span::current().record("gen_ai.usage.input_tokens", tokens);
+}
+
+/// Run `future` within `span`, recording its error, if it fails.
+pub(crate) async fn traced<T, E: Display>(
+    span: Span,
+    future: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let result = future.instrument(span.clone()).await;
+    record_result(&span, &result);
+    result
+}
+
+/// Record the error of the operation of `span`, if it failed.
+pub(crate) fn record_result<T, E: Display>(span: &Span, result: &Result<T, E>) {
+    if let Err(err) = result {
+        span.record("error.type", tracing::field::display(err));
+        span.record("otel.status_code", "ERROR");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fmt::Debug,
+        sync::{Arc, Mutex},
+    };
+
+    use tracing::{
+        Subscriber,
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+    };
+    use tracing_subscriber::{Layer, layer::Context, prelude::*, registry::LookupSpan};
+
+    use crate::{agent::AgentBuilder, completion::Prompt, test_utils::MockCompletionModel};
+
+    /// The name and fields of each span, by span id.
+    #[derive(Clone, Default)]
+    struct Spans(Arc<Mutex<HashMap<u64, (String, Fields)>>>);
+
+    #[derive(Default)]
+    struct Fields(HashMap<String, String>);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.insert(
+                field.name().to_string(),
+                format!("{value:?}").replace('"', ""),
+            );
+        }
+    }
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Spans {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _ctx: Context<'_, S>) {
+            let mut fields = Fields::default();
+            attrs.record(&mut fields);
+            self.0
+                .lock()
+                .unwrap()
+                .insert(id.into_u64(), (attrs.metadata().name().to_string(), fields));
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+            if let Some((_, fields)) = self.0.lock().unwrap().get_mut(&id.into_u64()) {
+                values.record(fields);
+            }
+        }
+    }
+
+    impl Spans {
+        fn field(&self, span: &str, field: &str) -> Option<String> {
+            self.0
+                .lock()
+                .unwrap()
+                .values()
+                .find(|(name, _)| name == span)
+                .and_then(|(_, fields)| fields.0.get(field).cloned())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_agent_spans() {
+        let spans = Spans::default();
+        let _guard = tracing_subscriber::registry()
+            .with(spans.clone())
+            .set_default();
+
+        let model = MockCompletionModel::new().with_tool_call(
+            "call_1",
+            "missing_tool",
+            serde_json::json!({}),
+        );
+        let agent = AgentBuilder::new(model).name("calculator").build();
+        let result = agent.prompt("Calculate 2 + 5").multi_turn(2).await;
+        assert!(result.is_err());
+
+        assert_eq!(
+            spans.field("invoke_agent", "gen_ai.agent.name").as_deref(),
+            Some("calculator")
+        );
+        assert_eq!(
+            spans.field("invoke_agent", "otel.status_code").as_deref(),
+            Some("ERROR")
+        );
+        assert_eq!(spans.field("turn", "rig.turn").as_deref(), Some("1"));
+        assert_eq!(
+            spans.field("chat", "gen_ai.operation.name").as_deref(),
+            Some("chat")
+        );
+        assert_eq!(
+            spans.field("chat", "gen_ai.usage.input_tokens").as_deref(),
+            Some("0")
+        );
+        assert_eq!(
+            spans.field("execute_tool", "gen_ai.tool.name").as_deref(),
+            Some("missing_tool")
+        );
+        assert!(spans.field("execute_tool", "error.type").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_embeddings_span() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        use crate::{client::EmbeddingsClient, embeddings::EmbeddingModel, providers::openai};
+
+        let spans = Spans::default();
+        let _guard = tracing_subscriber::registry()
+            .with(spans.clone())
+            .set_default();
+
+        // Answer the embedding request with a single embedding
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 4096];
+            let _ = socket.read(&mut buf).await.unwrap();
+            let body = serde_json::json!({
+                "object": "list",
+                "data": [{ "object": "embedding", "embedding": [0.1, 0.2], "index": 0 }],
+                "model": "text-embedding-3-small",
+                "usage": { "prompt_tokens": 4, "total_tokens": 4 }
+            })
+            .to_string();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{body}",
+                body.len()
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        let client = openai::Client::builder("invalid-key")
+            .base_url(&base_url)
+            .build()
+            .unwrap();
+
+        // The span is emitted by the model itself, not only through `EmbeddingModelDyn`
+        let model = client.embedding_model(openai::TEXT_EMBEDDING_3_SMALL);
+        model.embed_query("Hello, world!").await.unwrap();
+
+        assert_eq!(
+            spans.field("embeddings", "gen_ai.request.model").as_deref(),
+            Some("text-embedding-3-small")
+        );
+        assert_eq!(
+            spans
+                .field("embeddings", "gen_ai.usage.input_tokens")
+                .as_deref(),
+            Some("4")
+        );
+        assert_eq!(
+            spans
+                .field("embeddings", "rig.embeddings.inputs")
+                .as_deref(),
+            Some("1")
+        );
+    }
+}
@@ -0,0 +1,641 @@
+//! This module provides utilities to test code built with Rig without calling a model provider.
+//!
+//! Note: This module requires the `test-utils` feature to be enabled in the `Cargo.toml` file
+//! (typically as a dev-dependency feature).
+//!
+//! # Example
+//! ```rust,ignore
+//! use rig::{agent::AgentBuilder, completion::Prompt, test_utils::MockCompletionModel};
+//!
+//! // First turn: the model calls the `add` tool. Second turn: the model answers with text.
+//! let model = MockCompletionModel::new()
+//!     .with_tool_call("call_1", "add", serde_json::json!({"x": 2, "y": 2}))
+//!     .with_text("2 + 2 = 4");
+//!
+//! let agent = AgentBuilder::new(model.clone())
+//!     .tool(Adder)
+//!     .build();
+//!
+//! let answer = agent.prompt("What is 2 + 2?").multi_turn(2).await?;
+//!
+//! assert_eq!(answer, "2 + 2 = 4");
+//! assert_eq!(model.requests().len(), 2);
+//! ```
+//!
+//! Vector stores and RAG agents can be tested with a [MockEmbeddingModel], and an
+//! [InMemoryVectorStore] of known embeddings (see [in_memory_store]).
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    task::Poll,
+    time::Duration,
+};
+
+use async_stream::stream;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    OneOrMany,
+    completion::{
+        CompletionError, CompletionModel, CompletionRequest, CompletionResponse, Document,
+        GetTokenUsage, Usage,
+    },
+    embeddings::{EmbedKind, Embedding, EmbeddingError, EmbeddingModel},
+    message::AssistantContent,
+    streaming::{RawStreamingChoice, StreamingCompletionResponse},
+    vector_store::in_memory_store::InMemoryVectorStore,
+};
+
+/// Number of characters of the arguments of a tool call in each streamed delta.
+const ARGUMENTS_DELTA_LEN: usize = 8;
+
+/// A [CompletionModel] that returns scripted responses, used to deterministically test
+/// agents and multi-turn tool loops without any network access.
+///
+/// Responses are returned in the order in which they were added. Responses added with
+/// [MockCompletionModel::with_response_for] are only returned when the text of the incoming
+/// prompt matches, and take precedence over the other responses.
+///
+/// When streamed, the arguments of tool calls are first yielded as deltas of a few characters,
+/// followed by the complete tool call.
+///
+/// Clones of the model share the same script and recorded requests, so a clone can be given
+/// to an agent while the original is used for assertions (see [MockCompletionModel::requests]).
+#[derive(Clone, Default)]
+pub struct MockCompletionModel {
+    state: Arc<Mutex<MockState>>,
+    context_window: Option<usize>,
+}
+
+/// The final response of the streams of a [MockCompletionModel], reporting the usage of the
+/// scripted response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MockStreamingResponse {
+    pub usage: Usage,
+}
+
+impl GetTokenUsage for MockStreamingResponse {
+    fn token_usage(&self) -> Option<Usage> {
+        Some(self.usage)
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    responses: VecDeque<Result<CompletionResponse<()>, CompletionError>>,
+    responses_by_prompt: HashMap<String, VecDeque<CompletionResponse<()>>>,
+    requests: Vec<CompletionRequest>,
+}
+
+impl MockCompletionModel {
+    /// Create a new mock completion model without any scripted response.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a response to the queue of scripted responses.
+    pub fn with_response(self, response: CompletionResponse<()>) -> Self {
+        self.lock().responses.push_back(Ok(response));
+        self
+    }
+
+    /// Add an error to the queue of scripted responses, returned as a
+    /// [CompletionError::ProviderError] with the given message.
+    pub fn with_error(self, message: &str) -> Self {
+        self.lock()
+            .responses
+            .push_back(Err(CompletionError::ProviderError(message.to_string())));
+        self
+    }
+
+    /// Add an error response with HTTP status `status` to the queue of scripted responses,
+    /// returned as a [CompletionError::ProviderStatusError] with the given message.
+    pub fn with_status_error(self, status: u16, message: &str) -> Self {
+        self.lock()
+            .responses
+            .push_back(Err(CompletionError::ProviderStatusError {
+                status: reqwest::StatusCode::from_u16(status).expect("Invalid HTTP status"),
+                message: message.to_string(),
+            }));
+        self
+    }
+
+    /// Add a [CompletionError::ConnectionReset] to the queue of scripted responses. Streamed
+    /// requests fail after the stream was returned, like a connection dropped mid-stream.
+    pub fn with_connection_reset(self) -> Self {
+        self.lock()
+            .responses
+            .push_back(Err(CompletionError::ConnectionReset(
+                "connection reset by peer".to_string(),
+            )));
+        self
+    }
+
+    /// Add a response that is only returned when the text of the incoming prompt is `prompt`.
+    pub fn with_response_for(self, prompt: &str, response: CompletionResponse<()>) -> Self {
+        self.lock()
+            .responses_by_prompt
+            .entry(prompt.to_string())
+            .or_default()
+            .push_back(response);
+        self
+    }
+
+    /// Add a text response to the queue of scripted responses.
+    pub fn with_text(self, text: &str) -> Self {
+        self.with_response(Self::response(AssistantContent::text(text)))
+    }
+
+    /// Add a tool call response to the queue of scripted responses.
+    pub fn with_tool_call(self, id: &str, name: &str, arguments: serde_json::Value) -> Self {
+        self.with_response(Self::response(AssistantContent::tool_call(
+            id, name, arguments,
+        )))
+    }
+
+    /// Set the context window reported by the model (see [CompletionModel::context_window]).
+    pub fn with_context_window(mut self, context_window: usize) -> Self {
+        self.context_window = Some(context_window);
+        self
+    }
+
+    /// Helper constructor for a scripted response with a single piece of content and no token usage.
+    pub fn response(content: AssistantContent) -> CompletionResponse<()> {
+        CompletionResponse {
+            choice: OneOrMany::one(content),
+            usage: Usage::new(),
+            raw_response: (),
+        }
+    }
+
+    /// The completion requests received by the model so far, in order.
+    pub fn requests(&self) -> Vec<CompletionRequest> {
+        self.lock().requests.clone()
+    }
+
+    /// The number of scripted responses that have not been returned yet.
+    pub fn remaining_responses(&self) -> usize {
+        let state = self.lock();
+        state.responses.len()
+            + state
+                .responses_by_prompt
+                .values()
+                .map(VecDeque::len)
+                .sum::<usize>()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state
+            .lock()
+            .expect("MockCompletionModel state should not be poisoned")
+    }
+
+    fn next_response(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<()>, CompletionError> {
+        let prompt = request
+            .chat_history
+            .iter()
+            .last()
+            .and_then(|message| message.rag_text());
+        let mut state = self.lock();
+        state.requests.push(request);
+
+        let keyed_response = prompt.and_then(|prompt| {
+            state
+                .responses_by_prompt
+                .get_mut(&prompt)
+                .and_then(VecDeque::pop_front)
+        });
+
+        keyed_response
+            .map(Ok)
+            .or_else(|| state.responses.pop_front())
+            .unwrap_or_else(|| {
+                Err(CompletionError::ProviderError(
+                    "MockCompletionModel: no scripted response left".to_string(),
+                ))
+            })
+    }
+}
+
+impl CompletionModel for MockCompletionModel {
+    type Response = ();
+    type StreamingResponse = MockStreamingResponse;
+
+    fn context_window(&self) -> Option<usize> {
+        self.context_window
+    }
+
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<CompletionResponse<()>, CompletionError> {
+        self.next_response(request)
+    }
+
+    async fn stream(
+        &self,
+        request: CompletionRequest,
+    ) -> Result<StreamingCompletionResponse<MockStreamingResponse>, CompletionError> {
+        let response = match self.next_response(request) {
+            Err(CompletionError::ConnectionReset(e)) => {
+                let stream = stream! {
+                    yield Err(CompletionError::ConnectionReset(e));
+                };
+                return Ok(StreamingCompletionResponse::stream(Box::pin(stream)));
+            }
+            response => response?,
+        };
+        let usage = response.usage;
+
+        let stream = stream! {
+            for content in response.choice {
+                // Like providers do, stream the arguments of tool calls before the complete tool call
+                if let AssistantContent::ToolCall(tool_call) = &content {
+                    let arguments = tool_call.function.arguments.to_string().chars().collect::<Vec<_>>();
+                    for delta in arguments.chunks(ARGUMENTS_DELTA_LEN) {
+                        yield Ok(RawStreamingChoice::ToolCallDelta {
+                            id: tool_call.id.clone(),
+                            name: tool_call.function.name.clone(),
+                            delta: delta.iter().collect(),
+                        });
+                    }
+                }
+
+                yield Ok(match content {
+                    AssistantContent::Text(text) => RawStreamingChoice::Message(text.text),
+                    AssistantContent::Refusal(refusal) => RawStreamingChoice::Message(refusal.refusal),
+                    AssistantContent::ToolCall(tool_call) => RawStreamingChoice::ToolCall {
+                        id: tool_call.id,
+                        call_id: tool_call.call_id,
+                        name: tool_call.function.name,
+                        arguments: tool_call.function.arguments,
+                    },
+                    AssistantContent::Reasoning(reasoning) => RawStreamingChoice::Reasoning {
+                        id: reasoning.id,
+                        reasoning: reasoning.reasoning.join("\n"),
+                    },
+                });
+            }
+
+            yield Ok(RawStreamingChoice::FinalResponse(MockStreamingResponse { usage }));
+        };
+
+        Ok(StreamingCompletionResponse::stream(Box::pin(stream)))
+    }
+}
+
+/// Function embedding a text (see [MockEmbeddingModel::from_fn]).
+type EmbedFn = Arc<dyn Fn(&str) -> Vec<f64> + Send + Sync>;
+
+/// Function returning the latency of a request from its texts (see
+/// [MockEmbeddingModel::with_latency]).
+type LatencyFn = Arc<dyn Fn(&[String]) -> Duration + Send + Sync>;
+
+/// Kind and texts of the embedding requests received by a [MockEmbeddingModel].
+type EmbedRequests = Arc<Mutex<Vec<(EmbedKind, Vec<String>)>>>;
+
+/// An [EmbeddingModel] embedding every text as the same vector, or with a function of the text,
+/// used to deterministically test vector stores and RAG without any network access.
+///
+/// Clones of the model share the same recorded requests (see [MockEmbeddingModel::requests])
+/// and statistics (see [MockEmbeddingModel::peak_in_flight] and
+/// [MockEmbeddingModel::rate_limited]).
+#[derive(Clone)]
+pub struct MockEmbeddingModel {
+    ndims: usize,
+    embed: EmbedFn,
+    max_batch_size: Option<usize>,
+    max_input_tokens: Option<usize>,
+    latency: Option<LatencyFn>,
+    rate_limit: Option<usize>,
+    requests: EmbedRequests,
+    in_flight: Arc<AtomicUsize>,
+    peak_in_flight: Arc<AtomicUsize>,
+    rate_limited: Arc<AtomicUsize>,
+}
+
+impl MockEmbeddingModel {
+    /// Create a mock embedding model embedding every text as `vec`.
+    pub fn new(vec: Vec<f64>) -> Self {
+        Self::from_fn(vec.len(), move |_| vec.clone())
+    }
+
+    /// Create a mock embedding model of `ndims` dimensions, embedding each text with `embed`.
+    pub fn from_fn(ndims: usize, embed: impl Fn(&str) -> Vec<f64> + Send + Sync + 'static) -> Self {
+        Self {
+            ndims,
+            embed: Arc::new(embed),
+            max_batch_size: None,
+            max_input_tokens: None,
+            latency: None,
+            rate_limit: None,
+            requests: Arc::default(),
+            in_flight: Arc::default(),
+            peak_in_flight: Arc::default(),
+            rate_limited: Arc::default(),
+        }
+    }
+
+    /// Set the maximum number of texts embedded in a single request (see
+    /// [EmbeddingModel::max_batch_size]).
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = Some(max_batch_size);
+        self
+    }
+
+    /// Set the maximum number of tokens of a text (see [EmbeddingModel::max_input_tokens]).
+    pub fn with_max_input_tokens(mut self, max_input_tokens: usize) -> Self {
+        self.max_input_tokens = Some(max_input_tokens);
+        self
+    }
+
+    /// Delay each request by a function of its texts (e.g.: to complete concurrent requests out
+    /// of order).
+    pub fn with_latency(
+        mut self,
+        latency: impl Fn(&[String]) -> Duration + Send + Sync + 'static,
+    ) -> Self {
+        self.latency = Some(Arc::new(latency));
+        self
+    }
+
+    /// Fail the requests made while more than `max_in_flight` requests are in flight with a
+    /// rate limit error (i.e.: HTTP status 429, see [EmbeddingError::is_rate_limited]).
+    pub fn with_rate_limit(mut self, max_in_flight: usize) -> Self {
+        self.rate_limit = Some(max_in_flight);
+        self
+    }
+
+    /// The texts of the embedding requests received by the model so far, by request, in order.
+    pub fn requests(&self) -> Vec<Vec<String>> {
+        self.requests
+            .lock()
+            .expect("MockEmbeddingModel requests should not be poisoned")
+            .iter()
+            .map(|(_, texts)| texts.clone())
+            .collect()
+    }
+
+    /// The kind of the texts of the embedding requests received by the model so far, by
+    /// request, in order (see [EmbedKind]).
+    pub fn kinds(&self) -> Vec<EmbedKind> {
+        self.requests
+            .lock()
+            .expect("MockEmbeddingModel requests should not be poisoned")
+            .iter()
+            .map(|(kind, _)| *kind)
+            .collect()
+    }
+
+    /// The maximum number of requests that were in flight at the same time so far.
+    pub fn peak_in_flight(&self) -> usize {
+        self.peak_in_flight.load(Ordering::SeqCst)
+    }
+
+    /// The number of requests that failed with a rate limit error so far (see
+    /// [MockEmbeddingModel::with_rate_limit]).
+    pub fn rate_limited(&self) -> usize {
+        self.rate_limited.load(Ordering::SeqCst)
+    }
+}
+
+impl EmbeddingModel for MockEmbeddingModel {
+    const MAX_DOCUMENTS: usize = 1024;
+
+    fn ndims(&self) -> usize {
+        self.ndims
+    }
+
+    fn max_batch_size(&self) -> Option<usize> {
+        self.max_batch_size
+    }
+
+    fn max_input_tokens(&self) -> Option<usize> {
+        self.max_input_tokens
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        self.embed_texts_with_kind(texts, EmbedKind::Document).await
+    }
+
+    async fn embed_texts_with_kind(
+        &self,
+        texts: impl IntoIterator<Item = String> + Send,
+        kind: EmbedKind,
+    ) -> Result<Vec<Embedding>, EmbeddingError> {
+        let texts = texts.into_iter().collect::<Vec<_>>();
+        self.requests
+            .lock()
+            .expect("MockEmbeddingModel requests should not be poisoned")
+            .push((kind, texts.clone()));
+
+        let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+        // Like a network request, let the concurrent requests progress before completing
+        match &self.latency {
+            Some(latency) => tokio::time::sleep(latency(&texts)).await,
+            None => yield_now().await,
+        }
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+        if let Some(max_in_flight) = self.rate_limit
+            && in_flight > max_in_flight
+        {
+            self.rate_limited.fetch_add(1, Ordering::SeqCst);
+            return Err(EmbeddingError::ProviderStatusError {
+                status: reqwest::StatusCode::TOO_MANY_REQUESTS,
+                message: "Rate limit reached for requests".to_string(),
+            });
+        }
+
+        Ok(texts
+            .into_iter()
+            .map(|document| Embedding {
+                vec: (self.embed)(&document),
+                document,
+            })
+            .collect())
+    }
+}
+
+/// Yield to the executor once.
+async fn yield_now() {
+    let mut yielded = false;
+    futures::future::poll_fn(|cx| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+/// Create a context [Document] of id `id` and text `text`, without additional properties.
+pub fn document(id: &str, text: &str) -> Document {
+    Document {
+        id: id.to_string(),
+        text: text.to_string(),
+        additional_props: HashMap::new(),
+    }
+}
+
+/// Create an [InMemoryVectorStore] of `(id, document, embedding vector)`, where each document
+/// is its own embedded text.
+pub fn in_memory_store<'a>(
+    documents: impl IntoIterator<Item = (&'a str, &'a str, Vec<f64>)>,
+) -> InMemoryVectorStore<String> {
+    InMemoryVectorStore::from_documents_with_ids(documents.into_iter().map(
+        |(id, document, vec)| {
+            (
+                id,
+                document.to_string(),
+                OneOrMany::one(Embedding {
+                    document: document.to_string(),
+                    vec,
+                }),
+            )
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    use crate::{
+        agent::{AgentBuilder, FinalAnswerTool},
+        completion::{CompletionModel, Prompt, PromptError, ToolDefinition},
+        message::AssistantContent,
+        tool::Tool,
+    };
+
+    use super::MockCompletionModel;
+
+    #[derive(Deserialize)]
+    struct OperationArgs {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("Math error")]
+    struct MathError;
+
+    struct Adder;
+
+    impl Tool for Adder {
+        const NAME: &'static str = "add";
+        type Error = MathError;
+        type Args = OperationArgs;
+        type Output = i32;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: Self::NAME.to_string(),
+                description: "Add x and y together".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "x": { "type": "number" },
+                        "y": { "type": "number" }
+                    },
+                    "required": ["x", "y"]
+                }),
+            }
+        }
+
+        async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+            Ok(args.x + args.y)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scripted_responses() {
+        let model = MockCompletionModel::new()
+            .with_text("first")
+            .with_response_for(
+                "ping",
+                MockCompletionModel::response(AssistantContent::text("pong")),
+            )
+            .with_text("second");
+
+        let completion = |prompt: &'static str| {
+            let model = model.clone();
+            async move {
+                let response = model.completion_request(prompt).send().await.unwrap();
+                response.choice.first()
+            }
+        };
+
+        assert_eq!(completion("hello").await, AssistantContent::text("first"));
+        assert_eq!(completion("ping").await, AssistantContent::text("pong"));
+        assert_eq!(completion("ping").await, AssistantContent::text("second"));
+        assert!(model.completion_request("hello").send().await.is_err());
+        assert_eq!(model.requests().len(), 4);
+        assert_eq!(model.remaining_responses(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_multi_turn_tool_loop() {
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "add", json!({"x": 2, "y": 3}))
+            .with_text("2 + 3 = 5");
+
+        let agent = AgentBuilder::new(model.clone()).tool(Adder).build();
+
+        let answer = agent.prompt("What is 2 + 3?").multi_turn(2).await.unwrap();
+        assert_eq!(answer, "2 + 3 = 5");
+
+        // The second request contains the tool call and its result
+        let requests = model.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].chat_history.len(), 3);
+        assert_eq!(requests[0].tools[0].name, "add");
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize, schemars::JsonSchema)]
+    struct Sum {
+        result: i32,
+    }
+
+    #[tokio::test]
+    async fn test_final_answer_tool() {
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "add", json!({"x": 2, "y": 3}))
+            .with_tool_call("call_2", "final_answer", json!({"result": 5}))
+            .with_text("This should not be reached");
+
+        let agent = AgentBuilder::new(model.clone())
+            .tool(Adder)
+            .final_answer_tool(FinalAnswerTool::<Sum>::new())
+            .build();
+
+        let answer = agent
+            .prompt("What is 2 + 3?")
+            .multi_turn(5)
+            .final_answer::<Sum>()
+            .await
+            .unwrap();
+
+        assert_eq!(answer, Sum { result: 5 });
+        assert_eq!(model.remaining_responses(), 1);
+
+        // Answering with text instead of calling the final answer tool is an error
+        let answer = agent.prompt("What is 2 + 3?").final_answer::<Sum>().await;
+
+        assert!(matches!(answer, Err(PromptError::FinalAnswerError(_))));
+    }
+}
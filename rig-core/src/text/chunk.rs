@@ -0,0 +1,407 @@
+//! Text chunking strategies, used to split long documents into chunks that can be embedded
+//! separately (e.g.: using [EmbeddingsBuilder](crate::embeddings::EmbeddingsBuilder)).
+//!
+//! The following strategies are available:
+//! - [TokenChunker]: fixed-size windows of tokens (whitespace separated words), with overlap.
+//! - [SentenceChunker]: groups of whole sentences, up to a maximum length.
+//! - [RecursiveChunker]: recursively splits the text on a list of separators (by default:
+//!   paragraphs, then lines, then sentences, then words) until each chunk is short enough.
+//!
+//! Custom strategies can be implemented with the [Chunker] trait.
+//!
+//! # Example
+//! ```rust
+//! use rig::text::chunk::{Chunker, RecursiveChunker};
+//!
+//! let text = "Flurbos are green aliens.\n\nThey live on cold planets.";
+//! let chunks = RecursiveChunker::new(30).chunk(text);
+//!
+//! assert_eq!(chunks.len(), 2);
+//! assert_eq!(chunks[1].text, "They live on cold planets.");
+//! assert_eq!(&text[chunks[1].start..chunks[1].end], chunks[1].text);
+//! ```
+use serde::{Deserialize, Serialize};
+
+use crate::embeddings::{Embed, EmbedError, TextEmbedder};
+
+/// A chunk of text, along with its position in the original text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chunk {
+    /// The text of the chunk
+    pub text: String,
+    /// Byte offset of the start of the chunk in the original text
+    pub start: usize,
+    /// Byte offset of the end (exclusive) of the chunk in the original text
+    pub end: usize,
+}
+
+impl Chunk {
+    /// Create a chunk from the `start..end` byte range of `text`, trimming leading and
+    /// trailing whitespace. Returns `None` if the chunk only contains whitespace.
+    fn trimmed(text: &str, start: usize, end: usize) -> Option<Self> {
+        let slice = &text[start..end];
+        let trimmed = slice.trim_start();
+        let start = start + (slice.len() - trimmed.len());
+        let trimmed = trimmed.trim_end();
+
+        (!trimmed.is_empty()).then(|| Self {
+            text: trimmed.to_string(),
+            start,
+            end: start + trimmed.len(),
+        })
+    }
+}
+
+impl Embed for Chunk {
+    fn embed(&self, embedder: &mut TextEmbedder) -> Result<(), EmbedError> {
+        embedder.embed(self.text.clone());
+        Ok(())
+    }
+}
+
+/// Trait for text chunking strategies.
+pub trait Chunker {
+    /// Split `text` into chunks. Chunks are returned in the order in which they appear in the text.
+    fn chunk(&self, text: &str) -> Vec<Chunk>;
+}
+
+/// Splits text into windows of `size` tokens, where consecutive windows share `overlap` tokens.
+///
+/// Tokens are approximated as whitespace separated words.
+#[derive(Debug, Clone, Copy)]
+pub struct TokenChunker {
+    size: usize,
+    overlap: usize,
+}
+
+impl TokenChunker {
+    /// Create a new token chunker.
+    ///
+    /// # Panics
+    /// Panics if `size` is 0 or if `overlap` is not smaller than `size`.
+    pub fn new(size: usize, overlap: usize) -> Self {
+        assert!(size > 0, "Chunk size must be greater than 0");
+        assert!(
+            overlap < size,
+            "Chunk overlap must be smaller than the chunk size"
+        );
+
+        Self { size, overlap }
+    }
+}
+
+impl Chunker for TokenChunker {
+    fn chunk(&self, text: &str) -> Vec<Chunk> {
+        let tokens = word_spans(text);
+        let step = self.size - self.overlap;
+
+        let mut chunks = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            let last = (i + self.size).min(tokens.len()) - 1;
+            chunks.extend(Chunk::trimmed(text, tokens[i].0, tokens[last].1));
+
+            if last == tokens.len() - 1 {
+                break;
+            }
+            i += step;
+        }
+
+        chunks
+    }
+}
+
+/// Groups consecutive whole sentences into chunks of at most `max_len` bytes.
+///
+/// Sentences longer than `max_len` are returned as their own chunk.
+#[derive(Debug, Clone, Copy)]
+pub struct SentenceChunker {
+    max_len: usize,
+}
+
+impl SentenceChunker {
+    /// Create a new sentence chunker.
+    pub fn new(max_len: usize) -> Self {
+        Self { max_len }
+    }
+}
+
+impl Chunker for SentenceChunker {
+    fn chunk(&self, text: &str) -> Vec<Chunk> {
+        let sentences = sentence_spans(text)
+            .into_iter()
+            .filter_map(|(start, end)| Chunk::trimmed(text, start, end))
+            .map(|chunk| (chunk.start, chunk.end))
+            .collect::<Vec<_>>();
+
+        merge_spans(text, &sentences, self.max_len)
+    }
+}
+
+/// Recursively splits text on a list of separators until each chunk is at most `max_len` bytes.
+///
+/// The text is first split on the first separator (by default, paragraphs). Consecutive pieces
+/// are then merged back together as long as they fit in `max_len`, and pieces that are still
+/// too long are split on the next separator (lines, then sentences, then words). Pieces that
+/// are too long once all separators are exhausted are split at `max_len` bytes.
+#[derive(Debug, Clone)]
+pub struct RecursiveChunker {
+    max_len: usize,
+    separators: Vec<String>,
+}
+
+impl RecursiveChunker {
+    /// Create a new recursive chunker with the default separators (paragraphs, lines, sentences and words).
+    ///
+    /// # Panics
+    /// Panics if `max_len` is 0.
+    pub fn new(max_len: usize) -> Self {
+        assert!(max_len > 0, "Chunk max length must be greater than 0");
+
+        Self {
+            max_len,
+            separators: ["\n\n", "\n", ". ", " "]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        }
+    }
+
+    /// Set the separators to split the text on, from the coarsest to the finest.
+    pub fn separators(mut self, separators: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.separators = separators.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Split the `start..end` range of `text` into spans of at most `max_len` bytes.
+    fn split(
+        &self,
+        text: &str,
+        start: usize,
+        end: usize,
+        separators: &[String],
+    ) -> Vec<(usize, usize)> {
+        if end - start <= self.max_len {
+            return vec![(start, end)];
+        }
+
+        let Some((separator, separators)) = separators.split_first() else {
+            // No separators left, split at `max_len` (on a char boundary).
+            let mut spans = Vec::new();
+            let mut span_start = start;
+            while span_start < end {
+                let mut span_end = (span_start + self.max_len).min(end);
+                while !text.is_char_boundary(span_end) {
+                    span_end -= 1;
+                }
+                if span_end == span_start {
+                    // A single char is longer than `max_len`
+                    span_end =
+                        span_start + text[span_start..].chars().next().map_or(1, char::len_utf8);
+                }
+                spans.push((span_start, span_end));
+                span_start = span_end;
+            }
+            return spans;
+        };
+
+        // Split on the separator, keeping it at the end of each piece.
+        let pieces = text[start..end]
+            .split_inclusive(separator.as_str())
+            .scan(start, |offset, piece| {
+                let span = (*offset, *offset + piece.len());
+                *offset = span.1;
+                Some(span)
+            })
+            .flat_map(|(start, end)| self.split(text, start, end, separators))
+            .collect::<Vec<_>>();
+
+        merge_spans(text, &pieces, self.max_len)
+            .into_iter()
+            .map(|chunk| (chunk.start, chunk.end))
+            .collect()
+    }
+}
+
+impl Chunker for RecursiveChunker {
+    fn chunk(&self, text: &str) -> Vec<Chunk> {
+        self.split(text, 0, text.len(), &self.separators)
+            .into_iter()
+            .filter_map(|(start, end)| Chunk::trimmed(text, start, end))
+            .collect()
+    }
+}
+
+/// Greedily merge consecutive spans of `text` into chunks of at most `max_len` bytes.
+fn merge_spans(text: &str, spans: &[(usize, usize)], max_len: usize) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+
+    for &(start, end) in spans {
+        current = match current {
+            Some((current_start, _)) if end - current_start <= max_len => {
+                Some((current_start, end))
+            }
+            Some((current_start, current_end)) => {
+                chunks.extend(Chunk::trimmed(text, current_start, current_end));
+                Some((start, end))
+            }
+            None => Some((start, end)),
+        };
+    }
+
+    if let Some((start, end)) = current {
+        chunks.extend(Chunk::trimmed(text, start, end));
+    }
+
+    chunks
+}
+
+/// Byte ranges of the whitespace separated words of `text`.
+fn word_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut word_start = None;
+
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), word_start) {
+            (true, Some(start)) => {
+                spans.push((start, i));
+                word_start = None;
+            }
+            (false, None) => word_start = Some(i),
+            _ => {}
+        }
+    }
+
+    if let Some(start) = word_start {
+        spans.push((start, text.len()));
+    }
+
+    spans
+}
+
+/// Byte ranges of the sentences of `text`. A sentence ends with `.`, `!` or `?` followed by
+/// whitespace (or the end of the text).
+fn sentence_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut sentence_start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let end_of_sentence = matches!(c, '.' | '!' | '?')
+            && chars.peek().is_none_or(|(_, next)| next.is_whitespace());
+
+        if end_of_sentence {
+            let end = i + c.len_utf8();
+            spans.push((sentence_start, end));
+            sentence_start = end;
+        }
+    }
+
+    if sentence_start < text.len() {
+        spans.push((sentence_start, text.len()));
+    }
+
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Chunk, Chunker, RecursiveChunker, SentenceChunker, TokenChunker};
+
+    const TEXT: &str = "Flurbos are green aliens. They live on cold planets!\n\nGlarb-glarbs are ancient tools. Are they used to farm the land?";
+
+    fn texts(chunks: &[Chunk]) -> Vec<&str> {
+        chunks.iter().map(|chunk| chunk.text.as_str()).collect()
+    }
+
+    fn assert_offsets(text: &str, chunks: &[Chunk]) {
+        for chunk in chunks {
+            assert_eq!(&text[chunk.start..chunk.end], chunk.text);
+        }
+    }
+
+    #[test]
+    fn test_token_chunker() {
+        let chunks = TokenChunker::new(4, 1).chunk(TEXT);
+
+        assert_offsets(TEXT, &chunks);
+        assert_eq!(
+            texts(&chunks),
+            vec![
+                "Flurbos are green aliens.",
+                "aliens. They live on",
+                "on cold planets!\n\nGlarb-glarbs",
+                "Glarb-glarbs are ancient tools.",
+                "tools. Are they used",
+                "used to farm the",
+                "the land?",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_token_chunker_short_text() {
+        let chunks = TokenChunker::new(10, 2).chunk("  Flurbos are green aliens.  ");
+
+        assert_eq!(
+            chunks,
+            vec![Chunk {
+                text: "Flurbos are green aliens.".to_string(),
+                start: 2,
+                end: 27,
+            }]
+        );
+        assert!(TokenChunker::new(10, 2).chunk("   ").is_empty());
+    }
+
+    #[test]
+    fn test_sentence_chunker() {
+        let chunks = SentenceChunker::new(60).chunk(TEXT);
+
+        assert_offsets(TEXT, &chunks);
+        assert_eq!(
+            texts(&chunks),
+            vec![
+                "Flurbos are green aliens. They live on cold planets!",
+                "Glarb-glarbs are ancient tools.",
+                "Are they used to farm the land?",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sentence_chunker_long_sentence() {
+        let chunks = SentenceChunker::new(10).chunk("Flurbos are green aliens. Hi!");
+
+        assert_eq!(texts(&chunks), vec!["Flurbos are green aliens.", "Hi!"]);
+    }
+
+    #[test]
+    fn test_recursive_chunker() {
+        let chunks = RecursiveChunker::new(60).chunk(TEXT);
+
+        assert_offsets(TEXT, &chunks);
+        assert_eq!(
+            texts(&chunks),
+            vec![
+                "Flurbos are green aliens. They live on cold planets!",
+                "Glarb-glarbs are ancient tools.",
+                "Are they used to farm the land?",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recursive_chunker_words_and_chars() {
+        let chunks = RecursiveChunker::new(8).chunk("Flurbos are glarb-glarbs");
+
+        assert_eq!(texts(&chunks), vec!["Flurbos", "are", "glarb-gl", "arbs"]);
+
+        let chunks = RecursiveChunker::new(10)
+            .separators(["|"])
+            .chunk("Flurbos|are|green|aliens");
+
+        assert_eq!(texts(&chunks), vec!["Flurbos|", "are|green|", "aliens"]);
+    }
+}
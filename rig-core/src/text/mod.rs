@@ -0,0 +1,6 @@
+//! This module provides utilities for preprocessing text before embedding it, such as
+//! splitting long documents into smaller chunks (see [chunk]).
+
+pub mod chunk;
+
+pub use chunk::{Chunk, Chunker, RecursiveChunker, SentenceChunker, TokenChunker};
@@ -11,15 +11,19 @@
 
 use std::{collections::HashMap, pin::Pin};
 
-use futures::Future;
+use futures::{Future, Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    OneOrMany,
     completion::{self, ToolDefinition},
     embeddings::{embed::EmbedError, tool::ToolSchema},
+    message::{ToolCall, ToolResultContent},
+    schema::SchemaValidationError,
 };
 
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum ToolError {
     /// Error returned by the tool
     #[error("ToolCallError: {0}")]
@@ -27,6 +31,15 @@ pub enum ToolError {
 
     #[error("JsonError: {0}")]
     JsonError(#[from] serde_json::Error),
+
+    /// The arguments of the tool call could not be deserialized into the arguments of the tool
+    #[error("InvalidArguments: {0}")]
+    InvalidArguments(serde_json::Error),
+
+    /// The arguments of the tool call do not match the parameters schema of the tool
+    /// (see [AgentBuilder::schema_validator](crate::agent::AgentBuilder::schema_validator))
+    #[error("SchemaValidationError: {0}")]
+    SchemaValidationError(#[from] SchemaValidationError),
 }
 
 /// Trait that represents a simple LLM tool
@@ -84,6 +97,96 @@ pub enum ToolError {
 ///     }
 /// }
 /// ```
+///
+/// # Stateful tools
+/// Tools are shared by every call of the agent (including concurrent ones) and only get `&self`,
+/// so tools that need mutable state (e.g.: a counter, a database handle, a document being
+/// written) should use interior mutability. Keep a clone of the state when constructing the
+/// tool to access it outside of the agent, e.g. once the prompt is done:
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use rig::{completion::ToolDefinition, tool::Tool};
+///
+/// #[derive(serde::Deserialize)]
+/// struct TakeNoteArgs {
+///     note: String,
+/// }
+///
+/// #[derive(Clone, Default)]
+/// struct Notebook {
+///     notes: Arc<Mutex<Vec<String>>>,
+/// }
+///
+/// impl Tool for Notebook {
+///     const NAME: &'static str = "take_note";
+///
+///     type Error = std::convert::Infallible;
+///     type Args = TakeNoteArgs;
+///     type Output = usize;
+///
+///     async fn definition(&self, _prompt: String) -> ToolDefinition {
+///         ToolDefinition {
+///             name: Self::NAME.to_string(),
+///             description: "Write down a note".to_string(),
+///             parameters: serde_json::json!({
+///                 "type": "object",
+///                 "properties": { "note": { "type": "string" } }
+///             }),
+///         }
+///     }
+///
+///     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+///         // Don't hold a `std::sync::Mutex` guard across an `.await` (use `tokio::sync::Mutex` if needed)
+///         let mut notes = self.notes.lock().unwrap();
+///         notes.push(args.note);
+///         Ok(notes.len())
+///     }
+/// }
+///
+/// let notebook = Notebook::default();
+/// // let agent = openai.agent(openai::GPT_4O).tool(notebook.clone()).build();
+/// // agent.prompt("...").multi_turn(5).await?;
+/// let notes = notebook.notes.lock().unwrap().clone();
+/// ```
+///
+/// # Tool outputs
+/// The output of a tool is sent back to the model as is, as the JSON serialization of
+/// [Tool::Output] (see [Tool::result_content]). Rig does not limit its size: large outputs use
+/// up the model's context window (and may exceed it), so tools returning unbounded data (e.g.:
+/// file contents, web pages) should truncate or summarize it themselves.
+///
+/// Binary data should not be returned as is (e.g.: a `Vec<u8>` is serialized as an array of
+/// numbers): return it from [Tool::result_content] with [ToolResultContent::binary], which
+/// sends images as image content and other data base64 encoded:
+/// ```
+/// use rig::{OneOrMany, completion::ToolDefinition, message::ToolResultContent, tool::{Tool, ToolError}};
+///
+/// struct Screenshot;
+///
+/// impl Tool for Screenshot {
+///     const NAME: &'static str = "screenshot";
+///
+///     type Error = std::convert::Infallible;
+///     type Args = serde_json::Value;
+///     type Output = Vec<u8>;
+///
+///     async fn definition(&self, _prompt: String) -> ToolDefinition {
+///         ToolDefinition {
+///             name: Self::NAME.to_string(),
+///             description: "Take a screenshot of the screen".to_string(),
+///             parameters: serde_json::json!({"type": "object"}),
+///         }
+///     }
+///
+///     async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
+///         Ok(vec![0x89, 0x50, 0x4e, 0x47])
+///     }
+///
+///     fn result_content(&self, output: Self::Output) -> Result<OneOrMany<ToolResultContent>, ToolError> {
+///         Ok(OneOrMany::one(ToolResultContent::binary(&output, "image/png")))
+///     }
+/// }
+/// ```
 pub trait Tool: Sized + Send + Sync {
     /// The name of the tool. This name should be unique.
     const NAME: &'static str;
@@ -111,6 +214,40 @@ pub trait Tool: Sized + Send + Sync {
         &self,
         args: Self::Args,
     ) -> impl Future<Output = Result<Self::Output, Self::Error>> + Send;
+
+    /// A method converting the output of the tool into the content of the tool result
+    /// sent back to the model. By default, the output is serialized as a JSON string.
+    ///
+    /// Override this method to return rich content, e.g.: an image rendered or fetched by the tool.
+    /// Note that not all providers support non-text tool results.
+    fn result_content(
+        &self,
+        output: Self::Output,
+    ) -> Result<OneOrMany<ToolResultContent>, ToolError> {
+        Ok(OneOrMany::one(ToolResultContent::text(
+            serde_json::to_string(&output)?,
+        )))
+    }
+}
+
+/// A stream of the fragments of the (JSON) arguments of a tool call, as generated by the model.
+pub type ArgumentsStream = Pin<Box<dyn Stream<Item = String> + Send>>;
+
+/// Trait for tools that can start working before the model has finished generating their
+/// arguments (e.g.: a tool writing a large file).
+///
+/// When the agent is streamed and the provider streams the arguments of tool calls, the
+/// tool is called with [StreamingTool::call_streaming] as soon as the first fragment of its
+/// arguments is received. Otherwise, [Tool::call] is used.
+///
+/// Streaming tools are added to an agent with [AgentBuilder::streaming_tool](crate::agent::AgentBuilder::streaming_tool).
+pub trait StreamingTool: Tool {
+    /// The streaming tool execution method. The stream ends when the model has finished
+    /// generating the arguments: the concatenation of the fragments is the JSON arguments of the call.
+    fn call_streaming(
+        &self,
+        arguments: ArgumentsStream,
+    ) -> impl Future<Output = Result<Self::Output, Self::Error>> + Send;
 }
 
 /// Trait that represents an LLM tool that can be stored in a vector store and RAGged
@@ -153,6 +290,20 @@ pub trait ToolDyn: Send + Sync {
         &self,
         args: String,
     ) -> Pin<Box<dyn Future<Output = Result<String, ToolError>> + Send + '_>>;
+
+    /// Call the tool and return its output as the content of a tool result (see [Tool::result_content]).
+    /// By default, the output of [ToolDyn::call] is returned as text.
+    fn call_with_content(
+        &self,
+        args: String,
+    ) -> Pin<Box<dyn Future<Output = Result<OneOrMany<ToolResultContent>, ToolError>> + Send + '_>>
+    {
+        Box::pin(async move {
+            self.call(args)
+                .await
+                .map(|output| OneOrMany::one(ToolResultContent::text(output)))
+        })
+    }
 }
 
 impl<T: Tool> ToolDyn for T {
@@ -179,10 +330,47 @@ impl<T: Tool> ToolDyn for T {
                     .and_then(|output| {
                         serde_json::to_string(&output).map_err(ToolError::JsonError)
                     }),
-                Err(e) => Err(ToolError::JsonError(e)),
+                Err(e) => Err(ToolError::InvalidArguments(e)),
             }
         })
     }
+
+    fn call_with_content(
+        &self,
+        args: String,
+    ) -> Pin<Box<dyn Future<Output = Result<OneOrMany<ToolResultContent>, ToolError>> + Send + '_>>
+    {
+        Box::pin(async move {
+            let args = serde_json::from_str(&args).map_err(ToolError::InvalidArguments)?;
+            let output = <Self as Tool>::call(self, args)
+                .await
+                .map_err(|e| ToolError::ToolCallError(Box::new(e)))?;
+            self.result_content(output)
+        })
+    }
+}
+
+/// Wrapper trait to allow for dynamic dispatch of streaming tools
+pub trait StreamingToolDyn: ToolDyn {
+    fn call_streaming(
+        &self,
+        arguments: ArgumentsStream,
+    ) -> Pin<Box<dyn Future<Output = Result<OneOrMany<ToolResultContent>, ToolError>> + Send + '_>>;
+}
+
+impl<T: StreamingTool> StreamingToolDyn for T {
+    fn call_streaming(
+        &self,
+        arguments: ArgumentsStream,
+    ) -> Pin<Box<dyn Future<Output = Result<OneOrMany<ToolResultContent>, ToolError>> + Send + '_>>
+    {
+        Box::pin(async move {
+            let output = <Self as StreamingTool>::call_streaming(self, arguments)
+                .await
+                .map_err(|e| ToolError::ToolCallError(Box::new(e)))?;
+            self.result_content(output)
+        })
+    }
 }
 
 #[cfg_attr(docsrs, doc(cfg(feature = "rmcp")))]
@@ -331,6 +519,59 @@ pub mod rmcp {
     }
 }
 
+/// The results of the tool calls handled by a [BatchToolHandler], by tool call id.
+pub type BatchToolResults = HashMap<String, OneOrMany<ToolResultContent>>;
+
+/// Trait for handling all the tool calls of a turn at once, e.g.: to batch the external requests
+/// of tools backed by an expensive API into a single one.
+///
+/// When the toolset of an agent has a batch handler (see
+/// [AgentBuilder::batch_tool_handler](crate::agent::AgentBuilder::batch_tool_handler)), it
+/// receives the tool calls of each turn and returns the results of the calls it handled, keyed by
+/// tool call id. The calls without a result are dispatched to their tool as usual, as are the
+/// calls of unknown or unavailable tools, of the final answer tool, of the tools whose result is
+/// provided by the caller, and the calls whose arguments fail schema validation (see
+/// [AgentBuilder::schema_validator](crate::agent::AgentBuilder::schema_validator)).
+///
+/// Tool calls of streamed prompts are dispatched as they are received, so they are not batched.
+pub trait BatchToolHandler: Send + Sync {
+    /// Handle `tool_calls`, returning the result of each handled call by its id.
+    fn call_batch(
+        &self,
+        tool_calls: Vec<ToolCall>,
+    ) -> impl Future<Output = Result<BatchToolResults, ToolError>> + Send;
+}
+
+impl<F, Fut> BatchToolHandler for F
+where
+    F: Fn(Vec<ToolCall>) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<BatchToolResults, ToolError>> + Send,
+{
+    fn call_batch(
+        &self,
+        tool_calls: Vec<ToolCall>,
+    ) -> impl Future<Output = Result<BatchToolResults, ToolError>> + Send {
+        self(tool_calls)
+    }
+}
+
+/// Object-safe version of [BatchToolHandler], used to store the batch handler in toolsets.
+pub trait BatchToolHandlerDyn: Send + Sync {
+    fn call_batch(
+        &self,
+        tool_calls: Vec<ToolCall>,
+    ) -> Pin<Box<dyn Future<Output = Result<BatchToolResults, ToolError>> + Send + '_>>;
+}
+
+impl<H: BatchToolHandler> BatchToolHandlerDyn for H {
+    fn call_batch(
+        &self,
+        tool_calls: Vec<ToolCall>,
+    ) -> Pin<Box<dyn Future<Output = Result<BatchToolResults, ToolError>> + Send + '_>> {
+        Box::pin(BatchToolHandler::call_batch(self, tool_calls))
+    }
+}
+
 /// Wrapper trait to allow for dynamic dispatch of raggable tools
 pub trait ToolEmbeddingDyn: ToolDyn {
     fn context(&self) -> serde_json::Result<serde_json::Value>;
@@ -351,6 +592,13 @@ impl<T: ToolEmbedding> ToolEmbeddingDyn for T {
 pub(crate) enum ToolType {
     Simple(Box<dyn ToolDyn>),
     Embedding(Box<dyn ToolEmbeddingDyn>),
+    Streaming(Box<dyn StreamingToolDyn>),
+    /// A tool advertised to the model under the name `{namespace}_{name}`
+    /// (see [ToolSet::add_tools_namespaced]).
+    Namespaced {
+        namespace: String,
+        tool: Box<ToolType>,
+    },
 }
 
 impl ToolType {
@@ -358,6 +606,8 @@ impl ToolType {
         match self {
             ToolType::Simple(tool) => tool.name(),
             ToolType::Embedding(tool) => tool.name(),
+            ToolType::Streaming(tool) => tool.name(),
+            ToolType::Namespaced { namespace, tool } => format!("{namespace}_{}", tool.name()),
         }
     }
 
@@ -365,6 +615,12 @@ impl ToolType {
         match self {
             ToolType::Simple(tool) => tool.definition(prompt).await,
             ToolType::Embedding(tool) => tool.definition(prompt).await,
+            ToolType::Streaming(tool) => tool.definition(prompt).await,
+            ToolType::Namespaced { namespace, tool } => {
+                let mut definition = Box::pin(tool.definition(prompt)).await;
+                definition.name = format!("{namespace}_{}", definition.name);
+                definition
+            }
         }
     }
 
@@ -372,11 +628,50 @@ impl ToolType {
         match self {
             ToolType::Simple(tool) => tool.call(args).await,
             ToolType::Embedding(tool) => tool.call(args).await,
+            ToolType::Streaming(tool) => tool.call(args).await,
+            ToolType::Namespaced { tool, .. } => Box::pin(tool.call(args)).await,
+        }
+    }
+
+    pub async fn call_with_content(
+        &self,
+        args: String,
+    ) -> Result<OneOrMany<ToolResultContent>, ToolError> {
+        match self {
+            ToolType::Simple(tool) => tool.call_with_content(args).await,
+            ToolType::Embedding(tool) => tool.call_with_content(args).await,
+            ToolType::Streaming(tool) => tool.call_with_content(args).await,
+            ToolType::Namespaced { tool, .. } => Box::pin(tool.call_with_content(args)).await,
+        }
+    }
+
+    /// Call the tool with a stream of fragments of its arguments. Tools that are not
+    /// streaming tools are called once all the fragments have been received.
+    pub async fn call_streaming(
+        &self,
+        arguments: ArgumentsStream,
+    ) -> Result<OneOrMany<ToolResultContent>, ToolError> {
+        match self {
+            ToolType::Streaming(tool) => tool.call_streaming(arguments).await,
+            ToolType::Namespaced { tool, .. } => Box::pin(tool.call_streaming(arguments)).await,
+            _ => {
+                let args = arguments.collect::<Vec<_>>().await.concat();
+                self.call_with_content(args).await
+            }
+        }
+    }
+
+    fn is_streaming(&self) -> bool {
+        match self {
+            ToolType::Streaming(_) => true,
+            ToolType::Namespaced { tool, .. } => tool.is_streaming(),
+            _ => false,
         }
     }
 }
 
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum ToolSetError {
     /// Error returned by the tool
     #[error("ToolCallError: {0}")]
@@ -385,15 +680,138 @@ pub enum ToolSetError {
     #[error("ToolNotFoundError: {0}")]
     ToolNotFoundError(String),
 
+    /// The tool is currently unavailable to the agent (see [crate::agent::AgentBuilder::tool_filter])
+    #[error("ToolUnavailable: {0}")]
+    ToolUnavailable(String),
+
     // TODO: Revisit this
     #[error("JsonError: {0}")]
     JsonError(#[from] serde_json::Error),
+
+    /// There are more tools than the model provider accepts in a request
+    #[error("TooManyTools: {count} tools, which exceeds the limit of {limit} by {}", count - limit)]
+    TooManyTools { count: usize, limit: usize },
+
+    /// The definitions of the tools are larger than the model provider accepts in a request
+    #[error(
+        "SchemaTooLarge: tool definitions are {size} bytes, which exceeds the limit of {limit} bytes by {}",
+        size - limit
+    )]
+    SchemaTooLarge { size: usize, limit: usize },
+
+    /// The parameters schema of a tool is more deeply nested than the model provider accepts
+    #[error(
+        "SchemaTooDeep: the parameters of tool `{tool}` are nested {depth} levels deep, which exceeds the limit of {limit} by {}",
+        depth - limit
+    )]
+    SchemaTooDeep {
+        tool: String,
+        depth: usize,
+        limit: usize,
+    },
+}
+
+/// Limits of a model provider on the tools of a completion request (see
+/// [CompletionModel::tool_limits](crate::completion::CompletionModel::tool_limits)).
+/// Every limit is disabled by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ToolLimits {
+    max_tools: Option<usize>,
+    max_schema_bytes: Option<usize>,
+    max_schema_depth: Option<usize>,
+}
+
+impl ToolLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of tools in a request.
+    pub fn max_tools(mut self, max_tools: usize) -> Self {
+        self.max_tools = Some(max_tools);
+        self
+    }
+
+    /// Set the maximum total size (in bytes) of the serialized definitions of the tools of a request.
+    pub fn max_schema_bytes(mut self, max_schema_bytes: usize) -> Self {
+        self.max_schema_bytes = Some(max_schema_bytes);
+        self
+    }
+
+    /// Set the maximum nesting depth of the parameters schema of a tool, i.e.: the number of
+    /// nested levels of objects (`properties`) and arrays (`items`).
+    pub fn max_schema_depth(mut self, max_schema_depth: usize) -> Self {
+        self.max_schema_depth = Some(max_schema_depth);
+        self
+    }
+
+    /// Check the definitions of the tools of a request against the limits.
+    pub fn validate(&self, tools: &[ToolDefinition]) -> Result<(), ToolSetError> {
+        if let Some(limit) = self.max_tools
+            && tools.len() > limit
+        {
+            return Err(ToolSetError::TooManyTools {
+                count: tools.len(),
+                limit,
+            });
+        }
+
+        if let Some(limit) = self.max_schema_bytes {
+            let size = tools
+                .iter()
+                .map(|tool| serde_json::to_string(tool).map(|json| json.len()))
+                .sum::<Result<usize, _>>()?;
+            if size > limit {
+                return Err(ToolSetError::SchemaTooLarge { size, limit });
+            }
+        }
+
+        if let Some(limit) = self.max_schema_depth {
+            for tool in tools {
+                let depth = schema_depth(&tool.parameters);
+                if depth > limit {
+                    return Err(ToolSetError::SchemaTooDeep {
+                        tool: tool.name.clone(),
+                        depth,
+                        limit,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The number of nested levels of objects and arrays of a JSON schema. References (`$ref`) are
+/// not followed.
+fn schema_depth(schema: &serde_json::Value) -> usize {
+    let Some(schema) = schema.as_object() else {
+        return 0;
+    };
+
+    let nested = schema
+        .get("properties")
+        .and_then(serde_json::Value::as_object)
+        .into_iter()
+        .flat_map(|properties| properties.values())
+        .chain(schema.get("items"))
+        .chain(schema.get("additionalProperties"))
+        .map(|schema| 1 + schema_depth(schema));
+    let variants = ["anyOf", "oneOf", "allOf"]
+        .into_iter()
+        .filter_map(|keyword| schema.get(keyword).and_then(serde_json::Value::as_array))
+        .flatten()
+        .map(schema_depth);
+
+    nested.chain(variants).max().unwrap_or(0)
 }
 
 /// A struct that holds a set of tools
 #[derive(Default)]
 pub struct ToolSet {
     pub(crate) tools: HashMap<String, ToolType>,
+    batch_handler: Option<Box<dyn BatchToolHandlerDyn>>,
 }
 
 impl ToolSet {
@@ -422,13 +840,80 @@ impl ToolSet {
             .insert(tool.name(), ToolType::Simple(Box::new(tool)));
     }
 
+    /// Add a streaming tool to the toolset (see [StreamingTool])
+    pub fn add_streaming_tool(&mut self, tool: impl StreamingToolDyn + 'static) {
+        self.tools
+            .insert(tool.name(), ToolType::Streaming(Box::new(tool)));
+    }
+
+    /// Check if the tool with the given name is a streaming tool
+    pub fn is_streaming_tool(&self, toolname: &str) -> bool {
+        self.tools
+            .get(toolname)
+            .is_some_and(|tool| tool.is_streaming())
+    }
+
     pub fn delete_tool(&mut self, tool_name: &str) {
         let _ = self.tools.remove(tool_name);
     }
 
-    /// Merge another toolset into this one
+    /// Merge another toolset into this one. The batch handler of `toolset` is only kept if
+    /// this toolset has none.
     pub fn add_tools(&mut self, toolset: ToolSet) {
         self.tools.extend(toolset.tools);
+        if self.batch_handler.is_none() {
+            self.batch_handler = toolset.batch_handler;
+        }
+    }
+
+    /// Set the handler of all the tool calls of a turn (see [BatchToolHandler]).
+    pub fn set_batch_handler(&mut self, handler: impl BatchToolHandler + 'static) {
+        self.batch_handler = Some(Box::new(handler));
+    }
+
+    /// Check if the toolset has a batch handler
+    pub fn has_batch_handler(&self) -> bool {
+        self.batch_handler.is_some()
+    }
+
+    /// Call the batch handler of the toolset with `tool_calls`, returning the results of the
+    /// calls it handled by tool call id (none if the toolset has no batch handler).
+    pub async fn call_batch(
+        &self,
+        tool_calls: Vec<ToolCall>,
+    ) -> Result<BatchToolResults, ToolSetError> {
+        match &self.batch_handler {
+            Some(handler) if !tool_calls.is_empty() => {
+                tracing::info!(target: "rig",
+                    "Calling batch handler with {} tool calls",
+                    tool_calls.len()
+                );
+                Ok(handler.call_batch(tool_calls).await?)
+            }
+            _ => Ok(HashMap::new()),
+        }
+    }
+
+    /// Merge another toolset into this one, under a namespace: each tool is advertised to the
+    /// model as `{namespace}_{name}` (e.g.: `github_search`), and calls to that name are
+    /// dispatched to the original tool. Useful to combine tools from multiple sources (e.g.:
+    /// MCP servers) whose names may collide.
+    ///
+    /// Returns the namespaced names of the tools.
+    pub fn add_tools_namespaced(&mut self, namespace: &str, toolset: ToolSet) -> Vec<String> {
+        toolset
+            .tools
+            .into_values()
+            .map(|tool| {
+                let tool = ToolType::Namespaced {
+                    namespace: namespace.to_string(),
+                    tool: Box::new(tool),
+                };
+                let name = tool.name();
+                self.tools.insert(name.clone(), tool);
+                name
+            })
+            .collect()
     }
 
     pub(crate) fn get(&self, toolname: &str) -> Option<&ToolType> {
@@ -457,42 +942,56 @@ impl ToolSet {
         }
     }
 
+    /// Call a tool with the given name and arguments, returning its output as the content
+    /// of a tool result (which may contain images, see [Tool::result_content]).
+    pub async fn call_with_content(
+        &self,
+        toolname: &str,
+        args: String,
+    ) -> Result<OneOrMany<ToolResultContent>, ToolSetError> {
+        if let Some(tool) = self.tools.get(toolname) {
+            tracing::info!(target: "rig",
+                "Calling tool {toolname} with args:\n{}",
+                serde_json::to_string_pretty(&args).unwrap()
+            );
+            Ok(tool.call_with_content(args).await?)
+        } else {
+            Err(ToolSetError::ToolNotFoundError(toolname.to_string()))
+        }
+    }
+
+    /// Call a tool with the given name and a stream of fragments of its arguments
+    /// (see [StreamingTool]).
+    pub async fn call_streaming(
+        &self,
+        toolname: &str,
+        arguments: ArgumentsStream,
+    ) -> Result<OneOrMany<ToolResultContent>, ToolSetError> {
+        if let Some(tool) = self.tools.get(toolname) {
+            tracing::info!(target: "rig", "Calling tool {toolname} with streamed args");
+            Ok(tool.call_streaming(arguments).await?)
+        } else {
+            Err(ToolSetError::ToolNotFoundError(toolname.to_string()))
+        }
+    }
+
     /// Get the documents of all the tools in the toolset
     pub async fn documents(&self) -> Result<Vec<completion::Document>, ToolSetError> {
         let mut docs = Vec::new();
         for tool in self.tools.values() {
-            match tool {
-                ToolType::Simple(tool) => {
-                    docs.push(completion::Document {
-                        id: tool.name(),
-                        text: format!(
-                            "\
-                            Tool: {}\n\
-                            Definition: \n\
-                            {}\
-                        ",
-                            tool.name(),
-                            serde_json::to_string_pretty(&tool.definition("".to_string()).await)?
-                        ),
-                        additional_props: HashMap::new(),
-                    });
-                }
-                ToolType::Embedding(tool) => {
-                    docs.push(completion::Document {
-                        id: tool.name(),
-                        text: format!(
-                            "\
-                            Tool: {}\n\
-                            Definition: \n\
-                            {}\
-                        ",
-                            tool.name(),
-                            serde_json::to_string_pretty(&tool.definition("".to_string()).await)?
-                        ),
-                        additional_props: HashMap::new(),
-                    });
-                }
-            }
+            docs.push(completion::Document {
+                id: tool.name(),
+                text: format!(
+                    "\
+                    Tool: {}\n\
+                    Definition: \n\
+                    {}\
+                ",
+                    tool.name(),
+                    serde_json::to_string_pretty(&tool.definition("".to_string()).await)?
+                ),
+                additional_props: HashMap::new(),
+            });
         }
         Ok(docs)
     }
@@ -530,6 +1029,11 @@ impl ToolSetBuilder {
         self
     }
 
+    pub fn streaming_tool(mut self, tool: impl StreamingToolDyn + 'static) -> Self {
+        self.tools.push(ToolType::Streaming(Box::new(tool)));
+        self
+    }
+
     pub fn build(self) -> ToolSet {
         ToolSet {
             tools: self
@@ -537,12 +1041,18 @@ impl ToolSetBuilder {
                 .into_iter()
                 .map(|tool| (tool.name(), tool))
                 .collect(),
+            batch_handler: None,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
     use serde_json::json;
 
     use super::*;
@@ -653,4 +1163,297 @@ mod tests {
         assert!(!toolset.contains("add"));
         assert_eq!(toolset.tools.len(), 1);
     }
+
+    #[tokio::test]
+    async fn test_namespaced_tools() {
+        let mut toolset = ToolSet::default();
+        let mut names = toolset.add_tools_namespaced("math", get_test_toolset());
+        names.sort();
+        assert_eq!(names, vec!["math_add", "math_subtract"]);
+        assert!(!toolset.contains("add"));
+
+        let definition = toolset
+            .get("math_add")
+            .unwrap()
+            .definition(String::new())
+            .await;
+        assert_eq!(definition.name, "math_add");
+
+        // Calls to the namespaced name are dispatched to the original tool
+        let result = toolset
+            .call("math_subtract", json!({"x": 5, "y": 3}).to_string())
+            .await
+            .unwrap();
+        assert_eq!(result, "2");
+    }
+
+    #[tokio::test]
+    async fn test_agent_namespaced_tools() {
+        use crate::{
+            agent::AgentBuilder,
+            completion::{Message, Prompt},
+            message::UserContent,
+            test_utils::MockCompletionModel,
+        };
+
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "math_add", json!({"x": 1, "y": 2}))
+            .with_text("1 + 2 = 3");
+        let agent = AgentBuilder::new(model.clone())
+            .namespaced_tools("math", get_test_toolset())
+            .build();
+
+        let response = agent.prompt("What is 1 + 2?").multi_turn(2).await.unwrap();
+
+        assert_eq!(response, "1 + 2 = 3");
+        let requests = model.requests();
+        let mut tools = requests[0]
+            .tools
+            .iter()
+            .map(|tool| tool.name.as_str())
+            .collect::<Vec<_>>();
+        tools.sort();
+        assert_eq!(tools, vec!["math_add", "math_subtract"]);
+        let Some(Message::User { content }) = requests[1].chat_history.iter().last() else {
+            panic!("Expected the tool result in a user message");
+        };
+        let UserContent::ToolResult(tool_result) = content.first() else {
+            panic!("Expected a tool result");
+        };
+        assert_eq!(tool_result.content.first(), ToolResultContent::text("3"));
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("Chart error")]
+    struct ChartError;
+
+    struct Chart;
+
+    impl Tool for Chart {
+        const NAME: &'static str = "chart";
+        type Error = ChartError;
+        type Args = serde_json::Value;
+        type Output = String;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: Self::NAME.to_string(),
+                description: "Render a chart".to_string(),
+                parameters: json!({"type": "object"}),
+            }
+        }
+
+        async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
+            Ok("iVBORw0KGgo=".to_string())
+        }
+
+        fn result_content(
+            &self,
+            output: Self::Output,
+        ) -> Result<OneOrMany<ToolResultContent>, ToolError> {
+            Ok(OneOrMany::one(ToolResultContent::image(
+                output,
+                Some(crate::message::ContentFormat::Base64),
+                Some(crate::message::ImageMediaType::PNG),
+                None,
+            )))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call_with_content() {
+        let mut toolset = get_test_toolset();
+        toolset.add_tool(Chart);
+
+        let content = toolset
+            .call_with_content("add", json!({"x": 1, "y": 2}).to_string())
+            .await
+            .unwrap();
+        assert_eq!(content, OneOrMany::one(ToolResultContent::text("3")));
+
+        let content = toolset
+            .call_with_content("chart", "{}".to_string())
+            .await
+            .unwrap();
+        assert!(
+            matches!(content.first(), ToolResultContent::Image(image) if image.data == "iVBORw0KGgo=")
+        );
+
+        // The output of the tool as a string is unchanged
+        let output = toolset.call("chart", "{}".to_string()).await.unwrap();
+        assert_eq!(output, "\"iVBORw0KGgo=\"");
+    }
+
+    #[tokio::test]
+    async fn test_agent_image_tool_result() {
+        use crate::{
+            agent::AgentBuilder,
+            completion::{Message, Prompt},
+            message::UserContent,
+            test_utils::MockCompletionModel,
+        };
+
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "chart", json!({}))
+            .with_text("Here is your chart");
+
+        let agent = AgentBuilder::new(model.clone()).tool(Chart).build();
+        agent.prompt("Draw a chart").multi_turn(2).await.unwrap();
+
+        let requests = model.requests();
+        let Some(Message::User { content }) = requests[1].chat_history.iter().last() else {
+            panic!("Expected the tool result in a user message");
+        };
+        let UserContent::ToolResult(tool_result) = content.first() else {
+            panic!("Expected a tool result");
+        };
+        assert!(matches!(
+            tool_result.content.first(),
+            ToolResultContent::Image(_)
+        ));
+    }
+
+    struct WriteFile {
+        fragments: Arc<AtomicUsize>,
+    }
+
+    impl Tool for WriteFile {
+        const NAME: &'static str = "write_file";
+        type Error = ChartError;
+        type Args = serde_json::Value;
+        type Output = usize;
+
+        async fn definition(&self, _prompt: String) -> ToolDefinition {
+            ToolDefinition {
+                name: Self::NAME.to_string(),
+                description: "Write a file".to_string(),
+                parameters: json!({"type": "object"}),
+            }
+        }
+
+        async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+            Ok(args.to_string().len())
+        }
+    }
+
+    impl StreamingTool for WriteFile {
+        async fn call_streaming(
+            &self,
+            mut arguments: ArgumentsStream,
+        ) -> Result<Self::Output, Self::Error> {
+            let mut written = 0;
+            while let Some(fragment) = arguments.next().await {
+                self.fragments.fetch_add(1, Ordering::SeqCst);
+                written += fragment.len();
+            }
+            Ok(written)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_agent_streaming_tool() {
+        use crate::{
+            agent::{AgentBuilder, MultiTurnStreamItem},
+            completion::Message,
+            message::{Text, UserContent},
+            streaming::StreamingPrompt,
+            test_utils::MockCompletionModel,
+        };
+
+        let arguments = json!({"path": "notes.txt", "content": "Lorem ipsum dolor sit amet"});
+        let model = MockCompletionModel::new()
+            .with_tool_call("call_1", "write_file", arguments.clone())
+            .with_text("Done");
+
+        let fragments = Arc::new(AtomicUsize::new(0));
+        let agent = AgentBuilder::new(model.clone())
+            .streaming_tool(WriteFile {
+                fragments: fragments.clone(),
+            })
+            .build();
+
+        let mut stream = agent.stream_prompt("Write my notes").multi_turn(2).await;
+        let mut text = String::new();
+        while let Some(item) = stream.next().await {
+            if let MultiTurnStreamItem::Text(Text { text: chunk }) = item.unwrap() {
+                text.push_str(&chunk);
+            }
+        }
+        assert_eq!(text.trim(), "Done");
+
+        // The tool received the arguments in several fragments
+        assert!(fragments.load(Ordering::SeqCst) > 1);
+
+        let requests = model.requests();
+        let Some(Message::User { content }) = requests[1].chat_history.iter().last() else {
+            panic!("Expected the tool result in a user message");
+        };
+        let UserContent::ToolResult(tool_result) = content.first() else {
+            panic!("Expected a tool result");
+        };
+        assert_eq!(
+            tool_result.content.first(),
+            ToolResultContent::text(arguments.to_string().len().to_string())
+        );
+    }
+
+    #[test]
+    fn test_tool_limits() {
+        let tool = |name: &str, parameters| ToolDefinition {
+            name: name.to_string(),
+            description: String::new(),
+            parameters,
+        };
+        let tools = vec![
+            tool(
+                "flat",
+                json!({"type": "object", "properties": {"x": {"type": "number"}}}),
+            ),
+            tool(
+                "nested",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "points": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {"x": {"type": "number"}}
+                            }
+                        }
+                    }
+                }),
+            ),
+        ];
+        let size = tools
+            .iter()
+            .map(|tool| serde_json::to_string(tool).unwrap().len())
+            .sum::<usize>();
+
+        assert!(ToolLimits::new().validate(&tools).is_ok());
+        assert!(
+            ToolLimits::new()
+                .max_tools(2)
+                .max_schema_bytes(size)
+                .max_schema_depth(3)
+                .validate(&tools)
+                .is_ok()
+        );
+        assert!(matches!(
+            ToolLimits::new().max_tools(1).validate(&tools),
+            Err(ToolSetError::TooManyTools { count: 2, limit: 1 })
+        ));
+        assert!(matches!(
+            ToolLimits::new().max_schema_bytes(size - 10).validate(&tools),
+            Err(ToolSetError::SchemaTooLarge { limit, .. }) if limit == size - 10
+        ));
+        let err = ToolLimits::new()
+            .max_schema_depth(2)
+            .validate(&tools)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "SchemaTooDeep: the parameters of tool `nested` are nested 3 levels deep, which exceeds the limit of 2 by 1"
+        );
+    }
 }
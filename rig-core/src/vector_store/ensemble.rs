@@ -0,0 +1,237 @@
+//! Ensemble retrieval: a query is embedded with several embedding models, each embedding is
+//! searched in the vector index of its model, and the results are fused by rank.
+//!
+//! # Example
+//! ```rust,ignore
+//! use rig::vector_store::ensemble::EnsembleVectorIndex;
+//!
+//! // The same documents, embedded by two different models
+//! let index = EnsembleVectorIndex::new()
+//!     .member(openai_model, openai_index)
+//!     .member(cohere_model, cohere_index);
+//!
+//! let agent = openai_client
+//!     .agent("gpt-4o")
+//!     .dynamic_context(5, index)
+//!     .build();
+//! ```
+use std::collections::HashMap;
+
+use futures::future::try_join_all;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{
+    VectorSearchRequest, VectorStoreError, VectorStoreIndex, VectorStoreIndexDyn,
+    deserialize_document,
+    fusion::{DEFAULT_RRF_K, rrf},
+};
+use crate::embeddings::{EmbeddingModel, embedding::EmbeddingModelDyn};
+
+/// An embedding model and the vector index of the documents it embedded.
+struct Member {
+    model: Box<dyn EmbeddingModelDyn>,
+    index: Box<dyn VectorStoreIndexDyn>,
+    weight: f64,
+}
+
+/// A vector index combining the vector indexes of several embedding models, useful when the
+/// models capture complementary signals and no single index is best.
+///
+/// The query is embedded concurrently with each model (see [EmbeddingModel::embed_query]),
+/// each embedding is searched in the index of its model (see
+/// [VectorStoreIndex::top_n_by_vector], which the indexes must support), and the results are
+/// merged with reciprocal rank fusion (see [rrf]). The score of the results is their fused
+/// score, and documents returned by several indexes (i.e.: with the same id) are only returned
+/// once.
+///
+/// Searching by vector is not supported, since each index expects the embeddings of its own model.
+pub struct EnsembleVectorIndex {
+    members: Vec<Member>,
+    k: f64,
+}
+
+impl Default for EnsembleVectorIndex {
+    fn default() -> Self {
+        Self {
+            members: vec![],
+            k: DEFAULT_RRF_K,
+        }
+    }
+}
+
+impl EnsembleVectorIndex {
+    /// Create a new, empty, ensemble vector index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an embedding model and the vector index of the documents it embedded.
+    pub fn member(
+        self,
+        model: impl EmbeddingModel + 'static,
+        index: impl VectorStoreIndexDyn + 'static,
+    ) -> Self {
+        self.weighted_member(model, index, 1.0)
+    }
+
+    /// Add an embedding model and its vector index, whose ranking weighs `weight` in the
+    /// fusion (the weight of the members added with [EnsembleVectorIndex::member] is `1.0`).
+    pub fn weighted_member(
+        mut self,
+        model: impl EmbeddingModel + 'static,
+        index: impl VectorStoreIndexDyn + 'static,
+        weight: f64,
+    ) -> Self {
+        self.members.push(Member {
+            model: Box::new(model),
+            index: Box::new(index),
+            weight,
+        });
+        self
+    }
+
+    /// Set the `k` constant of the reciprocal rank fusion (defaults to [DEFAULT_RRF_K]).
+    pub fn rrf_k(mut self, k: f64) -> Self {
+        self.k = k;
+        self
+    }
+
+    /// Number of members of the ensemble.
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Embed `query` with each model, search the top `n` documents of each index and fuse them.
+    async fn search(
+        &self,
+        query: &str,
+        n: usize,
+    ) -> Result<Vec<(f64, String, Value)>, VectorStoreError> {
+        let results = try_join_all(self.members.iter().map(|member| async move {
+            let embedding = member.model.embed_query(query).await?;
+            member.index.top_n_by_vector(&embedding.vec, n).await
+        }))
+        .await?;
+
+        let mut documents = HashMap::new();
+        let result_sets = results
+            .into_iter()
+            .map(|mut results| {
+                results.sort_by(|(a, _, _), (b, _, _)| b.total_cmp(a));
+                results
+                    .into_iter()
+                    .map(|(score, id, doc)| {
+                        documents.entry(id.clone()).or_insert(doc);
+                        (score, id)
+                    })
+                    .collect()
+            })
+            .collect::<Vec<_>>();
+        let weights = self
+            .members
+            .iter()
+            .map(|member| member.weight)
+            .collect::<Vec<_>>();
+
+        Ok(rrf(&result_sets, &weights, self.k)
+            .into_iter()
+            .take(n)
+            .map(|(score, id)| {
+                let doc = documents.remove(&id).unwrap_or_default();
+                (score, id, doc)
+            })
+            .collect())
+    }
+}
+
+impl VectorStoreIndex for EnsembleVectorIndex {
+    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        req: VectorSearchRequest,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        self.search(req.query(), req.samples() as usize)
+            .await?
+            .into_iter()
+            .map(|(score, id, doc)| {
+                let doc = deserialize_document(&id, doc)?;
+                Ok((score, id, doc))
+            })
+            .collect()
+    }
+
+    async fn top_n_ids(
+        &self,
+        req: VectorSearchRequest,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        Ok(self
+            .search(req.query(), req.samples() as usize)
+            .await?
+            .into_iter()
+            .map(|(score, id, _)| (score, id))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_utils::{MockEmbeddingModel, in_memory_store},
+        vector_store::{VectorSearchRequest, VectorStoreIndex},
+    };
+
+    use super::EnsembleVectorIndex;
+
+    #[tokio::test]
+    async fn test_ensemble() {
+        // Each model ranks the documents differently, with embeddings of different dimensions
+        let a = MockEmbeddingModel::new(vec![1.0, 0.0]);
+        let b = MockEmbeddingModel::new(vec![0.0, 0.0, 1.0]);
+        let index = EnsembleVectorIndex::new()
+            .member(
+                a.clone(),
+                in_memory_store([
+                    ("doc1", "doc1", vec![1.0, 0.1]),
+                    ("doc2", "doc2", vec![1.0, 0.5]),
+                    ("doc3", "doc3", vec![0.0, 1.0]),
+                ])
+                .index(a),
+            )
+            .member(
+                b.clone(),
+                in_memory_store([
+                    ("doc1", "doc1", vec![1.0, 0.0, 0.0]),
+                    ("doc2", "doc2", vec![0.1, 0.0, 1.0]),
+                    ("doc3", "doc3", vec![0.0, 0.5, 1.0]),
+                ])
+                .index(b),
+            );
+        let req = VectorSearchRequest::builder()
+            .query("query")
+            .samples(2)
+            .build()
+            .unwrap();
+
+        let results = index.top_n::<String>(req.clone()).await.unwrap();
+
+        // doc2 is ranked 2nd by the first model and 1st by the second one, doc1 is only
+        // retrieved by the first model (1st) and doc3 by the second one (2nd)
+        let ids = results
+            .iter()
+            .map(|(_, id, doc)| (id.as_str(), doc.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![("doc2", "doc2"), ("doc1", "doc1")]);
+        assert_eq!(
+            results[0].0,
+            1.0 / (super::DEFAULT_RRF_K + 2.0) + 1.0 / (super::DEFAULT_RRF_K + 1.0)
+        );
+
+        let ids = index.top_n_ids(req).await.unwrap();
+        assert_eq!(ids.len(), 2);
+        assert_eq!(ids[0].1, "doc2");
+    }
+}
@@ -0,0 +1,532 @@
+//! Federated vector index, used to query multiple vector indexes as if they were one.
+//!
+//! # Example
+//! ```rust,ignore
+//! use rig::vector_store::federated::FederatedVectorIndex;
+//!
+//! let index = FederatedVectorIndex::new()
+//!     .index(products_index)
+//!     .index(reviews_index)
+//!     .normalize_scores(true);
+//!
+//! let agent = openai_client
+//!     .agent("gpt-4o")
+//!     .dynamic_context(5, index)
+//!     .build();
+//! ```
+use std::{collections::HashMap, future::Future};
+
+use futures::{StreamExt, TryStreamExt, stream};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{
+    DEFAULT_MAX_CONCURRENCY, VectorSearchRequest, VectorStoreError, VectorStoreIndex,
+    VectorStoreIndexDyn, deserialize_document, fusion::rrf,
+};
+
+/// A vector index that wraps multiple vector indexes (e.g.: one per MongoDB collection,
+/// shard or tenant), queries all of them concurrently and merges their results into a
+/// single global top n.
+///
+/// Since the indexes are type-erased, documents are retrieved as JSON from each index (see
+/// [VectorStoreIndexDyn]) before being deserialized into the requested type.
+///
+/// Scores from different indexes are only comparable if the indexes use the same similarity
+/// metric. If that is not the case, use [FederatedVectorIndex::normalize_scores] to min-max
+/// normalize the scores of each index to `[0, 1]` before merging, or
+/// [FederatedVectorIndex::rank_fusion] to merge the results by rank instead of by score.
+///
+/// At most [DEFAULT_MAX_CONCURRENCY] indexes are queried at a time, see
+/// [FederatedVectorIndex::max_concurrency].
+///
+/// To know which index each result comes from, label the indexes (see
+/// [FederatedVectorIndex::labeled_index]) and search with
+/// [FederatedVectorIndex::top_n_with_sources].
+pub struct FederatedVectorIndex {
+    indexes: Vec<Box<dyn VectorStoreIndexDyn>>,
+    /// The label of each index
+    labels: Vec<String>,
+    normalize_scores: bool,
+    rank_fusion: Option<f64>,
+    max_concurrency: usize,
+}
+
+impl Default for FederatedVectorIndex {
+    fn default() -> Self {
+        Self {
+            indexes: vec![],
+            labels: vec![],
+            normalize_scores: false,
+            rank_fusion: None,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+        }
+    }
+}
+
+impl FederatedVectorIndex {
+    /// Create a new, empty, federated vector index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a vector index to the federated vector index. Its label is its position, e.g.:
+    /// `index_0` for the first index (see [FederatedVectorIndex::labeled_index]).
+    pub fn index(self, index: impl VectorStoreIndexDyn + 'static) -> Self {
+        let label = format!("index_{}", self.indexes.len());
+        self.labeled_index(&label, index)
+    }
+
+    /// Add a vector index labeled `label` (e.g.: the name of its knowledge base or collection)
+    /// to the federated vector index. The label identifies the source of the results of
+    /// [FederatedVectorIndex::top_n_with_sources].
+    pub fn labeled_index(mut self, label: &str, index: impl VectorStoreIndexDyn + 'static) -> Self {
+        self.indexes.push(Box::new(index));
+        self.labels.push(label.to_string());
+        self
+    }
+
+    /// Add multiple vector indexes to the federated vector index.
+    pub fn indexes(
+        mut self,
+        indexes: impl IntoIterator<Item = Box<dyn VectorStoreIndexDyn>>,
+    ) -> Self {
+        for index in indexes {
+            self.labels.push(format!("index_{}", self.indexes.len()));
+            self.indexes.push(index);
+        }
+        self
+    }
+
+    /// Whether to min-max normalize the scores of each index before merging their results (defaults to `false`).
+    pub fn normalize_scores(mut self, normalize_scores: bool) -> Self {
+        self.normalize_scores = normalize_scores;
+        self
+    }
+
+    /// Merge the results of the indexes with reciprocal rank fusion using the given `k` constant
+    /// (see [rrf](super::fusion::rrf) and [DEFAULT_RRF_K](super::fusion::DEFAULT_RRF_K)) instead
+    /// of by score. The score of the merged results is their fused score, and documents
+    /// returned by several indexes (i.e.: with the same id) are only returned once.
+    pub fn rank_fusion(mut self, k: f64) -> Self {
+        self.rank_fusion = Some(k);
+        self
+    }
+
+    /// Set the maximum number of indexes queried concurrently (defaults to
+    /// [DEFAULT_MAX_CONCURRENCY]), e.g.: to avoid overwhelming a single database server hosting
+    /// many of the indexes.
+    pub fn max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency.max(1);
+        self
+    }
+
+    /// Number of vector indexes in the federated vector index.
+    pub fn len(&self) -> usize {
+        self.indexes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indexes.is_empty()
+    }
+
+    /// The labels of the vector indexes, in the order they were added.
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    /// Search the top `n` documents of the request like [VectorStoreIndex::top_n], along with
+    /// the label of the index each document comes from.
+    ///
+    /// With [FederatedVectorIndex::rank_fusion], the source of a document returned by several
+    /// indexes is the first of these indexes (in the order they were added).
+    pub async fn top_n_with_sources<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        req: VectorSearchRequest,
+    ) -> Result<Vec<FederatedResult<T>>, VectorStoreError> {
+        let n = req.samples() as usize;
+        let searches = self
+            .indexes
+            .iter()
+            .map(|index| index.top_n(req.clone()))
+            .collect();
+        let results = self.search_all(searches).await?;
+
+        self.merge(results, n)
+            .into_iter()
+            .map(|(score, id, doc, source)| {
+                let document = deserialize_document(&id, doc)?;
+                Ok(FederatedResult {
+                    score,
+                    id,
+                    document,
+                    source: self.labels[source].clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Run the searches of each index, at most `max_concurrency` at a time. The results are
+    /// returned in the order of the searches.
+    async fn search_all(
+        &self,
+        searches: Vec<impl Future<Output = Result<Vec<(f64, String, Value)>, VectorStoreError>>>,
+    ) -> Result<Vec<Vec<(f64, String, Value)>>, VectorStoreError> {
+        stream::iter(searches)
+            .buffered(self.max_concurrency)
+            .try_collect()
+            .await
+    }
+
+    /// Merge the results of each index into a single list of at most `n` results, sorted by
+    /// descending score, along with the position of the index each result comes from.
+    fn merge(
+        &self,
+        results: Vec<Vec<(f64, String, Value)>>,
+        n: usize,
+    ) -> Vec<(f64, String, Value, usize)> {
+        if let Some(k) = self.rank_fusion {
+            let mut documents = HashMap::new();
+            let result_sets = results
+                .into_iter()
+                .enumerate()
+                .map(|(source, mut results)| {
+                    // Scores of a single index are comparable, but may not be sorted
+                    results.sort_by(|(a, _, _), (b, _, _)| b.total_cmp(a));
+                    results
+                        .into_iter()
+                        .map(|(score, id, doc)| {
+                            documents.entry(id.clone()).or_insert((doc, source));
+                            (score, id)
+                        })
+                        .collect()
+                })
+                .collect::<Vec<_>>();
+
+            return rrf(&result_sets, &[], k)
+                .into_iter()
+                .take(n)
+                .map(|(score, id)| {
+                    let (doc, source) = documents.remove(&id).unwrap_or_default();
+                    (score, id, doc, source)
+                })
+                .collect();
+        }
+
+        let mut merged = results
+            .into_iter()
+            .enumerate()
+            .flat_map(|(source, mut results)| {
+                if self.normalize_scores {
+                    normalize(&mut results);
+                }
+                results
+                    .into_iter()
+                    .map(move |(score, id, doc)| (score, id, doc, source))
+            })
+            .collect::<Vec<_>>();
+
+        merged.sort_by(|(a, _, _, _), (b, _, _, _)| b.total_cmp(a));
+        merged.truncate(n);
+        merged
+    }
+}
+
+/// A result of [FederatedVectorIndex::top_n_with_sources].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FederatedResult<T> {
+    pub score: f64,
+    pub id: String,
+    pub document: T,
+    /// The label of the index the document comes from
+    pub source: String,
+}
+
+/// Min-max normalize the scores of the results of a single index to `[0, 1]`.
+/// If all the scores are equal, they are all set to `1.0`.
+fn normalize(results: &mut [(f64, String, Value)]) {
+    let (min, max) = results.iter().fold(
+        (f64::INFINITY, f64::NEG_INFINITY),
+        |(min, max), (score, _, _)| (min.min(*score), max.max(*score)),
+    );
+
+    for (score, _, _) in results.iter_mut() {
+        *score = if max > min {
+            (*score - min) / (max - min)
+        } else {
+            1.0
+        };
+    }
+}
+
+fn deserialize_documents<T: for<'a> Deserialize<'a>>(
+    results: Vec<(f64, String, Value, usize)>,
+) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+    results
+        .into_iter()
+        .map(|(score, id, doc, _)| {
+            let doc = deserialize_document(&id, doc)?;
+            Ok((score, id, doc))
+        })
+        .collect()
+}
+
+impl VectorStoreIndex for FederatedVectorIndex {
+    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        req: VectorSearchRequest,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let n = req.samples() as usize;
+        let searches = self
+            .indexes
+            .iter()
+            .map(|index| index.top_n(req.clone()))
+            .collect();
+        let results = self.search_all(searches).await?;
+
+        deserialize_documents(self.merge(results, n))
+    }
+
+    async fn top_n_ids(
+        &self,
+        req: VectorSearchRequest,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        let n = req.samples() as usize;
+        let searches = self
+            .indexes
+            .iter()
+            .map(|index| {
+                let req = req.clone();
+                async move {
+                    index.top_n_ids(req).await.map(|results| {
+                        results
+                            .into_iter()
+                            .map(|(score, id)| (score, id, Value::Null))
+                            .collect::<Vec<_>>()
+                    })
+                }
+            })
+            .collect();
+        let results = self.search_all(searches).await?;
+
+        Ok(self
+            .merge(results, n)
+            .into_iter()
+            .map(|(score, id, _, _)| (score, id))
+            .collect())
+    }
+
+    async fn top_n_by_vector<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        vector: &[f64],
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let searches = self
+            .indexes
+            .iter()
+            .map(|index| index.top_n_by_vector(vector, n))
+            .collect();
+        let results = self.search_all(searches).await?;
+
+        deserialize_documents(self.merge(results, n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+
+    use crate::{
+        test_utils::{MockEmbeddingModel, in_memory_store},
+        vector_store::{
+            DEFAULT_MAX_CONCURRENCY, VectorSearchRequest, VectorStoreError, VectorStoreIndex,
+        },
+    };
+
+    use super::FederatedVectorIndex;
+
+    /// A model embedding every text as the same vector.
+    fn model() -> MockEmbeddingModel {
+        MockEmbeddingModel::new(vec![0.0, 1.0, 0.0])
+    }
+
+    fn federated_index() -> FederatedVectorIndex {
+        FederatedVectorIndex::new()
+            .index(
+                in_memory_store([
+                    ("a1", "glarb-garb", vec![0.1, 0.1, 0.5]),
+                    ("a2", "marble-marble", vec![0.7, -0.3, 0.0]),
+                ])
+                .index(model()),
+            )
+            .index(
+                in_memory_store([
+                    ("b1", "flumb-flumb", vec![0.3, 0.7, 0.1]),
+                    ("b2", "brotato", vec![0.0, 0.9, 0.1]),
+                ])
+                .index(model()),
+            )
+    }
+
+    #[tokio::test]
+    async fn test_top_n_merges_indexes() {
+        let index = federated_index();
+        let req = VectorSearchRequest::builder()
+            .query("what is a brotato?")
+            .samples(3)
+            .build()
+            .unwrap();
+
+        let results = index.top_n::<String>(req.clone()).await.unwrap();
+        let ids = results
+            .iter()
+            .map(|(_, id, _)| id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["b2", "b1", "a1"]);
+        assert_eq!(results[0].2, "brotato");
+
+        let ids = index.top_n_ids(req).await.unwrap();
+        let ids = ids.iter().map(|(_, id)| id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["b2", "b1", "a1"]);
+    }
+
+    #[tokio::test]
+    async fn test_top_n_by_vector_normalized_scores() {
+        let index = federated_index().normalize_scores(true);
+
+        let results = index
+            .top_n_by_vector::<String>(&[0.0, 1.0, 0.0], 4)
+            .await
+            .unwrap();
+
+        let scores = results
+            .iter()
+            .map(|(score, id, _)| (id.as_str(), *score))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            scores,
+            vec![("a1", 1.0), ("b2", 1.0), ("a2", 0.0), ("b1", 0.0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_top_n_by_vector_rank_fusion() {
+        let index = federated_index()
+            .index(in_memory_store([("b2", "brotato", vec![0.0, 0.9, 0.1])]).index(model()))
+            .rank_fusion(1.0);
+
+        let results = index
+            .top_n_by_vector::<String>(&[0.0, 1.0, 0.0], 3)
+            .await
+            .unwrap();
+
+        let scores = results
+            .iter()
+            .map(|(score, id, _)| (id.as_str(), *score))
+            .collect::<Vec<_>>();
+        // b2 is ranked first by two indexes and a1 first by one index, a2 and b1 are both ranked
+        // second by one index (a2 wins the tie since its index was added first)
+        assert_eq!(scores, vec![("b2", 1.0), ("a1", 0.5), ("a2", 1.0 / 3.0)]);
+        assert_eq!(results[0].2, "brotato");
+    }
+
+    #[tokio::test]
+    async fn test_top_n_with_sources() {
+        let index = FederatedVectorIndex::new()
+            .labeled_index(
+                "docs",
+                in_memory_store([("a1", "glarb-garb", vec![0.1, 0.1, 0.5])]).index(model()),
+            )
+            .index(in_memory_store([("b2", "brotato", vec![0.0, 0.9, 0.1])]).index(model()));
+        assert_eq!(index.labels(), ["docs", "index_1"]);
+
+        let req = VectorSearchRequest::builder()
+            .query("what is a brotato?")
+            .samples(2)
+            .build()
+            .unwrap();
+        let results = index
+            .top_n_with_sources::<String>(req.clone())
+            .await
+            .unwrap();
+        let sources = results
+            .iter()
+            .map(|result| (result.id.as_str(), result.source.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(sources, vec![("b2", "index_1"), ("a1", "docs")]);
+        assert_eq!(results[0].document, "brotato");
+
+        // A document returned by several indexes comes from the first of them
+        let index = index
+            .labeled_index(
+                "wiki",
+                in_memory_store([("a1", "glarb-garb", vec![0.1, 0.1, 0.5])]).index(model()),
+            )
+            .rank_fusion(1.0);
+        let results = index.top_n_with_sources::<String>(req).await.unwrap();
+        assert_eq!(results[0].id, "a1");
+        assert_eq!(results[0].source, "docs");
+    }
+
+    /// An index recording the maximum number of searches running at the same time.
+    #[derive(Clone, Default)]
+    struct ConcurrencyIndex {
+        running: Arc<AtomicUsize>,
+        max_running: Arc<AtomicUsize>,
+    }
+
+    impl VectorStoreIndex for ConcurrencyIndex {
+        async fn top_n<T: for<'a> serde::Deserialize<'a> + Send>(
+            &self,
+            _req: VectorSearchRequest,
+        ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            Ok(vec![])
+        }
+
+        async fn top_n_ids(
+            &self,
+            _req: VectorSearchRequest,
+        ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrency() {
+        let index = ConcurrencyIndex::default();
+        let federated = (0..6)
+            .fold(FederatedVectorIndex::new(), |federated, _| {
+                federated.index(index.clone())
+            })
+            .max_concurrency(2);
+        let req = VectorSearchRequest::builder()
+            .query("query")
+            .samples(1)
+            .build()
+            .unwrap();
+
+        federated.top_n::<String>(req.clone()).await.unwrap();
+        assert_eq!(index.max_running.load(Ordering::SeqCst), 2);
+
+        // Batched searches are bounded as well
+        let index = ConcurrencyIndex::default();
+        index
+            .top_n_batch::<String>(vec![req; DEFAULT_MAX_CONCURRENCY + 5])
+            .await
+            .unwrap();
+        assert_eq!(
+            index.max_running.load(Ordering::SeqCst),
+            DEFAULT_MAX_CONCURRENCY
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Rank fusion functions, used to combine the ranked results of multiple retrievers (e.g.:
+//! vector search, keyword search, rerankers) into a single ranking.
+//!
+//! # Example
+//! ```rust
+//! use rig::vector_store::fusion::{DEFAULT_RRF_K, rrf};
+//!
+//! let vector_results = vec![(0.9, "doc1".to_string()), (0.8, "doc2".to_string())];
+//! let keyword_results = vec![(12.0, "doc2".to_string()), (7.5, "doc3".to_string())];
+//!
+//! // Trust the vector search twice as much as the keyword search
+//! let results = rrf(&[vector_results, keyword_results], &[2.0, 1.0], DEFAULT_RRF_K);
+//! assert_eq!(results[0].1, "doc2");
+//! ```
+use std::collections::HashMap;
+
+/// The `k` constant of reciprocal rank fusion used in the original paper (Cormack et al., 2009).
+pub const DEFAULT_RRF_K: f64 = 60.0;
+
+/// Weighted reciprocal rank fusion of ranked result sets (e.g.: the results of
+/// [VectorStoreIndex::top_n_ids](super::VectorStoreIndex::top_n_ids)).
+///
+/// Each result set must be sorted from most to least relevant: only the rank of the results is
+/// used, their scores are ignored (which is what makes the scores of different retrievers
+/// comparable). The fused score of an id is the sum over the result sets containing it of
+/// `weight / (k + rank)`, where `rank` starts at 1 and `weight` is the weight of the result set
+/// (`1.0` if `weights` has fewer elements than `result_sets`).
+///
+/// Returns the fused results, sorted by descending score (ties are broken by order of
+/// first appearance).
+pub fn rrf(result_sets: &[Vec<(f64, String)>], weights: &[f64], k: f64) -> Vec<(f64, String)> {
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut fused: Vec<(f64, String)> = vec![];
+
+    for (i, results) in result_sets.iter().enumerate() {
+        let weight = weights.get(i).copied().unwrap_or(1.0);
+
+        for (rank, (_, id)) in results.iter().enumerate() {
+            let score = weight / (k + (rank + 1) as f64);
+
+            match positions.get(id) {
+                Some(&position) => fused[position].0 += score,
+                None => {
+                    positions.insert(id.clone(), fused.len());
+                    fused.push((score, id.clone()));
+                }
+            }
+        }
+    }
+
+    // `sort_by` is stable, so ties keep their order of first appearance
+    fused.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    fused
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DEFAULT_RRF_K, rrf};
+
+    fn results(ids: &[&str]) -> Vec<(f64, String)> {
+        ids.iter()
+            .enumerate()
+            .map(|(i, id)| (100.0 - i as f64, id.to_string()))
+            .collect()
+    }
+
+    fn assert_scores(actual: Vec<(f64, String)>, expected: Vec<(f64, &str)>) {
+        assert_eq!(actual.len(), expected.len());
+        for ((score, id), (expected_score, expected_id)) in actual.iter().zip(expected) {
+            assert_eq!(id, expected_id);
+            assert!(
+                (score - expected_score).abs() < 1e-12,
+                "{id}: {score} != {expected_score}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_rrf() {
+        let fused = rrf(
+            &[results(&["a", "b", "c"]), results(&["c", "a", "d"])],
+            &[],
+            DEFAULT_RRF_K,
+        );
+
+        assert_scores(
+            fused,
+            vec![
+                (1.0 / 61.0 + 1.0 / 62.0, "a"),
+                (1.0 / 63.0 + 1.0 / 61.0, "c"),
+                (1.0 / 62.0, "b"),
+                (1.0 / 63.0, "d"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_weighted_rrf() {
+        let fused = rrf(
+            &[results(&["a", "b"]), results(&["b", "a"])],
+            &[1.0, 3.0],
+            1.0,
+        );
+
+        assert_scores(
+            fused,
+            vec![(1.0 / 3.0 + 3.0 / 2.0, "b"), (1.0 / 2.0 + 3.0 / 3.0, "a")],
+        );
+    }
+
+    #[test]
+    fn test_rrf_ignores_scores() {
+        // Only the ranks matter, not the (incomparable) scores of each result set
+        let fused = rrf(
+            &[
+                vec![(0.9, "a".to_string()), (0.1, "b".to_string())],
+                vec![(1000.0, "b".to_string()), (999.0, "a".to_string())],
+            ],
+            &[],
+            DEFAULT_RRF_K,
+        );
+
+        // Ties are broken by order of first appearance
+        assert_eq!(fused[0].1, "a");
+        assert_eq!(fused[1].1, "b");
+        assert_eq!(fused[0].0, fused[1].0);
+    }
+
+    #[test]
+    fn test_rrf_empty() {
+        assert!(rrf(&[], &[], DEFAULT_RRF_K).is_empty());
+        assert!(rrf(&[vec![], vec![]], &[1.0, 2.0], DEFAULT_RRF_K).is_empty());
+    }
+}
@@ -0,0 +1,443 @@
+//! Approximate nearest neighbor in-memory vector index, based on a Hierarchical Navigable Small
+//! World graph (HNSW, Malkov & Yashunin, 2016) built with the `hnsw_rs` crate. Requires the
+//! `hnsw` feature.
+//!
+//! Unlike [InMemoryVectorIndex](super::in_memory_store::InMemoryVectorIndex), which compares the
+//! query with every embedding, [HnswVectorIndex] only visits a small part of the graph, which
+//! keeps searches fast for up to a few hundred thousand embeddings, at the cost of a slower
+//! insertion and of an approximate result (the true nearest neighbors may occasionally be missed,
+//! which can be mitigated by increasing [HnswVectorIndex::ef_search]).
+//!
+//! Embeddings are compared with cosine similarity, the score of the results (in single precision).
+//!
+//! # Example
+//! ```rust,ignore
+//! use rig::vector_store::hnsw::HnswVectorIndex;
+//!
+//! let mut index = HnswVectorIndex::new(model).ef_search(100);
+//! index.add_documents_with_ids(documents);
+//!
+//! let results = index.top_n::<Document>(req).await?;
+//! ```
+use std::collections::HashSet;
+
+use hnsw_rs::{anndists::dist::Distance, hnsw::Hnsw};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    VectorStoreError, VectorStoreIndex, deserialize_document, request::VectorSearchRequest,
+};
+use crate::{
+    OneOrMany,
+    embeddings::{Embedding, EmbeddingModel},
+};
+
+/// Default number of neighbors of each node (`M` in the HNSW paper).
+pub const DEFAULT_M: usize = 16;
+/// Default size of the candidate list when inserting embeddings.
+pub const DEFAULT_EF_CONSTRUCTION: usize = 200;
+/// Default size of the candidate list when searching.
+pub const DEFAULT_EF_SEARCH: usize = 64;
+
+/// Maximum number of neighbors of each node supported by `hnsw_rs`.
+const MAX_M: usize = 256;
+/// Maximum number of levels of the graph supported by `hnsw_rs`.
+const MAX_LEVELS: usize = 16;
+/// Number of embeddings the graph is initially allocated for.
+const INITIAL_CAPACITY: usize = 1024;
+
+/// Cosine distance of normalized vectors. Zero vectors are at distance 1 of every vector (unlike
+/// with `hnsw_rs`'s cosine distance, which puts them at distance 0).
+#[derive(Clone, Copy, Default)]
+struct CosineDistance;
+
+impl Distance<f64> for CosineDistance {
+    fn eval(&self, a: &[f64], b: &[f64]) -> f32 {
+        distance(a, b) as f32
+    }
+}
+
+/// Approximate nearest neighbor vector index backed by an HNSW graph (see the
+/// [module documentation](self)).
+pub struct HnswVectorIndex<M: EmbeddingModel, D: Serialize> {
+    model: M,
+    m: usize,
+    ef_construction: usize,
+    ef_search: usize,
+    /// The documents, with their id
+    documents: Vec<(String, D)>,
+    /// The document of each embedding of the graph, by id of the embedding
+    embeddings: Vec<usize>,
+    /// The graph of the normalized embeddings, created when the first document is added
+    graph: Option<Hnsw<'static, f64, CosineDistance>>,
+}
+
+impl<M: EmbeddingModel, D: Serialize> HnswVectorIndex<M, D> {
+    /// Create an empty index, whose queries are embedded with `model`.
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            m: DEFAULT_M,
+            ef_construction: DEFAULT_EF_CONSTRUCTION,
+            ef_search: DEFAULT_EF_SEARCH,
+            documents: vec![],
+            embeddings: vec![],
+            graph: None,
+        }
+    }
+
+    /// Set the number of neighbors of each node (defaults to [DEFAULT_M], at most 256). Higher
+    /// values improve recall on high dimensional data, at the cost of memory and insertion
+    /// time. Must be set before adding documents.
+    pub fn m(mut self, m: usize) -> Self {
+        self.m = m.clamp(2, MAX_M);
+        self
+    }
+
+    /// Set the size of the candidate list when inserting embeddings (defaults to
+    /// [DEFAULT_EF_CONSTRUCTION]). Higher values build a better graph, at the cost of
+    /// insertion time. Must be set before adding documents.
+    pub fn ef_construction(mut self, ef_construction: usize) -> Self {
+        self.ef_construction = ef_construction.max(1);
+        self
+    }
+
+    /// Set the size of the candidate list when searching (defaults to [DEFAULT_EF_SEARCH], and
+    /// never less than the number of requested results). Higher values improve recall, at the
+    /// cost of search time.
+    pub fn ef_search(mut self, ef_search: usize) -> Self {
+        self.ef_search = ef_search.max(1);
+        self
+    }
+
+    /// Add documents and their corresponding embeddings to the index.
+    /// Ids are automatically generated have will have the form `"doc{n}"` where `n`
+    /// is the index of the document.
+    pub fn add_documents(
+        &mut self,
+        documents: impl IntoIterator<Item = (D, OneOrMany<Embedding>)>,
+    ) {
+        let current_index = self.documents.len();
+        self.add_documents_with_ids(
+            documents
+                .into_iter()
+                .enumerate()
+                .map(|(i, (doc, embeddings))| {
+                    (format!("doc{}", i + current_index), doc, embeddings)
+                }),
+        );
+    }
+
+    /// Add documents and their corresponding embeddings to the index with ids.
+    pub fn add_documents_with_ids(
+        &mut self,
+        documents: impl IntoIterator<Item = (impl ToString, D, OneOrMany<Embedding>)>,
+    ) {
+        let (m, ef_construction) = (self.m, self.ef_construction);
+        let graph = self.graph.get_or_insert_with(|| {
+            Hnsw::new(
+                m,
+                INITIAL_CAPACITY,
+                MAX_LEVELS,
+                ef_construction,
+                CosineDistance,
+            )
+        });
+
+        for (id, doc, embeddings) in documents {
+            let document = self.documents.len();
+            self.documents.push((id.to_string(), doc));
+
+            for embedding in embeddings.iter() {
+                graph.insert((&normalize(&embedding.vec), self.embeddings.len()));
+                self.embeddings.push(document);
+            }
+        }
+    }
+
+    /// Number of documents in the index.
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// Search the `n` documents closest to `query`, with their cosine similarity, from most to
+    /// least similar. The score of a document is the score of its closest embedding, and the
+    /// documents scoring below `threshold` (if any) are omitted.
+    fn vector_search(&self, query: &[f64], n: usize, threshold: Option<f64>) -> Vec<(f64, usize)> {
+        let Some(graph) = &self.graph else {
+            return vec![];
+        };
+        let query = normalize(query);
+        let ef = self.ef_search.max(n);
+
+        // Documents with several embeddings may appear more than once in the neighbors
+        let mut documents = HashSet::new();
+        graph
+            .search(&query, ef, ef)
+            .into_iter()
+            .map(|neighbor| {
+                (
+                    1.0 - f64::from(neighbor.distance),
+                    self.embeddings[neighbor.d_id],
+                )
+            })
+            .filter(|(_, document)| documents.insert(*document))
+            .filter(|(score, _)| threshold.is_none_or(|threshold| *score >= threshold))
+            .take(n)
+            .collect()
+    }
+
+    /// Deserialize the documents of search results into the given type.
+    fn to_documents<T: for<'a> Deserialize<'a>>(
+        &self,
+        results: Vec<(f64, usize)>,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        results
+            .into_iter()
+            .map(|(score, document)| {
+                let (id, doc) = &self.documents[document];
+                Ok((
+                    score,
+                    id.clone(),
+                    deserialize_document(id, serde_json::to_value(doc)?)?,
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Cosine distance of two normalized vectors.
+fn distance(a: &[f64], b: &[f64]) -> f64 {
+    1.0 - a.iter().zip(b).map(|(a, b)| a * b).sum::<f64>()
+}
+
+/// Scale `vector` to unit length (zero vectors are left unchanged).
+fn normalize(vector: &[f64]) -> Vec<f64> {
+    let norm = vector.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm == 0.0 {
+        return vector.to_vec();
+    }
+    vector.iter().map(|x| x / norm).collect()
+}
+
+impl<M: EmbeddingModel + Sync, D: Serialize + Send + Sync> VectorStoreIndex
+    for HnswVectorIndex<M, D>
+{
+    async fn top_n<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        req: VectorSearchRequest,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let prompt_embedding = self.model.embed_query(req.query()).await?;
+
+        self.to_documents(self.vector_search(
+            &prompt_embedding.vec,
+            req.samples() as usize,
+            req.threshold(),
+        ))
+    }
+
+    async fn top_n_ids(
+        &self,
+        req: VectorSearchRequest,
+    ) -> Result<Vec<(f64, String)>, VectorStoreError> {
+        let prompt_embedding = self.model.embed_query(req.query()).await?;
+
+        Ok(self
+            .vector_search(
+                &prompt_embedding.vec,
+                req.samples() as usize,
+                req.threshold(),
+            )
+            .into_iter()
+            .map(|(score, document)| (score, self.documents[document].0.clone()))
+            .collect())
+    }
+
+    async fn top_n_by_vector<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        vector: &[f64],
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        if vector.len() != self.model.ndims() {
+            return Err(VectorStoreError::DimensionMismatch {
+                expected: self.model.ndims(),
+                got: vector.len(),
+            });
+        }
+
+        self.to_documents(self.vector_search(vector, n, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        OneOrMany,
+        embeddings::Embedding,
+        test_utils::MockEmbeddingModel,
+        vector_store::{VectorStoreError, VectorStoreIndex, request::VectorSearchRequest},
+    };
+
+    use super::{HnswVectorIndex, distance, normalize};
+
+    /// A model embedding its texts as the comma separated numbers they contain.
+    fn model() -> MockEmbeddingModel {
+        MockEmbeddingModel::from_fn(3, |text| {
+            text.split(',').map(|x| x.parse().unwrap()).collect()
+        })
+    }
+
+    fn embedding(vec: Vec<f64>) -> Embedding {
+        Embedding {
+            document: String::new(),
+            vec,
+        }
+    }
+
+    /// Deterministic pseudo-random vectors.
+    fn vectors(count: usize, ndims: usize) -> Vec<Vec<f64>> {
+        let mut state = 42u64;
+        (0..count)
+            .map(|_| {
+                (0..ndims)
+                    .map(|_| {
+                        state = state
+                            .wrapping_mul(6364136223846793005)
+                            .wrapping_add(1442695040888963407);
+                        (state >> 33) as f64 / (1u64 << 31) as f64 - 0.5
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_top_n() {
+        let mut index = HnswVectorIndex::new(model());
+        index.add_documents_with_ids(vec![
+            (
+                "north",
+                "North",
+                OneOrMany::one(embedding(vec![0.0, 1.0, 0.0])),
+            ),
+            (
+                "east",
+                "East",
+                OneOrMany::one(embedding(vec![1.0, 0.0, 0.0])),
+            ),
+            (
+                "up",
+                "Up",
+                OneOrMany::many(vec![
+                    embedding(vec![0.0, 0.0, 1.0]),
+                    embedding(vec![0.0, 0.9, 0.1]),
+                ])
+                .unwrap(),
+            ),
+        ]);
+
+        let req = VectorSearchRequest::builder()
+            .query("0,1,0")
+            .samples(2)
+            .build()
+            .unwrap();
+        let results = index.top_n::<String>(req.clone()).await.unwrap();
+
+        // Each document is returned once, with the score of its closest embedding
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].1, "north");
+        assert_eq!(results[0].2, "North");
+        assert!((results[0].0 - 1.0).abs() < 1e-6);
+        assert_eq!(results[1].1, "up");
+        assert!((results[1].0 - 0.9 / 0.82f64.sqrt()).abs() < 1e-6);
+
+        let ids = index.top_n_ids(req).await.unwrap();
+        assert_eq!(
+            ids.into_iter().map(|(_, id)| id).collect::<Vec<_>>(),
+            vec!["north", "up"]
+        );
+
+        // Documents scoring below the threshold are omitted
+        let req = VectorSearchRequest::builder()
+            .query("0,1,0")
+            .samples(2)
+            .threshold(0.995)
+            .build()
+            .unwrap();
+        let results = index.top_n::<String>(req.clone()).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].1, "north");
+
+        let ids = index.top_n_ids(req).await.unwrap();
+        assert_eq!(
+            ids.into_iter().map(|(_, id)| id).collect::<Vec<_>>(),
+            vec!["north"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recall() {
+        let mut vectors = vectors(550, 8);
+        let queries = vectors.split_off(500);
+        let mut index = HnswVectorIndex::new(model()).ef_construction(100);
+        index.add_documents(
+            vectors
+                .iter()
+                .map(|vec| ((), OneOrMany::one(embedding(vec.clone())))),
+        );
+        assert_eq!(index.len(), 500);
+
+        let mut found = 0;
+        for query in &queries {
+            let results = index.vector_search(query, 10, None);
+            assert_eq!(results.len(), 10);
+
+            // Exact nearest neighbors
+            let query = normalize(query);
+            let mut exact = vectors
+                .iter()
+                .enumerate()
+                .map(|(i, vector)| (distance(&query, &normalize(vector)), i))
+                .collect::<Vec<_>>();
+            exact.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+            let exact = exact.into_iter().map(|(_, i)| i).collect::<Vec<_>>();
+
+            found += results
+                .iter()
+                .filter(|(_, document)| exact[..10].contains(document))
+                .count();
+        }
+
+        let recall = found as f64 / (queries.len() * 10) as f64;
+        assert!(recall > 0.95, "recall: {recall}");
+    }
+
+    #[tokio::test]
+    async fn test_empty_index() {
+        let index = HnswVectorIndex::<_, ()>::new(model());
+
+        let results = index
+            .top_n_by_vector::<()>(&[1.0, 0.0, 0.0], 10)
+            .await
+            .unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_top_n_by_vector_dimension_mismatch() {
+        let index = HnswVectorIndex::<_, ()>::new(model());
+
+        let result = index.top_n_by_vector::<()>(&[1.0, 0.0], 10).await;
+        assert!(matches!(
+            result,
+            Err(VectorStoreError::DimensionMismatch {
+                expected: 3,
+                got: 2
+            })
+        ));
+    }
+}
@@ -2,12 +2,16 @@
 use std::{
     cmp::Reverse,
     collections::{BinaryHeap, HashMap},
+    sync::Arc,
 };
 
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use super::{VectorStoreError, VectorStoreIndex, request::VectorSearchRequest};
+use super::{
+    VectorStoreError, VectorStoreIndex, deserialize_document, request::VectorSearchRequest,
+};
 use crate::{
     OneOrMany,
     embeddings::{Embedding, EmbeddingModel, distance::VectorDistance},
@@ -21,8 +25,14 @@ pub struct InMemoryVectorStore<D: Serialize> {
     /// Hashmap key is the document id.
     /// Hashmap value is a tuple of the serializable document and its corresponding embeddings.
     embeddings: HashMap<String, (D, OneOrMany<Embedding>)>,
+    /// Metadata of the documents, by document id (see [InMemoryVectorIndex::top_n_filtered]).
+    metadata: HashMap<String, Metadata>,
 }
 
+/// Arbitrary metadata attached to a document of an [InMemoryVectorStore], which vector
+/// searches can be filtered on (see [InMemoryVectorIndex::top_n_filtered]).
+pub type Metadata = HashMap<String, Value>;
+
 impl<D: Serialize + Eq> InMemoryVectorStore<D> {
     /// Create a new [InMemoryVectorStore] from documents and their corresponding embeddings.
     /// Ids are automatically generated have will have the form `"doc{n}"` where `n`
@@ -36,7 +46,10 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
                 store.insert(format!("doc{i}"), (doc, embeddings));
             });
 
-        Self { embeddings: store }
+        Self {
+            embeddings: store,
+            metadata: HashMap::new(),
+        }
     }
 
     /// Create a new [InMemoryVectorStore] from documents and their corresponding embeddings with ids.
@@ -48,7 +61,10 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
             store.insert(i.to_string(), (doc, embeddings));
         });
 
-        Self { embeddings: store }
+        Self {
+            embeddings: store,
+            metadata: HashMap::new(),
+        }
     }
 
     /// Create a new [InMemoryVectorStore] from documents and their corresponding embeddings.
@@ -62,24 +78,67 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
             store.insert(f(&doc), (doc, embeddings));
         });
 
-        Self { embeddings: store }
+        Self {
+            embeddings: store,
+            metadata: HashMap::new(),
+        }
+    }
+
+    /// Create a new [InMemoryVectorStore] from documents with ids, their corresponding
+    /// embeddings and their metadata.
+    pub fn from_documents_with_metadata(
+        documents: impl IntoIterator<Item = (impl ToString, D, OneOrMany<Embedding>, Metadata)>,
+    ) -> Self {
+        let mut store = Self {
+            embeddings: HashMap::new(),
+            metadata: HashMap::new(),
+        };
+        store.add_documents_with_metadata(documents);
+        store
     }
 
     /// Implement vector search on [InMemoryVectorStore].
     /// To be used by implementations of [VectorStoreIndex::top_n] and [VectorStoreIndex::top_n_ids] methods.
-    fn vector_search(&self, prompt_embedding: &Embedding, n: usize) -> EmbeddingRanking<'_, D> {
+    /// Embeddings are scored with `scorer`, or with cosine similarity if `None`.
+    fn vector_search(
+        &self,
+        prompt_embedding: &Embedding,
+        n: usize,
+        scorer: Option<&Scorer>,
+    ) -> EmbeddingRanking<'_, D> {
+        self.vector_search_filtered(prompt_embedding, n, scorer, None)
+    }
+
+    /// Same as [InMemoryVectorStore::vector_search], only scoring the documents whose metadata
+    /// (empty if the document has none) matches `filter`, if any.
+    fn vector_search_filtered(
+        &self,
+        prompt_embedding: &Embedding,
+        n: usize,
+        scorer: Option<&Scorer>,
+        filter: Option<&MetadataFilter>,
+    ) -> EmbeddingRanking<'_, D> {
         // Sort documents by best embedding distance
         let mut docs = BinaryHeap::new();
+        let no_metadata = Metadata::new();
 
         for (id, (doc, embeddings)) in self.embeddings.iter() {
+            // Filter the documents on their metadata before scoring them
+            if let Some(filter) = filter
+                && !filter(self.metadata.get(id).unwrap_or(&no_metadata))
+            {
+                continue;
+            }
+
             // Get the best context for the document given the prompt
             if let Some((distance, embed_doc)) = embeddings
                 .iter()
                 .map(|embedding| {
-                    (
-                        OrderedFloat(embedding.cosine_similarity(prompt_embedding, false)),
-                        &embedding.document,
-                    )
+                    let score = match scorer {
+                        Some(scorer) => scorer(&embedding.vec, &prompt_embedding.vec),
+                        None => embedding.cosine_similarity(prompt_embedding, false),
+                    };
+                    (OrderedFloat(score), &embedding.document)
                 })
                 .max_by(|a, b| a.0.cmp(&b.0))
             {
@@ -116,21 +175,65 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
             .into_iter()
             .enumerate()
             .for_each(|(index, (doc, embeddings))| {
-                self.embeddings
-                    .insert(format!("doc{}", index + current_index), (doc, embeddings));
+                self.insert(
+                    format!("doc{}", index + current_index),
+                    doc,
+                    embeddings,
+                    None,
+                );
             });
     }
 
     /// Add documents and their corresponding embeddings to the store with ids.
+    /// Documents replacing a document with the same id do not keep its metadata.
     pub fn add_documents_with_ids(
         &mut self,
         documents: impl IntoIterator<Item = (impl ToString, D, OneOrMany<Embedding>)>,
     ) {
         documents.into_iter().for_each(|(id, doc, embeddings)| {
-            self.embeddings.insert(id.to_string(), (doc, embeddings));
+            self.insert(id.to_string(), doc, embeddings, None);
         });
     }
 
+    /// Add documents with ids, their corresponding embeddings and their metadata to the store.
+    /// Documents replacing a document with the same id replace its metadata.
+    pub fn add_documents_with_metadata(
+        &mut self,
+        documents: impl IntoIterator<Item = (impl ToString, D, OneOrMany<Embedding>, Metadata)>,
+    ) {
+        documents
+            .into_iter()
+            .for_each(|(id, doc, embeddings, metadata)| {
+                self.insert(id.to_string(), doc, embeddings, Some(metadata));
+            });
+    }
+
+    /// Insert the document `id` with its metadata, if any, replacing the document with the same
+    /// id and its metadata.
+    fn insert(
+        &mut self,
+        id: String,
+        doc: D,
+        embeddings: OneOrMany<Embedding>,
+        metadata: Option<Metadata>,
+    ) {
+        match metadata {
+            Some(metadata) => self.metadata.insert(id.clone(), metadata),
+            None => self.metadata.remove(&id),
+        };
+        self.embeddings.insert(id, (doc, embeddings));
+    }
+
+    /// Set the metadata of the document `id`, replacing its previous metadata.
+    pub fn set_metadata(&mut self, id: &str, metadata: Metadata) {
+        self.metadata.insert(id.to_string(), metadata);
+    }
+
+    /// Get the metadata of the document `id`, if any.
+    pub fn metadata(&self, id: &str) -> Option<&Metadata> {
+        self.metadata.get(id)
+    }
+
     /// Add documents and their corresponding embeddings to the store.
     /// Document ids are generated using the provided function.
     pub fn add_documents_with_id_f(
@@ -140,7 +243,7 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
     ) {
         for (doc, embeddings) in documents {
             let id = f(&doc);
-            self.embeddings.insert(id, (doc, embeddings));
+            self.insert(id, doc, embeddings, None);
         }
     }
 
@@ -149,11 +252,10 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
         &self,
         id: &str,
     ) -> Result<Option<T>, VectorStoreError> {
-        Ok(self
-            .embeddings
+        self.embeddings
             .get(id)
-            .map(|(doc, _)| serde_json::from_str(&serde_json::to_string(doc)?))
-            .transpose()?)
+            .map(|(doc, _)| deserialize_document(id, serde_json::to_value(doc)?))
+            .transpose()
     }
 }
 
@@ -193,14 +295,37 @@ impl<D: Serialize> InMemoryVectorStore<D> {
     }
 }
 
+/// Scoring function used by [InMemoryVectorIndex] to compare a document embedding
+/// with the query embedding. Higher scores rank first.
+pub type Scorer = Arc<dyn Fn(&[f64], &[f64]) -> f64 + Send + Sync>;
+
+/// Predicate on the metadata of documents (see [InMemoryVectorIndex::top_n_filtered]).
+type MetadataFilter<'a> = dyn Fn(&Metadata) -> bool + Send + Sync + 'a;
+
 pub struct InMemoryVectorIndex<M: EmbeddingModel, D: Serialize> {
     model: M,
     pub store: InMemoryVectorStore<D>,
+    scorer: Option<Scorer>,
 }
 
 impl<M: EmbeddingModel, D: Serialize> InMemoryVectorIndex<M, D> {
     pub fn new(model: M, store: InMemoryVectorStore<D>) -> Self {
-        Self { model, store }
+        Self {
+            model,
+            store,
+            scorer: None,
+        }
+    }
+
+    /// Set the function used to score document embeddings against the query embedding,
+    /// instead of the default cosine similarity. Documents with higher scores rank first,
+    /// so a distance metric should be negated (e.g.: `|a, b| -euclidean(a, b)`).
+    pub fn with_scorer(
+        mut self,
+        scorer: impl Fn(&[f64], &[f64]) -> f64 + Send + Sync + 'static,
+    ) -> Self {
+        self.scorer = Some(Arc::new(scorer));
+        self
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (&String, &(D, OneOrMany<Embedding>))> {
@@ -216,6 +341,40 @@ impl<M: EmbeddingModel, D: Serialize> InMemoryVectorIndex<M, D> {
     }
 }
 
+impl<M: EmbeddingModel, D: Serialize + Eq> InMemoryVectorIndex<M, D> {
+    /// Same as [VectorStoreIndex::top_n], only searching the documents whose metadata matches
+    /// `filter` (see [InMemoryVectorStore::add_documents_with_metadata]). Documents without
+    /// metadata are filtered as if their metadata was empty.
+    ///
+    /// The documents are filtered before they are scored, like the pre-filter of the vector
+    /// searches of other vector stores (e.g.: MongoDB's `filter`).
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// let results = index
+    ///     .top_n_filtered::<String>(req, |metadata| {
+    ///         metadata.get("category") == Some(&serde_json::json!("science"))
+    ///     })
+    ///     .await?;
+    /// ```
+    pub async fn top_n_filtered<T: for<'a> Deserialize<'a>>(
+        &self,
+        req: VectorSearchRequest,
+        filter: impl Fn(&Metadata) -> bool + Send + Sync,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        let prompt_embedding = &self.model.embed_query(req.query()).await?;
+
+        let docs = self.store.vector_search_filtered(
+            prompt_embedding,
+            req.samples() as usize,
+            self.scorer.as_ref(),
+            Some(&filter),
+        );
+
+        ranking_to_documents(docs)
+    }
+}
+
 impl<M: EmbeddingModel + Sync, D: Serialize + Sync + Send + Eq> VectorStoreIndex
     for InMemoryVectorIndex<M, D>
 {
@@ -223,51 +382,117 @@ impl<M: EmbeddingModel + Sync, D: Serialize + Sync + Send + Eq> VectorStoreIndex
         &self,
         req: VectorSearchRequest,
     ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
-        let prompt_embedding = &self.model.embed_text(req.query()).await?;
+        let prompt_embedding = &self.model.embed_query(req.query()).await?;
 
-        let docs = self
-            .store
-            .vector_search(prompt_embedding, req.samples() as usize);
+        let docs = self.store.vector_search(
+            prompt_embedding,
+            req.samples() as usize,
+            self.scorer.as_ref(),
+        );
 
-        // Return n best
-        docs.into_iter()
-            // The distance should always be between 0 and 1, so distance should be fine to use as an absolute value
-            .map(|Reverse(RankingItem(distance, id, doc, _))| {
-                Ok((
-                    distance.0,
-                    id.clone(),
-                    serde_json::from_str(
-                        &serde_json::to_string(doc).map_err(VectorStoreError::JsonError)?,
-                    )
-                    .map_err(VectorStoreError::JsonError)?,
-                ))
+        ranking_to_documents(docs)
+    }
+
+    /// The queries of the requests are embedded at once (see [EmbeddingModel::embed_queries]).
+    async fn top_n_batch<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        requests: Vec<VectorSearchRequest>,
+    ) -> Result<Vec<Vec<(f64, String, T)>>, VectorStoreError> {
+        let prompt_embeddings = self
+            .model
+            .embed_queries(requests.iter().map(|req| req.query().to_string()).collect())
+            .await?;
+
+        requests
+            .iter()
+            .zip(prompt_embeddings)
+            .map(|(req, prompt_embedding)| {
+                let docs = self.store.vector_search(
+                    &prompt_embedding,
+                    req.samples() as usize,
+                    self.scorer.as_ref(),
+                );
+                ranking_to_documents(docs)
             })
-            .collect::<Result<Vec<_>, _>>()
+            .collect()
     }
 
     async fn top_n_ids(
         &self,
         req: VectorSearchRequest,
     ) -> Result<Vec<(f64, String)>, VectorStoreError> {
-        let prompt_embedding = &self.model.embed_text(req.query()).await?;
+        let prompt_embedding = &self.model.embed_query(req.query()).await?;
 
-        let docs = self
-            .store
-            .vector_search(prompt_embedding, req.samples() as usize);
+        let docs = self.store.vector_search(
+            prompt_embedding,
+            req.samples() as usize,
+            self.scorer.as_ref(),
+        );
 
         docs.into_iter()
             .map(|Reverse(RankingItem(distance, id, _, _))| Ok((distance.0, id.clone())))
             .collect::<Result<Vec<_>, _>>()
     }
+
+    async fn top_n_by_vector<T: for<'a> Deserialize<'a>>(
+        &self,
+        vector: &[f64],
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        if vector.len() != self.model.ndims() {
+            return Err(VectorStoreError::DimensionMismatch {
+                expected: self.model.ndims(),
+                got: vector.len(),
+            });
+        }
+
+        let prompt_embedding = Embedding {
+            document: String::new(),
+            vec: vector.to_vec(),
+        };
+
+        let docs = self
+            .store
+            .vector_search(&prompt_embedding, n, self.scorer.as_ref());
+
+        ranking_to_documents(docs)
+    }
+}
+
+/// Deserialize the documents of a ranking into the given type.
+fn ranking_to_documents<D: Serialize, T: for<'a> Deserialize<'a>>(
+    docs: EmbeddingRanking<'_, D>,
+) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+    // Return n best
+    docs.into_iter()
+        // The distance should always be between 0 and 1, so distance should be fine to use as an absolute value
+        .map(|Reverse(RankingItem(distance, id, doc, _))| {
+            Ok((
+                distance.0,
+                id.clone(),
+                deserialize_document(id, serde_json::to_value(doc)?)?,
+            ))
+        })
+        .collect::<Result<Vec<_>, _>>()
 }
 
 #[cfg(test)]
 mod tests {
     use std::cmp::Reverse;
 
-    use crate::{OneOrMany, embeddings::embedding::Embedding};
+    use crate::{
+        OneOrMany,
+        embeddings::{EmbedKind, EmbeddingsBuilder, embedding::Embedding},
+        test_utils::MockEmbeddingModel,
+        vector_store::{VectorStoreError, VectorStoreIndex, request::VectorSearchRequest},
+    };
 
-    use super::{InMemoryVectorStore, RankingItem};
+    use super::{InMemoryVectorStore, Metadata, RankingItem};
+
+    /// A model of 3 dimensions, for indexes searched by vector.
+    fn model() -> MockEmbeddingModel {
+        MockEmbeddingModel::new(vec![0.0, 0.0, 0.0])
+    }
 
     #[test]
     fn test_auto_ids() {
@@ -407,6 +632,7 @@ mod tests {
                 vec: vec![0.0, 0.1, 0.6],
             },
             1,
+            None,
         );
 
         assert_eq!(
@@ -484,6 +710,7 @@ mod tests {
                 vec: vec![0.0, 0.1, 0.6],
             },
             1,
+            None,
         );
 
         assert_eq!(
@@ -504,4 +731,325 @@ mod tests {
             )]
         )
     }
+
+    #[tokio::test]
+    async fn test_top_n_by_vector() {
+        let model = model();
+        let index = InMemoryVectorStore::from_documents_with_ids(vec![
+            (
+                "doc1",
+                "glarb-garb".to_string(),
+                OneOrMany::one(Embedding {
+                    document: "glarb-garb".to_string(),
+                    vec: vec![0.1, 0.1, 0.5],
+                }),
+            ),
+            (
+                "doc2",
+                "marble-marble".to_string(),
+                OneOrMany::one(Embedding {
+                    document: "marble-marble".to_string(),
+                    vec: vec![0.7, -0.3, 0.0],
+                }),
+            ),
+        ])
+        .index(model.clone());
+
+        let results = index
+            .top_n_by_vector::<String>(&[0.0, 0.1, 0.6], 1)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            results,
+            vec![(
+                0.9807965956109156,
+                "doc1".to_string(),
+                "glarb-garb".to_string()
+            )]
+        );
+
+        let err = index
+            .top_n_by_vector::<String>(&[0.0, 0.1], 1)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            VectorStoreError::DimensionMismatch {
+                expected: 3,
+                got: 2
+            }
+        ));
+        // Searching by vector does not embed the query
+        assert!(model.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_with_scorer() {
+        let index = InMemoryVectorStore::from_documents_with_ids(vec![
+            (
+                "doc1",
+                "glarb-garb".to_string(),
+                OneOrMany::one(Embedding {
+                    document: "glarb-garb".to_string(),
+                    vec: vec![1.0, 1.0, 1.0],
+                }),
+            ),
+            (
+                "doc2",
+                "marble-marble".to_string(),
+                OneOrMany::one(Embedding {
+                    document: "marble-marble".to_string(),
+                    vec: vec![10.0, 10.0, 9.0],
+                }),
+            ),
+        ])
+        .index(model());
+
+        // doc1 is closer by cosine similarity...
+        let results = index
+            .top_n_by_vector::<String>(&[10.0, 10.0, 10.0], 1)
+            .await
+            .unwrap();
+        assert_eq!(results[0].1, "doc1");
+
+        // ...but doc2 is closer by (negated) manhattan distance
+        let index =
+            index.with_scorer(|a, b| -a.iter().zip(b).map(|(x, y)| (x - y).abs()).sum::<f64>());
+        let results = index
+            .top_n_by_vector::<String>(&[10.0, 10.0, 10.0], 1)
+            .await
+            .unwrap();
+        assert_eq!(
+            results,
+            vec![(-1.0, "doc2".to_string(), "marble-marble".to_string())]
+        );
+    }
+
+    /// A model embedding every text as `[1.0]`, five texts per request.
+    fn one_dim_model() -> MockEmbeddingModel {
+        MockEmbeddingModel::new(vec![1.0]).with_max_batch_size(5)
+    }
+
+    #[tokio::test]
+    async fn test_embed_kinds() {
+        let model = one_dim_model();
+        let embeddings = EmbeddingsBuilder::new(model.clone())
+            .documents(vec!["glarb-garb".to_string()])
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+
+        let index = InMemoryVectorStore::from_documents(embeddings).index(model.clone());
+        let req = VectorSearchRequest::builder()
+            .query("glarb")
+            .samples(1)
+            .build()
+            .unwrap();
+        index.top_n::<String>(req).await.unwrap();
+
+        // Documents are embedded as documents, and the query as a query
+        assert_eq!(model.kinds(), vec![EmbedKind::Document, EmbedKind::Query]);
+    }
+
+    #[tokio::test]
+    async fn test_top_n_batch() {
+        let model = one_dim_model();
+        let index = InMemoryVectorStore::from_documents_with_ids(vec![(
+            "doc1",
+            "glarb-garb".to_string(),
+            OneOrMany::one(Embedding {
+                document: "glarb-garb".to_string(),
+                vec: vec![1.0],
+            }),
+        )])
+        .index(model.clone());
+        let requests = (0..7)
+            .map(|i| {
+                VectorSearchRequest::builder()
+                    .query(format!("query {i}"))
+                    .samples(1)
+                    .build()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let results = index.top_n_batch::<String>(requests).await.unwrap();
+
+        assert_eq!(results.len(), 7);
+        assert!(results.iter().all(|result| result[0].1 == "doc1"));
+        // The queries are embedded in batches of the max batch size, not one by one
+        assert_eq!(model.kinds(), vec![EmbedKind::Query, EmbedKind::Query]);
+    }
+
+    #[tokio::test]
+    async fn test_top_n_filtered() {
+        let document = |id: &str, category: Option<&str>| {
+            (
+                id.to_string(),
+                id.to_string(),
+                OneOrMany::one(Embedding {
+                    document: id.to_string(),
+                    vec: vec![1.0],
+                }),
+                category
+                    .map(|category| {
+                        [("category".to_string(), serde_json::json!(category))]
+                            .into_iter()
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            )
+        };
+        let mut store = InMemoryVectorStore::from_documents_with_metadata([
+            document("doc1", Some("science")),
+            document("doc2", Some("history")),
+            document("doc3", None),
+        ]);
+        store.add_documents_with_metadata([document("doc4", Some("science"))]);
+        let index = store.index(one_dim_model());
+
+        let req = VectorSearchRequest::builder()
+            .query("query")
+            .samples(10)
+            .build()
+            .unwrap();
+        let mut results = index
+            .top_n_filtered::<String>(req.clone(), |metadata| {
+                metadata.get("category") == Some(&serde_json::json!("science"))
+            })
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(_, id, _)| id)
+            .collect::<Vec<_>>();
+        results.sort();
+        assert_eq!(results, vec!["doc1", "doc4"]);
+
+        // Documents without metadata are filtered on empty metadata
+        let results = index
+            .top_n_filtered::<String>(req, |metadata| metadata.is_empty())
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].1, "doc3");
+        assert_eq!(
+            index.store.metadata("doc2").unwrap()["category"],
+            serde_json::json!("history")
+        );
+    }
+
+    #[test]
+    fn test_upsert_metadata() {
+        let embedding = || {
+            OneOrMany::one(Embedding {
+                document: String::new(),
+                vec: vec![1.0],
+            })
+        };
+        let metadata = |category: &str| -> Metadata {
+            [("category".to_string(), serde_json::json!(category))]
+                .into_iter()
+                .collect()
+        };
+        let mut store = InMemoryVectorStore::from_documents_with_metadata([
+            ("doc1", "Old", embedding(), metadata("science")),
+            ("doc2", "Old", embedding(), metadata("history")),
+        ]);
+
+        // Upserting a document with metadata replaces its metadata...
+        store.add_documents_with_metadata([("doc1", "New", embedding(), metadata("history"))]);
+        assert_eq!(store.metadata("doc1"), Some(&metadata("history")));
+
+        // ...and upserting it without metadata clears it
+        store.add_documents_with_ids([("doc2", "New", embedding())]);
+        assert_eq!(store.metadata("doc2"), None);
+        assert_eq!(
+            store.get_document::<String>("doc2").unwrap().as_deref(),
+            Some("New")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deserialization_error() {
+        #[derive(Debug, serde::Deserialize)]
+        struct Doc {
+            #[allow(dead_code)]
+            tags: Vec<String>,
+        }
+
+        let index = InMemoryVectorStore::from_documents_with_ids(vec![(
+            "doc1",
+            serde_json::json!({ "tags": ["a", 2] }),
+            OneOrMany::one(Embedding {
+                document: "doc1".to_string(),
+                vec: vec![0.1, 0.1, 0.5],
+            }),
+        )])
+        .index(model());
+
+        let err = index
+            .top_n_by_vector::<Doc>(&[0.0, 0.1, 0.6], 1)
+            .await
+            .unwrap_err();
+
+        match err {
+            VectorStoreError::Deserialization { id, path, .. } => {
+                assert_eq!(id, "doc1");
+                assert_eq!(path, "tags[1]");
+            }
+            err => panic!("Unexpected error: {err}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_top_n_lenient() {
+        #[derive(Debug, serde::Deserialize)]
+        struct Doc {
+            title: String,
+        }
+
+        let index = InMemoryVectorStore::from_documents_with_ids(vec![
+            (
+                "doc1",
+                serde_json::json!({ "title": "New document" }),
+                OneOrMany::one(Embedding {
+                    document: "doc1".to_string(),
+                    vec: vec![1.0],
+                }),
+            ),
+            (
+                "doc2",
+                serde_json::json!({ "name": "Old document" }),
+                OneOrMany::one(Embedding {
+                    document: "doc2".to_string(),
+                    vec: vec![0.5],
+                }),
+            ),
+        ])
+        .index(one_dim_model());
+        let req = VectorSearchRequest::builder()
+            .query("query")
+            .samples(2)
+            .build()
+            .unwrap();
+
+        assert!(matches!(
+            index.top_n::<Doc>(req.clone()).await,
+            Err(VectorStoreError::Deserialization { .. })
+        ));
+
+        let (documents, failures) = index.top_n_lenient::<Doc>(req).await.unwrap();
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].1, "doc1");
+        assert_eq!(documents[0].2.title, "New document");
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, "doc2");
+        assert!(matches!(
+            &failures[0].1,
+            VectorStoreError::Deserialization { path, .. } if path == "."
+        ));
+    }
 }
@@ -1,4 +1,4 @@
-use futures::future::BoxFuture;
+use futures::{StreamExt, TryStreamExt, future::BoxFuture};
 pub use request::VectorSearchRequest;
 use reqwest::StatusCode;
 use serde::Deserialize;
@@ -8,9 +8,19 @@ use serde_json::Value;
 use crate::embeddings::EmbeddingError;
 use crate::{Embed, OneOrMany, embeddings::Embedding};
 
+pub mod ensemble;
+pub mod federated;
+pub mod fusion;
+#[cfg(feature = "hnsw")]
+#[cfg_attr(docsrs, doc(cfg(feature = "hnsw")))]
+pub mod hnsw;
 pub mod in_memory_store;
 pub mod request;
 
+/// Default maximum number of searches run concurrently by [VectorStoreIndex::top_n_batch] and
+/// [FederatedVectorIndex](federated::FederatedVectorIndex).
+pub const DEFAULT_MAX_CONCURRENCY: usize = 10;
+
 #[derive(Debug, thiserror::Error)]
 pub enum VectorStoreError {
     #[error("Embedding error: {0}")]
@@ -34,6 +44,19 @@ pub enum VectorStoreError {
 
     #[error("Error while building VectorSearchRequest: {0}")]
     BuilderError(String),
+
+    /// The provided query vector does not have the same number of dimensions as the index
+    #[error("Dimension mismatch: expected a vector of {expected} dimensions, got {got}")]
+    DimensionMismatch { expected: usize, got: usize },
+
+    /// A retrieved document could not be deserialized into the requested type. `path` is the
+    /// path of the field that failed (e.g.: `metadata.tags[2]`, `.` for the document itself).
+    #[error("Failed to deserialize document {id} at `{path}`: {source}")]
+    Deserialization {
+        id: String,
+        path: String,
+        source: serde_json::Error,
+    },
 }
 
 /// Trait for inserting documents into a vector store.
@@ -60,10 +83,96 @@ pub trait VectorStoreIndex: Send + Sync {
         &self,
         req: VectorSearchRequest,
     ) -> impl std::future::Future<Output = Result<Vec<(f64, String)>, VectorStoreError>> + Send;
+
+    /// Get the top n documents of multiple requests at once (e.g.: for the sub-questions of a
+    /// question). The results are returned in the order of the requests.
+    ///
+    /// By default, the requests are run with [VectorStoreIndex::top_n], at most
+    /// [DEFAULT_MAX_CONCURRENCY] at a time. Vector stores embedding the queries themselves embed
+    /// all of them at once instead of making an embedding request per query.
+    #[allow(clippy::type_complexity)]
+    fn top_n_batch<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        requests: Vec<VectorSearchRequest>,
+    ) -> impl std::future::Future<Output = Result<Vec<Vec<(f64, String, T)>>, VectorStoreError>> + Send
+    {
+        let searches = requests
+            .into_iter()
+            .map(|req| self.top_n(req))
+            .collect::<Vec<_>>();
+
+        futures::stream::iter(searches)
+            .buffered(DEFAULT_MAX_CONCURRENCY)
+            .try_collect()
+    }
+
+    /// Same as `top_n`, but documents which cannot be deserialized into `T` (e.g.: older
+    /// documents lacking a field added since) do not fail the whole search. The documents that
+    /// could be deserialized are returned along with the ids and
+    /// [VectorStoreError::Deserialization] errors of the ones that could not.
+    #[allow(clippy::type_complexity)]
+    fn top_n_lenient<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        req: VectorSearchRequest,
+    ) -> impl std::future::Future<
+        Output = Result<(Vec<(f64, String, T)>, Vec<(String, VectorStoreError)>), VectorStoreError>,
+    > + Send {
+        async move {
+            let mut documents = vec![];
+            let mut failures = vec![];
+            for (score, id, document) in self.top_n::<Value>(req).await? {
+                match deserialize_document(&id, document) {
+                    Ok(document) => documents.push((score, id, document)),
+                    Err(err) => failures.push((id, err)),
+                }
+            }
+
+            Ok((documents, failures))
+        }
+    }
+
+    /// Get the top n documents based on the distance to the given, already computed, query vector.
+    /// Unlike `top_n`, the query is not embedded by the index's model, which saves an embedding call
+    /// (e.g.: when the query embedding is cached or was produced by another model).
+    /// The result is a list of tuples of the form (score, id, document)
+    ///
+    /// The length of `vector` must match the number of dimensions of the index.
+    /// Vector stores that do not support searching by vector return an error.
+    fn top_n_by_vector<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        vector: &[f64],
+        n: usize,
+    ) -> impl std::future::Future<Output = Result<Vec<(f64, String, T)>, VectorStoreError>> + Send
+    {
+        let _ = (vector, n);
+        async {
+            Err(VectorStoreError::DatastoreError(
+                "Searching by vector is not supported by this vector store".into(),
+            ))
+        }
+    }
 }
 
 pub type TopNResults = Result<Vec<(f64, String, Value)>, VectorStoreError>;
 
+/// A document retrieved from a vector index, with its similarity score to the query.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoredDocument {
+    pub score: f64,
+    pub id: String,
+    pub document: Value,
+}
+
+impl From<(f64, String, Value)> for ScoredDocument {
+    fn from((score, id, document): (f64, String, Value)) -> Self {
+        Self {
+            score,
+            id,
+            document,
+        }
+    }
+}
+
 pub trait VectorStoreIndexDyn: Send + Sync {
     fn top_n<'a>(&'a self, req: VectorSearchRequest) -> BoxFuture<'a, TopNResults>;
 
@@ -71,6 +180,8 @@ pub trait VectorStoreIndexDyn: Send + Sync {
         &'a self,
         req: VectorSearchRequest,
     ) -> BoxFuture<'a, Result<Vec<(f64, String)>, VectorStoreError>>;
+
+    fn top_n_by_vector<'a>(&'a self, vector: &'a [f64], n: usize) -> BoxFuture<'a, TopNResults>;
 }
 
 impl<I: VectorStoreIndex> VectorStoreIndexDyn for I {
@@ -94,6 +205,30 @@ impl<I: VectorStoreIndex> VectorStoreIndexDyn for I {
     ) -> BoxFuture<'a, Result<Vec<(f64, String)>, VectorStoreError>> {
         Box::pin(self.top_n_ids(req))
     }
+
+    fn top_n_by_vector<'a>(&'a self, vector: &'a [f64], n: usize) -> BoxFuture<'a, TopNResults> {
+        Box::pin(async move {
+            Ok(self
+                .top_n_by_vector::<serde_json::Value>(vector, n)
+                .await?
+                .into_iter()
+                .map(|(score, id, doc)| (score, id, prune_document(doc).unwrap_or_default()))
+                .collect::<Vec<_>>())
+        })
+    }
+}
+
+/// Deserialize a retrieved document into `T`, failing with a [VectorStoreError::Deserialization]
+/// error pointing to the document and the field that could not be deserialized.
+pub fn deserialize_document<T: for<'a> Deserialize<'a>>(
+    id: &str,
+    document: Value,
+) -> Result<T, VectorStoreError> {
+    serde_path_to_error::deserialize(document).map_err(|err| VectorStoreError::Deserialization {
+        id: id.to_string(),
+        path: err.path().to_string(),
+        source: err.into_inner(),
+    })
 }
 
 fn prune_document(document: serde_json::Value) -> Option<serde_json::Value> {
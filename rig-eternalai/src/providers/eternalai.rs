@@ -537,6 +537,10 @@ impl completion::CompletionModel for CompletionModel {
     type Response = CompletionResponse;
     type StreamingResponse = CompletionResponse;
 
+    fn model_name(&self) -> Option<&str> {
+        Some(&self.model)
+    }
+
     async fn completion(
         &self,
         completion_request: CompletionRequest,
@@ -670,6 +674,9 @@ impl completion::CompletionModel for CompletionModel {
                     AssistantContent::Text(text) => {
                         yield Ok(RawStreamingChoice::Message(text.text.clone()))
                     }
+                    AssistantContent::Refusal(refusal) => {
+                        yield Ok(RawStreamingChoice::Message(refusal.refusal.clone()))
+                    }
                     AssistantContent::ToolCall(tc) => {
                         yield Ok(RawStreamingChoice::ToolCall {
                             id: tc.id.clone(),
@@ -4,7 +4,9 @@ use lancedb::{
 };
 use rig::{
     embeddings::embedding::EmbeddingModel,
-    vector_store::{VectorStoreError, VectorStoreIndex, request::VectorSearchRequest},
+    vector_store::{
+        VectorStoreError, VectorStoreIndex, deserialize_document, request::VectorSearchRequest,
+    },
 };
 use serde::Deserialize;
 use serde_json::Value;
@@ -16,10 +18,6 @@ fn lancedb_to_rig_error(e: lancedb::Error) -> VectorStoreError {
     VectorStoreError::DatastoreError(Box::new(e))
 }
 
-fn serde_to_rig_error(e: serde_json::Error) -> VectorStoreError {
-    VectorStoreError::JsonError(e)
-}
-
 /// Type on which vector searches can be performed for a lanceDb table.
 /// # Example
 /// ```
@@ -198,7 +196,7 @@ impl<M: EmbeddingModel + Sync + Send> VectorStoreIndex for LanceDbVectorIndex<M>
         &self,
         req: VectorSearchRequest,
     ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
-        let prompt_embedding = self.model.embed_text(req.query()).await?;
+        let prompt_embedding = self.model.embed_query(req.query()).await?;
 
         let query = self
             .table
@@ -220,17 +218,16 @@ impl<M: EmbeddingModel + Sync + Send> VectorStoreIndex for LanceDbVectorIndex<M>
             .into_iter()
             .enumerate()
             .map(|(i, value)| {
-                Ok((
-                    match value.get("_distance") {
-                        Some(Value::Number(distance)) => distance.as_f64().unwrap_or_default(),
-                        _ => 0.0,
-                    },
-                    match value.get(self.id_field.clone()) {
-                        Some(Value::String(id)) => id.to_string(),
-                        _ => format!("unknown{i}"),
-                    },
-                    serde_json::from_value(value).map_err(serde_to_rig_error)?,
-                ))
+                let distance = match value.get("_distance") {
+                    Some(Value::Number(distance)) => distance.as_f64().unwrap_or_default(),
+                    _ => 0.0,
+                };
+                let id = match value.get(self.id_field.clone()) {
+                    Some(Value::String(id)) => id.to_string(),
+                    _ => format!("unknown{i}"),
+                };
+                let document = deserialize_document(&id, value)?;
+                Ok((distance, id, document))
             })
             .collect()
     }
@@ -256,7 +253,7 @@ impl<M: EmbeddingModel + Sync + Send> VectorStoreIndex for LanceDbVectorIndex<M>
         &self,
         req: VectorSearchRequest,
     ) -> Result<Vec<(f64, String)>, VectorStoreError> {
-        let prompt_embedding = self.model.embed_text(req.query()).await?;
+        let prompt_embedding = self.model.embed_query(req.query()).await?;
 
         let query = self
             .table
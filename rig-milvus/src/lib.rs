@@ -228,7 +228,7 @@ impl<M: EmbeddingModel> VectorStoreIndex for MilvusVectorStore<M> {
         &self,
         req: VectorSearchRequest,
     ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
-        let embedding = self.model.embed_text(req.query()).await?;
+        let embedding = self.model.embed_query(req.query()).await?;
         let url = format!(
             "{base_url}/v2/vectordb/entities/search",
             base_url = self.base_url
@@ -270,7 +270,7 @@ impl<M: EmbeddingModel> VectorStoreIndex for MilvusVectorStore<M> {
         &self,
         req: VectorSearchRequest,
     ) -> Result<Vec<(f64, String)>, VectorStoreError> {
-        let embedding = self.model.embed_text(req.query()).await?;
+        let embedding = self.model.embed_query(req.query()).await?;
         let url = format!(
             "{base_url}/v2/vectordb/entities/search",
             base_url = self.base_url
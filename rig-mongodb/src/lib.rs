@@ -1,11 +1,19 @@
-use futures::StreamExt;
-use mongodb::bson::{self, doc};
+pub mod partitioned;
+
+use std::{marker::PhantomData, time::Duration};
+
+use futures::{StreamExt, TryStreamExt};
+use mongodb::{
+    SearchIndexModel, SearchIndexType,
+    bson::{self, Bson, binary::Vector, doc},
+};
 
 use rig::{
     Embed, OneOrMany,
-    embeddings::embedding::{Embedding, EmbeddingModel},
+    embeddings::embedding::{Embedding, EmbeddingModel, Quantization, QuantizedVector},
     vector_store::{
-        InsertDocuments, VectorStoreError, VectorStoreIndex, request::VectorSearchRequest,
+        DEFAULT_MAX_CONCURRENCY, InsertDocuments, VectorStoreError, VectorStoreIndex,
+        deserialize_document, request::VectorSearchRequest,
     },
 };
 use serde::{Deserialize, Serialize};
@@ -46,14 +54,390 @@ struct LatestDefinition {
     fields: Vec<Field>,
 }
 
+/// A field of a vector search index definition: either a `vector` field (with its number of
+/// dimensions and similarity) or a `filter` field.
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct Field {
     #[serde(rename = "type")]
     field_type: String,
     path: String,
-    num_dimensions: i32,
-    similarity: String,
+    num_dimensions: Option<i32>,
+    similarity: Option<String>,
+}
+
+/// Similarity function of a vector search index, used to compare the vectors of the index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VectorSimilarity {
+    Euclidean,
+    #[default]
+    Cosine,
+    DotProduct,
+}
+
+impl VectorSimilarity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            VectorSimilarity::Euclidean => "euclidean",
+            VectorSimilarity::Cosine => "cosine",
+            VectorSimilarity::DotProduct => "dotProduct",
+        }
+    }
+
+    fn parse(similarity: &str) -> Option<Self> {
+        match similarity {
+            "euclidean" => Some(VectorSimilarity::Euclidean),
+            "cosine" => Some(VectorSimilarity::Cosine),
+            "dotProduct" => Some(VectorSimilarity::DotProduct),
+            _ => None,
+        }
+    }
+
+    /// The score of `vector` for the query vector `query`, normalized like the scores of
+    /// `$vectorSearch` (from 0 to 1, higher is closer).
+    pub fn score(&self, query: &[f64], vector: &[f64]) -> f64 {
+        let dot = query.iter().zip(vector).map(|(a, b)| a * b).sum::<f64>();
+        match self {
+            VectorSimilarity::Euclidean => {
+                let distance = query
+                    .iter()
+                    .zip(vector)
+                    .map(|(a, b)| (a - b).powi(2))
+                    .sum::<f64>()
+                    .sqrt();
+                1.0 / (1.0 + distance)
+            }
+            VectorSimilarity::Cosine => {
+                let norm = |v: &[f64]| v.iter().map(|x| x * x).sum::<f64>().sqrt();
+                let cosine = dot / (norm(query) * norm(vector));
+                (1.0 + if cosine.is_nan() { 0.0 } else { cosine }) / 2.0
+            }
+            VectorSimilarity::DotProduct => (1.0 + dot) / 2.0,
+        }
+    }
+}
+
+/// Typed definition of a MongoDB vector search index, to create the index without writing its
+/// `definition` document by hand.
+///
+/// By default, the index has a single `vector` field at path `embedding` (where
+/// [InsertDocuments] stores the embeddings) compared with [VectorSimilarity::Cosine].
+///
+/// # Example
+/// ```rust
+/// use rig_mongodb::{VectorIndexDefinition, VectorSimilarity};
+///
+/// let definition = VectorIndexDefinition::new(1536)
+///     .path("embedding")
+///     .similarity(VectorSimilarity::DotProduct)
+///     // Allow pre-filtering the search on the category of the documents
+///     .filter_field("document.category");
+///
+/// assert_eq!(
+///     definition.to_document(),
+///     mongodb::bson::doc! {
+///         "fields": [
+///             {
+///                 "type": "vector",
+///                 "path": "embedding",
+///                 "numDimensions": 1536,
+///                 "similarity": "dotProduct",
+///             },
+///             { "type": "filter", "path": "document.category" },
+///         ]
+///     }
+/// );
+///
+/// // Create the index with `collection.create_search_index(model).await?`
+/// let model = definition.search_index_model("vector_index");
+///
+/// // Or create it if needed and wait until it is ready with
+/// // `definition.ensure_index(&collection, "vector_index", &IndexCreationOptions::new()).await?`
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorIndexDefinition {
+    dimensions: usize,
+    path: String,
+    similarity: VectorSimilarity,
+    filter_fields: Vec<String>,
+    multi_vector: bool,
+}
+
+impl VectorIndexDefinition {
+    /// Create the definition of an index of vectors with `dimensions` dimensions (which must
+    /// match the number of dimensions of the embedding model).
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions,
+            path: "embedding".to_string(),
+            similarity: VectorSimilarity::default(),
+            filter_fields: vec![],
+            multi_vector: false,
+        }
+    }
+
+    /// Set the number of dimensions of the indexed vectors.
+    pub fn dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = dimensions;
+        self
+    }
+
+    /// Set the path of the field containing the vectors (defaults to `embedding`). For
+    /// multi-vector documents, this is the path of the vector within each chunk (see
+    /// [VectorIndexDefinition::multi_vector]).
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
+
+    /// Index the vectors of multi-vector documents (see [MongoDbVectorIndex::multi_vector]):
+    /// the `vector` field is then at the path of the vector within the chunks of the documents,
+    /// e.g.: `chunks.embedding` by default.
+    pub fn multi_vector(mut self, multi_vector: bool) -> Self {
+        self.multi_vector = multi_vector;
+        self
+    }
+
+    /// Set the similarity function of the index (defaults to [VectorSimilarity::Cosine]).
+    pub fn similarity(mut self, similarity: VectorSimilarity) -> Self {
+        self.similarity = similarity;
+        self
+    }
+
+    /// Declare a `filter` field, which can then be used in the pre-filter of vector searches
+    /// (see [SearchParams::filter]).
+    ///
+    /// Note: documents inserted with [InsertDocuments] are stored under the `document` field,
+    /// so their fields are at paths like `document.category`.
+    pub fn filter_field(mut self, path: &str) -> Self {
+        self.filter_fields.push(path.to_string());
+        self
+    }
+
+    /// The `definition` document of the index.
+    pub fn to_document(&self) -> bson::Document {
+        let path = if self.multi_vector {
+            format!("{CHUNKS_FIELD}.{}", self.path)
+        } else {
+            self.path.clone()
+        };
+        let vector_field = doc! {
+            "type": "vector",
+            "path": path,
+            "numDimensions": self.dimensions as i32,
+            "similarity": self.similarity.as_str(),
+        };
+        let filter_fields = self
+            .filter_fields
+            .iter()
+            .map(|path| doc! { "type": "filter", "path": path });
+
+        doc! {
+            "fields": std::iter::once(vector_field).chain(filter_fields).collect::<Vec<_>>(),
+        }
+    }
+
+    /// The model to create the vector search index named `index_name` with
+    /// [Collection::create_search_index](mongodb::Collection::create_search_index).
+    pub fn search_index_model(&self, index_name: &str) -> SearchIndexModel {
+        SearchIndexModel::builder()
+            .name(Some(index_name.to_string()))
+            .index_type(Some(SearchIndexType::VectorSearch))
+            .definition(self.to_document())
+            .build()
+    }
+
+    /// Create the vector search index named `index_name` on `collection` if it does not exist,
+    /// then wait until it is ready to be queried.
+    ///
+    /// Creating the index is retried while the server is not ready (e.g.: a local Atlas
+    /// deployment which is still starting), with the attempts, delay and overall timeout of
+    /// `options`. A rejected definition is not retried (see [IndexCreationError]).
+    pub async fn ensure_index<C: Send + Sync>(
+        &self,
+        collection: &mongodb::Collection<C>,
+        index_name: &str,
+        options: &IndexCreationOptions,
+    ) -> Result<(), IndexCreationError> {
+        let mut status = None;
+        let ensure_index = async {
+            let mut attempts = 0;
+            loop {
+                attempts += 1;
+                let created = match search_index_status(collection, index_name).await {
+                    // The index already exists
+                    Ok(Some(_)) => Ok(()),
+                    Ok(None) => collection
+                        .create_search_index(self.search_index_model(index_name))
+                        .await
+                        .map(|_| ()),
+                    Err(e) => Err(e),
+                };
+                match created {
+                    Ok(()) => break,
+                    Err(e) => match command_error_code(&e) {
+                        Some(INDEX_ALREADY_EXISTS) => break,
+                        Some(code) if DEFINITION_ERROR_CODES.contains(&code) => {
+                            return Err(IndexCreationError::Rejected(e.to_string()));
+                        }
+                        _ if attempts >= options.max_attempts => {
+                            return Err(IndexCreationError::NotReady {
+                                attempts,
+                                source: e,
+                            });
+                        }
+                        _ => {
+                            tracing::debug!(
+                                target: "rig",
+                                "Failed to create search index {index_name} (attempt {attempts}/{}): {e}",
+                                options.max_attempts
+                            );
+                            tokio::time::sleep(options.delay).await;
+                        }
+                    },
+                }
+            }
+
+            loop {
+                match search_index_status(collection, index_name).await {
+                    Ok(Some(current)) => status = Some(current),
+                    Ok(None) => {}
+                    Err(e) => {
+                        tracing::debug!(target: "rig", "Failed to get search index {index_name}: {e}")
+                    }
+                }
+                match status.as_deref() {
+                    Some("READY") => return Ok(()),
+                    Some("FAILED") => {
+                        return Err(IndexCreationError::Rejected(format!(
+                            "The build of search index {index_name} failed"
+                        )));
+                    }
+                    _ => tokio::time::sleep(options.delay).await,
+                }
+            }
+        };
+
+        match tokio::time::timeout(options.timeout, ensure_index).await {
+            Ok(result) => result,
+            Err(_) => Err(IndexCreationError::Timeout {
+                timeout: options.timeout,
+                status,
+            }),
+        }
+    }
+}
+
+/// Error code of a search index which already exists.
+const INDEX_ALREADY_EXISTS: i32 = 68;
+
+/// Error codes of invalid index definitions (`BadValue`, `FailedToParse`, `TypeMismatch`,
+/// `CannotCreateIndex`, `InvalidOptions`, `IndexOptionsConflict`, `IndexKeySpecsConflict`).
+const DEFINITION_ERROR_CODES: [i32; 7] = [2, 9, 14, 67, 72, 85, 86];
+
+/// The code of `e` if it is an error returned by the server for a command.
+fn command_error_code(e: &mongodb::error::Error) -> Option<i32> {
+    match e.kind.as_ref() {
+        mongodb::error::ErrorKind::Command(e) => Some(e.code),
+        _ => None,
+    }
+}
+
+/// The status of the search index named `index_name` (e.g.: `BUILDING` or `READY`), or `None`
+/// if it does not exist.
+async fn search_index_status<C: Send + Sync>(
+    collection: &mongodb::Collection<C>,
+    index_name: &str,
+) -> Result<Option<String>, mongodb::error::Error> {
+    let index = collection
+        .list_search_indexes()
+        .name(index_name)
+        .await?
+        .next()
+        .await
+        .transpose()?;
+
+    Ok(index.map(|index| index.get_str("status").unwrap_or_default().to_string()))
+}
+
+/// Options of [VectorIndexDefinition::ensure_index]: how many times and how often creating the
+/// index is attempted, and how long to wait overall for the index to be ready.
+///
+/// The defaults (5 attempts, 2 seconds apart, within 2 minutes) suit a local deployment; a
+/// CI environment starting Atlas in a container may need more attempts.
+#[derive(Debug, Clone)]
+pub struct IndexCreationOptions {
+    max_attempts: usize,
+    delay: Duration,
+    timeout: Duration,
+}
+
+impl Default for IndexCreationOptions {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            delay: Duration::from_secs(2),
+            timeout: Duration::from_secs(120),
+        }
+    }
+}
+
+impl IndexCreationOptions {
+    /// Initializes a new `IndexCreationOptions` with default values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of attempts to create the index while the server is not ready.
+    pub fn max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Sets the delay between the attempts to create the index, and between the checks of
+    /// whether it is ready.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Sets the overall time to create the index and wait until it is ready.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// Error returned by [VectorIndexDefinition::ensure_index].
+#[derive(Debug, thiserror::Error)]
+pub enum IndexCreationError {
+    /// The server could not create the index after all the attempts, e.g.: it is not reachable
+    /// or still starting. This is usually transient.
+    #[error(
+        "The server was not ready to create the search index after {attempts} attempts: {source}"
+    )]
+    NotReady {
+        attempts: usize,
+        #[source]
+        source: mongodb::error::Error,
+    },
+    /// The definition of the index was rejected by the server, or the index failed to build.
+    /// Retrying will not help.
+    #[error("The search index definition was rejected: {0}")]
+    Rejected(String),
+    /// The index was not ready before the timeout, with its last known status (`None` if it was
+    /// not created yet).
+    #[error("The search index was not ready after {timeout:?} (status: {status:?})")]
+    Timeout {
+        timeout: Duration,
+        status: Option<String>,
+    },
+}
+
+impl From<IndexCreationError> for VectorStoreError {
+    fn from(e: IndexCreationError) -> Self {
+        VectorStoreError::DatastoreError(Box::new(e))
+    }
 }
 
 fn mongodb_to_rig_error(e: mongodb::error::Error) -> VectorStoreError {
@@ -97,28 +481,34 @@ fn mongodb_to_rig_error(e: mongodb::error::Error) -> VectorStoreError {
 /// # }).unwrap()
 /// ```
 pub struct MongoDbVectorIndex<M: EmbeddingModel, C: Send + Sync> {
-    collection: mongodb::Collection<C>,
+    collection: mongodb::Collection<bson::Document>,
     model: M,
     index_name: String,
     embedded_field: String,
+    num_dimensions: usize,
+    similarity: VectorSimilarity,
     search_params: SearchParams,
+    quantization: Option<Quantization>,
+    multi_vector: bool,
+    _c: PhantomData<C>,
 }
 
 impl<M: EmbeddingModel, C: Send + Sync> MongoDbVectorIndex<M, C> {
     /// Vector search stage of aggregation pipeline of mongoDB collection.
     /// To be used by implementations of top_n and top_n_ids methods on VectorStoreIndex trait for MongoDbVectorIndex.
-    fn pipeline_search_stage(&self, prompt_embedding: &Embedding, n: usize) -> bson::Document {
+    fn pipeline_search_stage(&self, query_vector: &[f64], n: usize) -> bson::Document {
         let SearchParams {
             filter,
             exact,
             num_candidates,
+            ..
         } = &self.search_params;
 
         doc! {
           "$vectorSearch": {
             "index": &self.index_name,
             "path": self.embedded_field.clone(),
-            "queryVector": &prompt_embedding.vec,
+            "queryVector": self.vector_to_bson(query_vector),
             "numCandidates": num_candidates.unwrap_or((n * 10) as u32),
             "limit": n as u32,
             "filter": filter,
@@ -127,6 +517,18 @@ impl<M: EmbeddingModel, C: Send + Sync> MongoDbVectorIndex<M, C> {
         }
     }
 
+    /// Convert an embedding vector to BSON, as a quantized binary vector if the index is quantized.
+    fn vector_to_bson(&self, vec: &[f64]) -> Bson {
+        match self
+            .quantization
+            .map(|quantization| quantization.quantize(vec))
+        {
+            Some(QuantizedVector::Float32(vec)) => Vector::Float32(vec).into(),
+            Some(QuantizedVector::Int8(vec)) => Vector::Int8(vec).into(),
+            None => vec.into(),
+        }
+    }
+
     /// Score declaration stage of aggregation pipeline of mongoDB collection.
     /// /// To be used by implementations of top_n and top_n_ids methods on VectorStoreIndex trait for MongoDbVectorIndex.
     fn pipeline_score_stage(&self) -> bson::Document {
@@ -136,12 +538,156 @@ impl<M: EmbeddingModel, C: Send + Sync> MongoDbVectorIndex<M, C> {
           }
         }
     }
+
+    /// Run the vector search aggregation pipeline with the given query vector and deserialize the results.
+    /// To be used by implementations of top_n and top_n_by_vector methods on VectorStoreIndex trait for MongoDbVectorIndex.
+    async fn search_documents<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        query_vector: &[f64],
+        n: usize,
+    ) -> Result<Vec<SearchResult<T>>, VectorStoreError> {
+        let mut cursor = self
+            .collection
+            .aggregate(self.vector_search_pipeline(query_vector, n))
+            .await
+            .map_err(mongodb_to_rig_error)?;
+
+        let mut results = Vec::new();
+        while let Some(doc) = cursor.next().await {
+            let mut doc = doc.map_err(mongodb_to_rig_error)?;
+            // The vectors are removed from the document so that they are never deserialized into `T`
+            let (chunk, vector) = if self.multi_vector {
+                let chunks = remove_path(&mut doc, CHUNKS_FIELD);
+                match chunks.and_then(|chunks| self.best_chunk(query_vector, chunks)) {
+                    Some((chunk, vector)) => (Some(chunk), Some(vector)),
+                    None => (None, None),
+                }
+            } else {
+                let stored = remove_path(&mut doc, &self.embedded_field);
+                (None, stored.and_then(bson_to_vector))
+            };
+            let vector = vector.filter(|_| self.search_params.include_vectors);
+            let doc = bson::from_document::<serde_json::Value>(doc)
+                .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+            let score = doc.get("score").expect("score").as_f64().expect("f64");
+            let id = doc.get("_id").expect("_id").to_string();
+            let document: T = deserialize_document(&id, doc)?;
+            results.push(SearchResult {
+                score,
+                id,
+                document,
+                vector,
+                chunk,
+            });
+        }
+
+        tracing::info!(target: "rig",
+            "Selected documents: {}",
+            results.iter()
+                .map(|result| format!("{} ({})", result.id, result.score))
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+
+        Ok(results)
+    }
 }
 
 impl<M: EmbeddingModel, C: Send + Sync> MongoDbVectorIndex<M, C> {
+    /// The underlying MongoDB collection, for operations not wrapped by rig
+    /// (e.g.: aggregations, transactions, index management).
+    pub fn collection(&self) -> &mongodb::Collection<bson::Document> {
+        &self.collection
+    }
+
+    /// Build (without executing) the aggregation pipeline used to search the `n` documents
+    /// closest to `query_vector`: the `$vectorSearch` stage, followed by the stages adding
+    /// the `score` field and removing the embedded field (unless [SearchParams::include_vectors]
+    /// is set or the documents are multi-vector, see [MongoDbVectorIndex::multi_vector]).
+    ///
+    /// The pipeline can be extended with additional stages and run with [Self::collection].
+    /// To search with a text query, first embed it with the embedding model of the index.
+    pub fn vector_search_pipeline(&self, query_vector: &[f64], n: usize) -> Vec<bson::Document> {
+        let mut pipeline = vec![
+            self.pipeline_search_stage(query_vector, n),
+            self.pipeline_score_stage(),
+        ];
+
+        if !self.search_params.include_vectors && !self.multi_vector {
+            pipeline.push(doc! {
+                "$project": {
+                    self.embedded_field.clone(): 0,
+                },
+            });
+        }
+
+        pipeline
+    }
+
+    /// Same as [VectorStoreIndex::top_n], returning the results as [SearchResult]s, which also
+    /// include the stored vector of each document if [SearchParams::include_vectors] is set
+    /// (e.g.: to inspect or recompute the similarities client-side when tuning an index).
+    pub async fn top_n_with_vectors<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        req: VectorSearchRequest,
+    ) -> Result<Vec<SearchResult<T>>, VectorStoreError> {
+        let prompt_embedding = self.model.embed_query(req.query()).await?;
+
+        self.search_documents(&prompt_embedding.vec, req.samples() as usize)
+            .await
+    }
+
+    /// Same as [Self::top_n_with_vectors], also returning diagnostics of how MongoDB ran the
+    /// `$vectorSearch` stage (see [ExplainInfo]), e.g.: to find out why a query is slow or misses
+    /// expected results.
+    ///
+    /// The diagnostics come from the `explain` command (with the `executionStats` verbosity),
+    /// which runs the search a second time: avoid calling this method on every query.
+    pub async fn top_n_explained<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        req: VectorSearchRequest,
+    ) -> Result<(Vec<SearchResult<T>>, ExplainInfo), VectorStoreError> {
+        let prompt_embedding = self.model.embed_query(req.query()).await?;
+        let n = req.samples() as usize;
+
+        futures::try_join!(
+            self.search_documents(&prompt_embedding.vec, n),
+            self.explain(&prompt_embedding.vec, n),
+        )
+    }
+
+    /// Run the `explain` command on the vector search pipeline.
+    async fn explain(
+        &self,
+        query_vector: &[f64],
+        n: usize,
+    ) -> Result<ExplainInfo, VectorStoreError> {
+        let namespace = self.collection.namespace();
+        let explain = self
+            .collection
+            .client()
+            .database(&namespace.db)
+            .run_command(doc! {
+                "explain": {
+                    "aggregate": namespace.coll,
+                    "pipeline": self.vector_search_pipeline(query_vector, n),
+                    "cursor": {},
+                },
+                "verbosity": "executionStats",
+            })
+            .await
+            .map_err(mongodb_to_rig_error)?;
+
+        Ok(ExplainInfo::from_explain(
+            explain,
+            self.search_params.exact.unwrap_or(false),
+        ))
+    }
+
     /// Create a new `MongoDbVectorIndex`.
     ///
-    /// The index (of type "vector") must already exist for the MongoDB collection.
+    /// The index (of type "vector") must already exist for the MongoDB collection
+    /// (see [VectorIndexDefinition] to create it).
     /// See the MongoDB [documentation](https://www.mongodb.com/docs/atlas/atlas-vector-search/vector-search-type/) for more information on creating indexes.
     pub async fn new(
         collection: mongodb::Collection<C>,
@@ -161,30 +707,231 @@ impl<M: EmbeddingModel, C: Send + Sync> MongoDbVectorIndex<M, C> {
             .latest_definition
             .fields
             .into_iter()
-            .map(|field| field.path)
-            .next()
+            .find(|field| field.field_type == "vector")
             // This error shouldn't occur if the index is queryable
             .ok_or(VectorStoreError::DatastoreError(
                 "No embedded fields found".into(),
             ))?;
 
         Ok(Self {
-            collection,
+            collection: collection.clone_with_type(),
             model,
             index_name: index_name.to_string(),
-            embedded_field,
+            embedded_field: embedded_field.path,
+            num_dimensions: embedded_field.num_dimensions.unwrap_or_default() as usize,
+            similarity: embedded_field
+                .similarity
+                .as_deref()
+                .and_then(VectorSimilarity::parse)
+                .unwrap_or_default(),
             search_params,
+            quantization: None,
+            multi_vector: false,
+            _c: PhantomData,
         })
     }
+
+    /// Store (with [InsertDocuments]) and query embeddings as quantized BSON binary vectors
+    /// (`float32` or `int8`) instead of arrays of doubles, reducing their storage size 2x or 8x.
+    ///
+    /// The embedded field of the vector search index must then be indexed as a `vector` of the
+    /// same number of dimensions (no `quantization` option: the vectors are already quantized).
+    /// Use the same quantization when generating the embeddings (see
+    /// [EmbeddingsBuilder::quantize](rig::embeddings::EmbeddingsBuilder::quantize)).
+    pub fn quantization(mut self, quantization: Quantization) -> Self {
+        self.quantization = Some(quantization);
+        self
+    }
+
+    /// Store (with [InsertDocuments]) and query documents with one vector per chunk of the
+    /// document, instead of a single vector. Documents with multiple embeddings are then stored
+    /// once, with an array of chunks (each with its `embedding` and `embedded_text`) in the
+    /// `chunks` field, rather than once per embedding.
+    ///
+    /// The vectors of the chunks must be indexed (see [VectorIndexDefinition::multi_vector]),
+    /// so that a document matches the query with its closest chunk. Results of
+    /// [MongoDbVectorIndex::top_n_with_vectors] indicate which chunk matched (see
+    /// [SearchResult::chunk]), which is computed client-side with the similarity function of
+    /// the index: the vectors are retrieved with the documents.
+    pub fn multi_vector(mut self, multi_vector: bool) -> Self {
+        self.multi_vector = multi_vector;
+        self
+    }
+
+    /// The index and the vector of the chunk of `chunks` (the array of chunks of a multi-vector
+    /// document) closest to `query_vector`.
+    fn best_chunk(&self, query_vector: &[f64], chunks: Bson) -> Option<(usize, Vec<f64>)> {
+        let Bson::Array(chunks) = chunks else {
+            return None;
+        };
+        // The path of the vector within each chunk, e.g.: `embedding` for `chunks.embedding`
+        let vector_path = self
+            .embedded_field
+            .strip_prefix(&format!("{CHUNKS_FIELD}."))
+            .unwrap_or(&self.embedded_field);
+
+        chunks
+            .into_iter()
+            .enumerate()
+            .filter_map(|(chunk, doc)| match doc {
+                Bson::Document(mut doc) => {
+                    Some((chunk, bson_to_vector(remove_path(&mut doc, vector_path)?)?))
+                }
+                _ => None,
+            })
+            .max_by(|(_, a), (_, b)| {
+                self.similarity
+                    .score(query_vector, a)
+                    .total_cmp(&self.similarity.score(query_vector, b))
+            })
+    }
+}
+
+/// A result of [MongoDbVectorIndex::top_n_with_vectors].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult<T> {
+    pub score: f64,
+    pub id: String,
+    pub document: T,
+    /// The stored vector of the document, if [SearchParams::include_vectors] is set.
+    ///
+    /// Quantized vectors (see [MongoDbVectorIndex::quantization]) are returned as stored,
+    /// i.e.: `int8` vectors are not rescaled. For multi-vector documents, this is the vector of
+    /// the matching chunk.
+    pub vector: Option<Vec<f64>>,
+    /// The index of the chunk whose vector matched the query, for multi-vector documents (see
+    /// [MongoDbVectorIndex::multi_vector]).
+    pub chunk: Option<usize>,
+}
+
+/// Diagnostics of a vector search, returned by [MongoDbVectorIndex::top_n_explained].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExplainInfo {
+    /// Whether the search was exact (ENN), i.e.: compared the query with every document
+    /// matching the filter, rather than approximate (ANN).
+    pub exact: bool,
+    /// The number of nearest neighbor candidates considered by an approximate search
+    /// (`numCandidates`), if reported by MongoDB.
+    pub num_candidates: Option<u64>,
+    /// The query plan of the search, as reported by MongoDB: the types of the Lucene queries
+    /// run for the `$vectorSearch` stage (e.g.: `ExactVectorSearchQuery`, `KnnFloatVectorQuery`).
+    pub query_types: Vec<String>,
+    /// The full output of the `explain` command, for the statistics not parsed into this
+    /// struct (e.g.: timings of each stage).
+    pub raw: bson::Document,
+}
+
+impl ExplainInfo {
+    /// Parse the output of the `explain` command, using `exact` (the requested search type)
+    /// if the query plan of the `$vectorSearch` stage is missing from it.
+    fn from_explain(raw: bson::Document, exact: bool) -> Self {
+        let mut queries = Vec::new();
+        if let Some(explain) = vector_search_explain(&raw) {
+            collect_queries(explain, &mut queries);
+        }
+
+        Self {
+            exact: if queries.is_empty() {
+                exact
+            } else {
+                queries
+                    .iter()
+                    .any(|(query_type, _)| query_type.contains("Exact"))
+            },
+            num_candidates: queries
+                .iter()
+                .find_map(|(query_type, k)| k.filter(|_| query_type.contains("Knn"))),
+            query_types: queries
+                .into_iter()
+                .map(|(query_type, _)| query_type)
+                .collect(),
+            raw,
+        }
+    }
+}
+
+/// The `explain` document of the `$vectorSearch` stage in the output of the `explain` command.
+fn vector_search_explain(explain: &bson::Document) -> Option<&bson::Document> {
+    explain
+        .get_array("stages")
+        .ok()?
+        .iter()
+        .filter_map(Bson::as_document)
+        .find_map(|stage| stage.get_document("$vectorSearch").ok())?
+        .get_document("explain")
+        .ok()
+}
+
+/// Collect the `type` of every query of a `$vectorSearch` query plan, with its `k` argument
+/// (the number of candidates of approximate queries).
+fn collect_queries(value: &bson::Document, queries: &mut Vec<(String, Option<u64>)>) {
+    if let Ok(query_type) = value.get_str("type") {
+        let k = value
+            .get_document("args")
+            .ok()
+            .and_then(|args| args.get("k"))
+            .and_then(|k| match k {
+                Bson::Int32(k) => u64::try_from(*k).ok(),
+                Bson::Int64(k) => u64::try_from(*k).ok(),
+                Bson::Double(k) => Some(*k as u64),
+                _ => None,
+            });
+        queries.push((query_type.to_string(), k));
+    }
+
+    for (_, value) in value {
+        match value {
+            Bson::Document(doc) => collect_queries(doc, queries),
+            Bson::Array(values) => values
+                .iter()
+                .filter_map(Bson::as_document)
+                .for_each(|doc| collect_queries(doc, queries)),
+            _ => {}
+        }
+    }
+}
+
+/// The field holding the chunks of multi-vector documents (see [MongoDbVectorIndex::multi_vector]).
+const CHUNKS_FIELD: &str = "chunks";
+
+/// Remove the field at the (dotted) `path` of `doc`, returning its value.
+fn remove_path(doc: &mut bson::Document, path: &str) -> Option<Bson> {
+    match path.split_once('.') {
+        Some((key, rest)) => remove_path(doc.get_document_mut(key).ok()?, rest),
+        None => doc.remove(path),
+    }
+}
+
+/// Convert a stored vector (an array of numbers or a BSON binary vector) to a vector of `f64`.
+fn bson_to_vector(vector: Bson) -> Option<Vec<f64>> {
+    match vector {
+        Bson::Array(values) => values
+            .into_iter()
+            .map(|value| match value {
+                Bson::Double(x) => Some(x),
+                Bson::Int32(x) => Some(x as f64),
+                Bson::Int64(x) => Some(x as f64),
+                _ => None,
+            })
+            .collect(),
+        Bson::Binary(binary) => match Vector::try_from(binary).ok()? {
+            Vector::Float32(vec) => Some(vec.into_iter().map(f64::from).collect()),
+            Vector::Int8(vec) => Some(vec.into_iter().map(f64::from).collect()),
+            Vector::PackedBit(_) => None,
+        },
+        _ => None,
+    }
 }
 
 /// See [MongoDB Vector Search](`https://www.mongodb.com/docs/atlas/atlas-vector-search/vector-search-stage/`) for more information
 /// on each of the fields
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct SearchParams {
     filter: mongodb::bson::Document,
     exact: Option<bool>,
     num_candidates: Option<u32>,
+    include_vectors: bool,
+    max_concurrency: Option<usize>,
 }
 
 impl SearchParams {
@@ -194,6 +941,8 @@ impl SearchParams {
             filter: doc! {},
             exact: None,
             num_candidates: None,
+            include_vectors: false,
+            max_concurrency: None,
         }
     }
 
@@ -221,6 +970,27 @@ impl SearchParams {
         self.num_candidates = Some(num_candidates);
         self
     }
+
+    /// Sets whether the stored vector of each result is returned by
+    /// [MongoDbVectorIndex::top_n_with_vectors] (as [SearchResult::vector]).
+    /// By default, vectors are not returned, as they can be large (e.g.: 1536 floats each).
+    pub fn include_vectors(mut self, include_vectors: bool) -> Self {
+        self.include_vectors = include_vectors;
+        self
+    }
+
+    /// Sets the maximum number of vector search aggregations run concurrently by
+    /// [VectorStoreIndex::top_n_batch], and of collections queried concurrently by
+    /// [TimePartitionedIndex::range](partitioned::TimePartitionedIndex::range) indexes.
+    /// Defaults to [DEFAULT_MAX_CONCURRENCY].
+    pub fn max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = Some(max_concurrency.max(1));
+        self
+    }
+
+    fn max_concurrency_or_default(&self) -> usize {
+        self.max_concurrency.unwrap_or(DEFAULT_MAX_CONCURRENCY)
+    }
 }
 
 impl<M: EmbeddingModel + Sync + Send, C: Sync + Send> VectorStoreIndex
@@ -233,43 +1003,44 @@ impl<M: EmbeddingModel + Sync + Send, C: Sync + Send> VectorStoreIndex
         &self,
         req: VectorSearchRequest,
     ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
-        let prompt_embedding = self.model.embed_text(req.query()).await?;
-
-        let mut cursor = self
-            .collection
-            .aggregate([
-                self.pipeline_search_stage(&prompt_embedding, req.samples() as usize),
-                self.pipeline_score_stage(),
-                {
-                    doc! {
-                        "$project": {
-                            self.embedded_field.clone(): 0,
-                        },
-                    }
-                },
-            ])
-            .await
-            .map_err(mongodb_to_rig_error)?
-            .with_type::<serde_json::Value>();
+        Ok(self
+            .top_n_with_vectors(req)
+            .await?
+            .into_iter()
+            .map(|result| (result.score, result.id, result.document))
+            .collect())
+    }
 
-        let mut results = Vec::new();
-        while let Some(doc) = cursor.next().await {
-            let doc = doc.map_err(mongodb_to_rig_error)?;
-            let score = doc.get("score").expect("score").as_f64().expect("f64");
-            let id = doc.get("_id").expect("_id").to_string();
-            let doc_t: T = serde_json::from_value(doc).map_err(VectorStoreError::JsonError)?;
-            results.push((score, id, doc_t));
-        }
+    /// Implement the `top_n_batch` method of the `VectorStoreIndex` trait for `MongoDbVectorIndex`.
+    ///
+    /// The queries are embedded at once, then the vector search aggregations run concurrently
+    /// (see [SearchParams::max_concurrency]).
+    async fn top_n_batch<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        requests: Vec<VectorSearchRequest>,
+    ) -> Result<Vec<Vec<(f64, String, T)>>, VectorStoreError> {
+        let prompt_embeddings = self
+            .model
+            .embed_queries(requests.iter().map(|req| req.query().to_string()).collect())
+            .await?;
 
-        tracing::info!(target: "rig",
-            "Selected documents: {}",
-            results.iter()
-                .map(|(distance, id, _)| format!("{id} ({distance})"))
-                .collect::<Vec<String>>()
-                .join(", ")
-        );
+        let searches = requests
+            .iter()
+            .zip(prompt_embeddings)
+            .map(|(req, prompt_embedding)| async move {
+                Ok(self
+                    .search_documents(&prompt_embedding.vec, req.samples() as usize)
+                    .await?
+                    .into_iter()
+                    .map(|result| (result.score, result.id, result.document))
+                    .collect())
+            })
+            .collect::<Vec<_>>();
 
-        Ok(results)
+        futures::stream::iter(searches)
+            .buffered(self.search_params.max_concurrency_or_default())
+            .try_collect()
+            .await
     }
 
     /// Implement the `top_n_ids` method of the `VectorStoreIndex` trait for `MongoDbVectorIndex`.
@@ -277,12 +1048,12 @@ impl<M: EmbeddingModel + Sync + Send, C: Sync + Send> VectorStoreIndex
         &self,
         req: VectorSearchRequest,
     ) -> Result<Vec<(f64, String)>, VectorStoreError> {
-        let prompt_embedding = self.model.embed_text(req.query()).await?;
+        let prompt_embedding = self.model.embed_query(req.query()).await?;
 
         let mut cursor = self
             .collection
             .aggregate([
-                self.pipeline_search_stage(&prompt_embedding, req.samples() as usize),
+                self.pipeline_search_stage(&prompt_embedding.vec, req.samples() as usize),
                 self.pipeline_score_stage(),
                 doc! {
                     "$project": {
@@ -313,6 +1084,29 @@ impl<M: EmbeddingModel + Sync + Send, C: Sync + Send> VectorStoreIndex
 
         Ok(results)
     }
+
+    /// Implement the `top_n_by_vector` method of the `VectorStoreIndex` trait for `MongoDbVectorIndex`.
+    ///
+    /// The length of `vector` is checked against the number of dimensions of the vector search index.
+    async fn top_n_by_vector<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        vector: &[f64],
+        n: usize,
+    ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
+        if vector.len() != self.num_dimensions {
+            return Err(VectorStoreError::DimensionMismatch {
+                expected: self.num_dimensions,
+                got: vector.len(),
+            });
+        }
+
+        Ok(self
+            .search_documents(vector, n)
+            .await?
+            .into_iter()
+            .map(|result| (result.score, result.id, result.document))
+            .collect())
+    }
 }
 
 impl<M: EmbeddingModel + Send + Sync, C: Send + Sync> InsertDocuments for MongoDbVectorIndex<M, C> {
@@ -325,10 +1119,20 @@ impl<M: EmbeddingModel + Send + Sync, C: Send + Sync> InsertDocuments for MongoD
             .map(|(document, embeddings)| -> Result<Vec<mongodb::bson::Document>, VectorStoreError> {
                 let json_doc = serde_json::to_value(&document)?;
 
+                if self.multi_vector {
+                    return Ok(vec![doc! {
+                        "document": mongodb::bson::to_bson(&json_doc).map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?,
+                        CHUNKS_FIELD: embeddings.into_iter().map(|embedding| doc! {
+                            "embedding": self.vector_to_bson(&embedding.vec),
+                            "embedded_text": embedding.document,
+                        }).collect::<Vec<_>>(),
+                    }]);
+                }
+
                 embeddings.into_iter().map(|embedding| -> Result<mongodb::bson::Document, VectorStoreError> {
                     Ok(doc! {
                         "document": mongodb::bson::to_bson(&json_doc).map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?,
-                        "embedding": embedding.vec,
+                        "embedding": self.vector_to_bson(&embedding.vec),
                         "embedded_text": embedding.document,
                     })
                 }).collect::<Result<Vec<_>, _>>()
@@ -338,9 +1142,7 @@ impl<M: EmbeddingModel + Send + Sync, C: Send + Sync> InsertDocuments for MongoD
             .flatten()
             .collect::<Vec<_>>();
 
-        let collection = self.collection.clone_with_type::<mongodb::bson::Document>();
-
-        collection
+        self.collection
             .insert_many(mongo_documents)
             .await
             .map_err(mongodb_to_rig_error)?;
@@ -348,3 +1150,116 @@ impl<M: EmbeddingModel + Send + Sync, C: Send + Sync> InsertDocuments for MongoD
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use mongodb::bson::{Bson, doc};
+    use rig::test_utils::MockEmbeddingModel;
+
+    use super::{
+        MongoDbVectorIndex, SearchParams, VectorIndexDefinition, VectorSimilarity, remove_path,
+    };
+
+    /// A multi-vector index over `chunks.embedding`. The client connects lazily, so no server
+    /// is needed as long as the collection is not queried.
+    async fn multi_vector_index(
+        similarity: VectorSimilarity,
+    ) -> MongoDbVectorIndex<MockEmbeddingModel, ()> {
+        let collection = mongodb::Client::with_uri_str("mongodb://localhost:27017")
+            .await
+            .unwrap()
+            .database("rig")
+            .collection("documents");
+
+        MongoDbVectorIndex {
+            collection,
+            model: MockEmbeddingModel::new(vec![0.0, 0.0]),
+            index_name: "vector_index".to_string(),
+            embedded_field: "chunks.embedding".to_string(),
+            num_dimensions: 2,
+            similarity,
+            search_params: SearchParams::new(),
+            quantization: None,
+            multi_vector: true,
+            _c: PhantomData,
+        }
+    }
+
+    #[test]
+    fn test_remove_path() {
+        let mut doc = doc! { "document": { "title": "Rig", "year": 2024 }, "id": "doc0" };
+
+        assert_eq!(
+            remove_path(&mut doc, "document.title"),
+            Some(Bson::String("Rig".to_string()))
+        );
+        assert_eq!(doc, doc! { "document": { "year": 2024 }, "id": "doc0" });
+
+        // Missing fields, and paths through fields which are not documents
+        assert_eq!(remove_path(&mut doc, "document.title"), None);
+        assert_eq!(remove_path(&mut doc, "id.title"), None);
+        assert_eq!(
+            remove_path(&mut doc, "id"),
+            Some(Bson::String("doc0".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_multi_vector_definition() {
+        let definition = VectorIndexDefinition::new(2).filter_field("document.category");
+        assert_eq!(
+            definition.to_document(),
+            doc! {
+                "fields": [
+                    { "type": "vector", "path": "embedding", "numDimensions": 2, "similarity": "cosine" },
+                    { "type": "filter", "path": "document.category" },
+                ],
+            }
+        );
+
+        // The vectors of a multi-vector document are in its array of chunks
+        let definition = definition.multi_vector(true);
+        assert_eq!(
+            definition.to_document(),
+            doc! {
+                "fields": [
+                    { "type": "vector", "path": "chunks.embedding", "numDimensions": 2, "similarity": "cosine" },
+                    { "type": "filter", "path": "document.category" },
+                ],
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_best_chunk() {
+        let chunks = Bson::Array(vec![
+            Bson::Document(doc! { "embedding": [1.0, 0.0], "embedded_text": "first" }),
+            Bson::Document(doc! { "embedding": [0, 1], "embedded_text": "second" }),
+            // Chunks without a vector are skipped
+            Bson::Document(doc! { "embedded_text": "third" }),
+            Bson::String("fourth".to_string()),
+        ]);
+
+        let index = multi_vector_index(VectorSimilarity::Cosine).await;
+        assert_eq!(
+            index.best_chunk(&[0.1, 0.9], chunks.clone()),
+            Some((1, vec![0.0, 1.0]))
+        );
+        assert_eq!(
+            index.best_chunk(&[0.9, 0.1], chunks.clone()),
+            Some((0, vec![1.0, 0.0]))
+        );
+
+        // The closest chunk depends on the similarity of the index
+        let index = multi_vector_index(VectorSimilarity::Euclidean).await;
+        assert_eq!(
+            index.best_chunk(&[0.1, 0.9], chunks),
+            Some((1, vec![0.0, 1.0]))
+        );
+
+        assert_eq!(index.best_chunk(&[0.1, 0.9], Bson::Array(vec![])), None);
+        assert_eq!(index.best_chunk(&[0.1, 0.9], Bson::Null), None);
+    }
+}
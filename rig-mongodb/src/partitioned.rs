@@ -0,0 +1,267 @@
+//! Vector search across time-partitioned collections, e.g.: events or logs stored in one
+//! collection per month.
+//!
+//! A [TimePartitionedIndex] selects the collections holding the documents of a time range
+//! according to a [PartitionScheme] and federates their vector indexes into a single
+//! [FederatedVectorIndex].
+//!
+//! # Example
+//! ```rust,ignore
+//! use rig_mongodb::partitioned::{MonthlyPartitions, TimePartitionedIndex};
+//!
+//! // Collections named `events_2024_01`, `events_2024_02`, ...
+//! let events = TimePartitionedIndex::new(
+//!     database,
+//!     MonthlyPartitions::new("events_"),
+//!     model,
+//!     "vector_index",
+//! );
+//!
+//! let index = events
+//!     .range(start, end)
+//!     .await?
+//!     .normalize_scores(true);
+//!
+//! let results = index.top_n::<Event>(req).await?;
+//! ```
+use mongodb::bson::{self, DateTime};
+use rig::{
+    embeddings::EmbeddingModel,
+    vector_store::{VectorStoreError, federated::FederatedVectorIndex},
+};
+
+use crate::{MongoDbVectorIndex, SearchParams, mongodb_to_rig_error};
+
+const MILLIS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+/// A collection-naming scheme of time-partitioned documents.
+///
+/// Closures taking the start and the end of a time range and returning collection names
+/// implement this trait, e.g.: for daily or yearly partitions.
+pub trait PartitionScheme: Send + Sync {
+    /// The names of the collections holding the documents from `start` to `end` (inclusive).
+    fn collections(&self, start: DateTime, end: DateTime) -> Vec<String>;
+}
+
+impl<F> PartitionScheme for F
+where
+    F: Fn(DateTime, DateTime) -> Vec<String> + Send + Sync,
+{
+    fn collections(&self, start: DateTime, end: DateTime) -> Vec<String> {
+        self(start, end)
+    }
+}
+
+/// One collection per (UTC) month, named `{prefix}{year}_{month}` by default
+/// (e.g.: `events_2024_03` with the `events_` prefix).
+pub struct MonthlyPartitions {
+    name: Box<dyn Fn(i32, u32) -> String + Send + Sync>,
+}
+
+impl MonthlyPartitions {
+    /// Monthly collections named `{prefix}{year}_{month}`, the month being zero-padded.
+    pub fn new(prefix: &str) -> Self {
+        let prefix = prefix.to_string();
+        Self::with_naming(move |year, month| format!("{prefix}{year}_{month:02}"))
+    }
+
+    /// Monthly collections named by `name`, called with the year and the month (from 1 to 12).
+    pub fn with_naming(name: impl Fn(i32, u32) -> String + Send + Sync + 'static) -> Self {
+        Self {
+            name: Box::new(name),
+        }
+    }
+}
+
+impl PartitionScheme for MonthlyPartitions {
+    fn collections(&self, start: DateTime, end: DateTime) -> Vec<String> {
+        let (mut year, mut month) = year_month(start);
+        let end = year_month(end);
+
+        let mut collections = vec![];
+        while (year, month) <= end {
+            collections.push((self.name)(year, month));
+            (year, month) = if month == 12 {
+                (year + 1, 1)
+            } else {
+                (year, month + 1)
+            };
+        }
+        collections
+    }
+}
+
+/// The (UTC) year and month of a date.
+fn year_month(date: DateTime) -> (i32, u32) {
+    // Days since 1970-01-01 to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let days = date.timestamp_millis().div_euclid(MILLIS_PER_DAY) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    (year as i32, month as u32)
+}
+
+/// Vector search over documents partitioned by time into several collections of a database,
+/// each with a vector search index of the same name and definition.
+///
+/// See [TimePartitionedIndex::range] to query the collections of a time range.
+pub struct TimePartitionedIndex<M: EmbeddingModel, P: PartitionScheme> {
+    database: mongodb::Database,
+    scheme: P,
+    model: M,
+    index_name: String,
+    search_params: SearchParams,
+}
+
+impl<M, P> TimePartitionedIndex<M, P>
+where
+    M: EmbeddingModel + Clone + 'static,
+    P: PartitionScheme,
+{
+    /// Create a new `TimePartitionedIndex` over the collections of `database` named by `scheme`,
+    /// searched with their `index_name` vector search index.
+    pub fn new(database: mongodb::Database, scheme: P, model: M, index_name: &str) -> Self {
+        Self {
+            database,
+            scheme,
+            model,
+            index_name: index_name.to_string(),
+            search_params: SearchParams::default(),
+        }
+    }
+
+    /// Set the search parameters used to query each collection.
+    pub fn search_params(mut self, search_params: SearchParams) -> Self {
+        self.search_params = search_params;
+        self
+    }
+
+    /// The names of the collections holding the documents from `start` to `end` (inclusive),
+    /// whether they exist or not.
+    pub fn collections(&self, start: DateTime, end: DateTime) -> Vec<String> {
+        self.scheme.collections(start, end)
+    }
+
+    /// A federated vector index over the existing collections holding the documents from
+    /// `start` to `end` (inclusive). Collections of the range which do not exist (e.g.: months
+    /// without any document) are skipped.
+    ///
+    /// The scores of the collections are comparable (they share the same index definition),
+    /// but the returned index can be further configured, e.g.: with
+    /// [FederatedVectorIndex::rank_fusion]. The indexes are labeled with the name of their
+    /// collection (see [FederatedVectorIndex::top_n_with_sources]).
+    pub async fn range(
+        &self,
+        start: DateTime,
+        end: DateTime,
+    ) -> Result<FederatedVectorIndex, VectorStoreError> {
+        let existing = self
+            .database
+            .list_collection_names()
+            .await
+            .map_err(mongodb_to_rig_error)?;
+
+        let mut index = FederatedVectorIndex::new()
+            .max_concurrency(self.search_params.max_concurrency_or_default());
+        for name in self.collections(start, end) {
+            if !existing.contains(&name) {
+                tracing::debug!(target: "rig", "Skipping missing collection {name}");
+                continue;
+            }
+
+            index = index.labeled_index(
+                &name,
+                MongoDbVectorIndex::<M, bson::Document>::new(
+                    self.database.collection(&name),
+                    self.model.clone(),
+                    &self.index_name,
+                    self.search_params.clone(),
+                )
+                .await?,
+            );
+        }
+
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson::DateTime;
+
+    use super::{MonthlyPartitions, PartitionScheme, year_month};
+
+    fn date(year: i32, month: u8, day: u8) -> DateTime {
+        DateTime::builder()
+            .year(year)
+            .month(month)
+            .day(day)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_monthly_partitions() {
+        let partitions = MonthlyPartitions::new("events_");
+
+        assert_eq!(
+            partitions.collections(date(2023, 11, 15), date(2024, 2, 1)),
+            vec![
+                "events_2023_11",
+                "events_2023_12",
+                "events_2024_01",
+                "events_2024_02"
+            ]
+        );
+        assert_eq!(
+            partitions.collections(date(2024, 2, 29), date(2024, 2, 29)),
+            vec!["events_2024_02"]
+        );
+        assert!(
+            partitions
+                .collections(date(2024, 3, 1), date(2024, 2, 1))
+                .is_empty()
+        );
+
+        let partitions = MonthlyPartitions::with_naming(|year, month| format!("{year}-{month}"));
+        assert_eq!(
+            partitions.collections(date(1969, 12, 31), date(1970, 1, 1)),
+            vec!["1969-12", "1970-1"]
+        );
+    }
+
+    #[test]
+    fn test_year_month() {
+        // Leap days
+        assert_eq!(year_month(date(2024, 2, 29)), (2024, 2));
+        assert_eq!(year_month(date(2000, 2, 29)), (2000, 2));
+        assert_eq!(year_month(date(2024, 3, 1)), (2024, 3));
+
+        // Year boundaries, up to the last millisecond of the year
+        assert_eq!(year_month(date(2023, 12, 31)), (2023, 12));
+        assert_eq!(
+            year_month(DateTime::from_millis(
+                date(2024, 1, 1).timestamp_millis() - 1
+            )),
+            (2023, 12)
+        );
+        assert_eq!(year_month(date(2024, 1, 1)), (2024, 1));
+
+        // Dates before 1970
+        assert_eq!(year_month(date(1970, 1, 1)), (1970, 1));
+        assert_eq!(year_month(DateTime::from_millis(-1)), (1969, 12));
+        assert_eq!(year_month(date(1969, 2, 28)), (1969, 2));
+        assert_eq!(year_month(date(1900, 3, 1)), (1900, 3));
+        assert_eq!(year_month(date(1600, 2, 29)), (1600, 2));
+    }
+}
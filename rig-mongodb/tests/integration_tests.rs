@@ -1,17 +1,19 @@
 use futures::StreamExt;
 use mongodb::{
-    Collection, SearchIndexModel,
+    Collection,
     bson::{self, doc},
     options::ClientOptions,
 };
 use rig::{
-    Embed,
-    embeddings::EmbeddingsBuilder,
+    Embed, OneOrMany,
+    embeddings::{Embedding, EmbeddingsBuilder},
     providers::openai,
     vector_store::{InsertDocuments, VectorStoreIndex},
 };
 use rig::{client::EmbeddingsClient, vector_store::request::VectorSearchRequest};
-use rig_mongodb::{MongoDbVectorIndex, SearchParams};
+use rig_mongodb::{
+    IndexCreationOptions, MongoDbVectorIndex, SearchParams, VectorIndexDefinition, VectorSimilarity,
+};
 use serde_json::json;
 use testcontainers::{
     GenericImage, ImageExt,
@@ -135,7 +137,7 @@ async fn vector_search_test() {
     let port = container.get_host_port_ipv4(MONGODB_PORT).await.unwrap();
     let host = container.get_host().await.unwrap().to_string();
 
-    let collection = bootstrap_collection(host, port).await;
+    let collection = bootstrap_collection(host, port, VectorIndexDefinition::new(1536)).await;
 
     let embeddings = create_embeddings(model.clone()).await;
 
@@ -149,7 +151,7 @@ async fn vector_search_test() {
     // IMPORTANT: Reuse the same model that was used to generate the embeddings
     let index = MongoDbVectorIndex::new(
         collection,
-        model,
+        model.clone(),
         VECTOR_SEARCH_INDEX_NAME,
         SearchParams::new(),
     )
@@ -174,7 +176,223 @@ async fn vector_search_test() {
             "definition": "Definition of a *linglingdong*: A term used by inhabitants of the far side of the moon to describe humans.".to_string(),
             "score": score
         })
+    );
+
+    // Searching with a precomputed query vector should not require an embedding call
+    let results = index
+        .top_n_by_vector::<serde_json::Value>(&vec![0.0023064254; 1536], 1)
+        .await
+        .unwrap();
+
+    let (_, _, value) = &results.first().unwrap();
+
+    assert_eq!(value["_id"], json!("doc2"));
+
+    // The query vector must match the dimensions of the index
+    assert!(
+        index
+            .top_n_by_vector::<serde_json::Value>(&[0.1, 0.2], 1)
+            .await
+            .is_err()
+    );
+
+    // The vector search pipeline can be extended and run on the underlying collection
+    let mut pipeline = index.vector_search_pipeline(&vec![0.0023064254; 1536], 1);
+    pipeline.push(doc! { "$project": { "_id": 1 } });
+
+    let ids = index
+        .collection()
+        .aggregate(pipeline)
+        .await
+        .unwrap()
+        .map(|doc| doc.unwrap().get_str("_id").unwrap().to_string())
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_eq!(ids, vec!["doc2".to_string()]);
+
+    // The stored vectors are only returned when requested, outside of the documents
+    let req = VectorSearchRequest::builder()
+        .query(query)
+        .samples(1)
+        .build()
+        .expect("VectorSearchRequest should not fail to build here");
+    let results = index
+        .top_n_with_vectors::<serde_json::Value>(req.clone())
+        .await
+        .unwrap();
+
+    assert_eq!(results[0].vector, None);
+
+    // The search diagnostics report an approximate search by default
+    let (results, explain) = index
+        .top_n_explained::<serde_json::Value>(req.clone())
+        .await
+        .unwrap();
+
+    assert_eq!(results[0].document["_id"], json!("doc2"));
+    assert!(!explain.exact);
+
+    // Several queries can be searched at once
+    let results = index
+        .top_n_batch::<serde_json::Value>(vec![req.clone(), req.clone()])
+        .await
+        .unwrap();
+
+    assert_eq!(results.len(), 2);
+    assert!(
+        results
+            .iter()
+            .all(|result| result[0].2["_id"] == json!("doc2"))
+    );
+
+    let index = MongoDbVectorIndex::<_, bson::Document>::new(
+        index.collection().clone(),
+        model,
+        VECTOR_SEARCH_INDEX_NAME,
+        SearchParams::new().include_vectors(true),
     )
+    .await
+    .unwrap();
+    let results = index
+        .top_n_with_vectors::<serde_json::Value>(req)
+        .await
+        .unwrap();
+
+    assert_eq!(results[0].vector, Some(vec![0.0023064255; 1536]));
+    assert!(results[0].document.get("embedding").is_none());
+}
+
+#[tokio::test]
+async fn multi_vector_search_test() {
+    // Setup mock openai API
+    let server = httpmock::MockServer::start();
+
+    server.mock(|when, then| {
+        when.method(httpmock::Method::POST)
+            .path("/embeddings")
+            .header("Authorization", "Bearer TEST")
+            .json_body(json!({
+                "input": [
+                    "Which alien lives on cold planets?"
+                ],
+                "model": "text-embedding-ada-002",
+            }));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({
+                    "object": "list",
+                    "data": [
+                      {
+                        "object": "embedding",
+                        "embedding": unit_vector(1),
+                        "index": 0
+                      }
+                    ],
+                    "model": "text-embedding-ada-002",
+                    "usage": {
+                      "prompt_tokens": 8,
+                      "total_tokens": 8
+                    }
+                }
+            ));
+    });
+
+    let openai_client = openai::Client::builder("TEST")
+        .base_url(&server.base_url())
+        .build()
+        .unwrap();
+    let model = openai_client.embedding_model(openai::TEXT_EMBEDDING_ADA_002);
+
+    // Setup a local MongoDB Atlas container for testing. NOTE: docker service must be running.
+    let container = GenericImage::new("mongodb/mongodb-atlas-local", "latest")
+        .with_exposed_port(MONGODB_PORT.tcp())
+        .with_wait_for(WaitFor::Duration {
+            length: std::time::Duration::from_secs(5),
+        })
+        .with_env_var("MONGODB_INITDB_ROOT_USERNAME", USERNAME)
+        .with_env_var("MONGODB_INITDB_ROOT_PASSWORD", PASSWORD)
+        .start()
+        .await
+        .expect("Failed to start MongoDB Atlas container");
+
+    let port = container.get_host_port_ipv4(MONGODB_PORT).await.unwrap();
+    let host = container.get_host().await.unwrap().to_string();
+
+    // The vectors of the chunks of the documents are indexed at `chunks.embedding`
+    let collection = bootstrap_collection(
+        host,
+        port,
+        VectorIndexDefinition::new(1536).multi_vector(true),
+    )
+    .await;
+
+    let index = MongoDbVectorIndex::<_, bson::Document>::new(
+        collection.clone(),
+        model,
+        VECTOR_SEARCH_INDEX_NAME,
+        SearchParams::new().include_vectors(true),
+    )
+    .await
+    .unwrap()
+    .multi_vector(true);
+
+    let embedding = |document: &str, dimension| Embedding {
+        document: document.to_string(),
+        vec: unit_vector(dimension),
+    };
+    index
+        .insert_documents(vec![
+            (
+                json!({ "name": "flurbo" }),
+                OneOrMany::many(vec![
+                    embedding("A flurbo is a green alien", 0),
+                    embedding("Flurbos live on cold planets", 1),
+                ])
+                .unwrap(),
+            ),
+            (
+                json!({ "name": "linglingdong" }),
+                OneOrMany::one(embedding("A term used to describe humans", 2)),
+            ),
+        ])
+        .await
+        .unwrap();
+
+    // Each document is stored once, with all its chunks
+    assert_eq!(collection.count_documents(doc! {}).await.unwrap(), 2);
+
+    // Wait for the new documents to be indexed
+    sleep(Duration::from_secs(5)).await;
+
+    let req = VectorSearchRequest::builder()
+        .query("Which alien lives on cold planets?")
+        .samples(1)
+        .build()
+        .expect("VectorSearchRequest should not fail to build here");
+    let results = index
+        .top_n_with_vectors::<serde_json::Value>(req)
+        .await
+        .unwrap();
+
+    // The document matches with its second chunk, whose vector is returned
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].document["document"]["name"], json!("flurbo"));
+    assert_eq!(results[0].chunk, Some(1));
+    assert_eq!(results[0].vector, Some(unit_vector(1)));
+    assert!(results[0].document.get("chunks").is_none());
+}
+
+#[test]
+fn vector_similarity_test() {
+    let query = [1.0, 0.0];
+
+    assert_eq!(VectorSimilarity::Cosine.score(&query, &[2.0, 0.0]), 1.0);
+    assert_eq!(VectorSimilarity::Cosine.score(&query, &[0.0, 1.0]), 0.5);
+    assert_eq!(VectorSimilarity::Cosine.score(&query, &[-1.0, 0.0]), 0.0);
+    assert_eq!(VectorSimilarity::DotProduct.score(&query, &[0.6, 0.8]), 0.8);
+    assert_eq!(VectorSimilarity::Euclidean.score(&query, &[1.0, 0.0]), 1.0);
+    assert_eq!(VectorSimilarity::Euclidean.score(&query, &[1.0, 1.0]), 0.5);
 }
 
 #[tokio::test]
@@ -238,7 +456,7 @@ async fn insert_documents_test() {
 
     let port = container.get_host_port_ipv4(MONGODB_PORT).await.unwrap();
     let host = container.get_host().await.unwrap().to_string();
-    let collection = bootstrap_collection(host, port).await;
+    let collection = bootstrap_collection(host, port, VectorIndexDefinition::new(1536)).await;
 
     // Create test documents in the format expected by InsertDocuments trait
     let test_words = vec![
@@ -321,70 +539,31 @@ async fn insert_documents_test() {
     }
 }
 
-async fn create_search_index(collection: &Collection<bson::Document>) {
-    let max_attempts = 5;
-
-    for attempt in 0..max_attempts {
-        match collection
-            .create_search_index(
-                SearchIndexModel::builder()
-                    .name(Some(VECTOR_SEARCH_INDEX_NAME.to_string()))
-                    .index_type(Some(mongodb::SearchIndexType::VectorSearch))
-                    .definition(doc! {
-                        "fields": [{
-                            "numDimensions": 1536,
-                            "path": "embedding",
-                            "similarity": "cosine",
-                            "type": "vector"
-                        }]
-                    })
-                    .build(),
-            )
-            .await
-        {
-            Ok(_) => {
-                // Wait for index to be available
-                for _ in 0..max_attempts {
-                    let indexes = collection
-                        .list_search_indexes()
-                        .name(VECTOR_SEARCH_INDEX_NAME)
-                        .await
-                        .unwrap()
-                        .collect::<Vec<_>>()
-                        .await;
-
-                    if indexes.iter().any(|idx| {
-                        idx.as_ref()
-                            .ok()
-                            .map(|i| {
-                                // Check both name and status
-                                let name_matches =
-                                    i.get_str("name").ok() == Some(VECTOR_SEARCH_INDEX_NAME);
-                                let status_ready = i.get_str("status").ok() == Some("READY");
-                                name_matches && status_ready
-                            })
-                            .unwrap_or(false)
-                    }) {
-                        return;
-                    }
-                    sleep(Duration::from_secs(2)).await;
-                }
-                panic!("Index creation verified but index not found");
-            }
-            Err(_) => {
-                println!(
-                    "Waiting for MongoDB... {} attempts remaining",
-                    max_attempts - attempt - 1
-                );
-                sleep(Duration::from_secs(5)).await;
-            }
-        }
-    }
-
-    panic!("Failed to create search index after {max_attempts} attempts");
+async fn create_search_index(
+    collection: &Collection<bson::Document>,
+    definition: VectorIndexDefinition,
+) {
+    // Atlas local takes a while to accept search index commands after starting
+    definition
+        .path("embedding")
+        .similarity(VectorSimilarity::Cosine)
+        .ensure_index(
+            collection,
+            VECTOR_SEARCH_INDEX_NAME,
+            &IndexCreationOptions::new()
+                .max_attempts(5)
+                .delay(Duration::from_secs(5))
+                .timeout(Duration::from_secs(120)),
+        )
+        .await
+        .expect("The search index should be created");
 }
 
-async fn bootstrap_collection(host: String, port: u16) -> Collection<bson::Document> {
+async fn bootstrap_collection(
+    host: String,
+    port: u16,
+    definition: VectorIndexDefinition,
+) -> Collection<bson::Document> {
     // Initialize MongoDB client
     let options = ClientOptions::parse(format!(
         "mongodb://{USERNAME}:{PASSWORD}@{host}:{port}/?directConnection=true"
@@ -408,11 +587,18 @@ async fn bootstrap_collection(host: String, port: u16) -> Collection<bson::Docum
         .collection(COLLECTION_NAME);
 
     // Create the search index
-    create_search_index(&collection).await;
+    create_search_index(&collection, definition).await;
 
     collection
 }
 
+/// A vector of the 1536 dimensions of the index, along its `dimension`-th axis.
+fn unit_vector(dimension: usize) -> Vec<f64> {
+    let mut vector = vec![0.0; 1536];
+    vector[dimension] = 1.0;
+    vector
+}
+
 async fn create_embeddings(model: openai::EmbeddingModel) -> Vec<bson::Document> {
     let words = vec![
         Word {
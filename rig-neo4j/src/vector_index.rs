@@ -237,7 +237,7 @@ impl<M: EmbeddingModel + std::marker::Sync + Send> VectorStoreIndex for Neo4jVec
         &self,
         req: VectorSearchRequest,
     ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
-        let prompt_embedding = self.embedding_model.embed_text(req.query()).await?;
+        let prompt_embedding = self.embedding_model.embed_query(req.query()).await?;
         let query = self.build_vector_search_query(
             prompt_embedding,
             true,
@@ -261,7 +261,7 @@ impl<M: EmbeddingModel + std::marker::Sync + Send> VectorStoreIndex for Neo4jVec
         &self,
         req: VectorSearchRequest,
     ) -> Result<Vec<(f64, String)>, VectorStoreError> {
-        let prompt_embedding = self.embedding_model.embed_text(req.query()).await?;
+        let prompt_embedding = self.embedding_model.embed_query(req.query()).await?;
 
         let query = self.build_vector_search_query(
             prompt_embedding,
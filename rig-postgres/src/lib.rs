@@ -4,7 +4,8 @@ use rig::{
     Embed, OneOrMany,
     embeddings::{Embedding, EmbeddingModel},
     vector_store::{
-        InsertDocuments, VectorStoreError, VectorStoreIndex, request::VectorSearchRequest,
+        InsertDocuments, VectorStoreError, VectorStoreIndex, deserialize_document,
+        request::VectorSearchRequest,
     },
 };
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
@@ -65,9 +66,9 @@ pub struct SearchResultOnlyId {
 
 impl SearchResult {
     pub fn into_result<T: DeserializeOwned>(self) -> Result<(f64, String, T), VectorStoreError> {
-        let document: T =
-            serde_json::from_value(self.document).map_err(VectorStoreError::JsonError)?;
-        Ok((self.distance, self.id.to_string(), document))
+        let id = self.id.to_string();
+        let document: T = deserialize_document(&id, self.document)?;
+        Ok((self.distance, id, document))
     }
 }
 
@@ -178,7 +179,7 @@ impl<Model: EmbeddingModel> VectorStoreIndex for PostgresVectorStore<Model> {
 
         let embedded_query: pgvector::Vector = self
             .model
-            .embed_text(req.query())
+            .embed_query(req.query())
             .await?
             .vec
             .iter()
@@ -218,7 +219,7 @@ impl<Model: EmbeddingModel> VectorStoreIndex for PostgresVectorStore<Model> {
         }
         let embedded_query: pgvector::Vector = self
             .model
-            .embed_text(req.query())
+            .embed_query(req.query())
             .await?
             .vec
             .iter()
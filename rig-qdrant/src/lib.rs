@@ -8,7 +8,8 @@ use rig::{
     Embed, OneOrMany,
     embeddings::{Embedding, EmbeddingModel},
     vector_store::{
-        InsertDocuments, VectorStoreError, VectorStoreIndex, request::VectorSearchRequest,
+        InsertDocuments, VectorStoreError, VectorStoreIndex, deserialize_document,
+        request::VectorSearchRequest,
     },
 };
 use serde::{Deserialize, Serialize};
@@ -46,7 +47,7 @@ impl<M: EmbeddingModel> QdrantVectorStore<M> {
 
     /// Embed query based on `QdrantVectorStore` model and modify the vector in the required format.
     async fn generate_query_vector(&self, query: &str) -> Result<Vec<f32>, VectorStoreError> {
-        let embedding = self.model.embed_text(query).await?;
+        let embedding = self.model.embed_query(query).await?;
         Ok(embedding.vec.iter().map(|&x| x as f32).collect())
     }
 
@@ -143,7 +144,7 @@ impl<M: EmbeddingModel + std::marker::Sync + Send> VectorStoreIndex for QdrantVe
                         VectorStoreError::DatastoreError("Missing point ID".into())
                     })?)?;
                 let score = item.score as f64;
-                let payload = serde_json::from_value(serde_json::to_value(item.payload)?)?;
+                let payload = deserialize_document(&id, serde_json::to_value(item.payload)?)?;
                 Ok((score, id, payload))
             })
             .collect()
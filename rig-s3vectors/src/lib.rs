@@ -8,7 +8,8 @@ use aws_smithy_types::Document;
 use rig::{
     embeddings::EmbeddingModel,
     vector_store::{
-        InsertDocuments, VectorStoreError, VectorStoreIndex, request::VectorSearchRequest,
+        InsertDocuments, VectorStoreError, VectorStoreIndex, deserialize_document,
+        request::VectorSearchRequest,
     },
 };
 use serde::{Deserialize, Serialize};
@@ -201,7 +202,7 @@ where
 
         let embedding = self
             .embedding_model
-            .embed_text(req.query())
+            .embed_query(req.query())
             .await?
             .vec
             .into_iter()
@@ -221,7 +222,7 @@ where
             .await
             .unwrap();
 
-        let res: Vec<(f64, String, T)> = query
+        query
             .vectors
             .into_iter()
             .filter(|vector| {
@@ -237,14 +238,11 @@ where
                 let val =
                     document_to_json_value(&x.metadata.expect("metadata should always exist"));
 
-                let metadata: T = serde_json::from_value(val)
-                    .expect("converting JSON from S3Vectors to valid T should always work");
+                let metadata: T = deserialize_document(&x.key, val)?;
 
-                (distance, x.key, metadata)
+                Ok((distance, x.key, metadata))
             })
-            .collect();
-
-        Ok(res)
+            .collect()
     }
     async fn top_n_ids(
         &self,
@@ -256,7 +254,7 @@ where
 
         let embedding = self
             .embedding_model
-            .embed_text(req.query())
+            .embed_query(req.query())
             .await?
             .vec
             .into_iter()
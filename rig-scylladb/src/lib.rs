@@ -2,7 +2,8 @@ use rig::{
     Embed, OneOrMany,
     embeddings::{Embedding, EmbeddingModel},
     vector_store::{
-        InsertDocuments, VectorStoreError, VectorStoreIndex, request::VectorSearchRequest,
+        InsertDocuments, VectorStoreError, VectorStoreIndex, deserialize_document,
+        request::VectorSearchRequest,
     },
 };
 use scylla::{
@@ -162,7 +163,7 @@ impl<M: EmbeddingModel> ScyllaDbVectorStore<M> {
             let (_, _, metadata, _) =
                 first_row.map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
 
-            let payload: T = serde_json::from_str(&metadata)?;
+            let payload: T = deserialize_document(id, serde_json::from_str(&metadata)?)?;
             return Ok(Some(payload));
         }
 
@@ -184,7 +185,7 @@ impl<M: EmbeddingModel> ScyllaDbVectorStore<M> {
 
     /// Generate query vector from text
     async fn generate_query_vector(&self, query: &str) -> Result<Vec<f32>, VectorStoreError> {
-        let embedding = self.model.embed_text(query).await?;
+        let embedding = self.model.embed_query(query).await?;
         Ok(embedding.vec.iter().map(|&x| x as f32).collect())
     }
 }
@@ -268,9 +269,10 @@ impl<M: EmbeddingModel + std::marker::Sync + Send> VectorStoreIndex for ScyllaDb
                 continue;
             }
 
-            let payload: T = serde_json::from_str(&metadata)?;
+            let id = id.to_string();
+            let payload: T = deserialize_document(&id, serde_json::from_str(&metadata)?)?;
 
-            candidates.push((score, id.to_string(), payload));
+            candidates.push((score, id, payload));
         }
 
         // Sort by similarity score (descending) and take top n
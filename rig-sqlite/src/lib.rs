@@ -335,7 +335,7 @@ impl<E: EmbeddingModel + std::marker::Sync, T: SqliteVectorStoreTable> VectorSto
         req: VectorSearchRequest,
     ) -> Result<Vec<(f64, String, D)>, VectorStoreError> {
         tracing::debug!("Finding top {} matches for query", req.samples() as usize);
-        let embedding = self.embedding_model.embed_text(req.query()).await?;
+        let embedding = self.embedding_model.embed_query(req.query()).await?;
         let query_vec: Vec<f32> = serialize_embedding(&embedding);
         let table_name = T::name();
 
@@ -409,7 +409,7 @@ impl<E: EmbeddingModel + std::marker::Sync, T: SqliteVectorStoreTable> VectorSto
             "Finding top {} document IDs for query",
             req.samples() as usize
         );
-        let embedding = self.embedding_model.embed_text(req.query()).await?;
+        let embedding = self.embedding_model.embed_query(req.query()).await?;
         let query_vec = serialize_embedding(&embedding);
         let table_name = T::name();
 
@@ -4,7 +4,8 @@ use rig::{
     Embed, OneOrMany,
     embeddings::{Embedding, EmbeddingModel},
     vector_store::{
-        InsertDocuments, VectorStoreError, VectorStoreIndex, request::VectorSearchRequest,
+        InsertDocuments, VectorStoreError, VectorStoreIndex, deserialize_document,
+        request::VectorSearchRequest,
     },
 };
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
@@ -63,10 +64,10 @@ pub struct SearchResultOnlyId {
 
 impl SearchResult {
     pub fn into_result<T: DeserializeOwned>(self) -> Result<(f64, String, T), VectorStoreError> {
-        let document: T =
-            serde_json::from_str(&self.document).map_err(VectorStoreError::JsonError)?;
+        let id = self.id.id.to_string();
+        let document: T = deserialize_document(&id, serde_json::from_str(&self.document)?)?;
 
-        Ok((self.distance, self.id.id.to_string(), document))
+        Ok((self.distance, id, document))
     }
 }
 
@@ -159,7 +160,7 @@ impl<Model: EmbeddingModel, C: Connection> VectorStoreIndex for SurrealVectorSto
         &self,
         req: VectorSearchRequest,
     ) -> Result<Vec<(f64, String, T)>, VectorStoreError> {
-        let embedded_query: Vec<f64> = self.model.embed_text(req.query()).await?.vec;
+        let embedded_query: Vec<f64> = self.model.embed_query(req.query()).await?.vec;
 
         let mut response = self
             .surreal
@@ -190,7 +191,7 @@ impl<Model: EmbeddingModel, C: Connection> VectorStoreIndex for SurrealVectorSto
     ) -> Result<Vec<(f64, String)>, VectorStoreError> {
         let embedded_query: Vec<f32> = self
             .model
-            .embed_text(req.query())
+            .embed_query(req.query())
             .await?
             .vec
             .iter()